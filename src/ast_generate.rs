@@ -1,13 +1,24 @@
 pub mod ast;
+mod display;
+pub mod span;
+pub mod token;
+use crate::diagnostic::Diagnostic;
 use ast::*;
-use lalrpop_util::lalrpop_mod;
+use lalrpop_util::lexer::Token;
+use lalrpop_util::{lalrpop_mod, ParseError};
+use span::LineIndex;
+use std::sync::OnceLock;
 
-lalrpop_mod!(sysy);
+lalrpop_mod!(
+    #[allow(clippy::all)]
+    sysy
+);
 
 /// Convert the input SysY source code to AST.
 ///
 /// `lalrpop` crate is used to generate the parser.
-/// If an error occurs, `Err(())` is returned.
+/// Every node of the AST records its `Span` in the source code.
+/// If there are syntax errors, all the `Diagnostic`s found by the parser are returned.
 /// Otherwise, return the root of the AST (i.e. `CompUnit`) wrapped by `Ok`.
 ///
 /// # Errors
@@ -23,8 +34,102 @@ lalrpop_mod!(sysy);
 /// "#;
 /// let ast = parse_sysy_to_ast(input).unwrap();
 /// ```
-pub fn parse_sysy_to_ast(input: &str) -> Result<CompUnit, ()> {
-    sysy::CompUnitParser::new().parse(input).map_err(|_| ())
+pub fn parse_sysy_to_ast(input: &str) -> Result<CompUnit, Vec<Diagnostic>> {
+    match parse_sysy_to_partial_ast(input) {
+        (Some(ast), errors) if errors.is_empty() => Ok(ast),
+        (_, errors) => Err(errors),
+    }
+}
+
+/// Convert the input SysY source code to AST, recovering from syntax errors.
+///
+/// When the parser meets a statement, a declaration or a function definition that it cannot parse,
+/// it records a syntax error, skips the tokens until the next item begins and carries on.
+/// The items with syntax errors are left out of the returned AST,
+/// so the AST is a partial one unless the list of syntax errors is empty.
+/// The AST is `None` if the parser fails to recover (e.g. it reaches the end of file too early).
+pub fn parse_sysy_to_partial_ast(input: &str) -> (Option<CompUnit>, Vec<Diagnostic>) {
+    let index = LineIndex::new(input);
+    let mut recovered = Vec::new();
+    // building the parser compiles the regexes of the lexer, so it's built only once
+    static PARSER: OnceLock<sysy::CompUnitParser> = OnceLock::new();
+    let parser = PARSER.get_or_init(sysy::CompUnitParser::new);
+    let result = parser.parse(&index, &mut recovered, input);
+
+    let mut errors: Vec<Diagnostic> = recovered
+        .into_iter()
+        .map(|e| syntax_error_to_diagnostic(e.error, &index))
+        .collect();
+    let ast = match result {
+        Ok(ast) => Some(ast),
+        Err(e) => {
+            errors.push(syntax_error_to_diagnostic(e, &index));
+            None
+        }
+    };
+    (ast, errors)
+}
+
+/// Describe a terminal of the grammar as it appears in the lalrpop's list of expected tokens.
+fn describe_expected(terminal: &str) -> String {
+    match terminal {
+        r##"r#"[_a-zA-Z][_a-zA-Z0-9]*"#"## => String::from("identifier"),
+        t if t.starts_with("r#") => String::from("integer literal"),
+        // a quoted literal, e.g. "\";\""
+        t => format!("`{}`", t.trim_matches('"').replace("\\\"", "\"")),
+    }
+}
+
+/// Join the expected tokens in the form of "`a`", "one of `a` or `b`" or "one of `a`, `b` or `c`".
+fn join_expected(expected: &[String]) -> String {
+    let mut descriptions: Vec<String> = Vec::new();
+    for d in expected.iter().map(|t| describe_expected(t)) {
+        if !descriptions.contains(&d) {
+            descriptions.push(d);
+        }
+    }
+    match descriptions.split_last() {
+        None => String::from("nothing"),
+        Some((last, [])) => last.clone(),
+        Some((last, others)) => format!("one of {} or {}", others.join(", "), last),
+    }
+}
+
+fn syntax_error_to_diagnostic(
+    e: ParseError<usize, Token<'_>, &str>,
+    index: &LineIndex,
+) -> Diagnostic {
+    let (l, r, found, expected) = match e {
+        ParseError::InvalidToken { location } => {
+            return Diagnostic::error("syntax error: invalid token")
+                .with_primary(index.span(location, location + 1), "invalid token");
+        }
+        ParseError::UnrecognizedEof { location, expected } => {
+            (location, location, String::from("end of file"), expected)
+        }
+        ParseError::UnrecognizedToken {
+            token: (l, t, r),
+            expected,
+        } => (l, r, format!("`{}`", t), expected),
+        ParseError::ExtraToken { token: (l, t, r) } => (l, r, format!("`{}`", t), Vec::new()),
+        ParseError::User { error } => return Diagnostic::error(error),
+    };
+    let span = index.span(l, r);
+    if expected.is_empty() {
+        return Diagnostic::error(format!("syntax error: unexpected {}", found))
+            .with_primary(span, "unexpected token");
+    }
+    let label = if expected.len() == 1 {
+        format!("expected {}", describe_expected(&expected[0]))
+    } else {
+        String::from("unexpected token")
+    };
+    Diagnostic::error(format!(
+        "expected {} found {}",
+        join_expected(&expected),
+        found
+    ))
+    .with_primary(span, label)
 }
 
 #[cfg(test)]
@@ -131,4 +236,79 @@ mod tests {
     fn astgen_lv9_3_test() {
         astgen_test("tests/sysy_scripts/lv9-3.c");
     }
+
+    #[test]
+    fn astgen_span_test() {
+        let input = "int main() {\n  int a = 1;\n  return a + 2;\n}\n";
+        let ast = parse_sysy_to_ast(input).unwrap();
+        let CompUnitItem::FuncDef(func_def) = &ast.items[0] else {
+            panic!("expected a function definition");
+        };
+        assert_eq!(func_def.ident.name, "main");
+        assert_eq!((func_def.ident.span.line, func_def.ident.span.col), (1, 5));
+
+        let BlockItem::Decl(Decl::Var(var_decl)) = &func_def.block.items[0] else {
+            panic!("expected a variable declaration");
+        };
+        assert_eq!(&input[var_decl.span.start..var_decl.span.end], "int a = 1;");
+        assert_eq!((var_decl.span.line, var_decl.span.col), (2, 3));
+
+        let BlockItem::Stmt(stmt) = &func_def.block.items[1] else {
+            panic!("expected a statement");
+        };
+        assert_eq!(&input[stmt.span.start..stmt.span.end], "return a + 2;");
+        let StmtKind::Return(Some(exp)) = &stmt.kind else {
+            panic!("expected a return statement");
+        };
+        assert_eq!(&input[exp.span.start..exp.span.end], "a + 2");
+        assert_eq!((exp.span.line, exp.span.col), (3, 10));
+    }
+
+    #[test]
+    fn astgen_error_recovery_test() {
+        let input =
+            "int main() {\n  int a = 1\n  a = a + 1;\n  return a\n}\nint f() { return 0; }\n";
+        let (ast, errors) = parse_sysy_to_partial_ast(input);
+        let messages: Vec<String> = errors.iter().map(|e| e.message.clone()).collect();
+        assert_eq!(messages.len(), 2, "{:#?}", messages);
+        assert_eq!(messages[1], "expected `;` found `}`");
+        let primary = errors[1].primary.as_ref().unwrap();
+        assert_eq!((primary.span.line, primary.span.col), (5, 1));
+
+        // the function definitions are kept, while the broken items are left out
+        let ast = ast.unwrap();
+        assert_eq!(ast.items.len(), 2);
+        assert!(parse_sysy_to_ast(input).is_err());
+    }
+
+    #[test]
+    fn astgen_int_literal_test() {
+        let input = "int main() { return 2147483648 + 0xFFFFFFFF + 017777777777 + 0x100000000; }";
+        let ast = parse_sysy_to_ast(input).unwrap();
+        let mut nums = Vec::new();
+        let CompUnitItem::FuncDef(func_def) = &ast.items[0] else {
+            panic!("expected a function definition");
+        };
+        let BlockItem::Stmt(stmt) = &func_def.block.items[0] else {
+            panic!("expected a statement");
+        };
+        let StmtKind::Return(Some(exp)) = &stmt.kind else {
+            panic!("expected a return statement");
+        };
+        let LOrExp::LAnd(LAndExp::Eq(EqExp::Rel(RelExp::Add(add)))) = &exp.exp else {
+            panic!("expected an additive expression");
+        };
+        let mut add = add;
+        while let AddExp::AddMul(lhs, _, MulExp::Unary(UnaryExp::Primary(PrimaryExp::Num(n, _)))) =
+            add
+        {
+            nums.push(*n);
+            add = &**lhs;
+        }
+        let AddExp::Mul(MulExp::Unary(UnaryExp::Primary(PrimaryExp::Num(n, _)))) = add else {
+            panic!("expected an integer literal");
+        };
+        nums.push(*n);
+        assert_eq!(nums, vec![0x100000000, 2147483647, -1, 2147483648]);
+    }
 }
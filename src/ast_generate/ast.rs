@@ -1,27 +1,50 @@
-/// CompUnit ::= [CompUnit] (GlobalDecl | FuncDef);
+pub use super::span::Span;
+
+/// CompUnit ::= [CompUnit] (GlobalDecl | FuncDef | FuncDecl);
 /// GlobalDecl ::= Decl;
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct CompUnit {
     pub items: Vec<CompUnitItem>,
+    pub span: Span,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum CompUnitItem {
     GlobalDecl(GlobalDecl),
     FuncDef(FuncDef),
+    FuncDecl(FuncDecl),
+}
+
+/// An identifier, together with the place where it appears.
+#[derive(Debug, Clone)]
+pub struct Ident {
+    pub name: String,
+    pub span: Span,
 }
 
 /// FuncDef ::= FuncType IDENT "(" [FuncFParams] ")" Block;
 /// FuncFParams ::= FuncFParam {"," FuncFParam};
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct FuncDef {
     pub func_type: FuncType,
-    pub ident: String,
+    pub ident: Ident,
     pub params: Vec<FuncFParam>,
     pub block: Block,
+    pub span: Span,
+}
+
+/// A function prototype, which declares a function defined later or provided by the runtime.
+///
+/// FuncDecl ::= FuncType IDENT "(" [FuncFParams] ")" ";";
+#[derive(Debug, Clone)]
+pub struct FuncDecl {
+    pub func_type: FuncType,
+    pub ident: Ident,
+    pub params: Vec<FuncFParam>,
+    pub span: Span,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum FuncType {
     Int,
     Void,
@@ -29,24 +52,32 @@ pub enum FuncType {
 
 /// FuncFParam ::= BType IDENT [ "[" "]" { "[" ConstExp "]" } ];
 /// BType ::= "int"
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct FuncFParam {
     // there's only `int` type in SysY!
-    pub ident: String,
+    pub ident: Ident,
     pub dims: Option<Vec<ConstExp>>,
+    pub span: Span,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Block {
     pub items: Vec<BlockItem>,
+    pub span: Span,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum BlockItem {
-    Stmt(Stmt),
+    Stmt(Box<Stmt>),
     Decl(Decl),
 }
 
+#[derive(Debug, Clone)]
+pub struct Stmt {
+    pub kind: StmtKind,
+    pub span: Span,
+}
+
 /// Stmt ::= LVal "=" Exp ";"
 ///        | [Exp] ";"
 ///        | Block
@@ -55,8 +86,8 @@ pub enum BlockItem {
 ///        | "break" ";"
 ///        | "continue" ";"
 ///        | "return" [Exp] ";";
-#[derive(Debug)]
-pub enum Stmt {
+#[derive(Debug, Clone)]
+pub enum StmtKind {
     Assign(LVal, Exp),
     Exp(Option<Exp>),
     Block(Block),
@@ -67,102 +98,107 @@ pub enum Stmt {
     Return(Option<Exp>),
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct GlobalDecl {
     pub decl: Decl,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum Decl {
     Const(ConstDecl),
     Var(VarDecl),
 }
 
 /// ConstDecl ::= "const" BType ConstDef {"," ConstDef} ";";
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct ConstDecl {
     // there's only `int` type in SysY!
     pub defs: Vec<ConstDef>,
+    pub span: Span,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct ConstDef {
-    pub ident: String,
+    pub ident: Ident,
     pub dims: Vec<ConstExp>,
     pub init: ConstInitVal,
+    pub span: Span,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum ConstInitVal {
     Exp(ConstExp),
-    Array(Vec<ConstInitVal>),
+    Array(Vec<ConstInitVal>, Span),
 }
 
 /// VarDecl ::= BType VarDef {"," VarDef} ";";
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct VarDecl {
     // there's only `int` type in SysY!
     pub defs: Vec<VarDef>,
+    pub span: Span,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct VarDef {
-    pub ident: String,
+    pub ident: Ident,
     pub dims: Vec<ConstExp>,
     pub init: Option<InitVal>,
+    pub span: Span,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum InitVal {
     Exp(Exp),
-    Array(Vec<InitVal>),
+    Array(Vec<InitVal>, Span),
 }
 
 ////////////////////////////////////////////////////////////////////////////
 // Expressions                                                            //
 ////////////////////////////////////////////////////////////////////////////
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct ConstExp {
     pub exp: Exp,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Exp {
     pub exp: LOrExp,
+    pub span: Span,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum LOrExp {
     LAnd(LAndExp),
     LOrLAnd(Box<LOrExp>, LAndExp),
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum LAndExp {
     Eq(EqExp),
     LAndEq(Box<LAndExp>, EqExp),
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum EqExp {
     Rel(RelExp),
     EqRel(Box<EqExp>, EqExpOp, RelExp),
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum RelExp {
     Add(AddExp),
     RelAdd(Box<RelExp>, RelExpOp, AddExp),
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum AddExp {
     Mul(MulExp),
     AddMul(Box<AddExp>, AddExpOp, MulExp),
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum MulExp {
     Unary(UnaryExp),
     MulUnary(Box<MulExp>, MulExpOp, UnaryExp),
@@ -172,37 +208,39 @@ pub enum MulExp {
 ///            | IDENT "(" [FuncRParams] ")"
 ///            | UnaryOp UnaryExp;
 /// FuncRParams ::= Exp {"," Exp};
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum UnaryExp {
     Primary(PrimaryExp),
-    FuncCall(String, Vec<Exp>),
-    Unary(UnaryExpOp, Box<UnaryExp>),
+    FuncCall(Ident, Vec<Exp>, Span),
+    Unary(UnaryExpOp, Box<UnaryExp>, Span),
 }
 
-#[derive(Debug)]
+/// The value of `Num` may be out of the range of `int`, so it must be checked before use.
+#[derive(Debug, Clone)]
 pub enum PrimaryExp {
     Exp(Box<Exp>),
     LVal(LVal),
-    Num(i32),
+    Num(i64, Span),
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct LVal {
-    pub ident: String,
+    pub ident: Ident,
     pub idx: Vec<Exp>,
+    pub span: Span,
 }
 
 ///////////////////////////////////////////////////////////////////////////
 // Operators                                                             //
 ///////////////////////////////////////////////////////////////////////////
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum EqExpOp {
     Eq,
     Neq,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum RelExpOp {
     Le,
     Ge,
@@ -210,22 +248,218 @@ pub enum RelExpOp {
     Gt,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum AddExpOp {
     Add,
     Sub,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum MulExpOp {
     Mul,
     Div,
     Mod,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum UnaryExpOp {
     Pos,
     Neg,
     Not,
 }
+
+impl UnaryExp {
+    /// Return whether the expression is the literal `2147483648`,
+    /// which is only valid as the operand of a unary minus.
+    pub fn is_int_min_magnitude(&self) -> bool {
+        matches!(self, Self::Primary(PrimaryExp::Num(num, _)) if *num == -(i32::MIN as i64))
+    }
+}
+
+/// The common shape of `ConstInitVal` and `InitVal`.
+///
+/// An initializer is either an expression or a list of initializers.
+pub trait Initializer: Sized {
+    type Exp;
+
+    /// Return the expression if it's not an initializer list.
+    fn exp(&self) -> Option<&Self::Exp>;
+    /// Return the elements if it's an initializer list.
+    fn elements(&self) -> Option<&[Self]>;
+    fn span(&self) -> Span;
+}
+
+impl Initializer for ConstInitVal {
+    type Exp = ConstExp;
+
+    fn exp(&self) -> Option<&ConstExp> {
+        match self {
+            Self::Exp(exp) => Some(exp),
+            Self::Array(..) => None,
+        }
+    }
+
+    fn elements(&self) -> Option<&[Self]> {
+        match self {
+            Self::Exp(_) => None,
+            Self::Array(inits, _) => Some(inits),
+        }
+    }
+
+    fn span(&self) -> Span {
+        ConstInitVal::span(self)
+    }
+}
+
+impl Initializer for InitVal {
+    type Exp = Exp;
+
+    fn exp(&self) -> Option<&Exp> {
+        match self {
+            Self::Exp(exp) => Some(exp),
+            Self::Array(..) => None,
+        }
+    }
+
+    fn elements(&self) -> Option<&[Self]> {
+        match self {
+            Self::Exp(_) => None,
+            Self::Array(inits, _) => Some(inits),
+        }
+    }
+
+    fn span(&self) -> Span {
+        InitVal::span(self)
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////
+// Spans of the nodes without a `span` field                             //
+///////////////////////////////////////////////////////////////////////////
+
+impl CompUnitItem {
+    pub fn span(&self) -> Span {
+        match self {
+            Self::GlobalDecl(global_decl) => global_decl.decl.span(),
+            Self::FuncDef(func_def) => func_def.span,
+            Self::FuncDecl(func_decl) => func_decl.span,
+        }
+    }
+}
+
+impl BlockItem {
+    pub fn span(&self) -> Span {
+        match self {
+            Self::Stmt(stmt) => stmt.span,
+            Self::Decl(decl) => decl.span(),
+        }
+    }
+}
+
+impl Decl {
+    pub fn span(&self) -> Span {
+        match self {
+            Self::Const(const_decl) => const_decl.span,
+            Self::Var(var_decl) => var_decl.span,
+        }
+    }
+}
+
+impl ConstInitVal {
+    pub fn span(&self) -> Span {
+        match self {
+            Self::Exp(exp) => exp.span(),
+            Self::Array(_, span) => *span,
+        }
+    }
+}
+
+impl InitVal {
+    pub fn span(&self) -> Span {
+        match self {
+            Self::Exp(exp) => exp.span,
+            Self::Array(_, span) => *span,
+        }
+    }
+}
+
+impl ConstExp {
+    pub fn span(&self) -> Span {
+        self.exp.span
+    }
+}
+
+// A binary expression spans from its left operand to its right operand.
+
+impl LOrExp {
+    pub fn span(&self) -> Span {
+        match self {
+            Self::LAnd(exp) => exp.span(),
+            Self::LOrLAnd(exp1, exp2) => exp1.span().join(exp2.span()),
+        }
+    }
+}
+
+impl LAndExp {
+    pub fn span(&self) -> Span {
+        match self {
+            Self::Eq(exp) => exp.span(),
+            Self::LAndEq(exp1, exp2) => exp1.span().join(exp2.span()),
+        }
+    }
+}
+
+impl EqExp {
+    pub fn span(&self) -> Span {
+        match self {
+            Self::Rel(exp) => exp.span(),
+            Self::EqRel(exp1, _, exp2) => exp1.span().join(exp2.span()),
+        }
+    }
+}
+
+impl RelExp {
+    pub fn span(&self) -> Span {
+        match self {
+            Self::Add(exp) => exp.span(),
+            Self::RelAdd(exp1, _, exp2) => exp1.span().join(exp2.span()),
+        }
+    }
+}
+
+impl AddExp {
+    pub fn span(&self) -> Span {
+        match self {
+            Self::Mul(exp) => exp.span(),
+            Self::AddMul(exp1, _, exp2) => exp1.span().join(exp2.span()),
+        }
+    }
+}
+
+impl MulExp {
+    pub fn span(&self) -> Span {
+        match self {
+            Self::Unary(exp) => exp.span(),
+            Self::MulUnary(exp1, _, exp2) => exp1.span().join(exp2.span()),
+        }
+    }
+}
+
+impl UnaryExp {
+    pub fn span(&self) -> Span {
+        match self {
+            Self::Primary(exp) => exp.span(),
+            Self::FuncCall(_, _, span) | Self::Unary(_, _, span) => *span,
+        }
+    }
+}
+
+impl PrimaryExp {
+    pub fn span(&self) -> Span {
+        match self {
+            Self::Exp(exp) => exp.span,
+            Self::LVal(lval) => lval.span,
+            Self::Num(_, span) => *span,
+        }
+    }
+}
@@ -0,0 +1,334 @@
+use super::ast::*;
+use std::fmt::{self, Display, Formatter, Write};
+
+// Print the AST back to SysY source code, which parses to the same AST except for the spans.
+// The branches of `if` and the bodies of `while` are always enclosed in braces,
+// so that an `else` is never attached to a wrong `if`.
+
+impl Display for CompUnit {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let mut printer = Printer { f, indent: 0 };
+        for item in &self.items {
+            match item {
+                CompUnitItem::GlobalDecl(global_decl) => printer.decl(&global_decl.decl)?,
+                CompUnitItem::FuncDef(func_def) => {
+                    printer.line(&signature(
+                        &func_def.func_type,
+                        &func_def.ident,
+                        &func_def.params,
+                    ))?;
+                    printer.block(&func_def.block, " ")?;
+                }
+                CompUnitItem::FuncDecl(func_decl) => {
+                    let line = signature(&func_decl.func_type, &func_decl.ident, &func_decl.params);
+                    printer.line(&line)?;
+                    writeln!(printer.f, ";")?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn signature(func_type: &FuncType, ident: &Ident, params: &[FuncFParam]) -> String {
+    let func_type = match func_type {
+        FuncType::Int => "int",
+        FuncType::Void => "void",
+    };
+    let params: Vec<String> = params
+        .iter()
+        .map(|param| match &param.dims {
+            None => format!("int {}", param.ident.name),
+            Some(dims) => format!("int {}[]{}", param.ident.name, dims_text(dims)),
+        })
+        .collect();
+    format!("{} {}({})", func_type, ident.name, params.join(", "))
+}
+
+fn dims_text(dims: &[ConstExp]) -> String {
+    let mut text = String::new();
+    for dim in dims {
+        write!(text, "[{}]", dim.exp).unwrap();
+    }
+    text
+}
+
+struct Printer<'a, 'b> {
+    f: &'a mut Formatter<'b>,
+    indent: usize,
+}
+
+impl Printer<'_, '_> {
+    /// Start a new line with the text, without ending it.
+    fn line(&mut self, text: &str) -> fmt::Result {
+        write!(self.f, "{}{}", "    ".repeat(self.indent), text)
+    }
+
+    /// Print a block following the text on the current line, and end the line.
+    fn block(&mut self, block: &Block, before: &str) -> fmt::Result {
+        writeln!(self.f, "{}{{", before)?;
+        self.indent += 1;
+        for item in &block.items {
+            match item {
+                BlockItem::Decl(decl) => self.decl(decl)?,
+                BlockItem::Stmt(stmt) => self.stmt(stmt)?,
+            }
+        }
+        self.indent -= 1;
+        self.line("}")?;
+        writeln!(self.f)
+    }
+
+    fn decl(&mut self, decl: &Decl) -> fmt::Result {
+        let line = match decl {
+            Decl::Const(const_decl) => {
+                let defs: Vec<String> = const_decl
+                    .defs
+                    .iter()
+                    .map(|def| format!("{}{} = {}", def.ident.name, dims_text(&def.dims), def.init))
+                    .collect();
+                format!("const int {};", defs.join(", "))
+            }
+            Decl::Var(var_decl) => {
+                let defs: Vec<String> = var_decl
+                    .defs
+                    .iter()
+                    .map(|def| {
+                        let mut text = format!("{}{}", def.ident.name, dims_text(&def.dims));
+                        if let Some(init) = &def.init {
+                            write!(text, " = {}", init).unwrap();
+                        }
+                        text
+                    })
+                    .collect();
+                format!("int {};", defs.join(", "))
+            }
+        };
+        self.line(&line)?;
+        writeln!(self.f)
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) -> fmt::Result {
+        let line = match &stmt.kind {
+            StmtKind::Assign(lval, exp) => format!("{} = {};", lval, exp),
+            StmtKind::Exp(Some(exp)) => format!("{};", exp),
+            StmtKind::Exp(None) => String::from(";"),
+            StmtKind::Block(block) => {
+                self.line("")?;
+                return self.block(block, "");
+            }
+            StmtKind::If(cond, then, otherwise) => {
+                self.line(&format!("if ({})", cond))?;
+                self.body(then)?;
+                if let Some(otherwise) = otherwise {
+                    self.line("else")?;
+                    self.body(otherwise)?;
+                }
+                return Ok(());
+            }
+            StmtKind::While(cond, body) => {
+                self.line(&format!("while ({})", cond))?;
+                return self.body(body);
+            }
+            StmtKind::Break => String::from("break;"),
+            StmtKind::Continue => String::from("continue;"),
+            StmtKind::Return(Some(exp)) => format!("return {};", exp),
+            StmtKind::Return(None) => String::from("return;"),
+        };
+        self.line(&line)?;
+        writeln!(self.f)
+    }
+
+    /// Print the body of `if` or `while` in braces, following the current line.
+    fn body(&mut self, stmt: &Stmt) -> fmt::Result {
+        if let StmtKind::Block(block) = &stmt.kind {
+            return self.block(block, " ");
+        }
+        writeln!(self.f, " {{")?;
+        self.indent += 1;
+        self.stmt(stmt)?;
+        self.indent -= 1;
+        self.line("}")?;
+        writeln!(self.f)
+    }
+}
+
+impl Display for ConstInitVal {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Self::Exp(exp) => write!(f, "{}", exp.exp),
+            Self::Array(inits, _) => write_list(f, inits),
+        }
+    }
+}
+
+impl Display for InitVal {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Self::Exp(exp) => write!(f, "{}", exp),
+            Self::Array(inits, _) => write_list(f, inits),
+        }
+    }
+}
+
+fn write_list(f: &mut Formatter, items: &[impl Display]) -> fmt::Result {
+    let items: Vec<String> = items.iter().map(|item| item.to_string()).collect();
+    write!(f, "{{{}}}", items.join(", "))
+}
+
+impl Display for Exp {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}", self.exp)
+    }
+}
+
+impl Display for LOrExp {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Self::LAnd(exp) => write!(f, "{}", exp),
+            Self::LOrLAnd(exp1, exp2) => write!(f, "{} || {}", exp1, exp2),
+        }
+    }
+}
+
+impl Display for LAndExp {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Self::Eq(exp) => write!(f, "{}", exp),
+            Self::LAndEq(exp1, exp2) => write!(f, "{} && {}", exp1, exp2),
+        }
+    }
+}
+
+impl Display for EqExp {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Self::Rel(exp) => write!(f, "{}", exp),
+            Self::EqRel(exp1, op, exp2) => {
+                let op = match op {
+                    EqExpOp::Eq => "==",
+                    EqExpOp::Neq => "!=",
+                };
+                write!(f, "{} {} {}", exp1, op, exp2)
+            }
+        }
+    }
+}
+
+impl Display for RelExp {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Self::Add(exp) => write!(f, "{}", exp),
+            Self::RelAdd(exp1, op, exp2) => {
+                let op = match op {
+                    RelExpOp::Le => "<=",
+                    RelExpOp::Ge => ">=",
+                    RelExpOp::Lt => "<",
+                    RelExpOp::Gt => ">",
+                };
+                write!(f, "{} {} {}", exp1, op, exp2)
+            }
+        }
+    }
+}
+
+impl Display for AddExp {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Self::Mul(exp) => write!(f, "{}", exp),
+            Self::AddMul(exp1, op, exp2) => {
+                let op = match op {
+                    AddExpOp::Add => "+",
+                    AddExpOp::Sub => "-",
+                };
+                write!(f, "{} {} {}", exp1, op, exp2)
+            }
+        }
+    }
+}
+
+impl Display for MulExp {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Self::Unary(exp) => write!(f, "{}", exp),
+            Self::MulUnary(exp1, op, exp2) => {
+                let op = match op {
+                    MulExpOp::Mul => "*",
+                    MulExpOp::Div => "/",
+                    MulExpOp::Mod => "%",
+                };
+                write!(f, "{} {} {}", exp1, op, exp2)
+            }
+        }
+    }
+}
+
+impl Display for UnaryExp {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Self::Primary(exp) => write!(f, "{}", exp),
+            Self::FuncCall(ident, args, _) => {
+                let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+                write!(f, "{}({})", ident.name, args.join(", "))
+            }
+            Self::Unary(op, exp, _) => {
+                let op = match op {
+                    UnaryExpOp::Pos => "+",
+                    UnaryExpOp::Neg => "-",
+                    UnaryExpOp::Not => "!",
+                };
+                write!(f, "{}{}", op, exp)
+            }
+        }
+    }
+}
+
+impl Display for PrimaryExp {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Self::Exp(exp) => write!(f, "({})", exp),
+            Self::LVal(lval) => write!(f, "{}", lval),
+            Self::Num(num, _) => write!(f, "{}", num),
+        }
+    }
+}
+
+impl Display for LVal {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}", self.ident.name)?;
+        for idx in &self.idx {
+            write!(f, "[{}]", idx)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ast_generate::parse_sysy_to_ast;
+
+    #[test]
+    fn display_test() {
+        let source = r#"
+            const int N = 2, M[2][N] = {{1}, 2 * -(3 + 4)};
+            int g[N + 1] = {0x10, 010};
+            int f(int a[], int b[][3]);
+            void h() { return; }
+            int main() {
+                int x = 1, y;
+                if (x < 2 && !y || x != 3) if (x) y = 1; else { y = 2; }
+                while (x) { x = x - 1; if (x % 2 == 0) continue; else break; }
+                ;
+                { h(); }
+                return M[1][0] / (x + 1);
+            }
+        "#;
+        let printed = parse_sysy_to_ast(source).unwrap().to_string();
+        assert!(printed.contains("const int N = 2, M[2][N] = {{1}, 2 * -(3 + 4)};\n"));
+        assert!(printed.contains("int f(int a[], int b[][3]);\n"));
+        assert!(printed.contains("    if (x < 2 && !y || x != 3) {\n        if (x) {\n            y = 1;\n        }\n        else {\n"));
+        // printing is a fixed point, and the printed program parses to the same AST
+        let reprinted = parse_sysy_to_ast(&printed).unwrap().to_string();
+        assert_eq!(printed, reprinted);
+    }
+}
@@ -0,0 +1,114 @@
+/// A region of the SysY source code.
+///
+/// `start` and `end` are byte offsets (`end` is exclusive), as reported by `lalrpop`'s `@L` and `@R` markers.
+/// `line` and `col` locate `start`, and both of them count from 1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    pub col: usize,
+}
+
+impl Span {
+    /// Return the smallest span covering both `self` and `other`.
+    pub fn join(self, other: Span) -> Span {
+        let first = if self.start <= other.start {
+            self
+        } else {
+            other
+        };
+        Span {
+            start: first.start,
+            end: self.end.max(other.end),
+            line: first.line,
+            col: first.col,
+        }
+    }
+
+    /// Return the length of the span in bytes.
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    /// Return whether the span covers no source text at all.
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+}
+
+/// The start offsets of all the lines in a source file.
+///
+/// It is passed to the parser, so that byte offsets can be turned into line/column positions.
+pub struct LineIndex {
+    line_starts: Vec<usize>,
+}
+
+impl LineIndex {
+    /// Scan the source code and record where each line starts.
+    pub fn new(source: &str) -> Self {
+        let mut line_starts = vec![0];
+        line_starts.extend(source.match_indices('\n').map(|(i, _)| i + 1));
+        Self { line_starts }
+    }
+
+    /// Get the (line, column) position of a byte offset.
+    pub fn position(&self, offset: usize) -> (usize, usize) {
+        let line = match self.line_starts.binary_search(&offset) {
+            Ok(i) => i,
+            Err(i) => i - 1,
+        };
+        (line + 1, offset - self.line_starts[line] + 1)
+    }
+
+    /// Build a `Span` from the byte offsets `[start, end)`.
+    pub fn span(&self, start: usize, end: usize) -> Span {
+        let (line, col) = self.position(start);
+        Span {
+            start,
+            end,
+            line,
+            col,
+        }
+    }
+
+    /// Get the byte range `[start, end)` of the given line (counting from 1), without the line break.
+    pub fn line_range(&self, line: usize, source: &str) -> (usize, usize) {
+        let start = self.line_starts[line - 1];
+        let end = match self.line_starts.get(line) {
+            Some(&next) => next - 1,
+            None => source.len(),
+        };
+        if end > start && source.as_bytes()[end - 1] == b'\r' {
+            (start, end - 1)
+        } else {
+            (start, end)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn line_index_test() {
+        let src = "int main() {\n  return 0;\n}\n";
+        let index = LineIndex::new(src);
+        assert_eq!(index.position(0), (1, 1));
+        assert_eq!(index.position(4), (1, 5));
+        assert_eq!(index.position(15), (2, 3));
+        assert_eq!(index.position(25), (3, 1));
+        assert_eq!(index.line_range(2, src), (13, 24));
+        assert_eq!(index.line_range(4, src), (27, 27));
+    }
+
+    #[test]
+    fn span_join_test() {
+        let index = LineIndex::new("a + b");
+        let a = index.span(0, 1);
+        let b = index.span(4, 5);
+        assert_eq!(a.join(b), index.span(0, 5));
+        assert_eq!(b.join(a), index.span(0, 5));
+    }
+}
@@ -0,0 +1,152 @@
+use super::span::{LineIndex, Span};
+use crate::diagnostic::Diagnostic;
+use std::fmt;
+
+const KEYWORDS: [&str; 9] = [
+    "break", "const", "continue", "else", "if", "int", "return", "void", "while",
+];
+
+/// The punctuations, where the longer ones come first.
+const PUNCTUATIONS: [&str; 22] = [
+    "&&", "||", "<=", ">=", "==", "!=", "+", "-", "*", "/", "%", "!", "<", ">", "=", "(", ")", "{",
+    "}", "[", "]", ";",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenKind {
+    Keyword,
+    Ident,
+    IntLiteral,
+    Punctuation,
+}
+
+/// A token of the SysY source code.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpannedToken {
+    pub kind: TokenKind,
+    pub text: String,
+    pub span: Span,
+}
+
+impl fmt::Display for SpannedToken {
+    /// Print the token like `3:5 ident main`.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let kind = match self.kind {
+            TokenKind::Keyword => "keyword",
+            TokenKind::Ident => "ident",
+            TokenKind::IntLiteral => "int",
+            TokenKind::Punctuation => "punct",
+        };
+        write!(
+            f,
+            "{}:{} {} {}",
+            self.span.line, self.span.col, kind, self.text
+        )
+    }
+}
+
+/// Split the SysY source code into tokens, skipping the spaces and the comments.
+///
+/// The tokens are the same as those of the parser: an integer literal is the longest decimal,
+/// octal (prefixed by `0`) or hexadecimal (prefixed by `0x`) literal, so `09` is two tokens.
+///
+/// # Errors
+/// An error is returned at the first character that cannot start a token.
+pub fn tokenize(input: &str) -> Result<Vec<SpannedToken>, Diagnostic> {
+    let index = LineIndex::new(input);
+    let bytes = input.as_bytes();
+    let mut tokens = Vec::new();
+    let mut pos = 0;
+    while pos < bytes.len() {
+        let rest = &input[pos..];
+        let c = bytes[pos];
+        if c.is_ascii_whitespace() {
+            pos += 1;
+            continue;
+        }
+        if rest.starts_with("//") {
+            pos += rest.find(['\n', '\r']).unwrap_or(rest.len());
+            continue;
+        }
+        if let Some(end) = rest.strip_prefix("/*").and_then(|body| body.find("*/")) {
+            pos += end + 4;
+            continue;
+        }
+
+        let is_word = |c: u8| c.is_ascii_alphanumeric() || c == b'_';
+        let len_while = |f: &dyn Fn(u8) -> bool, from: usize| {
+            from + rest.bytes().skip(from).take_while(|&c| f(c)).count()
+        };
+        let (kind, len) = if c.is_ascii_alphabetic() || c == b'_' {
+            let len = len_while(&is_word, 1);
+            match KEYWORDS.contains(&&rest[..len]) {
+                true => (TokenKind::Keyword, len),
+                false => (TokenKind::Ident, len),
+            }
+        } else if rest.len() > 2
+            && (rest.starts_with("0x") || rest.starts_with("0X"))
+            && bytes[pos + 2].is_ascii_hexdigit()
+        {
+            (
+                TokenKind::IntLiteral,
+                len_while(&|c| c.is_ascii_hexdigit(), 2),
+            )
+        } else if c == b'0' {
+            (
+                TokenKind::IntLiteral,
+                len_while(&|c| (b'0'..=b'7').contains(&c), 1),
+            )
+        } else if c.is_ascii_digit() {
+            (TokenKind::IntLiteral, len_while(&|c| c.is_ascii_digit(), 1))
+        } else if let Some(p) = PUNCTUATIONS.iter().find(|p| rest.starts_with(*p)) {
+            (TokenKind::Punctuation, p.len())
+        } else {
+            let len = rest.chars().next().unwrap().len_utf8();
+            return Err(Diagnostic::error("syntax error: invalid token")
+                .with_primary(index.span(pos, pos + len), "invalid token"));
+        };
+        tokens.push(SpannedToken {
+            kind,
+            text: rest[..len].into(),
+            span: index.span(pos, pos + len),
+        });
+        pos += len;
+    }
+    Ok(tokens)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tokenize_test() {
+        let input = "int main() {\n  // comment\n  return 0x1F+09 /* a\n b */ <= a_1;\n}";
+        let tokens: Vec<String> = tokenize(input)
+            .unwrap()
+            .iter()
+            .map(|t| t.to_string())
+            .collect();
+        assert_eq!(
+            tokens,
+            [
+                "1:1 keyword int",
+                "1:5 ident main",
+                "1:9 punct (",
+                "1:10 punct )",
+                "1:12 punct {",
+                "3:3 keyword return",
+                "3:10 int 0x1F",
+                "3:14 punct +",
+                "3:15 int 0",
+                "3:16 int 9",
+                "4:7 punct <=",
+                "4:10 ident a_1",
+                "4:13 punct ;",
+                "5:1 punct }",
+            ]
+        );
+        let error = tokenize("int a = 1 & 2;").unwrap_err();
+        assert_eq!(error.message, "syntax error: invalid token");
+    }
+}
@@ -0,0 +1,762 @@
+use crate::ast_generate::ast::*;
+use crate::diagnostic::Diagnostic;
+use crate::koopa_interpret::{InterpretOptions, InterpretResult};
+use crate::sysy_runtime::{SysyIo, RUNTIME_FUNCTIONS};
+use std::collections::HashMap;
+
+/// Calls nested deeper than this are reported as an error, instead of overflowing the stack of the interpreter.
+const MAX_CALL_DEPTH: usize = 10000;
+/// The stack size of the thread running the interpreter, which is enough for `MAX_CALL_DEPTH` calls.
+const INTERPRETER_STACK_SIZE: usize = 1 << 30;
+
+/// Run a SysY program by walking its AST, as the reference of the compiled programs.
+///
+/// The AST should have passed `semantic::check_ast`.
+/// The options and the result are the same as the ones of the Koopa interpreter,
+/// and the instruction count is the number of executed statements.
+/// Unlike the compiled programs, undefined behaviors are reported as errors,
+/// including integer overflow, division by zero, out-of-bounds indexes and uninitialized variables.
+///
+/// # Errors
+/// An error is returned if the program has undefined behavior, or it runs for too long.
+pub fn interpret_ast(
+    ast: &CompUnit,
+    stdin: &str,
+    options: &InterpretOptions,
+) -> Result<InterpretResult, Diagnostic> {
+    // the interpreter recurses on the AST, which needs a larger stack than the default one
+    std::thread::scope(|scope| {
+        std::thread::Builder::new()
+            .stack_size(INTERPRETER_STACK_SIZE)
+            .spawn_scoped(scope, || run(ast, stdin, options))
+            .expect("cannot spawn the thread of the AST interpreter")
+            .join()
+            .expect("the AST interpreter panics")
+    })
+}
+
+fn run(
+    ast: &CompUnit,
+    stdin: &str,
+    options: &InterpretOptions,
+) -> Result<InterpretResult, Diagnostic> {
+    let mut interpreter = AstInterpreter {
+        funcs: HashMap::new(),
+        globals: HashMap::new(),
+        frames: Vec::new(),
+        memory: Vec::new(),
+        memory_limit: options.stack_size / 4,
+        io: SysyIo::new(stdin),
+        max_statements: options.max_instructions,
+        statement_count: 0,
+    };
+    for item in &ast.items {
+        match item {
+            CompUnitItem::FuncDef(func_def) => {
+                interpreter
+                    .funcs
+                    .insert(func_def.ident.name.clone(), func_def);
+            }
+            CompUnitItem::GlobalDecl(global_decl) => interpreter.decl(&global_decl.decl)?,
+            CompUnitItem::FuncDecl(_) => {}
+        }
+    }
+    interpreter.memory_limit += interpreter.memory.len();
+    let exit_code = interpreter
+        .call("main", Vec::new(), Span::default())?
+        .unwrap_or(0);
+    Ok(InterpretResult {
+        stdout: interpreter.io.into_stdout(),
+        exit_code,
+        instruction_count: interpreter.statement_count,
+    })
+}
+
+/// A (sub-)array, which is the value of an array name or a partially indexed array.
+#[derive(Debug, Clone)]
+struct ArrayRef {
+    /// The address of the first element.
+    address: usize,
+    /// The length of each dimension.
+    dims: Vec<usize>,
+    /// The end of the memory allocated together with the array, which bounds the array parameters.
+    end: usize,
+}
+
+/// What a name refers to.
+#[derive(Debug, Clone)]
+enum Binding {
+    /// An `int` variable or constant at the address.
+    Int(usize),
+    Array(ArrayRef),
+}
+
+/// The value of an expression.
+#[derive(Debug, Clone)]
+enum Value {
+    Int(i32),
+    Array(ArrayRef),
+}
+
+/// How a statement finishes.
+enum Flow {
+    Normal,
+    Break,
+    Continue,
+    Return(Option<i32>),
+}
+
+struct AstInterpreter<'a> {
+    funcs: HashMap<String, &'a FuncDef>,
+    globals: HashMap<String, Binding>,
+    /// The scopes of the running functions, from the outermost to the innermost.
+    frames: Vec<Vec<HashMap<String, Binding>>>,
+    /// Every variable lives here, and `None` means an uninitialized one.
+    memory: Vec<Option<i32>>,
+    memory_limit: usize,
+    io: SysyIo,
+    max_statements: Option<u64>,
+    statement_count: u64,
+}
+
+impl<'a> AstInterpreter<'a> {
+    fn lookup(&self, ident: &Ident) -> Result<Binding, Diagnostic> {
+        let local = self
+            .frames
+            .last()
+            .and_then(|scopes| scopes.iter().rev().find_map(|scope| scope.get(&ident.name)));
+        local
+            .or_else(|| self.globals.get(&ident.name))
+            .cloned()
+            .ok_or_else(|| {
+                Diagnostic::error(format!("`{}` is not defined", ident.name))
+                    .with_primary(ident.span, "undefined name")
+            })
+    }
+
+    fn bind(&mut self, ident: &Ident, binding: Binding) {
+        match self.frames.last_mut() {
+            Some(scopes) => scopes
+                .last_mut()
+                .unwrap()
+                .insert(ident.name.clone(), binding),
+            None => self.globals.insert(ident.name.clone(), binding),
+        };
+    }
+
+    fn alloc(&mut self, size: usize, init: Option<i32>, span: Span) -> Result<usize, Diagnostic> {
+        if self.frames.is_empty() {
+            // the limit only applies to the stack
+            self.memory_limit += size;
+        }
+        if self.memory.len() + size > self.memory_limit {
+            return Err(Diagnostic::error("the program overflows the stack")
+                .with_primary(span, "too much memory"));
+        }
+        let address = self.memory.len();
+        self.memory.resize(address + size, init);
+        Ok(address)
+    }
+
+    fn load(&self, address: usize, span: Span) -> Result<i32, Diagnostic> {
+        self.memory[address].ok_or_else(|| {
+            Diagnostic::error("the program reads an uninitialized variable")
+                .with_primary(span, "uninitialized")
+        })
+    }
+
+    ////////////////////////////////////////////////////////////////////////
+    // Declarations                                                       //
+    ////////////////////////////////////////////////////////////////////////
+
+    fn decl(&mut self, decl: &Decl) -> Result<(), Diagnostic> {
+        match decl {
+            Decl::Const(const_decl) => {
+                for def in &const_decl.defs {
+                    let dims = self.dims(&def.dims)?;
+                    self.define(&def.ident, &dims, Some(&def.init), def.span)?;
+                }
+            }
+            Decl::Var(var_decl) => {
+                for def in &var_decl.defs {
+                    let dims = self.dims(&def.dims)?;
+                    self.define(&def.ident, &dims, def.init.as_ref(), def.span)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn dims(&mut self, dims: &[ConstExp]) -> Result<Vec<usize>, Diagnostic> {
+        dims.iter()
+            .map(|dim| match self.int(&dim.exp)? {
+                d if d > 0 => Ok(d as usize),
+                d => Err(Diagnostic::error(format!(
+                    "the length of an array dimension must be positive, but it is {}",
+                    d
+                ))
+                .with_primary(dim.span(), "invalid array length")),
+            })
+            .collect()
+    }
+
+    /// Allocate and initialize a variable or a constant.
+    ///
+    /// Global variables and arrays with an initializer are filled with zeros first.
+    fn define<I: Initializer>(
+        &mut self,
+        ident: &Ident,
+        dims: &[usize],
+        init: Option<&I>,
+        span: Span,
+    ) -> Result<(), Diagnostic>
+    where
+        I::Exp: AsExp,
+    {
+        let size = dims.iter().product();
+        let zeroed = self.frames.is_empty() || (init.is_some() && !dims.is_empty());
+        let address = self.alloc(size, zeroed.then_some(0), span)?;
+        if let Some(init) = init {
+            let mut values = Vec::new();
+            self.flatten(init, dims, &mut values)?;
+            for (i, value) in values.into_iter().enumerate() {
+                self.memory[address + i] = Some(value);
+            }
+        }
+        let binding = if dims.is_empty() {
+            Binding::Int(address)
+        } else {
+            Binding::Array(ArrayRef {
+                address,
+                dims: dims.to_vec(),
+                end: address + size,
+            })
+        };
+        self.bind(ident, binding);
+        Ok(())
+    }
+
+    /// Evaluate an initializer of an array with the given dimensions, and append the elements.
+    ///
+    /// The elements not given are zeros.
+    /// An initializer list inside a list initializes the largest sub-array aligned with the current element,
+    /// which is a single element if no sub-array is aligned.
+    fn flatten<I: Initializer>(
+        &mut self,
+        init: &I,
+        dims: &[usize],
+        values: &mut Vec<i32>,
+    ) -> Result<(), Diagnostic>
+    where
+        I::Exp: AsExp,
+    {
+        let size: usize = dims.iter().product();
+        let start = values.len();
+        match (init.exp(), init.elements()) {
+            (Some(exp), _) if dims.is_empty() => values.push(self.int(exp.as_exp())?),
+            (_, Some(elements)) => {
+                for element in elements {
+                    let filled = values.len() - start;
+                    if filled >= size {
+                        return Err(
+                            Diagnostic::error("too many elements in the initializer list")
+                                .with_primary(element.span(), "extra element"),
+                        );
+                    }
+                    match element.exp() {
+                        Some(exp) => values.push(self.int(exp.as_exp())?),
+                        None => {
+                            let mut level = 1.min(dims.len());
+                            while level < dims.len()
+                                && !filled.is_multiple_of(dims[level..].iter().product::<usize>())
+                            {
+                                level += 1;
+                            }
+                            self.flatten(element, &dims[level..], values)?;
+                        }
+                    }
+                }
+            }
+            _ => {
+                return Err(
+                    Diagnostic::error("an array is initialized by an expression")
+                        .with_primary(init.span(), "mismatched initializer"),
+                )
+            }
+        }
+        values.resize(start + size, 0);
+        Ok(())
+    }
+
+    ////////////////////////////////////////////////////////////////////////
+    // Functions and statements                                           //
+    ////////////////////////////////////////////////////////////////////////
+
+    /// Call a function, and return its return value.
+    fn call(
+        &mut self,
+        name: &str,
+        args: Vec<Value>,
+        span: Span,
+    ) -> Result<Option<i32>, Diagnostic> {
+        let Some(&func_def) = self.funcs.get(name) else {
+            if RUNTIME_FUNCTIONS.contains(&name) {
+                return self.call_runtime(name, &args, span);
+            }
+            return Err(Diagnostic::error(format!(
+                "the function `{}` is declared but not defined",
+                name
+            ))
+            .with_primary(span, "undefined function"));
+        };
+        if self.frames.len() >= MAX_CALL_DEPTH {
+            return Err(
+                Diagnostic::error("the calls are nested too deeply").with_primary(span, "too deep")
+            );
+        }
+
+        let stack_mark = self.memory.len();
+        let mut scope = HashMap::new();
+        for (param, arg) in func_def.params.iter().zip(args) {
+            let binding = match arg {
+                Value::Int(i) => {
+                    let address = self.alloc(1, Some(i), param.span)?;
+                    Binding::Int(address)
+                }
+                Value::Array(mut array) => {
+                    // the first dimension of an array parameter is bounded by the memory it points to
+                    let inner: usize = array.dims[1..].iter().product();
+                    array.dims[0] = (array.end - array.address) / inner;
+                    Binding::Array(array)
+                }
+            };
+            scope.insert(param.ident.name.clone(), binding);
+        }
+        self.frames.push(vec![scope]);
+        let flow = self.block(&func_def.block);
+        self.frames.pop();
+        self.memory.truncate(stack_mark);
+
+        match flow? {
+            Flow::Return(value) => Ok(value),
+            _ if name == "main" => Ok(Some(0)),
+            _ => Ok(None),
+        }
+    }
+
+    fn call_runtime(
+        &mut self,
+        name: &str,
+        args: &[Value],
+        span: Span,
+    ) -> Result<Option<i32>, Diagnostic> {
+        let array_arg = |i: usize| match args.get(i) {
+            Some(Value::Array(array)) => Ok(array.clone()),
+            _ => Err(Diagnostic::error(format!("`{}` expects an array", name))
+                .with_primary(span, "invalid arguments")),
+        };
+        let int_arg = |i: usize| match args.get(i) {
+            Some(&Value::Int(value)) => Ok(value),
+            _ => Err(Diagnostic::error(format!("`{}` expects an integer", name))
+                .with_primary(span, "invalid arguments")),
+        };
+        let out_of_bounds = || {
+            Diagnostic::error("the program accesses an array out of bounds")
+                .with_primary(span, "out of bounds")
+        };
+        Ok(match name {
+            "getint" => Some(self.io.get_int()),
+            "getch" => Some(self.io.get_ch()),
+            "getarray" => {
+                let array = array_arg(0)?;
+                let n = self.io.get_int();
+                for i in 0..n.max(0) as usize {
+                    if array.address + i >= array.end {
+                        return Err(out_of_bounds());
+                    }
+                    self.memory[array.address + i] = Some(self.io.get_int());
+                }
+                Some(n)
+            }
+            "putint" => {
+                self.io.put_int(int_arg(0)?);
+                None
+            }
+            "putch" => {
+                self.io.put_ch(int_arg(0)?);
+                None
+            }
+            "putarray" => {
+                let (n, array) = (int_arg(0)?, array_arg(1)?);
+                let mut values = Vec::new();
+                for i in 0..n.max(0) as usize {
+                    if array.address + i >= array.end {
+                        return Err(out_of_bounds());
+                    }
+                    values.push(self.load(array.address + i, span)?);
+                }
+                self.io.put_array(&values);
+                None
+            }
+            // the timer is not simulated
+            _ => None,
+        })
+    }
+
+    fn block(&mut self, block: &Block) -> Result<Flow, Diagnostic> {
+        self.frames.last_mut().unwrap().push(HashMap::new());
+        let mut flow = Ok(Flow::Normal);
+        for item in &block.items {
+            flow = match item {
+                BlockItem::Decl(decl) => self.decl(decl).map(|()| Flow::Normal),
+                BlockItem::Stmt(stmt) => self.stmt(stmt),
+            };
+            if !matches!(flow, Ok(Flow::Normal)) {
+                break;
+            }
+        }
+        self.frames.last_mut().unwrap().pop();
+        flow
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) -> Result<Flow, Diagnostic> {
+        self.statement_count += 1;
+        if self
+            .max_statements
+            .is_some_and(|max| self.statement_count > max)
+        {
+            return Err(Diagnostic::error(format!(
+                "the program does not finish within {} statements",
+                self.statement_count - 1
+            )));
+        }
+        match &stmt.kind {
+            StmtKind::Assign(lval, exp) => {
+                let address = self.lval_address(lval)?;
+                let value = self.int(exp)?;
+                self.memory[address] = Some(value);
+            }
+            StmtKind::Exp(exp) => {
+                if let Some(exp) = exp {
+                    self.exp(exp)?;
+                }
+            }
+            StmtKind::Block(block) => return self.block(block),
+            StmtKind::If(cond, then, otherwise) => {
+                if self.int(cond)? != 0 {
+                    return self.stmt(then);
+                } else if let Some(otherwise) = otherwise {
+                    return self.stmt(otherwise);
+                }
+            }
+            StmtKind::While(cond, body) => {
+                while self.int(cond)? != 0 {
+                    match self.stmt(body)? {
+                        Flow::Break => break,
+                        Flow::Normal | Flow::Continue => {}
+                        flow @ Flow::Return(_) => return Ok(flow),
+                    }
+                }
+            }
+            StmtKind::Break => return Ok(Flow::Break),
+            StmtKind::Continue => return Ok(Flow::Continue),
+            StmtKind::Return(exp) => {
+                let value = exp.as_ref().map(|exp| self.int(exp)).transpose()?;
+                return Ok(Flow::Return(value));
+            }
+        }
+        Ok(Flow::Normal)
+    }
+
+    ////////////////////////////////////////////////////////////////////////
+    // Expressions                                                        //
+    ////////////////////////////////////////////////////////////////////////
+
+    fn int(&mut self, exp: &Exp) -> Result<i32, Diagnostic> {
+        as_int(self.exp(exp)?, exp.span)
+    }
+
+    fn exp(&mut self, exp: &Exp) -> Result<Value, Diagnostic> {
+        match &exp.exp {
+            LOrExp::LAnd(and) => self.land(and),
+            lor => self.lor(lor).map(Value::Int),
+        }
+    }
+
+    fn lor(&mut self, exp: &LOrExp) -> Result<i32, Diagnostic> {
+        match exp {
+            LOrExp::LAnd(and) => self.land_int(and),
+            LOrExp::LOrLAnd(lhs, rhs) => {
+                Ok((self.lor(lhs)? != 0 || self.land_int(rhs)? != 0) as i32)
+            }
+        }
+    }
+
+    fn land_int(&mut self, exp: &LAndExp) -> Result<i32, Diagnostic> {
+        as_int(self.land(exp)?, exp.span())
+    }
+
+    fn land(&mut self, exp: &LAndExp) -> Result<Value, Diagnostic> {
+        match exp {
+            LAndExp::Eq(eq) => self.eq(eq),
+            LAndExp::LAndEq(lhs, rhs) => {
+                let value = self.land_int(lhs)? != 0 && as_int(self.eq(rhs)?, rhs.span())? != 0;
+                Ok(Value::Int(value as i32))
+            }
+        }
+    }
+
+    fn eq(&mut self, exp: &EqExp) -> Result<Value, Diagnostic> {
+        match exp {
+            EqExp::Rel(rel) => self.rel(rel),
+            EqExp::EqRel(lhs, op, rhs) => {
+                let lhs = as_int(self.eq(lhs)?, lhs.span())?;
+                let rhs = as_int(self.rel(rhs)?, rhs.span())?;
+                let value = match op {
+                    EqExpOp::Eq => lhs == rhs,
+                    EqExpOp::Neq => lhs != rhs,
+                };
+                Ok(Value::Int(value as i32))
+            }
+        }
+    }
+
+    fn rel(&mut self, exp: &RelExp) -> Result<Value, Diagnostic> {
+        match exp {
+            RelExp::Add(add) => self.add(add),
+            RelExp::RelAdd(lhs, op, rhs) => {
+                let lhs = as_int(self.rel(lhs)?, lhs.span())?;
+                let rhs = as_int(self.add(rhs)?, rhs.span())?;
+                let value = match op {
+                    RelExpOp::Le => lhs <= rhs,
+                    RelExpOp::Ge => lhs >= rhs,
+                    RelExpOp::Lt => lhs < rhs,
+                    RelExpOp::Gt => lhs > rhs,
+                };
+                Ok(Value::Int(value as i32))
+            }
+        }
+    }
+
+    fn add(&mut self, exp: &AddExp) -> Result<Value, Diagnostic> {
+        match exp {
+            AddExp::Mul(mul) => self.mul(mul),
+            AddExp::AddMul(lhs, op, rhs) => {
+                let span = exp.span();
+                let lhs = as_int(self.add(lhs)?, lhs.span())?;
+                let rhs = as_int(self.mul(rhs)?, rhs.span())?;
+                let value = match op {
+                    AddExpOp::Add => lhs.checked_add(rhs),
+                    AddExpOp::Sub => lhs.checked_sub(rhs),
+                };
+                value.map(Value::Int).ok_or_else(|| overflow(span))
+            }
+        }
+    }
+
+    fn mul(&mut self, exp: &MulExp) -> Result<Value, Diagnostic> {
+        match exp {
+            MulExp::Unary(unary) => self.unary(unary),
+            MulExp::MulUnary(lhs, op, rhs) => {
+                let span = exp.span();
+                let lhs = as_int(self.mul(lhs)?, lhs.span())?;
+                let rhs = as_int(self.unary(rhs)?, rhs.span())?;
+                if rhs == 0 && !matches!(op, MulExpOp::Mul) {
+                    return Err(Diagnostic::error("the program divides by zero")
+                        .with_primary(span, "division by zero"));
+                }
+                let value = match op {
+                    MulExpOp::Mul => lhs.checked_mul(rhs),
+                    MulExpOp::Div => lhs.checked_div(rhs),
+                    MulExpOp::Mod => lhs.checked_rem(rhs),
+                };
+                value.map(Value::Int).ok_or_else(|| overflow(span))
+            }
+        }
+    }
+
+    fn unary(&mut self, exp: &UnaryExp) -> Result<Value, Diagnostic> {
+        match exp {
+            UnaryExp::Primary(primary) => self.primary(primary),
+            UnaryExp::Unary(UnaryExpOp::Neg, operand, _) if operand.is_int_min_magnitude() => {
+                Ok(Value::Int(i32::MIN))
+            }
+            UnaryExp::Unary(op, operand, span) => {
+                let value = as_int(self.unary(operand)?, operand.span())?;
+                let value = match op {
+                    UnaryExpOp::Pos => value,
+                    UnaryExpOp::Neg => value.checked_neg().ok_or_else(|| overflow(*span))?,
+                    UnaryExpOp::Not => (value == 0) as i32,
+                };
+                Ok(Value::Int(value))
+            }
+            UnaryExp::FuncCall(ident, args, span) => {
+                let args = args
+                    .iter()
+                    .map(|arg| self.exp(arg))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let value = self.call(&ident.name, args, *span)?;
+                // a void call is only allowed as a statement, where the value is discarded
+                Ok(Value::Int(value.unwrap_or(0)))
+            }
+        }
+    }
+
+    fn primary(&mut self, exp: &PrimaryExp) -> Result<Value, Diagnostic> {
+        match exp {
+            PrimaryExp::Exp(exp) => self.exp(exp),
+            PrimaryExp::Num(num, span) => i32::try_from(*num).map(Value::Int).map_err(|_| {
+                Diagnostic::error("the integer literal is too large")
+                    .with_primary(*span, "too large")
+            }),
+            PrimaryExp::LVal(lval) => match self.lval(lval)? {
+                Binding::Int(address) => Ok(Value::Int(self.load(address, lval.span)?)),
+                Binding::Array(array) => Ok(Value::Array(array)),
+            },
+        }
+    }
+
+    /// Resolve a variable or an array element, checking the indexes.
+    fn lval(&mut self, lval: &LVal) -> Result<Binding, Diagnostic> {
+        let mut binding = self.lookup(&lval.ident)?;
+        for idx in &lval.idx {
+            let i = self.int(idx)?;
+            let Binding::Array(mut array) = binding else {
+                return Err(Diagnostic::error("an integer is indexed")
+                    .with_primary(lval.span, "not an array"));
+            };
+            if i < 0 || i as usize >= array.dims[0] {
+                return Err(Diagnostic::error(format!(
+                    "the index {} is out of bounds of the array of length {}",
+                    i, array.dims[0]
+                ))
+                .with_primary(idx.span, "out of bounds"));
+            }
+            let inner: usize = array.dims[1..].iter().product();
+            array.address += i as usize * inner;
+            array.dims.remove(0);
+            binding = if array.dims.is_empty() {
+                Binding::Int(array.address)
+            } else {
+                Binding::Array(array)
+            };
+        }
+        Ok(binding)
+    }
+
+    fn lval_address(&mut self, lval: &LVal) -> Result<usize, Diagnostic> {
+        match self.lval(lval)? {
+            Binding::Int(address) => Ok(address),
+            Binding::Array(_) => {
+                Err(Diagnostic::error("an array is assigned").with_primary(lval.span, "array"))
+            }
+        }
+    }
+}
+
+fn as_int(value: Value, span: Span) -> Result<i32, Diagnostic> {
+    match value {
+        Value::Int(value) => Ok(value),
+        Value::Array(_) => {
+            Err(Diagnostic::error("an array is used as an integer").with_primary(span, "array"))
+        }
+    }
+}
+
+fn overflow(span: Span) -> Diagnostic {
+    Diagnostic::error("the integer overflows").with_primary(span, "overflow")
+}
+
+/// The expression in an initializer, which is an `Exp` or a `ConstExp`.
+trait AsExp {
+    fn as_exp(&self) -> &Exp;
+}
+
+impl AsExp for Exp {
+    fn as_exp(&self) -> &Exp {
+        self
+    }
+}
+
+impl AsExp for ConstExp {
+    fn as_exp(&self) -> &Exp {
+        &self.exp
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast_generate::parse_sysy_to_ast;
+
+    fn interpret(source: &str, stdin: &str) -> Result<InterpretResult, Diagnostic> {
+        let ast = parse_sysy_to_ast(source).unwrap();
+        crate::semantic::check_ast(&ast).unwrap();
+        interpret_ast(&ast, stdin, &InterpretOptions::default())
+    }
+
+    #[test]
+    fn interpret_ast_test() {
+        let source = r"
+const int N = 3;
+int g[N][2] = {1, 2, {3}, {4}};
+int sum(int a[][2], int n) {
+  int s = 0, i = 0;
+  while (1) {
+    if (i >= n) break;
+    s = s + a[i][0] * 10 + a[i][1];
+    i = i + 1;
+  }
+  return s;
+}
+int side(int x) { putint(x); return x; }
+int main() {
+  int a[2][2][2] = {{1}, 2, 3, {4, 5}};
+  putarray(8, a[0][0]);
+  if (side(0) && side(1) || side(2)) putch(10);
+  putint(sum(g, N));
+  return -2147483648 / 2 + getint();
+}";
+        let result = interpret(source, "7").unwrap();
+        assert_eq!(result.stdout, "8: 1 0 0 0 2 3 4 5\n02\n82");
+        assert_eq!(result.exit_code, -1073741824 + 7);
+    }
+
+    #[test]
+    fn interpret_ast_undefined_behavior_test() {
+        let cases = [
+            (
+                "int main() { int a = 2147483647; return a + 1; }",
+                "the integer overflows",
+            ),
+            (
+                "int main() { int a = 0; return 1 % a; }",
+                "the program divides by zero",
+            ),
+            (
+                "int main() { int a; return a; }",
+                "the program reads an uninitialized variable",
+            ),
+            (
+                "int main() { int a[2][3] = {}; return a[0][3]; }",
+                "the index 3 is out of bounds of the array of length 3",
+            ),
+            (
+                "int f(int a[]) { return a[2]; } int main() { int a[2][2] = {}; return f(a[1]); }",
+                "the index 2 is out of bounds of the array of length 2",
+            ),
+            (
+                "int f(int n) { return f(n + 1); } int main() { return f(0); }",
+                "the calls are nested too deeply",
+            ),
+        ];
+        for (source, message) in cases {
+            assert_eq!(
+                interpret(source, "").unwrap_err().message,
+                message,
+                "{}",
+                source
+            );
+        }
+    }
+}
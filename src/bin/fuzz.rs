@@ -0,0 +1,77 @@
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process::exit;
+use sysy_compiler::fuzz::{fuzz_with, Verdict};
+
+const FUZZ_HELP: &str = r#"
+Differential fuzzer of the sysy compiler
+
+Usage: <path-to-fuzz> [--seed N] [--count N] [--out DIR]
+    --seed N: the seed of the first program (default: 0)
+    --count N: the number of programs (default: 1000)
+    --out DIR: write the programs on which the levels disagree to DIR/seed_N.sy and DIR/seed_N.in
+"#;
+
+struct Cli {
+    seed: u64,
+    count: u64,
+    out: Option<PathBuf>,
+}
+
+impl Cli {
+    fn parse() -> Result<Self, ()> {
+        let mut cli = Self {
+            seed: 0,
+            count: 1000,
+            out: None,
+        };
+        let mut args = env::args().skip(1);
+        while let Some(arg) = args.next() {
+            let value = args.next().ok_or(())?;
+            match arg.as_str() {
+                "--seed" => cli.seed = value.parse().map_err(|_| ())?,
+                "--count" => cli.count = value.parse().map_err(|_| ())?,
+                "--out" => cli.out = Some(PathBuf::from(value)),
+                _ => return Err(()),
+            }
+        }
+        Ok(cli)
+    }
+}
+
+fn main() {
+    let Ok(Cli { seed, count, out }) = Cli::parse() else {
+        eprintln!("Error: invalid command line argument!");
+        eprintln!("{}", FUZZ_HELP);
+        exit(2)
+    };
+
+    let mut skipped = 0;
+    let findings = fuzz_with(seed, count, |seed, verdict| match verdict {
+        Verdict::Agree => {}
+        Verdict::Skip(_) => skipped += 1,
+        Verdict::Mismatch(message) => eprintln!("seed {}: {}", seed, message),
+    });
+    println!(
+        "{} programs, {} skipped, {} findings",
+        count,
+        skipped,
+        findings.len()
+    );
+
+    if let Some(dir) = out {
+        for finding in &findings {
+            let path = dir.join(format!("seed_{}", finding.seed));
+            let written = fs::create_dir_all(&dir)
+                .and_then(|()| fs::write(path.with_extension("sy"), &finding.program.source))
+                .and_then(|()| fs::write(path.with_extension("in"), &finding.program.stdin));
+            if let Err(e) = written {
+                eprintln!("Error: cannot write {}: {}", path.display(), e);
+            }
+        }
+    }
+    if !findings.is_empty() {
+        exit(1);
+    }
+}
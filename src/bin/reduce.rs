@@ -0,0 +1,111 @@
+use std::env;
+use std::fs;
+use std::process::exit;
+use sysy_compiler::reduce::{reduce, Predicate};
+use sysy_compiler::Mode;
+
+const REDUCE_HELP: &str = r#"
+Test-case reducer of the sysy compiler
+
+Usage: <path-to-reduce> INPUT -o OUTPUT PREDICATE [--stdin FILE]
+    INPUT: the SysY source file to reduce
+    OUTPUT: the file to write the smallest program found
+PREDICATE (the property the reduced program keeps):
+    --panic MODE: the compiler panics in MODE ("koopa", "riscv" or "perf")
+    --error MODE NAME: the compiler fails in MODE with the error NAME, e.g. "KoopaProgram2RiscvError"
+    --differ LEVEL LEVEL: the program runs differently at the two levels,
+        where a LEVEL is "ast", "koopa" or "riscv", optionally followed by "-O0", "-O1" or "-O2"
+    --stdin FILE: the standard input of the program for "--differ"
+"#;
+
+struct Cli {
+    input: String,
+    output: String,
+    predicate: Predicate,
+    stdin: Option<String>,
+}
+
+fn parse_mode(mode: &str) -> Result<Mode, ()> {
+    match mode {
+        "koopa" => Ok(Mode::Koopa),
+        "riscv" => Ok(Mode::Riscv),
+        "perf" => Ok(Mode::Perf),
+        _ => Err(()),
+    }
+}
+
+impl Cli {
+    fn parse() -> Result<Self, ()> {
+        let mut args = env::args().skip(1);
+        let input = args.next().ok_or(())?;
+        if args.next().as_deref() != Some("-o") {
+            return Err(());
+        }
+        let output = args.next().ok_or(())?;
+        let mut predicate = None;
+        let mut stdin = None;
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or(());
+            match arg.as_str() {
+                "--panic" => predicate = Some(Predicate::Panic(parse_mode(&value()?)?)),
+                "--error" => {
+                    let mode = parse_mode(&value()?)?;
+                    predicate = Some(Predicate::Error(mode, value()?));
+                }
+                "--differ" => {
+                    let reference = value()?.parse()?;
+                    predicate = Some(Predicate::Differ(reference, value()?.parse()?));
+                }
+                "--stdin" => stdin = Some(value()?),
+                _ => return Err(()),
+            }
+        }
+        Ok(Self {
+            input,
+            output,
+            predicate: predicate.ok_or(())?,
+            stdin,
+        })
+    }
+}
+
+fn main() {
+    let Ok(Cli {
+        input,
+        output,
+        predicate,
+        stdin,
+    }) = Cli::parse()
+    else {
+        eprintln!("Error: invalid command line argument!");
+        eprintln!("{}", REDUCE_HELP);
+        exit(2)
+    };
+    let Ok(source) = fs::read_to_string(&input) else {
+        eprintln!("Error: cannot read input file {}!", input);
+        exit(2)
+    };
+    let stdin = match stdin.map(fs::read_to_string) {
+        Some(Ok(stdin)) => stdin,
+        Some(Err(_)) => {
+            eprintln!("Error: cannot read the standard input file!");
+            exit(2)
+        }
+        None => String::new(),
+    };
+
+    match reduce(&source, &stdin, &predicate) {
+        Ok(ast) => {
+            let reduced = ast.to_string();
+            if fs::write(&output, &reduced).is_err() {
+                eprintln!("Error: cannot write file {}!", output);
+                exit(2)
+            }
+            println!("reduced {} to {} lines", input, reduced.lines().count());
+        }
+        Err(message) => {
+            eprintln!("Error: {}", message);
+            exit(1)
+        }
+    }
+}
@@ -0,0 +1,212 @@
+use crate::ast_generate::span::{LineIndex, Span};
+use std::fmt;
+
+/// How serious a diagnostic is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+    Note,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Error => write!(f, "error"),
+            Self::Warning => write!(f, "warning"),
+            Self::Note => write!(f, "note"),
+        }
+    }
+}
+
+/// A piece of source code pointed at by a diagnostic, with a short explanation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Label {
+    pub span: Span,
+    pub message: String,
+}
+
+/// A problem found by the compiler.
+///
+/// The primary label marks the code that causes the problem,
+/// while secondary labels mark related code (e.g. a previous definition).
+/// Diagnostics raised by the back end usually carry no label at all,
+/// since the Koopa program has no connection to the SysY source code.
+///
+/// The primary label is boxed to keep `Result<_, Diagnostic>` small.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    pub primary: Option<Box<Label>>,
+    pub secondary: Vec<Label>,
+    pub notes: Vec<String>,
+}
+
+impl Diagnostic {
+    /// Construct a new diagnostic without any label or note.
+    pub fn new(severity: Severity, message: impl Into<String>) -> Self {
+        Self {
+            severity,
+            message: message.into(),
+            primary: None,
+            secondary: Vec::new(),
+            notes: Vec::new(),
+        }
+    }
+
+    /// Construct a new error.
+    pub fn error(message: impl Into<String>) -> Self {
+        Self::new(Severity::Error, message)
+    }
+
+    /// Construct a new warning.
+    pub fn warning(message: impl Into<String>) -> Self {
+        Self::new(Severity::Warning, message)
+    }
+
+    /// Set the primary label.
+    pub fn with_primary(mut self, span: Span, message: impl Into<String>) -> Self {
+        self.primary = Some(Box::new(Label {
+            span,
+            message: message.into(),
+        }));
+        self
+    }
+
+    /// Add a secondary label.
+    pub fn with_secondary(mut self, span: Span, message: impl Into<String>) -> Self {
+        self.secondary.push(Label {
+            span,
+            message: message.into(),
+        });
+        self
+    }
+
+    /// Add a note, which is printed after the source snippet.
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+
+    /// Return whether the diagnostic is an error.
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+
+    /// Render the diagnostic in the style of `rustc`.
+    ///
+    /// ```text
+    /// error: cannot find value `x` in this scope
+    ///  --> main.c:3:12
+    ///   |
+    /// 3 |     return x;
+    ///   |            ^ not found in this scope
+    /// ```
+    ///
+    /// The primary label is underlined with `^`, and the secondary ones with `-`.
+    /// `source` must be the text that the spans refer to.
+    pub fn render(&self, source: &str, file_name: &str) -> String {
+        let mut out = format!("{}: {}", self.severity, self.message);
+
+        let mut labels: Vec<(&Label, bool)> = self
+            .primary
+            .iter()
+            .map(|l| (l.as_ref(), true))
+            .chain(self.secondary.iter().map(|l| (l, false)))
+            .collect();
+        let width = labels
+            .iter()
+            .map(|(l, _)| l.span.line.to_string().len())
+            .max()
+            .unwrap_or(0);
+        let pad = " ".repeat(width);
+
+        if let Some((first, _)) = labels.first() {
+            let index = LineIndex::new(source);
+            out.push_str(&format!(
+                "\n{}--> {}:{}:{}",
+                pad, file_name, first.span.line, first.span.col
+            ));
+            out.push_str(&format!("\n{} |", pad));
+
+            labels.sort_by_key(|(l, _)| l.span.start);
+            for (label, is_primary) in labels {
+                let (line_start, line_end) = index.line_range(label.span.line, source);
+                let line = &source[line_start..line_end];
+                // keep the tabs, so that the marks are aligned with the code above them
+                let indent: String = source[line_start..label.span.start.min(line_end)]
+                    .chars()
+                    .map(|c| if c == '\t' { '\t' } else { ' ' })
+                    .collect();
+                let n_marks = label
+                    .span
+                    .end
+                    .min(line_end)
+                    .saturating_sub(label.span.start)
+                    .max(1);
+                let mark = if is_primary { "^" } else { "-" };
+
+                out.push_str(&format!("\n{:>width$} | {}", label.span.line, line));
+                out.push_str(&format!("\n{} | {}{}", pad, indent, mark.repeat(n_marks)));
+                if !label.message.is_empty() {
+                    out.push_str(&format!(" {}", label.message));
+                }
+            }
+            if !self.notes.is_empty() {
+                out.push_str(&format!("\n{} |", pad));
+            }
+        }
+
+        for note in self.notes.iter() {
+            out.push_str(&format!("\n{} = note: {}", pad, note));
+        }
+        out
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.severity, self.message)?;
+        if let Some(ref primary) = self.primary {
+            write!(f, " (at {}:{})", primary.span.line, primary.span.col)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_test() {
+        let src = "int main() {\n  const int a = 1;\n  a = 2;\n  return 0;\n}\n";
+        let index = LineIndex::new(src);
+        let diag = Diagnostic::error("cannot assign twice to constant `a`")
+            .with_primary(index.span(34, 39), "cannot assign to a constant")
+            .with_secondary(index.span(25, 26), "`a` is defined as a constant here")
+            .with_note("constants must be initialized when they are defined");
+        assert_eq!(
+            diag.render(src, "main.c"),
+            "error: cannot assign twice to constant `a`
+ --> main.c:3:3
+  |
+2 |   const int a = 1;
+  |             - `a` is defined as a constant here
+3 |   a = 2;
+  |   ^^^^^ cannot assign to a constant
+  |
+  = note: constants must be initialized when they are defined"
+        );
+    }
+
+    #[test]
+    fn render_without_label_test() {
+        let diag = Diagnostic::error("unsupported instruction").with_note("found `undef`");
+        assert_eq!(
+            diag.render("", "main.c"),
+            "error: unsupported instruction\n = note: found `undef`"
+        );
+    }
+}
@@ -0,0 +1,228 @@
+mod generate;
+
+use crate::ast_generate::ast::CompUnit;
+use crate::ast_generate::parse_sysy_to_ast;
+use crate::ast_interpret::interpret_ast;
+use crate::diagnostic::Diagnostic;
+use crate::koopa_interpret::{interpret_koopa_with_options, InterpretOptions};
+use crate::opt::{self, OptLevel, OptOptions};
+use crate::riscv_simulate::{simulate_riscv_with_options, SimulateOptions};
+use crate::{ir_generate, semantic, target_generate};
+pub use generate::{generate_program, GeneratedProgram, Rng};
+use std::fmt;
+use std::str::FromStr;
+
+/// Programs running more statements than this in the AST interpreter are skipped.
+const MAX_STATEMENTS: u64 = 1_000_000;
+/// A compiled program is considered hanging after executing this many instructions.
+const MAX_INSTRUCTIONS: u64 = 100_000_000;
+
+/// A level at which a program runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Level {
+    /// The AST interpreter, which is the reference.
+    Ast,
+    /// The Koopa interpreter, running the program optimized at the level.
+    ///
+    /// At `O2` all the available passes are enabled, including those not in the pipeline of the level.
+    Koopa(OptLevel),
+    /// The RISC-V simulator, running the program compiled at the level.
+    Riscv(OptLevel),
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Ast => write!(f, "AST"),
+            Self::Koopa(level) => write!(f, "Koopa -{:?}", level),
+            Self::Riscv(level) => write!(f, "RISC-V -{:?}", level),
+        }
+    }
+}
+
+impl FromStr for Level {
+    type Err = ();
+
+    /// Parse `ast`, or `koopa` or `riscv` optionally followed by the optimization level, e.g. `riscv-O2`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, level) = match s.split_once("-O") {
+            Some((name, level)) => (name, level.parse()?),
+            None => (s, OptLevel::O0),
+        };
+        match name {
+            "ast" if level == OptLevel::O0 => Ok(Self::Ast),
+            "koopa" => Ok(Self::Koopa(level)),
+            "riscv" => Ok(Self::Riscv(level)),
+            _ => Err(()),
+        }
+    }
+}
+
+/// All the levels, in the order they run.
+pub const LEVELS: [Level; 5] = [
+    Level::Ast,
+    Level::Koopa(OptLevel::O0),
+    Level::Koopa(OptLevel::O2),
+    Level::Riscv(OptLevel::O0),
+    Level::Riscv(OptLevel::O2),
+];
+
+/// The observable behavior of a program.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Outcome {
+    pub stdout: String,
+    pub exit_code: i32,
+}
+
+/// The result of checking a program at all the levels.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Verdict {
+    /// All the levels agree.
+    Agree,
+    /// The program is not checked, e.g. it runs too long.
+    Skip(String),
+    /// A level fails or disagrees with the AST interpreter.
+    Mismatch(String),
+}
+
+/// A program on which the levels don't agree.
+#[derive(Debug, Clone)]
+pub struct Finding {
+    /// The seed that generates the program.
+    pub seed: u64,
+    pub program: GeneratedProgram,
+    pub message: String,
+}
+
+/// Compile the AST and run it at the level.
+pub fn run_at(level: Level, ast: &CompUnit, stdin: &str) -> Result<Outcome, Diagnostic> {
+    let (stdout, exit_code) = match level {
+        Level::Ast => {
+            let options = InterpretOptions {
+                max_instructions: Some(MAX_STATEMENTS),
+                ..InterpretOptions::default()
+            };
+            let result = interpret_ast(ast, stdin, &options)?;
+            (result.stdout, result.exit_code)
+        }
+        Level::Koopa(opt_level) => {
+            let mut options = OptOptions::default();
+            if opt_level == OptLevel::O2 {
+                options.enabled_passes = opt::available_passes()
+                    .map(|(name, _)| name.into())
+                    .collect();
+            }
+            let mut program = ir_generate::parse_ast_to_koopa_program(ast)?;
+            opt::optimize(&mut program, opt_level, &options)?;
+            let options = InterpretOptions {
+                max_instructions: Some(MAX_INSTRUCTIONS),
+                ..InterpretOptions::default()
+            };
+            let result = interpret_koopa_with_options(&program, stdin, &options)?;
+            (result.stdout, result.exit_code)
+        }
+        Level::Riscv(opt_level) => {
+            let mut program = ir_generate::parse_ast_to_koopa_program(ast)?;
+            opt::optimize(&mut program, opt_level, &OptOptions::default())?;
+            let text = target_generate::parse_koopa_program_to_riscv(&program)?;
+            let options = SimulateOptions {
+                max_instructions: Some(MAX_INSTRUCTIONS),
+                ..SimulateOptions::default()
+            };
+            let result = simulate_riscv_with_options(&text, stdin, &options)?;
+            (result.stdout, result.exit_code)
+        }
+    };
+    Ok(Outcome { stdout, exit_code })
+}
+
+/// Run a SysY program at all the levels, and compare the outcomes with that of the AST interpreter.
+///
+/// A program the AST interpreter rejects is a mismatch too, since a generated program must be well-defined.
+pub fn check_program(source: &str, stdin: &str) -> Verdict {
+    let ast = match parse_sysy_to_ast(source) {
+        Ok(ast) => ast,
+        Err(diags) => return Verdict::Mismatch(format!("cannot parse: {}", diags[0].message)),
+    };
+    if let Err(diags) = semantic::check_ast(&ast) {
+        return Verdict::Mismatch(format!("invalid program: {}", diags[0].message));
+    }
+    let expected = match run_at(Level::Ast, &ast, stdin) {
+        Ok(outcome) => outcome,
+        Err(diag) if diag.message.contains("does not finish") => {
+            return Verdict::Skip(diag.message)
+        }
+        Err(diag) => return Verdict::Mismatch(format!("{}: {}", Level::Ast, diag.message)),
+    };
+    for &level in &LEVELS[1..] {
+        match run_at(level, &ast, stdin) {
+            Ok(outcome) if outcome == expected => {}
+            Ok(outcome) => {
+                return Verdict::Mismatch(format!(
+                    "{} outputs {:?} and exits with {}, but {} outputs {:?} and exits with {}",
+                    Level::Ast,
+                    expected.stdout,
+                    expected.exit_code,
+                    level,
+                    outcome.stdout,
+                    outcome.exit_code
+                ))
+            }
+            Err(diag) => return Verdict::Mismatch(format!("{}: {}", level, diag.message)),
+        }
+    }
+    Verdict::Agree
+}
+
+/// Generate programs with the seeds `seed..seed + count`, and check each of them.
+///
+/// `on_program` is called after each program is checked, e.g. to report the progress.
+pub fn fuzz_with(seed: u64, count: u64, mut on_program: impl FnMut(u64, &Verdict)) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    for seed in seed..seed.saturating_add(count) {
+        let program = generate_program(seed);
+        let verdict = check_program(&program.source, &program.stdin);
+        on_program(seed, &verdict);
+        if let Verdict::Mismatch(message) = verdict {
+            findings.push(Finding {
+                seed,
+                program,
+                message,
+            });
+        }
+    }
+    findings
+}
+
+/// Generate programs with the seeds `seed..seed + count`, and return those on which the levels don't agree.
+pub fn fuzz(seed: u64, count: u64) -> Vec<Finding> {
+    fuzz_with(seed, count, |_, _| {})
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_program_test() {
+        let source = "int a[2]; int main() { a[1] = getint(); putint(a[1] / 2); return a[0] + 3; }";
+        assert_eq!(check_program(source, "9"), Verdict::Agree);
+        assert!(matches!(
+            check_program("int main() { return 1 / 0; }", ""),
+            Verdict::Mismatch(_)
+        ));
+        assert!(matches!(
+            check_program("int main() { while (1); }", ""),
+            Verdict::Skip(_)
+        ));
+    }
+
+    #[test]
+    fn level_test() {
+        assert_eq!("ast".parse(), Ok(Level::Ast));
+        assert_eq!("riscv-O2".parse(), Ok(Level::Riscv(OptLevel::O2)));
+        assert_eq!("koopa".parse(), Ok(Level::Koopa(OptLevel::O0)));
+        assert!("ast-O1".parse::<Level>().is_err());
+        assert!("riscv-O".parse::<Level>().is_err());
+    }
+}
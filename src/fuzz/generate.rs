@@ -0,0 +1,1031 @@
+use std::fmt::Write;
+
+/// Every `int` variable holds a value in `[-VALUE_BOUND, VALUE_BOUND]`,
+/// which is kept by reducing the assigned values modulo 1000.
+const VALUE_BOUND: i64 = 1000;
+/// The bound of an expression that is safe to compute.
+const INT_LIMIT: i64 = i32::MAX as i64;
+/// The maximum number of iterations of a loop.
+const MAX_TRIP_COUNT: i64 = 6;
+
+/// A random number generator (SplitMix64), so that a seed always produces the same program.
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// A random integer in `[lo, hi]`.
+    pub fn range(&mut self, lo: i64, hi: i64) -> i64 {
+        lo + (self.next_u64() % (hi - lo + 1) as u64) as i64
+    }
+
+    /// Return true with the given probability in percent.
+    pub fn chance(&mut self, percent: u64) -> bool {
+        self.next_u64() % 100 < percent
+    }
+
+    /// Choose an index with the given weights.
+    pub fn weighted(&mut self, weights: &[u64]) -> usize {
+        let mut x = self.next_u64() % weights.iter().sum::<u64>();
+        for (i, &w) in weights.iter().enumerate() {
+            if x < w {
+                return i;
+            }
+            x -= w;
+        }
+        unreachable!()
+    }
+}
+
+/// A random SysY program together with its standard input.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GeneratedProgram {
+    pub source: String,
+    pub stdin: String,
+}
+
+/// Generate a random SysY program without undefined behavior.
+///
+/// The program uses nested `&&`/`||`, arrays (also as parameters), early `return`s,
+/// `break`/`continue`, shadowing and the SysY runtime library. It's well-defined:
+/// - the values are bounded, so that the arithmetic never overflows, and divisors are never zero;
+/// - all array indexes are reduced into the bounds;
+/// - every loop has a counter with a small trip count, and no function is recursive;
+/// - the operands of an operator are never a call and another call or a read of the memory,
+///   since their order of evaluation is unspecified.
+///
+/// The same seed always produces the same program.
+pub fn generate_program(seed: u64) -> GeneratedProgram {
+    let mut generator = Generator {
+        rng: Rng::new(seed),
+        out: String::new(),
+        indent: 0,
+        globals: Vec::new(),
+        funcs: Vec::new(),
+        scopes: Vec::new(),
+        name_count: 0,
+        loop_depth: 0,
+        returns_int: true,
+        budget: 0,
+    };
+    generator.program();
+    let stdin = (0..256)
+        .map(|i| {
+            let sep = if i % 16 == 15 { "\n" } else { " " };
+            format!("{}{}", generator.rng.range(-VALUE_BOUND, VALUE_BOUND), sep)
+        })
+        .collect();
+    GeneratedProgram {
+        source: generator.out,
+        stdin,
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Ty {
+    /// A variable or a parameter holding a value bounded by `VALUE_BOUND`.
+    Int,
+    /// A constant with the known value.
+    Const(i64),
+    /// A loop counter, which is read-only and bounded by the trip count.
+    Counter(i64),
+    /// An array, with the assumed length of the first dimension for a parameter.
+    Array { dims: Vec<usize>, constant: bool },
+}
+
+#[derive(Debug, Clone)]
+struct Var {
+    name: String,
+    ty: Ty,
+    global: bool,
+}
+
+#[derive(Debug, Clone)]
+enum Param {
+    Int,
+    /// `int a[]` or `int a[][C]`, with the dimensions the function may access.
+    Array(Vec<usize>),
+}
+
+struct Func {
+    name: String,
+    params: Vec<Param>,
+    returns_int: bool,
+}
+
+/// Which operands are not allowed in an expression, because an operand evaluated in an unspecified order
+/// makes a call or reads the memory.
+#[derive(Debug, Clone, Copy, Default)]
+struct Restrict {
+    no_calls: bool,
+    no_memory: bool,
+}
+
+impl Restrict {
+    const LOCALS_ONLY: Self = Self {
+        no_calls: true,
+        no_memory: true,
+    };
+
+    /// The restriction of an operand unsequenced with `e`.
+    fn after(self, e: &Expr) -> Self {
+        Self {
+            no_calls: self.no_calls || e.calls || e.memory,
+            no_memory: self.no_memory || e.calls,
+        }
+    }
+}
+
+/// A generated expression, which is parenthesized unless it's a primary expression.
+struct Expr {
+    text: String,
+    /// The absolute value of the expression is at most `bound`.
+    bound: i64,
+    /// Whether the expression calls a function.
+    calls: bool,
+    /// Whether the expression reads a global variable or an array.
+    memory: bool,
+}
+
+impl Expr {
+    fn leaf(text: String, bound: i64, memory: bool) -> Self {
+        Self {
+            text,
+            bound,
+            calls: false,
+            memory,
+        }
+    }
+
+    /// Reduce the value into `(-1000, 1000)` if it may be out of the range.
+    fn reduced(self) -> Self {
+        if self.bound < VALUE_BOUND {
+            return self;
+        }
+        Self {
+            text: format!("({} % 1000)", self.text),
+            bound: VALUE_BOUND - 1,
+            ..self
+        }
+    }
+}
+
+struct Generator {
+    rng: Rng,
+    out: String,
+    indent: usize,
+    globals: Vec<Var>,
+    funcs: Vec<Func>,
+    /// The scopes of the current function, from the outermost to the innermost.
+    scopes: Vec<Vec<Var>>,
+    name_count: usize,
+    loop_depth: usize,
+    /// Whether the current function returns `int`.
+    returns_int: bool,
+    /// The number of statements that may still be generated in the current function.
+    budget: usize,
+}
+
+impl Generator {
+    fn line(&mut self, text: &str) {
+        for _ in 0..self.indent {
+            self.out.push_str("  ");
+        }
+        self.out.push_str(text);
+        self.out.push('\n');
+    }
+
+    fn new_name(&mut self, prefix: &str) -> String {
+        self.name_count += 1;
+        format!("{}{}", prefix, self.name_count)
+    }
+
+    /// The variables visible in the current scope, where the inner ones shadow the outer ones.
+    fn visible(&self) -> Vec<Var> {
+        let mut vars: Vec<Var> = Vec::new();
+        for var in self
+            .scopes
+            .iter()
+            .rev()
+            .flatten()
+            .chain(self.globals.iter())
+        {
+            if vars.iter().all(|v| v.name != var.name) {
+                vars.push(var.clone());
+            }
+        }
+        vars
+    }
+
+    fn pick<T: Clone>(&mut self, items: &[T]) -> Option<T> {
+        match items.len() {
+            0 => None,
+            n => Some(items[self.rng.range(0, n as i64 - 1) as usize].clone()),
+        }
+    }
+
+    /// Declare a local variable, which sometimes shadows a variable of an outer scope.
+    ///
+    /// The variable is in scope in its own initializer, so a name the initializer uses is never reused.
+    fn declare_local(&mut self, prefix: &str, ty: Ty, init: &str) -> String {
+        let used: Vec<&str> = init
+            .split(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+            .collect();
+        let outer: Vec<String> = self
+            .visible()
+            .into_iter()
+            .filter(|v| self.scopes.last().unwrap().iter().all(|w| w.name != v.name))
+            .map(|v| v.name)
+            .filter(|name| !used.contains(&name.as_str()))
+            .collect();
+        // the outermost scope of `main` never shadows, since the globals are printed there
+        let name = match self.scopes.len() > 1 && self.rng.chance(10) {
+            true => self.pick(&outer).unwrap_or_else(|| self.new_name(prefix)),
+            false => self.new_name(prefix),
+        };
+        self.scopes.last_mut().unwrap().push(Var {
+            name: name.clone(),
+            ty,
+            global: false,
+        });
+        name
+    }
+
+    ////////////////////////////////////////////////////////////////////////
+    // Top level                                                          //
+    ////////////////////////////////////////////////////////////////////////
+
+    fn program(&mut self) {
+        for _ in 0..self.rng.range(1, 4) {
+            self.global_decl();
+        }
+        for _ in 0..self.rng.range(0, 3) {
+            self.function();
+        }
+        self.main();
+    }
+
+    fn literal(&mut self, max: i64) -> (String, i64) {
+        let value = match self.rng.chance(70) {
+            true => self.rng.range(0, 20.min(max)),
+            false => self.rng.range(0, max),
+        };
+        let text = match self.rng.weighted(&[8, 1, 1]) {
+            0 => value.to_string(),
+            1 => format!("{:#x}", value),
+            _ if value == 0 => String::from("0"),
+            _ => format!("0{:o}", value),
+        };
+        (text, value)
+    }
+
+    fn global_decl(&mut self) {
+        match self.rng.weighted(&[3, 1, 2]) {
+            0 => {
+                let name = self.new_name("g");
+                let (text, _) = self.literal(VALUE_BOUND);
+                let init = if self.rng.chance(70) {
+                    format!(" = {}", text)
+                } else {
+                    String::new()
+                };
+                self.line(&format!("int {}{};", name, init));
+                self.globals.push(Var {
+                    name,
+                    ty: Ty::Int,
+                    global: true,
+                });
+            }
+            1 => {
+                let name = self.new_name("c");
+                let (text, value) = self.literal(VALUE_BOUND);
+                self.line(&format!("const int {} = {};", name, text));
+                self.globals.push(Var {
+                    name,
+                    ty: Ty::Const(value),
+                    global: true,
+                });
+            }
+            _ => {
+                let constant = self.rng.chance(20);
+                let name = self.new_name(if constant { "ca" } else { "ga" });
+                let dims = self.array_dims();
+                let init = match !constant && self.rng.chance(30) {
+                    true => String::new(),
+                    false => format!(
+                        " = {}",
+                        self.initializer(&dims, true, Restrict::default()).0
+                    ),
+                };
+                let prefix = if constant { "const int" } else { "int" };
+                self.line(&format!("{} {}{}{};", prefix, name, dims_text(&dims), init));
+                self.globals.push(Var {
+                    name,
+                    ty: Ty::Array { dims, constant },
+                    global: true,
+                });
+            }
+        }
+    }
+
+    fn array_dims(&mut self) -> Vec<usize> {
+        match self.rng.chance(60) {
+            true => vec![self.rng.range(1, 8) as usize],
+            false => vec![self.rng.range(1, 4) as usize, self.rng.range(1, 4) as usize],
+        }
+    }
+
+    /// Generate an initializer list of an array, with literal elements for a global array.
+    ///
+    /// Rows are sometimes enclosed in braces, and the trailing elements are sometimes omitted.
+    /// The elements of a local array are expressions, which may make calls since each element is a full expression.
+    fn initializer(
+        &mut self,
+        dims: &[usize],
+        literal: bool,
+        mut restrict: Restrict,
+    ) -> (String, Restrict) {
+        let cols = *dims.last().unwrap();
+        let total: usize = dims.iter().product();
+        let len = match self.rng.chance(50) {
+            true => total,
+            false => self.rng.range(0, total as i64) as usize,
+        };
+        let mut items = Vec::new();
+        let mut pos = 0;
+        while pos < len {
+            let row = dims.len() > 1 && pos % cols == 0 && self.rng.chance(50);
+            let n = if row {
+                self.rng.range(0, cols as i64) as usize
+            } else {
+                1
+            };
+            let mut elements = Vec::new();
+            for _ in 0..n {
+                let element = match literal {
+                    true => self.literal(VALUE_BOUND).0,
+                    false => {
+                        let e = self.exp(2, restrict).reduced();
+                        restrict = restrict.after(&e);
+                        e.text
+                    }
+                };
+                elements.push(element);
+            }
+            if row {
+                items.push(format!("{{{}}}", elements.join(", ")));
+                pos += cols;
+            } else {
+                items.extend(elements);
+                pos += 1;
+            }
+        }
+        (format!("{{{}}}", items.join(", ")), restrict)
+    }
+
+    fn function(&mut self) {
+        let returns_int = self.rng.chance(70);
+        let name = self.new_name("f");
+        let mut params = Vec::new();
+        let mut param_vars = Vec::new();
+        let mut param_texts = Vec::new();
+        for _ in 0..self.rng.range(0, 4) {
+            let param_name = self.new_name("p");
+            if self.rng.chance(25) {
+                let dims = match self.rng.chance(70) {
+                    true => vec![self.rng.range(1, 8) as usize],
+                    false => vec![self.rng.range(1, 4) as usize, self.rng.range(1, 4) as usize],
+                };
+                param_texts.push(format!("int {}[]{}", param_name, dims_text(&dims[1..])));
+                params.push(Param::Array(dims.clone()));
+                param_vars.push(Var {
+                    name: param_name,
+                    ty: Ty::Array {
+                        dims,
+                        constant: false,
+                    },
+                    global: false,
+                });
+            } else {
+                param_texts.push(format!("int {}", param_name));
+                params.push(Param::Int);
+                param_vars.push(Var {
+                    name: param_name,
+                    ty: Ty::Int,
+                    global: false,
+                });
+            }
+        }
+
+        let ret = if returns_int { "int" } else { "void" };
+        self.line(&format!("{} {}({}) {{", ret, name, param_texts.join(", ")));
+        self.returns_int = returns_int;
+        self.budget = 16;
+        self.scopes = vec![param_vars];
+        self.block_items(3, 6);
+        if returns_int {
+            let e = self.exp(3, Restrict::default()).reduced();
+            self.indented(&format!("return {};", e.text));
+        }
+        self.scopes.clear();
+        self.line("}");
+        self.funcs.push(Func {
+            name,
+            params,
+            returns_int,
+        });
+    }
+
+    fn main(&mut self) {
+        self.line("int main() {");
+        self.returns_int = true;
+        self.budget = 32;
+        self.scopes = vec![Vec::new()];
+        self.block_items(3, 12);
+        // print the global variables, so that a wrong store is observable
+        self.indent += 1;
+        for global in self.globals.clone() {
+            match &global.ty {
+                Ty::Int => self.line(&format!("putint({}); putch(32);", global.name)),
+                Ty::Array { dims, .. } if dims.len() == 1 => {
+                    self.line(&format!("putarray({}, {});", dims[0], global.name))
+                }
+                Ty::Array { dims, .. } => {
+                    for i in 0..dims[0] {
+                        self.line(&format!("putarray({}, {}[{}]);", dims[1], global.name, i));
+                    }
+                }
+                _ => {}
+            }
+        }
+        self.indent -= 1;
+        let e = self.exp(3, Restrict::default()).reduced();
+        self.indented(&format!("return {};", e.text));
+        self.scopes.clear();
+        self.line("}");
+    }
+
+    fn indented(&mut self, text: &str) {
+        self.indent += 1;
+        self.line(text);
+        self.indent -= 1;
+    }
+
+    ////////////////////////////////////////////////////////////////////////
+    // Statements                                                         //
+    ////////////////////////////////////////////////////////////////////////
+
+    /// Generate at most `max_items` items of a block, with the braces written by the caller.
+    fn block_items(&mut self, depth: usize, max_items: i64) {
+        self.indent += 1;
+        for _ in 0..self.rng.range(1, max_items) {
+            if self.budget == 0 {
+                break;
+            }
+            self.budget -= 1;
+            match self.rng.weighted(&[4, 2, 10]) {
+                0 => self.local_decl(),
+                1 if depth > 0 && self.loop_depth < 2 => self.while_loop(depth),
+                _ => self.stmt(depth),
+            }
+        }
+        self.indent -= 1;
+    }
+
+    /// Generate a block statement, which opens a new scope.
+    fn block(&mut self, header: &str, depth: usize) {
+        self.line(&format!("{}{{", header));
+        self.scopes.push(Vec::new());
+        self.block_items(depth, 4);
+        self.scopes.pop();
+        self.line("}");
+    }
+
+    fn local_decl(&mut self) {
+        match self.rng.weighted(&[6, 1, 2]) {
+            0 => {
+                // the later definitions may use the earlier ones
+                let mut defs = Vec::new();
+                for _ in 0..self.rng.range(1, 2) {
+                    let e = self.exp(3, Restrict::default()).reduced();
+                    let name = self.declare_local("v", Ty::Int, &e.text);
+                    defs.push(format!("{} = {}", name, e.text));
+                }
+                self.line(&format!("int {};", defs.join(", ")));
+            }
+            1 => {
+                let consts: Vec<(String, i64)> = self
+                    .visible()
+                    .into_iter()
+                    .filter_map(|v| match v.ty {
+                        Ty::Const(value) => Some((v.name, value)),
+                        _ => None,
+                    })
+                    .collect();
+                let (mut text, mut value) = self.literal(VALUE_BOUND);
+                if let Some((name, c)) = self.pick(&consts) {
+                    text = format!("{} - {}", name, text);
+                    value = c - value;
+                }
+                let name = self.declare_local("c", Ty::Const(value), &text);
+                self.line(&format!("const int {} = {};", name, text));
+            }
+            _ => {
+                let dims = self.array_dims();
+                let (init, _) = self.initializer(&dims, false, Restrict::default());
+                let name = self.declare_local(
+                    "a",
+                    Ty::Array {
+                        dims: dims.clone(),
+                        constant: false,
+                    },
+                    &init,
+                );
+                self.line(&format!("int {}{} = {};", name, dims_text(&dims), init));
+            }
+        }
+    }
+
+    fn while_loop(&mut self, depth: usize) {
+        let trip_count = self.rng.range(1, MAX_TRIP_COUNT);
+        let counter = self.declare_local("i", Ty::Counter(trip_count), "");
+        self.line(&format!("int {} = 0;", counter));
+        let mut cond = format!("{} < {}", counter, trip_count);
+        if self.rng.chance(40) {
+            let op = if self.rng.chance(70) { "&&" } else { "||" };
+            let extra = self.exp(3, Restrict::default());
+            // `||` must not make the loop infinite, so the counter is checked again
+            cond = match op {
+                "&&" => format!("{} && {}", cond, extra.text),
+                _ => format!(
+                    "({} || {}) && {} < {}",
+                    cond, extra.text, counter, trip_count
+                ),
+            };
+        }
+        self.line(&format!("while ({}) {{", cond));
+        self.loop_depth += 1;
+        self.scopes.push(Vec::new());
+        self.indented(&format!("{} = {} + 1;", counter, counter));
+        self.block_items(depth - 1, 4);
+        self.scopes.pop();
+        self.loop_depth -= 1;
+        self.line("}");
+    }
+
+    fn stmt(&mut self, depth: usize) {
+        let in_loop = self.loop_depth > 0;
+        let weights = [
+            10,                                        // assignment
+            if depth > 0 { 5 } else { 0 },             // if
+            4,                                         // output
+            if self.funcs.is_empty() { 0 } else { 3 }, // call
+            if in_loop { 3 } else { 0 },               // break or continue
+            1,                                         // early return
+            1,                                         // expression
+            if depth > 0 { 1 } else { 0 },             // block
+        ];
+        match self.rng.weighted(&weights) {
+            0 => self.assignment(),
+            1 => {
+                let cond = self.condition();
+                self.block(&format!("if ({}) ", cond), depth - 1);
+                if self.rng.chance(50) {
+                    self.block("else ", depth - 1);
+                }
+            }
+            2 => match self.rng.weighted(&[3, 1, 1]) {
+                0 => {
+                    let e = self.exp(3, Restrict::default());
+                    self.line(&format!("putint({});", e.text));
+                    self.line("putch(32);");
+                }
+                1 => self.line("putch(10);"),
+                _ => {
+                    let e = self.exp(2, Restrict::default());
+                    self.line(&format!("putch(65 + ({} % 26 + 26) % 26);", e.text));
+                }
+            },
+            3 => {
+                let index = self.rng.range(0, self.funcs.len() as i64 - 1) as usize;
+                let call = self.call(index, 2, Restrict::default());
+                self.line(&format!("{};", call.text));
+            }
+            4 => {
+                let cond = self.condition();
+                let jump = if self.rng.chance(50) {
+                    "break"
+                } else {
+                    "continue"
+                };
+                self.line(&format!("if ({}) {};", cond, jump));
+            }
+            5 => {
+                let cond = self.condition();
+                let ret = match self.returns_int {
+                    true => format!(
+                        "return {};",
+                        self.exp(2, Restrict::default()).reduced().text
+                    ),
+                    false => String::from("return;"),
+                };
+                self.line(&format!("if ({}) {}", cond, ret));
+            }
+            6 => {
+                let e = self.exp(3, Restrict::default());
+                self.line(&format!("{};", e.text));
+            }
+            _ => self.block("", depth - 1),
+        }
+    }
+
+    /// Generate a condition, which is often made of `&&` and `||`.
+    fn condition(&mut self) -> String {
+        let e = match self.rng.chance(60) {
+            true => self.logic(3, Restrict::default()),
+            false => self.exp(3, Restrict::default()),
+        };
+        e.text
+    }
+
+    fn assignment(&mut self) {
+        let targets: Vec<Var> = self
+            .visible()
+            .into_iter()
+            .filter(|v| {
+                matches!(
+                    v.ty,
+                    Ty::Int
+                        | Ty::Array {
+                            constant: false,
+                            ..
+                        }
+                )
+            })
+            .collect();
+        let Some(target) = self.pick(&targets) else {
+            let e = self.exp(2, Restrict::default());
+            self.line(&format!("{};", e.text));
+            return;
+        };
+        // the indexes only read local scalars, so they don't depend on the calls on the right-hand side
+        let lval = match &target.ty {
+            Ty::Array { dims, .. } => {
+                let mut lval = target.name.clone();
+                for &dim in dims.clone().iter() {
+                    lval += &format!("[{}]", self.index(dim, 2, Restrict::LOCALS_ONLY).text);
+                }
+                lval
+            }
+            _ => target.name.clone(),
+        };
+        let e = self.exp(3, Restrict::default()).reduced();
+        self.line(&format!("{} = {};", lval, e.text));
+    }
+
+    ////////////////////////////////////////////////////////////////////////
+    // Expressions                                                        //
+    ////////////////////////////////////////////////////////////////////////
+
+    fn exp(&mut self, depth: usize, restrict: Restrict) -> Expr {
+        if depth == 0 || self.rng.chance(20) {
+            return self.leaf(depth, restrict);
+        }
+        let can_call = !restrict.no_calls && !self.funcs.iter().all(|f| !f.returns_int);
+        match self
+            .rng
+            .weighted(&[12, 4, 2, 2, 3, if can_call { 4 } else { 0 }])
+        {
+            0 => self.arith(depth, restrict),
+            1 => self.div(depth, restrict),
+            2 => {
+                let lhs = self.exp(depth - 1, restrict);
+                let rhs = self.exp(depth - 1, restrict.after(&lhs));
+                let ops = ["<", ">", "<=", ">=", "==", "!="];
+                let op = self.pick(&ops).unwrap();
+                combine(format!("({} {} {})", lhs.text, op, rhs.text), 1, &lhs, &rhs)
+            }
+            3 => self.logic(depth, restrict),
+            4 => {
+                let e = self.exp(depth - 1, restrict);
+                match self.rng.weighted(&[3, 2, 1]) {
+                    0 => Expr {
+                        text: format!("(-{})", e.text),
+                        ..e
+                    },
+                    1 => Expr {
+                        text: format!("(!{})", e.text),
+                        bound: 1,
+                        ..e
+                    },
+                    _ => Expr {
+                        text: format!("(+{})", e.text),
+                        ..e
+                    },
+                }
+            }
+            _ => {
+                let funcs: Vec<usize> = (0..self.funcs.len())
+                    .filter(|&i| self.funcs[i].returns_int)
+                    .collect();
+                let index = self.pick(&funcs).unwrap();
+                self.call(index, depth - 1, restrict)
+            }
+        }
+    }
+
+    fn leaf(&mut self, depth: usize, restrict: Restrict) -> Expr {
+        let vars: Vec<Var> = self
+            .visible()
+            .into_iter()
+            .filter(|v| match v.ty {
+                Ty::Array { .. } => !restrict.no_memory,
+                _ => !(v.global && restrict.no_memory),
+            })
+            .collect();
+        let weights = [
+            3,
+            if vars.is_empty() { 0 } else { 8 },
+            if restrict.no_calls { 0 } else { 1 },
+        ];
+        match self.rng.weighted(&weights) {
+            0 => {
+                let (text, value) = self.literal(VALUE_BOUND);
+                Expr::leaf(text, value, false)
+            }
+            1 => {
+                let var = self.pick(&vars).unwrap();
+                match var.ty {
+                    Ty::Int => Expr::leaf(var.name, VALUE_BOUND, var.global),
+                    Ty::Const(value) => Expr::leaf(var.name, value.abs(), var.global),
+                    Ty::Counter(trip_count) => Expr::leaf(var.name, trip_count, false),
+                    Ty::Array { dims, .. } => {
+                        let mut text = var.name;
+                        let mut restrict = restrict;
+                        let mut calls = false;
+                        for dim in dims {
+                            let index = self.index(dim, depth, restrict);
+                            restrict = restrict.after(&index);
+                            calls |= index.calls;
+                            text += &format!("[{}]", index.text);
+                        }
+                        Expr {
+                            text,
+                            bound: VALUE_BOUND,
+                            calls,
+                            memory: true,
+                        }
+                    }
+                }
+            }
+            _ => {
+                let (text, bound) = match self.rng.chance(80) {
+                    true => ("getint()", VALUE_BOUND),
+                    false => ("getch()", 255),
+                };
+                Expr {
+                    text: text.into(),
+                    bound,
+                    calls: true,
+                    memory: false,
+                }
+            }
+        }
+    }
+
+    /// Generate `+`, `-` or `*`, reducing the operands if the result may overflow.
+    fn arith(&mut self, depth: usize, restrict: Restrict) -> Expr {
+        let mut lhs = self.exp(depth - 1, restrict);
+        let mut rhs = self.exp(depth - 1, restrict.after(&lhs));
+        let op = self.pick(&["+", "-", "*"]).unwrap();
+        let bound = |lhs: &Expr, rhs: &Expr| match op {
+            "*" => lhs.bound * rhs.bound,
+            _ => lhs.bound + rhs.bound,
+        };
+        if bound(&lhs, &rhs) > INT_LIMIT {
+            lhs = lhs.reduced();
+            rhs = rhs.reduced();
+        }
+        combine(
+            format!("({} {} {})", lhs.text, op, rhs.text),
+            bound(&lhs, &rhs),
+            &lhs,
+            &rhs,
+        )
+    }
+
+    /// Generate `/` or `%` with a divisor that is never zero.
+    fn div(&mut self, depth: usize, restrict: Restrict) -> Expr {
+        let lhs = self.exp(depth - 1, restrict);
+        let rhs = match self.rng.chance(40) {
+            true => {
+                let (text, value) = self.literal(20);
+                let text = match (value, self.rng.chance(30)) {
+                    (0, _) => String::from("7"),
+                    (_, true) => format!("(-{})", text),
+                    (_, false) => text,
+                };
+                Expr::leaf(text, 20, false)
+            }
+            false => {
+                // `e % 7` is in `[-6, 6]`, so the divisor is in `[2, 14]` or `[-14, -2]`
+                let e = self.exp(depth - 1, restrict.after(&lhs));
+                let sign = if self.rng.chance(70) { "+" } else { "-" };
+                Expr {
+                    text: format!("({} % 7 {} 8)", e.text, sign),
+                    bound: 14,
+                    ..e
+                }
+            }
+        };
+        let op = self.pick(&["/", "%"]).unwrap();
+        combine(
+            format!("({} {} {})", lhs.text, op, rhs.text),
+            lhs.bound,
+            &lhs,
+            &rhs,
+        )
+    }
+
+    /// Generate nested `&&` and `||`, whose operands are sequenced.
+    fn logic(&mut self, depth: usize, restrict: Restrict) -> Expr {
+        let mut e = self.exp(depth.saturating_sub(1), restrict);
+        for _ in 0..self.rng.range(1, 3) {
+            let rhs = match self.rng.chance(30) && depth > 1 {
+                true => self.logic(depth - 1, restrict),
+                false => self.exp(depth.saturating_sub(1), restrict),
+            };
+            let op = if self.rng.chance(50) { "&&" } else { "||" };
+            e = combine(format!("({} {} {})", e.text, op, rhs.text), 1, &e, &rhs);
+        }
+        e
+    }
+
+    /// Generate an index in `[0, dim)`.
+    fn index(&mut self, dim: usize, depth: usize, restrict: Restrict) -> Expr {
+        let dim = dim as i64;
+        let counters: Vec<String> = self
+            .visible()
+            .into_iter()
+            .filter(|v| matches!(v.ty, Ty::Counter(trip_count) if trip_count < dim))
+            .map(|v| v.name)
+            .collect();
+        match self.rng.weighted(&[
+            2,
+            if counters.is_empty() { 0 } else { 3 },
+            if depth > 0 { 3 } else { 0 },
+        ]) {
+            0 => Expr::leaf(self.rng.range(0, dim - 1).to_string(), dim - 1, false),
+            1 => Expr::leaf(self.pick(&counters).unwrap(), dim - 1, false),
+            _ => {
+                let e = self.exp(depth.min(3) - 1, restrict);
+                Expr {
+                    text: format!("(({} % {} + {}) % {})", e.text, dim, dim, dim),
+                    bound: dim - 1,
+                    ..e
+                }
+            }
+        }
+    }
+
+    /// Generate a call of the function, with the arguments unsequenced with each other.
+    fn call(&mut self, index: usize, depth: usize, mut restrict: Restrict) -> Expr {
+        let params = self.funcs[index].params.clone();
+        let mut args = Vec::new();
+        let mut memory = false;
+        for param in params {
+            let arg = match param {
+                Param::Int => self.exp(depth, restrict).reduced(),
+                Param::Array(dims) => self.array_arg(&dims, restrict),
+            };
+            restrict = restrict.after(&arg);
+            memory |= arg.memory;
+            args.push(arg.text);
+        }
+        Expr {
+            text: format!("{}({})", self.funcs[index].name, args.join(", ")),
+            bound: VALUE_BOUND - 1,
+            calls: true,
+            memory,
+        }
+    }
+
+    /// Generate an array argument for a parameter that may access the given dimensions.
+    ///
+    /// A literal `0` is never passed, so if there's no suitable array, a new global array is declared.
+    fn array_arg(&mut self, dims: &[usize], restrict: Restrict) -> Expr {
+        let mut candidates = Vec::new();
+        for var in self.visible() {
+            let Ty::Array {
+                dims: var_dims,
+                constant: false,
+            } = &var.ty
+            else {
+                continue;
+            };
+            let fits =
+                |d: &[usize]| d.len() == dims.len() && d[0] >= dims[0] && d[1..] == dims[1..];
+            if fits(var_dims) {
+                candidates.push((var.name.clone(), None));
+            }
+            if var_dims.len() == 2 && fits(&var_dims[1..]) {
+                candidates.push((var.name.clone(), Some(var_dims[0])));
+            }
+        }
+        let (name, row) = match self.pick(&candidates) {
+            Some(candidate) => candidate,
+            None => {
+                // the global is declared before all the functions
+                let name = self.new_name("ga");
+                let decl = format!("int {}{};\n", name, dims_text(dims));
+                self.out.insert_str(0, &decl);
+                self.globals.push(Var {
+                    name: name.clone(),
+                    ty: Ty::Array {
+                        dims: dims.to_vec(),
+                        constant: false,
+                    },
+                    global: true,
+                });
+                (name, None)
+            }
+        };
+        match row {
+            None => Expr::leaf(name, 0, true),
+            Some(rows) => {
+                let index = self.index(rows, 2, restrict);
+                Expr {
+                    text: format!("{}[{}]", name, index.text),
+                    bound: 0,
+                    memory: true,
+                    ..index
+                }
+            }
+        }
+    }
+}
+
+/// Combine the flags of the operands of an operator.
+fn combine(text: String, bound: i64, lhs: &Expr, rhs: &Expr) -> Expr {
+    Expr {
+        text,
+        bound,
+        calls: lhs.calls || rhs.calls,
+        memory: lhs.memory || rhs.memory,
+    }
+}
+
+fn dims_text(dims: &[usize]) -> String {
+    let mut text = String::new();
+    for dim in dims {
+        write!(text, "[{}]", dim).unwrap();
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast_generate::parse_sysy_to_ast;
+    use crate::ast_interpret::interpret_ast;
+    use crate::koopa_interpret::InterpretOptions;
+    use crate::semantic::check_ast;
+
+    #[test]
+    fn rng_test() {
+        let mut rng = Rng::new(1);
+        let xs: Vec<i64> = (0..100).map(|_| rng.range(-3, 3)).collect();
+        assert!(xs.iter().all(|x| (-3..=3).contains(x)));
+        assert!((-3..=3).all(|x| xs.contains(&x)));
+        assert_eq!(Rng::new(7).next_u64(), Rng::new(7).next_u64());
+    }
+
+    #[test]
+    fn generate_program_test() {
+        assert_eq!(generate_program(42), generate_program(42));
+        assert_ne!(generate_program(42).source, generate_program(43).source);
+
+        let options = InterpretOptions {
+            max_instructions: Some(1_000_000),
+            ..InterpretOptions::default()
+        };
+        for seed in 0..100 {
+            let program = generate_program(seed);
+            let ast = parse_sysy_to_ast(&program.source).unwrap();
+            check_ast(&ast).unwrap_or_else(|e| panic!("{:?}\n{}", e, program.source));
+            // the reference run never finds undefined behavior
+            interpret_ast(&ast, &program.stdin, &options)
+                .unwrap_or_else(|e| panic!("{:?}\n{}", e, program.source));
+        }
+    }
+}
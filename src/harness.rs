@@ -0,0 +1,266 @@
+use crate::koopa_interpret::{interpret_koopa_with_options, InterpretOptions};
+use crate::opt::OptOptions;
+use crate::riscv_simulate::{simulate_riscv_with_options, SimulateOptions};
+use crate::{compile, ir_generate, Mode, RunError};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A program is considered hanging after executing this many instructions.
+const MAX_INSTRUCTIONS: u64 = 1 << 32;
+
+/// A test case in the layout of the course judge, found by `discover`.
+///
+/// It's a SysY source file `xxx.sy`, with the standard input in `xxx.in` (optional)
+/// and the expected output in `xxx.out`.
+/// The expected output is the standard output of the program, followed by its exit code
+/// (truncated to 8 bits) on a new line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TestCase {
+    /// The file name without the extension, e.g. `01_main`.
+    pub name: String,
+    /// The directory of the case relative to the root, e.g. `lv1`, which groups the cases in the report.
+    pub level: String,
+    pub source: PathBuf,
+    /// The standard input, `None` if there's no `.in` file.
+    pub input: Option<PathBuf>,
+    pub output: PathBuf,
+}
+
+/// The result of a test case in one mode.
+#[derive(Debug, Clone)]
+pub struct CaseResult {
+    pub case: TestCase,
+    pub mode: Mode,
+    /// Why the case fails, `None` if it passes.
+    pub failure: Option<String>,
+}
+
+/// The results of all test cases, in the order of levels, cases and modes.
+#[derive(Debug, Clone, Default)]
+pub struct Report {
+    pub results: Vec<CaseResult>,
+}
+
+impl Report {
+    /// Count the passed and the failed results of every level.
+    pub fn levels(&self) -> BTreeMap<&str, (usize, usize)> {
+        let mut levels = BTreeMap::new();
+        for result in &self.results {
+            let (passed, failed) = levels.entry(result.case.level.as_str()).or_insert((0, 0));
+            match result.failure {
+                None => *passed += 1,
+                Some(_) => *failed += 1,
+            }
+        }
+        levels
+    }
+
+    pub fn failures(&self) -> impl Iterator<Item = &CaseResult> {
+        self.results
+            .iter()
+            .filter(|result| result.failure.is_some())
+    }
+
+    pub fn all_passed(&self) -> bool {
+        self.failures().next().is_none()
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for result in self.failures() {
+            writeln!(
+                f,
+                "FAIL {}/{} ({:?}): {}",
+                result.case.level,
+                result.case.name,
+                result.mode,
+                result.failure.as_ref().unwrap()
+            )?;
+        }
+        for (level, (passed, failed)) in self.levels() {
+            let status = if failed == 0 { "ok" } else { "FAILED" };
+            writeln!(
+                f,
+                "{}: {} passed, {} failed ... {}",
+                level, passed, failed, status
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Find the test cases under the root directory recursively, sorted by their paths.
+///
+/// A `.sy` file without the `.out` file is not a test case.
+pub fn discover(root: &Path) -> std::io::Result<Vec<TestCase>> {
+    let mut cases = Vec::new();
+    let mut dirs = vec![root.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        for entry in fs::read_dir(&dir)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                dirs.push(path);
+                continue;
+            }
+            if path.extension().is_none_or(|ext| ext != "sy") {
+                continue;
+            }
+            let output = path.with_extension("out");
+            if !output.is_file() {
+                continue;
+            }
+            let input = Some(path.with_extension("in")).filter(|input| input.is_file());
+            let level = match dir.strip_prefix(root) {
+                Ok(rel) if !rel.as_os_str().is_empty() => rel.to_string_lossy().into_owned(),
+                _ => String::from("."),
+            };
+            cases.push(TestCase {
+                name: path.file_stem().unwrap().to_string_lossy().into_owned(),
+                level,
+                source: path,
+                input,
+                output,
+            });
+        }
+    }
+    cases.sort_by(|a, b| a.source.cmp(&b.source));
+    Ok(cases)
+}
+
+/// Format the output of a program like the `.out` files.
+pub fn expected_output(stdout: &str, exit_code: i32) -> String {
+    let mut output = String::from(stdout);
+    if !output.is_empty() && !output.ends_with('\n') {
+        output.push('\n');
+    }
+    output.push_str(&(exit_code & 0xff).to_string());
+    output
+}
+
+/// Compile a test case in the given mode, run it, and compare the output with the `.out` file.
+///
+/// Koopa programs run on the Koopa interpreter, and RISC-V programs run on the RISC-V simulator.
+/// Trailing whitespace is ignored in the comparison.
+pub fn run_case(case: &TestCase, mode: Mode) -> Result<(), String> {
+    let stdin = match &case.input {
+        Some(input) => {
+            fs::read_to_string(input).map_err(|e| format!("cannot read the input: {}", e))?
+        }
+        None => String::new(),
+    };
+    let expected =
+        fs::read_to_string(&case.output).map_err(|e| format!("cannot read the output: {}", e))?;
+
+    let source =
+        fs::read_to_string(&case.source).map_err(|e| format!("cannot read the source: {}", e))?;
+    let text =
+        compile(mode, &source, &OptOptions::default()).map_err(|e| compile_error_message(&e))?;
+
+    let (stdout, exit_code) = match mode {
+        Mode::Koopa => {
+            let program = ir_generate::get_koopa_program(&text).map_err(|d| d.message)?;
+            let options = InterpretOptions {
+                max_instructions: Some(MAX_INSTRUCTIONS),
+                ..InterpretOptions::default()
+            };
+            let result =
+                interpret_koopa_with_options(&program, &stdin, &options).map_err(|d| d.message)?;
+            (result.stdout, result.exit_code)
+        }
+        Mode::Riscv | Mode::Perf => {
+            let options = SimulateOptions {
+                max_instructions: Some(MAX_INSTRUCTIONS),
+                ..SimulateOptions::default()
+            };
+            let result =
+                simulate_riscv_with_options(&text, &stdin, &options).map_err(|d| d.message)?;
+            (result.stdout, result.exit_code)
+        }
+    };
+
+    let actual = expected_output(&stdout, exit_code);
+    if actual.trim_end() != expected.trim_end() {
+        return Err(format!(
+            "expected {:?}, found {:?}",
+            expected.trim_end(),
+            actual.trim_end()
+        ));
+    }
+    Ok(())
+}
+
+fn compile_error_message(e: &RunError) -> String {
+    match e.diagnostics().first() {
+        Some(diag) => format!("cannot compile: {}", diag.message),
+        None => format!("cannot compile: {:?}", e),
+    }
+}
+
+/// Discover the test cases under the root directory, and run each of them in every mode.
+pub fn run_tests(root: &Path) -> std::io::Result<Report> {
+    let mut report = Report::default();
+    for case in discover(root)? {
+        for mode in [Mode::Koopa, Mode::Riscv, Mode::Perf] {
+            report.results.push(CaseResult {
+                failure: run_case(&case, mode).err(),
+                case: case.clone(),
+                mode,
+            });
+        }
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expected_output_test() {
+        assert_eq!(expected_output("", 0), "0");
+        assert_eq!(expected_output("1 2", 258), "1 2\n2");
+        assert_eq!(expected_output("x\n", -1), "x\n255");
+    }
+
+    #[test]
+    fn harness_test() {
+        let root =
+            std::env::temp_dir().join(format!("sysy_compiler_harness_test_{}", std::process::id()));
+        let level = root.join("lv1");
+        fs::create_dir_all(&level).unwrap();
+        fs::write(
+            level.join("add.sy"),
+            "int main() { putint(getint() + 1); return 300; }",
+        )
+        .unwrap();
+        fs::write(level.join("add.in"), "41").unwrap();
+        fs::write(level.join("add.out"), "42\n44\n").unwrap();
+        fs::write(level.join("wrong.sy"), "int main() { return 1; }").unwrap();
+        fs::write(level.join("wrong.out"), "2").unwrap();
+        fs::write(root.join("no_output.sy"), "int main() { return 0; }").unwrap();
+
+        let cases = discover(&root).unwrap();
+        assert_eq!(cases.len(), 2);
+        assert_eq!(
+            (cases[0].name.as_str(), cases[0].level.as_str()),
+            ("add", "lv1")
+        );
+        assert!(cases[0].input.is_some() && cases[1].input.is_none());
+
+        let report = run_tests(&root).unwrap();
+        fs::remove_dir_all(&root).unwrap();
+        assert_eq!(report.levels()["lv1"], (3, 3));
+        let failure = report.failures().next().unwrap();
+        assert_eq!(failure.case.name, "wrong");
+        assert_eq!(
+            failure.failure.as_deref(),
+            Some("expected \"2\", found \"1\"")
+        );
+        assert!(report
+            .to_string()
+            .ends_with("lv1: 3 passed, 3 failed ... FAILED\n"));
+    }
+}
@@ -1,40 +1,63 @@
 mod array_utils;
-mod exp_solve;
+pub(crate) mod exp_solve;
+mod ir_builder;
 mod koopa_generate;
 mod named_symbol;
 mod scopes;
-mod temp_symbol;
 
 use crate::ast_generate::ast::*;
+use crate::diagnostic::Diagnostic;
+use koopa::back::KoopaGenerator;
 use koopa::front::Driver;
 use koopa::ir::*;
-use koopa_generate::KoopaTextGenerate;
+use koopa_generate::KoopaGenerate;
 use named_symbol::NamedSymbolCounter;
 use scopes::Scopes;
-use temp_symbol::TempSymbolManager;
 
-/// Convert the AST to Koopa text.
+/// Convert the AST to Koopa program.
 ///
 /// The only argument is a reference to the root of the AST (i.e. `&CompUnit`).
-/// If an error occurs, `Err(())` is returned.
-/// Otherwise, return the Koopa text wrapped by `Ok`.
+/// The program is built in memory with the builder API of the `koopa` crate.
+/// If an error occurs, the `Diagnostic` describing the first problem found is returned.
+/// Otherwise, return the Koopa program wrapped by `Ok`.
 ///
 /// # Errors
 /// An error may occur when the AST is not valid.
-pub fn parse_ast_to_koopa_text(ast: &CompUnit) -> Result<String, ()> {
-    let mut text = String::new();
+pub fn parse_ast_to_koopa_program(ast: &CompUnit) -> Result<Program, Diagnostic> {
+    let mut program = Program::new();
     ast.generate(
-        &mut text,
+        &mut program,
         &mut Scopes::new(),
-        &mut TempSymbolManager::new(),
         &mut NamedSymbolCounter::new(),
     )?;
-    Ok(text)
+    Ok(program)
+}
+
+/// Convert the AST to Koopa text.
+///
+/// The Koopa program is built by `parse_ast_to_koopa_program`, and then printed.
+pub fn parse_ast_to_koopa_text(ast: &CompUnit) -> Result<String, Diagnostic> {
+    koopa_program_to_text(&parse_ast_to_koopa_program(ast)?)
+}
+
+/// Print a Koopa program in the text form.
+pub fn koopa_program_to_text(program: &Program) -> Result<String, Diagnostic> {
+    let mut generator = KoopaGenerator::new(Vec::new());
+    generator
+        .generate_on(program)
+        .map_err(|e| Diagnostic::error(format!("cannot print the Koopa program: {}", e)))?;
+    String::from_utf8(generator.writer())
+        .map_err(|_| Diagnostic::error("the Koopa text is not valid UTF-8"))
 }
 
 /// Convert a Koopa text to Koopa program.
-pub fn get_koopa_program(text: &str) -> Result<Program, ()> {
+///
+/// The details of a failure are printed to stderr by the front end of the `koopa` crate.
+pub fn get_koopa_program(text: &str) -> Result<Program, Diagnostic> {
     let driver = Driver::from(text);
 
-    driver.generate_program().map_err(|_| ())
+    driver.generate_program().map_err(|e| {
+        Diagnostic::error("cannot parse the Koopa text")
+            .with_note(format!("the Koopa front end reports {:?}", e))
+    })
 }
@@ -1,339 +1,172 @@
 use super::exp_solve::ExpSolve;
-use super::koopa_generate::KoopaTextGenerate;
+use super::ir_builder::*;
+use super::koopa_generate::KoopaGenerate;
 use super::named_symbol::NamedSymbolCounter;
 use super::scopes::*;
-use super::temp_symbol::TempSymbolManager;
 use crate::ast_generate::ast::*;
-use crate::tools::*;
+use crate::diagnostic::Diagnostic;
+use koopa::ir::builder::{LocalInstBuilder, ValueBuilder};
+use koopa::ir::*;
 
-/// Generate the type of an array used in `alloc` instructions.
-pub fn generate_allocate_dims(dims: &[i32], i: usize) -> String {
-    let l = dims.len();
-    if l == 0usize {
-        return String::from("i32");
-    }
-    if i == l - 1 {
-        format!("[i32, {}]", dims[i])
-    } else {
-        format!("[{}, {}]", generate_allocate_dims(dims, i + 1), dims[i])
-    }
-}
-
-/// Return a pointer to an array element, and generate the corresponding code.
+/// Solve the length of each dimension of an array.
 ///
-/// Use the style of `KoopaTextGenerate`.
-/// The indices are expressions, and they are solved before getting the pointer.
-pub fn get_pointer_to_element_exp_idx(
-    lines: &mut String,
-    array: &str,
-    idx: &Vec<Exp>,
-    scopes: &mut Scopes,
-    tsm: &mut TempSymbolManager,
-    nsc: &mut NamedSymbolCounter,
-) -> Result<String, ()> {
-    let mut old_handler = String::from(array);
-    let mut new_handler: String = String::from(array);
-    for (j, exp) in idx.iter().enumerate() {
-        if (j == 0) && scopes.has_cur_func_param(array) {
-            let mut pre = String::new();
-            let i = exp.generate(&mut pre, scopes, tsm, nsc)?;
-            append_line(lines, &pre);
-            let intermediate_handler = tsm.new_temp_symbol();
-            new_handler = nsc.inc_and_get_named_symbol("%array_ptr")?;
-            append_line(lines, &format!("  {} = load {}", intermediate_handler, old_handler));
-            append_line(
-                lines,
-                &format!("  {} = getptr {}, {}", new_handler, intermediate_handler, i),
-            );
-            old_handler = new_handler.clone();
-        } else {
-            let mut pre = String::new();
-            let i = exp.generate(&mut pre, scopes, tsm, nsc)?;
-            append_line(lines, &pre);
-
-            new_handler = nsc.inc_and_get_named_symbol("%array_ptr")?;
-            append_line(
-                lines,
-                &format!("  {} = getelemptr {}, {}", new_handler, old_handler, i),
-            );
-            old_handler = new_handler.clone();
-        }
-    }
+/// Every length must be a positive constant.
+pub fn solve_array_dims(dims: &[ConstExp], scopes: &Scopes) -> Result<Vec<i32>, Diagnostic> {
+    dims.iter()
+        .map(|const_exp| {
+            let d = const_exp.solve(scopes)?;
+            if d <= 0 {
+                return Err(Diagnostic::error(format!(
+                    "the length of an array dimension must be positive, but it is {}",
+                    d
+                ))
+                .with_primary(const_exp.span(), "invalid array length"));
+            }
+            Ok(d)
+        })
+        .collect()
+}
 
-    Ok(new_handler)
+/// Get the type of an array used in `alloc` instructions, e.g. `[[i32, 3], 2]` for `int a[2][3]`.
+pub fn array_type(dims: &[i32]) -> Type {
+    dims.iter().rev().fold(Type::get_i32(), |base, &d| {
+        Type::get_array(base, d as usize)
+    })
 }
 
 /// Return a pointer to an array element, and generate the corresponding code.
 ///
-/// Use the style of `KoopaTextGenerate`.
-/// The indices are integers, and they are directly used to get the pointer.
-pub fn get_pointer_to_element_int_idx(
-    lines: &mut String,
-    array: &str,
-    idx: Vec<usize>,
+/// The indices are expressions, and they are evaluated before getting the pointer.
+/// An array parameter holds a pointer to its first sub-array,
+/// so the pointer is loaded and the first index is applied by `getptr`.
+pub fn get_pointer_to_element_exp_idx(
+    program: &mut Program,
+    array: Value,
+    idx: &[Exp],
     scopes: &mut Scopes,
     nsc: &mut NamedSymbolCounter,
-) -> Result<String, ()> {
-    let mut old_handler = String::from(array);
-    let mut new_handler: String = String::from(array);
-    for (j, i) in idx.iter().enumerate() {
+) -> Result<Value, Diagnostic> {
+    let mut ptr = array;
+    for (j, exp) in idx.iter().enumerate() {
+        let i = exp.generate(program, scopes, nsc)?;
         if (j == 0) && scopes.has_cur_func_param(array) {
-            let intermediate_handler = nsc.inc_and_get_named_symbol("%array_ptr")?;
-            new_handler = nsc.inc_and_get_named_symbol("%array_ptr")?;
-            append_line(lines, &format!("  {} = load {}", intermediate_handler, old_handler));
-            append_line(
-                lines,
-                &format!("  {} = getptr {}, {}", new_handler, intermediate_handler, i),
-            );
-            old_handler = new_handler.clone();
+            let base = emit(program, scopes, nsc, move |b| b.load(ptr))?;
+            ptr = emit(program, scopes, nsc, move |b| b.get_ptr(base, i))?;
         } else {
-            new_handler = nsc.inc_and_get_named_symbol("%array_ptr")?;
-            append_line(
-                lines,
-                &format!("  {} = getelemptr {}, {}", new_handler, old_handler, i),
-            );
-            old_handler = new_handler.clone();
-        }
-    }
-
-    Ok(new_handler)
-}
-
-fn walk_const_init_val(
-    // information
-    init: &ConstInitVal,
-    full_initializer: &mut Vec<String>,
-    backward_prod: &[usize],
-    // states
-    level: usize,
-    idx: usize,
-    scopes: &mut Scopes,
-) -> Result<(), ()> {
-    match init {
-        ConstInitVal::Exp(exp) => {
-            let j = exp.solve(scopes)?;
-            full_initializer[idx] = j.to_string();
-        }
-        ConstInitVal::Array(arr) => {
-            let mut current_idx = idx;
-            for sub_init in arr.iter() {
-                walk_const_init_val(
-                    sub_init,
-                    full_initializer,
-                    backward_prod,
-                    level + 1,
-                    current_idx,
-                    scopes,
-                )?;
-                match sub_init {
-                    ConstInitVal::Exp(_) => {
-                        current_idx += 1;
-                    }
-                    ConstInitVal::Array(_) => {
-                        let l = backward_prod.len();
-                        for i in level..l {
-                            if current_idx % backward_prod[i] == 0 {
-                                current_idx += backward_prod[i];
-                                break;
-                            }
-                        }
-                    }
-                }
-            }
+            ptr = emit(program, scopes, nsc, move |b| b.get_elem_ptr(ptr, i))?;
         }
     }
 
-    Ok(())
+    Ok(ptr)
 }
 
-pub fn parse_const_array_initializer(
-    init: &ConstInitVal,
-    dims: &[i32],
-    scopes: &mut Scopes,
-) -> Result<Vec<String>, ()> {
-    let n_total = dims.iter().product::<i32>();
-    let mut full_initializer = vec![String::from("0"); n_total as usize];
+/// `backward_prod[i]` is the number of elements in a sub-array of level `i + 1`.
+fn backward_prod(dims: &[i32]) -> Vec<usize> {
     let mut backward_prod = vec![1usize; dims.len()];
     for i in 2..=dims.len() {
         let j = dims.len() - i;
         backward_prod[j] = backward_prod[j + 1] * (dims[j + 1] as usize);
     }
+    backward_prod
+}
 
-    walk_const_init_val(init, &mut full_initializer, &backward_prod, 0, 0, scopes)?;
-
-    Ok(full_initializer)
+/// Move the index past a sub-array initialized by an initializer list.
+///
+/// The sub-array is the largest one (with level no less than `level`) that is aligned with `idx`.
+fn skip_to_next_aligned(idx: usize, backward_prod: &[usize], level: usize) -> usize {
+    match backward_prod[level.min(backward_prod.len())..]
+        .iter()
+        .find(|&&p| idx.is_multiple_of(p))
+    {
+        Some(p) => idx + p,
+        None => idx,
+    }
 }
 
-fn walk_var_init_val(
-    pre_lines: &mut String,
-    init: &InitVal,
-    full_initializer: &mut Vec<String>,
+fn walk_initializer<'a, I: Initializer>(
+    // information
+    init: &'a I,
+    full_initializer: &mut [Option<&'a I::Exp>],
     backward_prod: &[usize],
+    // states
     level: usize,
     idx: usize,
-    scopes: &mut Scopes,
-    tsm: &mut TempSymbolManager,
-    nsc: &mut NamedSymbolCounter,
-) -> Result<(), ()> {
-    match init {
-        InitVal::Exp(exp) => {
-            let handle = exp.solve(scopes);
-            match handle {
-                Ok(h) => {
-                    full_initializer[idx] = h.to_string();
-                }
-                Err(_) => {
-                    let handle = exp.generate(pre_lines, scopes, tsm, nsc)?;
-                    full_initializer[idx] = handle;
-                }
-            }
-        }
-        InitVal::Array(arr) => {
-            let mut current_idx = idx;
-            for sub_init in arr.iter() {
-                walk_var_init_val(
-                    pre_lines,
-                    sub_init,
-                    full_initializer,
-                    backward_prod,
-                    level + 1,
-                    current_idx,
-                    scopes,
-                    tsm,
-                    nsc,
-                )?;
-                match sub_init {
-                    InitVal::Exp(_) => {
-                        current_idx += 1;
-                    }
-                    InitVal::Array(_) => {
-                        let l = backward_prod.len();
-                        for i in level..l {
-                            if current_idx % backward_prod[i] == 0 {
-                                current_idx += backward_prod[i];
-                                break;
-                            }
-                        }
-                    }
-                }
-            }
+) {
+    let Some(elements) = init.elements() else {
+        if let Some(slot) = full_initializer.get_mut(idx) {
+            *slot = init.exp();
         }
+        return;
+    };
+
+    let mut current_idx = idx;
+    for sub_init in elements.iter() {
+        walk_initializer(
+            sub_init,
+            full_initializer,
+            backward_prod,
+            level + 1,
+            current_idx,
+        );
+        current_idx = match sub_init.elements() {
+            None => current_idx + 1,
+            Some(_) => skip_to_next_aligned(current_idx, backward_prod, level),
+        };
     }
-
-    Ok(())
 }
 
-pub fn parse_var_array_initializer(
-    pre_lines: &mut String,
-    init: &InitVal,
+/// Flatten the initializer of an array into its elements in row-major order.
+///
+/// The elements without an initial value are `None`, which means they are initialized to zero.
+/// The shape of the initializer has been checked by the semantic analysis.
+pub fn flatten_array_initializer<'a, I: Initializer>(
+    init: &'a I,
     dims: &[i32],
-    scopes: &mut Scopes,
-    tsm: &mut TempSymbolManager,
-    nsc: &mut NamedSymbolCounter,
-) -> Result<Vec<String>, ()> {
+) -> Vec<Option<&'a I::Exp>> {
     let n_total = dims.iter().product::<i32>();
-    let mut full_initializer = vec![String::from("0"); n_total as usize];
-    let mut backward_prod = vec![1usize; dims.len()];
-    for i in 2..=dims.len() {
-        let j = dims.len() - i;
-        backward_prod[j] = backward_prod[j + 1] * (dims[j + 1] as usize);
-    }
-
-    walk_var_init_val(
-        pre_lines,
-        init,
-        &mut full_initializer,
-        &backward_prod,
-        0,
-        0,
-        scopes,
-        tsm,
-        nsc,
-    )?;
-
-    Ok(full_initializer)
+    let mut full_initializer = vec![None; n_total as usize];
+    walk_initializer(init, &mut full_initializer, &backward_prod(dims), 0, 0);
+    full_initializer
 }
 
-fn walk_full_initializer(
-    full_init: &Vec<String>,
+/// Build the initial value of a global array from its flattened elements.
+pub fn full_initializer_to_global_aggregate(
+    program: &mut Program,
+    full_init: &[i32],
     dims: &[i32],
-    backward_prod: &[usize],
-    now_level: usize,
-    now_idx: &mut Vec<usize>,
-) -> String {
-    if now_level == dims.len() {
-        return full_init[now_idx
-            .iter()
-            .zip(backward_prod.iter())
-            .map(|(a, b)| (*a) * (*b as usize))
-            .sum::<usize>()]
-        .clone();
-    }
-
-    let n = dims[now_level] as usize;
-    let mut ans = String::from("{");
-    for i in 0..n {
-        now_idx[now_level] = i;
-        ans.push_str(&walk_full_initializer(
-            full_init,
-            dims,
-            backward_prod,
-            now_level + 1,
-            now_idx,
-        ));
-        if i != n - 1 {
-            ans.push_str(", ");
-        }
-    }
-    ans.push_str("}");
-
-    ans
-}
-
-pub fn full_initializer_to_global_aggregate(full_init: &Vec<String>, dims: &[i32]) -> String {
-    let mut now_idx = vec![0usize; dims.len()];
-    let mut backward_prod = vec![1usize; dims.len()];
-    for i in 2..=dims.len() {
-        let j = dims.len() - i;
-        backward_prod[j] = backward_prod[j + 1] * (dims[j + 1] as usize);
-    }
-    walk_full_initializer(full_init, dims, &backward_prod, 0, &mut now_idx)
+) -> Value {
+    let Some((&n, sub_dims)) = dims.split_first() else {
+        return program.new_value().integer(full_init[0]);
+    };
+    let elems = full_init
+        .chunks(full_init.len() / n as usize)
+        .map(|sub_init| full_initializer_to_global_aggregate(program, sub_init, sub_dims))
+        .collect();
+    program.new_value().aggregate(elems)
 }
 
-pub fn full_initializer_to_local_lines(
-    array: &str,
-    full_init: &Vec<String>,
+/// Store the flattened elements into a local array one by one.
+pub fn full_initializer_to_local_stores(
+    program: &mut Program,
+    array: Value,
+    full_init: &[Value],
     dims: &[i32],
     scopes: &mut Scopes,
     nsc: &mut NamedSymbolCounter,
-) -> Result<String, ()> {
-    let l = full_init.len();
-    let mut backward_prod = vec![1usize; dims.len()];
-    for i in 2..=dims.len() {
-        let j = dims.len() - i;
-        backward_prod[j] = backward_prod[j + 1] * (dims[j + 1] as usize);
-    }
-
-    let mut lines = String::new();
-    for i in 0..l {
-        let from_handle = &full_init[i];
-
-        let ll = dims.len();
-        let mut idx = vec![0; ll];
+) -> Result<(), Diagnostic> {
+    let backward_prod = backward_prod(dims);
+    for (i, &value) in full_init.iter().enumerate() {
         let mut ii = i;
-        for j in 0..ll {
-            idx[j] = ii / backward_prod[j];
-            ii = ii % backward_prod[j];
+        let mut ptr = array;
+        for p in backward_prod.iter() {
+            let index = integer(program, scopes, (ii / p) as i32);
+            ii %= p;
+            ptr = emit(program, scopes, nsc, move |b| b.get_elem_ptr(ptr, index))?;
         }
-
-        let to_handle = get_pointer_to_element_int_idx(&mut lines, array, idx, scopes, nsc)?;
-        append_line(
-            &mut lines,
-            &format!("  store {}, {}", from_handle, to_handle),
-        );
+        emit(program, scopes, nsc, move |b| b.store(value, ptr))?;
     }
 
-    Ok(lines)
+    Ok(())
 }
 
 #[cfg(test)]
@@ -341,7 +174,7 @@ mod tests {
     use super::*;
 
     #[test]
-    fn generate_allocate_dims_test() {
-        assert_eq!(generate_allocate_dims(&[1, 2, 3], 0), "[[[i32, 3], 2], 1]");
+    fn array_type_test() {
+        assert_eq!(array_type(&[1, 2, 3]).to_string(), "[[[i32, 3], 2], 1]");
     }
 }
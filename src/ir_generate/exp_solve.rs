@@ -1,28 +1,31 @@
 use super::scopes::*;
 use crate::ast_generate::ast::*;
+use crate::diagnostic::Diagnostic;
 
 /// Solve the value of an expression.
 pub trait ExpSolve {
     /// Evaluate the expression and return its value.
     ///
     /// This method is called when generating AST.
-    fn solve(&self, scopes: &Scopes) -> Result<i32, ()>;
+    /// An error is returned if the expression cannot be evaluated at compile time.
+    /// The arithmetic wraps around on overflow, just like the generated code does at run time.
+    fn solve(&self, scopes: &impl ConstTable) -> Result<i32, Diagnostic>;
 }
 
 impl ExpSolve for ConstExp {
-    fn solve(&self, scopes: &Scopes) -> Result<i32, ()> {
+    fn solve(&self, scopes: &impl ConstTable) -> Result<i32, Diagnostic> {
         self.exp.solve(scopes)
     }
 }
 
 impl ExpSolve for Exp {
-    fn solve(&self, scopes: &Scopes) -> Result<i32, ()> {
+    fn solve(&self, scopes: &impl ConstTable) -> Result<i32, Diagnostic> {
         self.exp.solve(scopes)
     }
 }
 
 impl ExpSolve for LOrExp {
-    fn solve(&self, scopes: &Scopes) -> Result<i32, ()> {
+    fn solve(&self, scopes: &impl ConstTable) -> Result<i32, Diagnostic> {
         match self {
             LOrExp::LAnd(exp) => exp.solve(scopes),
             LOrExp::LOrLAnd(exp1, exp2) => {
@@ -35,7 +38,7 @@ impl ExpSolve for LOrExp {
 }
 
 impl ExpSolve for LAndExp {
-    fn solve(&self, scopes: &Scopes) -> Result<i32, ()> {
+    fn solve(&self, scopes: &impl ConstTable) -> Result<i32, Diagnostic> {
         match self {
             LAndExp::Eq(exp) => exp.solve(scopes),
             LAndExp::LAndEq(exp1, exp2) => {
@@ -48,7 +51,7 @@ impl ExpSolve for LAndExp {
 }
 
 impl ExpSolve for EqExp {
-    fn solve(&self, scopes: &Scopes) -> Result<i32, ()> {
+    fn solve(&self, scopes: &impl ConstTable) -> Result<i32, Diagnostic> {
         match self {
             EqExp::Rel(exp) => exp.solve(scopes),
             EqExp::EqRel(exp1, op, exp2) => {
@@ -64,7 +67,7 @@ impl ExpSolve for EqExp {
 }
 
 impl ExpSolve for RelExp {
-    fn solve(&self, scopes: &Scopes) -> Result<i32, ()> {
+    fn solve(&self, scopes: &impl ConstTable) -> Result<i32, Diagnostic> {
         match self {
             RelExp::Add(exp) => exp.solve(scopes),
             RelExp::RelAdd(exp1, op, exp2) => {
@@ -82,15 +85,15 @@ impl ExpSolve for RelExp {
 }
 
 impl ExpSolve for AddExp {
-    fn solve(&self, scopes: &Scopes) -> Result<i32, ()> {
+    fn solve(&self, scopes: &impl ConstTable) -> Result<i32, Diagnostic> {
         match self {
             AddExp::Mul(exp) => exp.solve(scopes),
             AddExp::AddMul(exp1, op, exp2) => {
                 let v1 = exp1.solve(scopes)?;
                 let v2 = exp2.solve(scopes)?;
                 match op {
-                    AddExpOp::Add => Ok(v1 + v2),
-                    AddExpOp::Sub => Ok(v1 - v2),
+                    AddExpOp::Add => Ok(v1.wrapping_add(v2)),
+                    AddExpOp::Sub => Ok(v1.wrapping_sub(v2)),
                 }
             }
         }
@@ -98,16 +101,21 @@ impl ExpSolve for AddExp {
 }
 
 impl ExpSolve for MulExp {
-    fn solve(&self, scopes: &Scopes) -> Result<i32, ()> {
+    fn solve(&self, scopes: &impl ConstTable) -> Result<i32, Diagnostic> {
         match self {
             MulExp::Unary(exp) => exp.solve(scopes),
             MulExp::MulUnary(exp1, op, exp2) => {
                 let v1 = exp1.solve(scopes)?;
                 let v2 = exp2.solve(scopes)?;
                 match op {
-                    MulExpOp::Mul => Ok(v1 * v2),
-                    MulExpOp::Div => Ok(v1 / v2),
-                    MulExpOp::Mod => Ok(v1 % v2),
+                    MulExpOp::Mul => Ok(v1.wrapping_mul(v2)),
+                    MulExpOp::Div | MulExpOp::Mod if v2 == 0 => {
+                        Err(Diagnostic::error("attempt to divide by zero")
+                            .with_primary(self.span(), "division by zero in a constant expression")
+                            .with_secondary(exp2.span(), "this evaluates to 0"))
+                    }
+                    MulExpOp::Div => Ok(v1.wrapping_div(v2)),
+                    MulExpOp::Mod => Ok(v1.wrapping_rem(v2)),
                 }
             }
         }
@@ -115,37 +123,75 @@ impl ExpSolve for MulExp {
 }
 
 impl ExpSolve for UnaryExp {
-    fn solve(&self, scopes: &Scopes) -> Result<i32, ()> {
+    fn solve(&self, scopes: &impl ConstTable) -> Result<i32, Diagnostic> {
         match self {
             UnaryExp::Primary(exp) => exp.solve(scopes),
-            UnaryExp::Unary(op, exp) => {
+            UnaryExp::Unary(UnaryExpOp::Neg, exp, _) if exp.is_int_min_magnitude() => Ok(i32::MIN),
+            UnaryExp::Unary(op, exp, _) => {
                 let v = exp.solve(scopes)?;
                 match op {
                     UnaryExpOp::Pos => Ok(v),
-                    UnaryExpOp::Neg => Ok(-v),
+                    UnaryExpOp::Neg => Ok(v.wrapping_neg()),
                     UnaryExpOp::Not => Ok((v == 0) as i32),
                 }
             }
-            UnaryExp::FuncCall(_, _) => Err(()), // Cannot evaluate the return value of a function during compile time!
+            // Cannot evaluate the return value of a function during compile time!
+            UnaryExp::FuncCall(ident, _, span) => Err(Diagnostic::error(format!(
+                "cannot call function `{}` in a constant expression",
+                ident.name
+            ))
+            .with_primary(*span, "not a constant")),
         }
     }
 }
 
 impl ExpSolve for PrimaryExp {
-    fn solve(&self, scopes: &Scopes) -> Result<i32, ()> {
+    fn solve(&self, scopes: &impl ConstTable) -> Result<i32, Diagnostic> {
         match self {
             PrimaryExp::Exp(exp) => exp.solve(scopes),
             PrimaryExp::LVal(lval) => lval.solve(scopes),
-            PrimaryExp::Num(num) => Ok(*num),
+            PrimaryExp::Num(num, span) => check_int_literal(*num, *span),
         }
     }
 }
 
+/// Check whether an integer literal fits in `int`, and return its value.
+pub fn check_int_literal(num: i64, span: Span) -> Result<i32, Diagnostic> {
+    i32::try_from(num).map_err(|_| {
+        Diagnostic::error("integer literal is out of range for `int`")
+            .with_primary(span, "this literal does not fit in 32 bits")
+            .with_note("the range of `int` is -2147483648..=2147483647")
+    })
+}
+
 impl ExpSolve for LVal {
-    fn solve(&self, scopes: &Scopes) -> Result<i32, ()> {
-        let SymbolTableValue::Const(v) = scopes.get_value(&self.ident)? else {
-            return Err(());
+    fn solve(&self, scopes: &impl ConstTable) -> Result<i32, Diagnostic> {
+        scopes.get_const(self)
+    }
+}
+
+/// A symbol table in which `ExpSolve` looks up the values of constants.
+pub trait ConstTable {
+    /// Get the value of the constant scalar that `lval` refers to.
+    ///
+    /// An error is returned if `lval` is not a constant scalar.
+    fn get_const(&self, lval: &LVal) -> Result<i32, Diagnostic>;
+}
+
+impl ConstTable for Scopes {
+    fn get_const(&self, lval: &LVal) -> Result<i32, Diagnostic> {
+        let SymbolTableValue::Const(v) = self.get_value(&lval.ident)? else {
+            let mut diag = Diagnostic::error(format!(
+                "`{}` cannot be used in a constant expression",
+                lval.ident.name
+            ))
+            .with_primary(lval.span, "not a constant");
+            if let Some(def_span) = self.get_value_span(&lval.ident.name) {
+                diag =
+                    diag.with_secondary(def_span, format!("`{}` is defined here", lval.ident.name));
+            }
+            return Err(diag);
         };
-        i32::from_str_radix(&v, 10).map_err(|_| ())
+        Ok(v)
     }
 }
@@ -0,0 +1,141 @@
+use super::named_symbol::NamedSymbolCounter;
+use super::scopes::Scopes;
+use crate::diagnostic::Diagnostic;
+use koopa::ir::builder::{
+    BasicBlockBuilder, GlobalInstBuilder, LocalBuilder, LocalInstBuilder, ValueBuilder,
+};
+use koopa::ir::*;
+
+/// Get an integer constant.
+///
+/// The constant is local to the current function, or global if we're not in a function.
+pub fn integer(program: &mut Program, scopes: &Scopes, value: i32) -> Value {
+    match scopes.cur_func() {
+        Ok(func) => program.func_mut(func).dfg_mut().new_value().integer(value),
+        Err(_) => program.new_value().integer(value),
+    }
+}
+
+/// Create a new basic block in the current function, without putting it into the layout.
+///
+/// `name` should be prefixed with `%`, and a unique suffix is appended to it.
+pub fn new_bb(
+    program: &mut Program,
+    scopes: &Scopes,
+    nsc: &mut NamedSymbolCounter,
+    name: &str,
+) -> Result<BasicBlock, Diagnostic> {
+    let func = scopes.cur_func()?;
+    let name = nsc.inc_and_get_named_symbol(name);
+    Ok(program
+        .func_mut(func)
+        .dfg_mut()
+        .new_bb()
+        .basic_block(Some(name)))
+}
+
+/// Put the basic block at the end of the current function, and append the following instructions to it.
+pub fn enter_bb(
+    program: &mut Program,
+    scopes: &mut Scopes,
+    bb: BasicBlock,
+) -> Result<(), Diagnostic> {
+    let func = scopes.cur_func()?;
+    program
+        .func_mut(func)
+        .layout_mut()
+        .bbs_mut()
+        .push_key_back(bb)
+        .map_err(|_| Diagnostic::error("a basic block is put into the layout twice"))?;
+    scopes.cur_bb = Some(bb);
+    Ok(())
+}
+
+/// Build an instruction and append it to the current basic block.
+///
+/// Instructions following a terminator (e.g. the statements after `return`) are unreachable.
+/// A new basic block is started for them, so every basic block ends with exactly one terminator.
+pub fn emit(
+    program: &mut Program,
+    scopes: &mut Scopes,
+    nsc: &mut NamedSymbolCounter,
+    build: impl FnOnce(LocalBuilder) -> Value,
+) -> Result<Value, Diagnostic> {
+    let bb = match scopes.cur_bb {
+        Some(bb) => bb,
+        None => {
+            let bb = new_bb(program, scopes, nsc, "%unreachable")?;
+            enter_bb(program, scopes, bb)?;
+            bb
+        }
+    };
+    let func = program.func_mut(scopes.cur_func()?);
+    let inst = build(func.dfg_mut().new_value());
+    let is_terminator = matches!(
+        func.dfg().value(inst).kind(),
+        ValueKind::Branch(_) | ValueKind::Jump(_) | ValueKind::Return(_)
+    );
+    func.layout_mut()
+        .bb_mut(bb)
+        .insts_mut()
+        .push_key_back(inst)
+        .map_err(|_| Diagnostic::error("an instruction is put into the layout twice"))?;
+    if is_terminator {
+        scopes.cur_bb = None;
+    }
+    Ok(inst)
+}
+
+/// Allocate a named memory slot in the current function.
+///
+/// `name` should be prefixed with `@` or `%`, and a unique suffix is appended to it.
+pub fn emit_named_alloc(
+    program: &mut Program,
+    scopes: &mut Scopes,
+    nsc: &mut NamedSymbolCounter,
+    name: &str,
+    ty: Type,
+) -> Result<Value, Diagnostic> {
+    let alloc = emit(program, scopes, nsc, |b| b.alloc(ty))?;
+    let name = nsc.inc_and_get_named_symbol(name);
+    program
+        .func_mut(scopes.cur_func()?)
+        .dfg_mut()
+        .set_value_name(alloc, Some(name));
+    Ok(alloc)
+}
+
+/// Jump to `target` if the current basic block has not been terminated.
+pub fn emit_jump_if_open(
+    program: &mut Program,
+    scopes: &mut Scopes,
+    nsc: &mut NamedSymbolCounter,
+    target: BasicBlock,
+) -> Result<bool, Diagnostic> {
+    if scopes.cur_bb.is_none() {
+        return Ok(false);
+    }
+    emit(program, scopes, nsc, |b| b.jump(target))?;
+    Ok(true)
+}
+
+/// Allocate a named global memory slot with the given initial value.
+///
+/// `name` should be prefixed with `@`, and a unique suffix is appended to it.
+pub fn global_named_alloc(
+    program: &mut Program,
+    nsc: &mut NamedSymbolCounter,
+    name: &str,
+    init: Value,
+) -> Value {
+    let alloc = program.new_value().global_alloc(init);
+    program.set_value_name(alloc, Some(nsc.inc_and_get_named_symbol(name)));
+    alloc
+}
+
+/// Drop a basic block that is never put into the layout.
+pub fn remove_bb(program: &mut Program, scopes: &Scopes, bb: BasicBlock) -> Result<(), Diagnostic> {
+    let func = scopes.cur_func()?;
+    program.func_mut(func).dfg_mut().remove_bb(bb);
+    Ok(())
+}
@@ -1,992 +1,829 @@
 use super::array_utils::*;
-use super::exp_solve::ExpSolve;
+use super::exp_solve::{check_int_literal, ExpSolve};
+use super::ir_builder::*;
 use super::named_symbol::NamedSymbolCounter;
 use super::scopes::*;
-use super::temp_symbol::TempSymbolManager;
 use crate::ast_generate::ast::*;
-use crate::tools::*;
+use crate::diagnostic::Diagnostic;
+use koopa::ir::builder::{LocalInstBuilder, ValueBuilder};
+use koopa::ir::*;
+use std::collections::HashSet;
 
-/// Run DFS on the AST and generate the Koopa text.
-pub trait KoopaTextGenerate {
-    /// Generate the Koopa text recursively.
+/// Run DFS on the AST and build the Koopa IR in memory.
+pub trait KoopaGenerate {
+    type Ret;
+
+    /// Generate the Koopa IR recursively.
     ///
-    /// `lines` should always be empty when entering the method.
+    /// Instructions are appended to the current basic block of the current function,
+    /// both of which are recorded in `scopes`.
     fn generate(
         &self,
-        lines: &mut String,
+        program: &mut Program,
         scopes: &mut Scopes,
-        tsm: &mut TempSymbolManager,
         nsc: &mut NamedSymbolCounter,
-    ) -> Result<String, ()>;
+    ) -> Result<Self::Ret, Diagnostic>;
 }
 
-impl KoopaTextGenerate for CompUnit {
+impl KoopaGenerate for CompUnit {
+    type Ret = ();
+
     fn generate(
         &self,
-        lines: &mut String,
+        program: &mut Program,
         scopes: &mut Scopes,
-        tsm: &mut TempSymbolManager,
         nsc: &mut NamedSymbolCounter,
-    ) -> Result<String, ()> {
-        // global values are placed before library function declarations
+    ) -> Result<Self::Ret, Diagnostic> {
         for item in self.items.iter() {
             if let CompUnitItem::GlobalDecl(global_decl) = item {
-                let mut global_decl_text = String::new();
-                global_decl.generate(&mut global_decl_text, scopes, tsm, nsc)?;
-                append_line(lines, &global_decl_text);
+                global_decl.generate(program, scopes, nsc)?;
             }
         }
-        if !lines.is_empty() {
-            append_line(lines, " ");
-        }
 
         // declarations of SysY library functions
-        append_line(lines, "decl @getint(): i32\n");
-        append_line(lines, "decl @getch(): i32\n");
-        append_line(lines, "decl @getarray(*i32): i32\n");
-        append_line(lines, "decl @putint(i32)\n");
-        append_line(lines, "decl @putch(i32)\n");
-        append_line(lines, "decl @putarray(i32, *i32)\n");
-        append_line(lines, "decl @starttime()\n");
-        append_line(lines, "decl @stoptime()\n");
+        let int_ptr = Type::get_pointer(Type::get_i32());
+        let library = [
+            ("getint", Vec::new(), Type::get_i32()),
+            ("getch", Vec::new(), Type::get_i32()),
+            ("getarray", vec![int_ptr.clone()], Type::get_i32()),
+            ("putint", vec![Type::get_i32()], Type::get_unit()),
+            ("putch", vec![Type::get_i32()], Type::get_unit()),
+            ("putarray", vec![Type::get_i32(), int_ptr], Type::get_unit()),
+            ("starttime", Vec::new(), Type::get_unit()),
+            ("stoptime", Vec::new(), Type::get_unit()),
+        ];
         // put these functions into the global scope
-        scopes.add_function("getint", "@getint", false, Vec::new())?;
-        scopes.add_function("getch", "@getch", false, Vec::new())?;
-        scopes.add_function("getarray", "@getarray", false, vec![true])?;
-        scopes.add_function("putint", "@putint", true, vec![false])?;
-        scopes.add_function("putch", "@putch", true, vec![false])?;
-        scopes.add_function("putarray", "@putarray", true, vec![false, true])?;
-        scopes.add_function("starttime", "@starttime", true, Vec::new())?;
-        scopes.add_function("stoptime", "@stoptime", true, Vec::new())?;
+        for (name, params_ty, ret_ty) in library {
+            let array_param = params_ty.iter().map(|ty| !ty.is_i32()).collect();
+            let return_void = ret_ty.is_unit();
+            let func = program.new_func(FunctionData::new_decl(
+                format!("@{}", name),
+                params_ty,
+                ret_ty,
+            ));
+            scopes.add_function(name, func, return_void, array_param, None)?;
+        }
+
+        // Prototypes of the functions defined in the source code are simply skipped.
+        // The others declare runtime functions, which are lowered to `decl`.
+        let defined: HashSet<&str> = self
+            .items
+            .iter()
+            .filter_map(|item| match item {
+                CompUnitItem::FuncDef(func_def) => Some(func_def.ident.name.as_str()),
+                _ => None,
+            })
+            .collect();
+        for item in self.items.iter() {
+            if let CompUnitItem::FuncDecl(func_decl) = item {
+                if defined.contains(func_decl.ident.name.as_str())
+                    || scopes.has_function(&func_decl.ident.name)
+                {
+                    continue;
+                }
+                let params_ty = generate_param_types(&func_decl.params, scopes)?;
+                let ret_ty = func_decl.func_type.generate(program, scopes, nsc)?;
+                let func = program.new_func(FunctionData::new_decl(
+                    format!("@{}", func_decl.ident.name),
+                    params_ty,
+                    ret_ty,
+                ));
+                scopes.add_function(
+                    &func_decl.ident.name,
+                    func,
+                    matches!(func_decl.func_type, FuncType::Void),
+                    func_decl.params.iter().map(|p| p.dims.is_some()).collect(),
+                    Some(func_decl.ident.span),
+                )?;
+            }
+        }
+
+        // Create all the functions before generating any function body,
+        // so that a function can be called before it's defined.
+        for item in self.items.iter() {
+            if let CompUnitItem::FuncDef(func_def) = item {
+                let params_ty = generate_param_types(&func_def.params, scopes)?;
+                let ret_ty = func_def.func_type.generate(program, scopes, nsc)?;
+                let func = program.new_func(FunctionData::new(
+                    format!("@{}", func_def.ident.name),
+                    params_ty,
+                    ret_ty,
+                ));
+                scopes.add_function(
+                    &func_def.ident.name,
+                    func,
+                    matches!(func_def.func_type, FuncType::Void),
+                    func_def.params.iter().map(|p| p.dims.is_some()).collect(),
+                    Some(func_def.ident.span),
+                )?;
+            }
+        }
 
         // generate function definitions
         for item in self.items.iter() {
             if let CompUnitItem::FuncDef(func_def) = item {
-                let mut func_text = String::new();
-                func_def.generate(&mut func_text, scopes, tsm, nsc)?;
-                append_line(lines, &func_text);
-                append_line(lines, " ");
+                func_def.generate(program, scopes, nsc)?;
             }
         }
-        Ok(String::new())
+        Ok(())
     }
 }
 
-impl KoopaTextGenerate for FuncDef {
+impl KoopaGenerate for FuncDef {
+    type Ret = ();
+
     fn generate(
         &self,
-        lines: &mut String,
+        program: &mut Program,
         scopes: &mut Scopes,
-        tsm: &mut TempSymbolManager,
         nsc: &mut NamedSymbolCounter,
-    ) -> Result<String, ()> {
-        scopes.clear_cur_func_params();
-
-        // function return type
-        let mut ft = self
-            .func_type
-            .generate(&mut String::new(), scopes, tsm, nsc)?;
-        if !ft.is_empty() {
-            ft = format!(": {}", ft);
-        }
-
-        // function name
-        let func_name = format!("@{}", self.ident);
+    ) -> Result<Self::Ret, Diagnostic> {
+        // the function itself has been created by `CompUnit`
+        let FunctionInfo {
+            handle,
+            return_void,
+            ..
+        } = scopes.get_function(&self.ident)?;
+        scopes.enter_func(handle);
+        let entry = new_bb(program, scopes, nsc, "%entry")?;
+        enter_bb(program, scopes, entry)?;
 
         // function parameters
-        let mut param_text = String::new();
-        let mut func_param_reallocation_text = String::new();
-        let mut array_param = Vec::new();
-        for (i, param) in self.params.iter().enumerate() {
-            let formal_param_symbol = tsm.new_temp_symbol();
-            let param_ident = param.generate(&mut String::new(), scopes, tsm, nsc)?;
-            let reallocated_param_symbol =
-                nsc.inc_and_get_named_symbol(&format!("@{}", param_ident))?;
-
-            let type_str = match param.dims {
-                None => {
-                    array_param.push(false);
-                    String::from("i32")
-                },
-                Some(ref dims) => {
-                    array_param.push(true);
-                    let dims: Vec<i32> = dims
-                        .iter()
-                        .map(|const_exp| const_exp.solve(scopes).unwrap())
-                        .collect();
-                    format!("*{}", generate_allocate_dims(&dims, 0))
-                }
-            };
-
-            if i != 0 {
-                param_text.push_str(", ")
-            }
-            param_text.push_str(&format!("{}: {}", formal_param_symbol, type_str));
-
-            append_line(
-                &mut func_param_reallocation_text,
-                &format!("  {} = alloc {}", reallocated_param_symbol, type_str),
-            );
-            append_line(
-                &mut func_param_reallocation_text,
-                &format!(
-                    "  store {}, {}",
-                    formal_param_symbol, reallocated_param_symbol
-                ),
-            );
-            let n_array_dim = if let Some(dims) = &param.dims {
-                Some(dims.len() + 1)
-            } else {
-                None
+        // Each parameter is moved into a memory slot, so that it can be assigned like a local variable.
+        let param_types = generate_param_types(&self.params, scopes)?;
+        let formal_params = program.func(handle).params().to_vec();
+        for ((param, ty), formal_param) in self.params.iter().zip(param_types).zip(formal_params) {
+            let reallocated_param =
+                emit_named_alloc(program, scopes, nsc, &format!("@{}", param.ident.name), ty)?;
+            emit(program, scopes, nsc, |b| {
+                b.store(formal_param, reallocated_param)
+            })?;
+
+            let v = match param.dims {
+                Some(ref dims) => SymbolTableValue::Array(reallocated_param, dims.len() + 1),
+                None => SymbolTableValue::Var(reallocated_param),
             };
-            scopes.add_value_to_buffer(&param_ident, &reallocated_param_symbol, false, n_array_dim);
-            scopes.add_cur_func_param(&reallocated_param_symbol);
+            scopes.add_value_to_buffer(&param.ident, v);
+            scopes.add_cur_func_param(reallocated_param);
         }
 
-        scopes.add_function(&self.ident, &func_name, ft.is_empty(), array_param)?;
-
         // function body
-        let mut body_text = String::new();
-        self.block.generate(&mut body_text, scopes, tsm, nsc)?;
-
-        // Return statements
-        // 1. If there's no `ret` instruction in the function body, we only need to add one at the last line.
-        // 2. Only when the return type is `void` can the `ret` instruction be omitted by the original function body.
-        let Some(last_line) = body_text.split("\n").last() else {
-            return Err(());
-        };
-        if last_line.contains("%after_return") {
-            let Some(idx) = body_text.rfind("%after_return") else {
-                return Err(());
-            };
-            body_text = body_text[..(idx-1)].to_string();
-        } else if !last_line.contains("ret") {
-            append_line(&mut body_text, "  ret");
-        }
+        self.block.generate(program, scopes, nsc)?;
 
-        append_line(
-            lines,
-            &format!("fun {}({}){} {{", func_name, param_text, ft),
-        );
-        append_line(lines, &format!("{}:", &nsc.inc_and_get_named_symbol("%entry")?));
-        append_line(lines, &func_param_reallocation_text);
-        append_line(lines, &body_text);
-        append_line(lines, "}");
+        // The control flow may reach the end of the function without a `return`,
+        // e.g. a `void` function, or an `int` function whose result is not used.
+        if scopes.cur_bb.is_some() {
+            let ret = (!return_void).then(|| integer(program, scopes, 0));
+            emit(program, scopes, nsc, |b| b.ret(ret))?;
+        }
 
-        Ok(String::new())
+        scopes.exit_func();
+        Ok(())
     }
 }
 
-impl KoopaTextGenerate for FuncType {
+/// Generate the Koopa types of function parameters.
+///
+/// An array parameter is a pointer to its sub-array, e.g. `int a[][3]` is `*[i32, 3]`.
+fn generate_param_types(params: &[FuncFParam], scopes: &Scopes) -> Result<Vec<Type>, Diagnostic> {
+    params
+        .iter()
+        .map(|param| match param.dims {
+            None => Ok(Type::get_i32()),
+            Some(ref dims) => {
+                let dims = solve_array_dims(dims, scopes)?;
+                Ok(Type::get_pointer(array_type(&dims)))
+            }
+        })
+        .collect()
+}
+
+impl KoopaGenerate for FuncType {
+    type Ret = Type;
+
     fn generate(
         &self,
-        _lines: &mut String,
+        _program: &mut Program,
         _scopes: &mut Scopes,
-        _tsm: &mut TempSymbolManager,
         _nsc: &mut NamedSymbolCounter,
-    ) -> Result<String, ()> {
+    ) -> Result<Self::Ret, Diagnostic> {
         match self {
-            Self::Int => Ok(String::from("i32")),
-            Self::Void => Ok(String::new()),
+            Self::Int => Ok(Type::get_i32()),
+            Self::Void => Ok(Type::get_unit()),
         }
     }
 }
 
-impl KoopaTextGenerate for FuncFParam {
-    /// Returns the id of the parameter.
-    fn generate(
-        &self,
-        _lines: &mut String,
-        _scopes: &mut Scopes,
-        _tsm: &mut TempSymbolManager,
-        _nsc: &mut NamedSymbolCounter,
-    ) -> Result<String, ()> {
-        Ok(self.ident.clone())
-    }
-}
+impl KoopaGenerate for Block {
+    type Ret = ();
 
-impl KoopaTextGenerate for Block {
     fn generate(
         &self,
-        lines: &mut String,
+        program: &mut Program,
         scopes: &mut Scopes,
-        tsm: &mut TempSymbolManager,
         nsc: &mut NamedSymbolCounter,
-    ) -> Result<String, ()> {
+    ) -> Result<Self::Ret, Diagnostic> {
         scopes.enter()?;
 
         for item in self.items.iter() {
-            let mut s = String::new();
-            item.generate(&mut s, scopes, tsm, nsc)?;
-            append_line(lines, &s);
+            item.generate(program, scopes, nsc)?;
         }
 
         scopes.exit();
-        Ok(String::new())
+        Ok(())
     }
 }
 
-impl KoopaTextGenerate for BlockItem {
+impl KoopaGenerate for BlockItem {
+    type Ret = ();
+
     fn generate(
         &self,
-        lines: &mut String,
+        program: &mut Program,
         scopes: &mut Scopes,
-        tsm: &mut TempSymbolManager,
         nsc: &mut NamedSymbolCounter,
-    ) -> Result<String, ()> {
+    ) -> Result<Self::Ret, Diagnostic> {
         match self {
-            Self::Stmt(stmt) => stmt.generate(lines, scopes, tsm, nsc),
-            Self::Decl(decl) => decl.generate(lines, scopes, tsm, nsc),
+            Self::Stmt(stmt) => stmt.generate(program, scopes, nsc),
+            Self::Decl(decl) => decl.generate(program, scopes, nsc),
         }
     }
 }
 
-impl KoopaTextGenerate for Stmt {
+impl KoopaGenerate for Stmt {
+    type Ret = ();
+
     fn generate(
         &self,
-        lines: &mut String,
+        program: &mut Program,
         scopes: &mut Scopes,
-        tsm: &mut TempSymbolManager,
         nsc: &mut NamedSymbolCounter,
-    ) -> Result<String, ()> {
-        match self {
-            Self::Assign(lval, exp) => {
-                let mut pre = String::new();
-                if let SymbolTableValue::Const(_) = scopes.get_value(&lval.ident)? {
-                    return Err(()); // assignment to constant
-                }
-                let ptr = lval.generate(&mut pre, scopes, tsm, nsc)?;
-                append_line(lines, &pre);
-
-                pre = String::new();
-                let right = exp.generate(&mut pre, scopes, tsm, nsc)?;
-                append_line(lines, &pre);
-
-                append_line(lines, &format!("  store {}, {}", right, ptr));
-            }
-            Self::Exp(exp) => {
+    ) -> Result<Self::Ret, Diagnostic> {
+        match &self.kind {
+            StmtKind::Assign(lval, exp) => {
+                // assignments to constants have been rejected by the semantic analysis
+                let ptr = lval.generate(program, scopes, nsc)?;
+                let right = exp.generate(program, scopes, nsc)?;
+                emit(program, scopes, nsc, |b| b.store(right, ptr))?;
+            }
+            StmtKind::Exp(exp) => {
                 if let Some(expression) = exp {
-                    let mut pre = String::new();
-                    expression.generate(&mut pre, scopes, tsm, nsc)?;
-                    append_line(lines, &pre);
+                    expression.generate(program, scopes, nsc)?;
                 }
             }
-            Self::Block(block) => {
-                let mut pre = String::new();
-                block.generate(&mut pre, scopes, tsm, nsc)?;
-                append_line(lines, &pre);
+            StmtKind::Block(block) => {
+                block.generate(program, scopes, nsc)?;
             }
-            Self::If(cond, then, otherwise) => {
-                // prepare the labels
-                let then_label = nsc.inc_and_get_named_symbol("%if_then")?;
-                let else_label = nsc.inc_and_get_named_symbol("%if_else")?;
-                let end_label = nsc.inc_and_get_named_symbol("%if_end")?;
-
+            StmtKind::If(cond, then, otherwise) => {
                 // cond generation
-                let mut text_for_cond = String::new();
-                let cond_handle = cond.generate(&mut text_for_cond, scopes, tsm, nsc)?;
-                append_line(lines, &text_for_cond);
-                append_line(
-                    lines,
-                    &format!("  br {}, {}, {}", cond_handle, then_label, else_label),
-                );
+                let cond_handle = cond.generate(program, scopes, nsc)?;
+
+                // prepare the basic blocks
+                let then_bb = new_bb(program, scopes, nsc, "%if_then")?;
+                let else_bb = match otherwise {
+                    Some(_) => Some(new_bb(program, scopes, nsc, "%if_else")?),
+                    None => None,
+                };
+                let end_bb = new_bb(program, scopes, nsc, "%if_end")?;
+                emit(program, scopes, nsc, |b| {
+                    b.branch(cond_handle, then_bb, else_bb.unwrap_or(end_bb))
+                })?;
 
                 // then generation
-                append_line(lines, &format!("\n{}:", then_label));
-                let mut text_for_then = String::new();
-                then.generate(&mut text_for_then, scopes, tsm, nsc)?;
-                append_line(lines, &text_for_then);
-                append_line(lines, &format!("  jump {}", end_label));
+                enter_bb(program, scopes, then_bb)?;
+                then.generate(program, scopes, nsc)?;
+                let mut end_reachable = emit_jump_if_open(program, scopes, nsc, end_bb)?;
 
                 // else generation
-                append_line(lines, &format!("\n{}:", else_label));
-                let mut text_for_else = String::new();
-                if let Some(otherwise) = otherwise {
-                    otherwise.generate(&mut text_for_else, scopes, tsm, nsc)?;
+                match (otherwise, else_bb) {
+                    (Some(otherwise), Some(else_bb)) => {
+                        enter_bb(program, scopes, else_bb)?;
+                        otherwise.generate(program, scopes, nsc)?;
+                        end_reachable |= emit_jump_if_open(program, scopes, nsc, end_bb)?;
+                    }
+                    _ => end_reachable = true,
                 }
-                append_line(lines, &text_for_else);
-                append_line(lines, &format!("  jump {}", end_label));
 
-                // end label generation
-                append_line(lines, &format!("\n{}:", end_label));
+                // If both branches have returned, the code following the `if` statement is unreachable.
+                if end_reachable {
+                    enter_bb(program, scopes, end_bb)?;
+                } else {
+                    remove_bb(program, scopes, end_bb)?;
+                }
             }
-            Self::While(cond, body) => {
-                // prepare the labels
-                let entry_label = nsc.inc_and_get_named_symbol("%while_entry")?;
-                let body_label = nsc.inc_and_get_named_symbol("%while_body")?;
-                let end_label = nsc.inc_and_get_named_symbol("%while_end")?;
+            StmtKind::While(cond, body) => {
+                // prepare the basic blocks
+                let entry_bb = new_bb(program, scopes, nsc, "%while_entry")?;
+                let body_bb = new_bb(program, scopes, nsc, "%while_body")?;
+                let end_bb = new_bb(program, scopes, nsc, "%while_end")?;
 
                 // cond generation
-                append_line(lines, &format!("  jump {}", entry_label));
-                append_line(lines, &format!("\n{}:", entry_label));
-                let mut text_for_cond = String::new();
-                let cond_handle = cond.generate(&mut text_for_cond, scopes, tsm, nsc)?;
-                append_line(lines, &text_for_cond);
-                append_line(
-                    lines,
-                    &format!("  br {}, {}, {}", cond_handle, body_label, end_label),
-                );
+                emit(program, scopes, nsc, |b| b.jump(entry_bb))?;
+                enter_bb(program, scopes, entry_bb)?;
+                let cond_handle = cond.generate(program, scopes, nsc)?;
+                emit(program, scopes, nsc, |b| {
+                    b.branch(cond_handle, body_bb, end_bb)
+                })?;
 
                 // body generation
-                append_line(lines, &format!("\n{}:", body_label));
-                scopes.enter_loop(&entry_label, &body_label, &end_label);
-                let mut text_for_body = String::new();
-                body.generate(&mut text_for_body, scopes, tsm, nsc)?;
+                enter_bb(program, scopes, body_bb)?;
+                scopes.enter_loop(entry_bb, body_bb, end_bb);
+                body.generate(program, scopes, nsc)?;
                 scopes.exit_loop();
-                append_line(lines, &text_for_body);
-                append_line(lines, &format!("  jump {}", entry_label));
+                emit_jump_if_open(program, scopes, nsc, entry_bb)?;
 
-                // end label generation
-                append_line(lines, &format!("\n{}:", end_label));
+                // end generation
+                enter_bb(program, scopes, end_bb)?;
             }
-            Self::Break => {
-                let Ok(LoopLabel{entry: _, body: _, end: end_label}) = scopes.get_cur_loop_labels() else {
-                    return Err(());
+            StmtKind::Break => {
+                let Some(LoopLabel { end, .. }) = scopes.get_cur_loop_labels() else {
+                    return Err(Diagnostic::error("`break` outside of a loop")
+                        .with_primary(self.span, "cannot `break` outside of a loop"));
                 };
-                append_line(lines, &format!("  jump {}", end_label));
-
-                // The original basic block is splitted into two halves by the `jump` instruction.
-                // Hence, we need to add a new label here to indicate the start of a new basic block.
-                let new_label = nsc.inc_and_get_named_symbol("%after_break")?;
-                append_line(lines, &format!("\n{}:", new_label));
+                emit(program, scopes, nsc, |b| b.jump(end))?;
             }
-            Self::Continue => {
-                let Ok(LoopLabel{entry: entry_label, body: _, end: _}) = scopes.get_cur_loop_labels() else {
-                    return Err(());
+            StmtKind::Continue => {
+                let Some(LoopLabel { entry, .. }) = scopes.get_cur_loop_labels() else {
+                    return Err(Diagnostic::error("`continue` outside of a loop")
+                        .with_primary(self.span, "cannot `continue` outside of a loop"));
                 };
-                append_line(lines, &format!("  jump {}", entry_label));
-
-                // The original basic block is splitted into two halves by the `jump` instruction.
-                // Hence, we need to add a new label here to indicate the start of a new basic block.
-                let new_label = nsc.inc_and_get_named_symbol("%after_continue")?;
-                append_line(lines, &format!("\n{}:", new_label));
+                emit(program, scopes, nsc, |b| b.jump(entry))?;
             }
-            Self::Return(exp) => {
-                // `ret` indicates the end of a basic block!!!
-                let mut pre = String::new();
-                if let Some(expression) = exp {
-                    let ret = expression.generate(&mut pre, scopes, tsm, nsc)?;
-                    append_line(&mut pre, &format!("  ret {}", ret));
-                } else {
-                    append_line(&mut pre, "  ret");
-                }
-                append_line(lines, &pre);
-                let bb_label = nsc.inc_and_get_named_symbol("%after_return")?;
-                append_line(lines, &format!("{}:", bb_label));
+            StmtKind::Return(exp) => {
+                let ret = match exp {
+                    Some(expression) => Some(expression.generate(program, scopes, nsc)?),
+                    None => None,
+                };
+                emit(program, scopes, nsc, |b| b.ret(ret))?;
             }
         }
 
-        Ok(String::new())
+        Ok(())
     }
 }
 
-impl KoopaTextGenerate for GlobalDecl {
+impl KoopaGenerate for GlobalDecl {
+    type Ret = ();
+
     fn generate(
         &self,
-        lines: &mut String,
+        program: &mut Program,
         scopes: &mut Scopes,
-        tsm: &mut TempSymbolManager,
         nsc: &mut NamedSymbolCounter,
-    ) -> Result<String, ()> {
+    ) -> Result<Self::Ret, Diagnostic> {
         match self.decl {
             // global constant
-            Decl::Const(ref const_decl) => const_decl.generate(lines, scopes, tsm, nsc),
+            Decl::Const(ref const_decl) => const_decl.generate(program, scopes, nsc),
             // global variables
-            Decl::Var(ref var_decl) => var_decl.generate(lines, scopes, tsm, nsc),
+            Decl::Var(ref var_decl) => var_decl.generate(program, scopes, nsc),
         }
     }
 }
 
-impl KoopaTextGenerate for Decl {
+impl KoopaGenerate for Decl {
+    type Ret = ();
+
     fn generate(
         &self,
-        lines: &mut String,
+        program: &mut Program,
         scopes: &mut Scopes,
-        tsm: &mut TempSymbolManager,
         nsc: &mut NamedSymbolCounter,
-    ) -> Result<String, ()> {
+    ) -> Result<Self::Ret, Diagnostic> {
         match self {
-            Self::Const(const_decl) => const_decl.generate(lines, scopes, tsm, nsc),
-            Self::Var(var_decl) => var_decl.generate(lines, scopes, tsm, nsc),
+            Self::Const(const_decl) => const_decl.generate(program, scopes, nsc),
+            Self::Var(var_decl) => var_decl.generate(program, scopes, nsc),
         }
     }
 }
 
-impl KoopaTextGenerate for ConstDecl {
+impl KoopaGenerate for ConstDecl {
+    type Ret = ();
+
     fn generate(
         &self,
-        lines: &mut String,
+        program: &mut Program,
         scopes: &mut Scopes,
-        tsm: &mut TempSymbolManager,
         nsc: &mut NamedSymbolCounter,
-    ) -> Result<String, ()> {
+    ) -> Result<Self::Ret, Diagnostic> {
         for def in self.defs.iter() {
-            let mut pre = String::new();
-            def.generate(&mut pre, scopes, tsm, nsc)?;
-            append_line(lines, &pre);
+            def.generate(program, scopes, nsc)?;
         }
 
-        Ok(String::new())
+        Ok(())
     }
 }
 
-impl KoopaTextGenerate for ConstDef {
+impl KoopaGenerate for ConstDef {
+    type Ret = ();
+
     fn generate(
         &self,
-        lines: &mut String,
+        program: &mut Program,
         scopes: &mut Scopes,
-        tsm: &mut TempSymbolManager,
         nsc: &mut NamedSymbolCounter,
-    ) -> Result<String, ()> {
+    ) -> Result<Self::Ret, Diagnostic> {
         if self.dims.is_empty() {
             // Constant scalars, both global and local.
-            // No code line is generated, and the symbol will be replaced directly by its value.
-            let init = self.init.generate(&mut String::new(), scopes, tsm, nsc)?; // Get the initial value.
-            scopes.add_value(&self.ident, &init, true, None)?;
+            // No instruction is generated, and the symbol will be replaced directly by its value.
+            let ConstInitVal::Exp(ref exp) = self.init else {
+                return Err(
+                    Diagnostic::error("invalid initializer for a scalar constant").with_primary(
+                        self.init.span(),
+                        "expected an expression, found an initializer list",
+                    ),
+                );
+            };
+            let init = exp.solve(scopes)?;
+            scopes.add_value(&self.ident, SymbolTableValue::Const(init))?;
         } else {
             // Constant arrays.
-            let symbol = nsc.inc_and_get_named_symbol(&format!("@{}", self.ident))?;
-            scopes.add_value(&self.ident, &symbol, true, Some(self.dims.len()))?;
-
-            let dims: Vec<i32> = self
-                .dims
-                .iter()
-                .map(|const_exp| const_exp.solve(scopes).unwrap())
-                .collect();
-            let dims_str = generate_allocate_dims(&dims, 0);
-            let full_init = parse_const_array_initializer(&self.init, &dims, scopes)?;
-
-            if scopes.now_global() {
+            let dims = solve_array_dims(&self.dims, scopes)?;
+            let full_init = flatten_array_initializer(&self.init, &dims)
+                .into_iter()
+                .map(|exp| exp.map_or(Ok(0), |exp| exp.solve(scopes)))
+                .collect::<Result<Vec<i32>, Diagnostic>>()?;
+            let symbol = format!("@{}", self.ident.name);
+
+            let array = if scopes.now_global() {
                 // Global constant arrays.
-                let init = full_initializer_to_global_aggregate(&full_init, &dims);
-                append_line(
-                    lines,
-                    &format!("global {} = alloc {}, {}", symbol, dims_str, init),
-                );
+                let init = full_initializer_to_global_aggregate(program, &full_init, &dims);
+                global_named_alloc(program, nsc, &symbol, init)
             } else {
                 // Local constant arrays.
-                append_line(lines, &format!("  {} = alloc {}", symbol, dims_str));
-                let new_lines = full_initializer_to_local_lines(&symbol, &full_init, &dims, scopes, nsc)?;
-                append_line(lines, &new_lines);
-            }
+                let array = emit_named_alloc(program, scopes, nsc, &symbol, array_type(&dims))?;
+                let full_init: Vec<Value> = full_init
+                    .into_iter()
+                    .map(|v| integer(program, scopes, v))
+                    .collect();
+                full_initializer_to_local_stores(program, array, &full_init, &dims, scopes, nsc)?;
+                array
+            };
+            scopes.add_value(&self.ident, SymbolTableValue::Array(array, dims.len()))?;
         }
 
-        Ok(String::new())
+        Ok(())
     }
 }
 
-impl KoopaTextGenerate for ConstInitVal {
-    fn generate(
-        &self,
-        lines: &mut String,
-        scopes: &mut Scopes,
-        tsm: &mut TempSymbolManager,
-        nsc: &mut NamedSymbolCounter,
-    ) -> Result<String, ()> {
-        match self {
-            Self::Exp(exp) => exp.generate(lines, scopes, tsm, nsc),
-            _ => Err(()),
-        }
-    }
-}
+impl KoopaGenerate for VarDecl {
+    type Ret = ();
 
-impl KoopaTextGenerate for VarDecl {
     fn generate(
         &self,
-        lines: &mut String,
+        program: &mut Program,
         scopes: &mut Scopes,
-        tsm: &mut TempSymbolManager,
         nsc: &mut NamedSymbolCounter,
-    ) -> Result<String, ()> {
+    ) -> Result<Self::Ret, Diagnostic> {
         for def in self.defs.iter() {
-            let mut pre = String::new();
-            def.generate(&mut pre, scopes, tsm, nsc)?;
-            append_line(lines, &pre);
+            def.generate(program, scopes, nsc)?;
         }
 
-        Ok(String::new())
+        Ok(())
     }
 }
 
-impl KoopaTextGenerate for VarDef {
+impl KoopaGenerate for VarDef {
+    type Ret = ();
+
     fn generate(
         &self,
-        lines: &mut String,
+        program: &mut Program,
         scopes: &mut Scopes,
-        tsm: &mut TempSymbolManager,
         nsc: &mut NamedSymbolCounter,
-    ) -> Result<String, ()> {
-        let symbol_name = nsc.inc_and_get_named_symbol(&format!("@{}", &self.ident))?;
+    ) -> Result<Self::Ret, Diagnostic> {
+        let symbol = format!("@{}", self.ident.name);
 
         if self.dims.is_empty() {
-            scopes.add_value(&self.ident, &symbol_name, false, None)?;
-
             if scopes.now_global() {
                 // global scalars
                 let init = match self.init {
-                    Some(ref init) => match init {
-                        InitVal::Exp(exp) => exp.solve(scopes)?.to_string(),
-                        _ => "zeroinit".to_string(),
-                    },
-                    None => "zeroinit".to_string(),
+                    Some(InitVal::Exp(ref exp)) => program.new_value().integer(exp.solve(scopes)?),
+                    _ => program.new_value().zero_init(Type::get_i32()),
                 };
-                append_line(
-                    lines,
-                    &format!("global {} = alloc i32, {}", symbol_name, init),
-                );
+                let var = global_named_alloc(program, nsc, &symbol, init);
+                scopes.add_value(&self.ident, SymbolTableValue::Var(var))?;
             } else {
                 // local scalars
-                append_line(lines, &format!("  {} = alloc i32", symbol_name));
+                let var = emit_named_alloc(program, scopes, nsc, &symbol, Type::get_i32())?;
+                scopes.add_value(&self.ident, SymbolTableValue::Var(var))?;
                 if let Some(ref init) = self.init {
                     // has initial value
-                    let mut pre = String::new();
-                    let init_handle = init.generate(&mut pre, scopes, tsm, nsc)?;
-                    append_line(lines, &pre);
-                    append_line(lines, &format!("  store {}, {}", init_handle, symbol_name));
+                    let init_handle = init.generate(program, scopes, nsc)?;
+                    emit(program, scopes, nsc, |b| b.store(init_handle, var))?;
                 }
             }
         } else {
-            scopes.add_value(&self.ident, &symbol_name, false, Some(self.dims.len()))?;
-
-            let dims: Vec<i32> = self
-                .dims
-                .iter()
-                .map(|const_exp| const_exp.solve(scopes).unwrap())
-                .collect();
-            let dims_str = generate_allocate_dims(&dims, 0);
-            let mut pre_lines = String::new();
-            let full_init = match self.init {
-                Some(ref init) => Some(parse_var_array_initializer(
-                    &mut pre_lines,
-                    init,
-                    &dims,
-                    scopes,
-                    tsm,
-                    nsc,
-                )?),
-                None => None,
-            };
-            append_line(lines, &pre_lines);
+            let dims = solve_array_dims(&self.dims, scopes)?;
+            let full_init = self
+                .init
+                .as_ref()
+                .map(|init| flatten_array_initializer(init, &dims));
 
             if scopes.now_global() {
                 // global arrays
                 let init = match full_init {
-                    Some(ref full_init_vec) => {
-                        full_initializer_to_global_aggregate(&full_init_vec, &dims)
+                    Some(full_init) => {
+                        let full_init = full_init
+                            .into_iter()
+                            .map(|exp| exp.map_or(Ok(0), |exp| exp.solve(scopes)))
+                            .collect::<Result<Vec<i32>, Diagnostic>>()?;
+                        full_initializer_to_global_aggregate(program, &full_init, &dims)
                     }
-                    None => "zeroinit".to_string(),
+                    None => program.new_value().zero_init(array_type(&dims)),
                 };
-                append_line(
-                    lines,
-                    &format!("global {} = alloc {}, {}", symbol_name, dims_str, init),
-                );
+                let array = global_named_alloc(program, nsc, &symbol, init);
+                scopes.add_value(&self.ident, SymbolTableValue::Array(array, dims.len()))?;
             } else {
                 // local arrays
-                append_line(lines, &format!("  {} = alloc {}", symbol_name, dims_str));
-                if let Some(ref full_init_content) = full_init {
-                    let new_lines = full_initializer_to_local_lines(
-                        &symbol_name,
-                        &full_init_content,
-                        &dims,
-                        scopes,
-                        nsc,
-                    )?;
-                    append_line(lines, &new_lines);
+                let array = emit_named_alloc(program, scopes, nsc, &symbol, array_type(&dims))?;
+                scopes.add_value(&self.ident, SymbolTableValue::Array(array, dims.len()))?;
+                if let Some(full_init) = full_init {
+                    let mut values = Vec::new();
+                    for exp in full_init {
+                        let value = match exp.map(|exp| (exp, exp.solve(scopes))) {
+                            None => integer(program, scopes, 0),
+                            Some((_, Ok(v))) => integer(program, scopes, v),
+                            Some((exp, Err(_))) => exp.generate(program, scopes, nsc)?,
+                        };
+                        values.push(value);
+                    }
+                    full_initializer_to_local_stores(program, array, &values, &dims, scopes, nsc)?;
                 }
             }
         }
 
-        Ok(String::new())
+        Ok(())
     }
 }
 
-impl KoopaTextGenerate for InitVal {
+impl KoopaGenerate for InitVal {
+    type Ret = Value;
+
     fn generate(
         &self,
-        lines: &mut String,
+        program: &mut Program,
         scopes: &mut Scopes,
-        tsm: &mut TempSymbolManager,
         nsc: &mut NamedSymbolCounter,
-    ) -> Result<String, ()> {
+    ) -> Result<Self::Ret, Diagnostic> {
         match self {
-            Self::Exp(exp) => exp.generate(lines, scopes, tsm, nsc),
-            _ => Ok(String::new()),
+            Self::Exp(exp) => exp.generate(program, scopes, nsc),
+            Self::Array(_, span) => Err(Diagnostic::error(
+                "invalid initializer for a scalar variable",
+            )
+            .with_primary(*span, "expected an expression, found an initializer list")),
         }
     }
 }
 
-impl KoopaTextGenerate for ConstExp {
+impl KoopaGenerate for Exp {
+    type Ret = Value;
+
     fn generate(
         &self,
-        _lines: &mut String,
+        program: &mut Program,
         scopes: &mut Scopes,
-        _tsm: &mut TempSymbolManager,
-        _nsc: &mut NamedSymbolCounter,
-    ) -> Result<String, ()> {
-        let v = self.solve(scopes)?; // evaluate the constant expression while generating AST.
-        Ok(v.to_string()) // return the constant value (as a `String`).
+        nsc: &mut NamedSymbolCounter,
+    ) -> Result<Self::Ret, Diagnostic> {
+        self.exp.generate(program, scopes, nsc)
     }
 }
 
-impl KoopaTextGenerate for Exp {
-    fn generate(
-        &self,
-        lines: &mut String,
-        scopes: &mut Scopes,
-        tsm: &mut TempSymbolManager,
-        nsc: &mut NamedSymbolCounter,
-    ) -> Result<String, ()> {
-        let mut pre = String::new();
-        let var = self.exp.generate(&mut pre, scopes, tsm, nsc)?;
-        append_line(lines, &pre);
-        Ok(var)
+/// Generate a short-circuit `||` (if `is_or`) or `&&` expression.
+///
+/// Koopa has "SSA" feature, so we have to allocate a memory slot to store the result.
+/// Since the result is actually a temporary value, we don't need to add it to the symbol table.
+fn generate_short_circuit(
+    program: &mut Program,
+    scopes: &mut Scopes,
+    nsc: &mut NamedSymbolCounter,
+    lhs: &impl KoopaGenerate<Ret = Value>,
+    rhs: &impl KoopaGenerate<Ret = Value>,
+    is_or: bool,
+) -> Result<Value, Diagnostic> {
+    let name = if is_or { "%or" } else { "%and" };
+    let result = emit_named_alloc(program, scopes, nsc, name, Type::get_i32())?;
+
+    // left-hand side
+    let var1 = lhs.generate(program, scopes, nsc)?;
+    let zero = integer(program, scopes, 0);
+    let lvar1 = emit(program, scopes, nsc, |b| {
+        b.binary(BinaryOp::NotEq, var1, zero)
+    })?;
+    emit(program, scopes, nsc, |b| b.store(lvar1, result))?;
+    let rhs_bb = new_bb(program, scopes, nsc, &format!("{}_rhs", name))?;
+    let end_bb = new_bb(program, scopes, nsc, &format!("{}_end", name))?;
+    if is_or {
+        emit(program, scopes, nsc, |b| b.branch(lvar1, end_bb, rhs_bb))?;
+    } else {
+        emit(program, scopes, nsc, |b| b.branch(lvar1, rhs_bb, end_bb))?;
     }
+
+    // right-hand side
+    enter_bb(program, scopes, rhs_bb)?;
+    let var2 = rhs.generate(program, scopes, nsc)?;
+    let zero = integer(program, scopes, 0);
+    let lvar2 = emit(program, scopes, nsc, |b| {
+        b.binary(BinaryOp::NotEq, var2, zero)
+    })?;
+    emit(program, scopes, nsc, |b| b.store(lvar2, result))?;
+    emit(program, scopes, nsc, |b| b.jump(end_bb))?;
+
+    // end
+    enter_bb(program, scopes, end_bb)?;
+    emit(program, scopes, nsc, |b| b.load(result))
 }
 
-impl KoopaTextGenerate for LOrExp {
+impl KoopaGenerate for LOrExp {
+    type Ret = Value;
+
     fn generate(
         &self,
-        lines: &mut String,
+        program: &mut Program,
         scopes: &mut Scopes,
-        tsm: &mut TempSymbolManager,
         nsc: &mut NamedSymbolCounter,
-    ) -> Result<String, ()> {
+    ) -> Result<Self::Ret, Diagnostic> {
         match self {
-            Self::LAnd(exp) => {
-                let mut pre = String::new();
-                let var = exp.generate(&mut pre, scopes, tsm, nsc)?;
-                append_line(lines, &pre);
-                Ok(var)
-            }
+            Self::LAnd(exp) => exp.generate(program, scopes, nsc),
             Self::LOrLAnd(exp1, exp2) => {
-                // Prepare the labels
-                let rhs_label = nsc.inc_and_get_named_symbol("%or_rhs")?;
-                let end_label = nsc.inc_and_get_named_symbol("%or_end")?;
-
-                // Koopa has "SSA" feature, so we have to allocate a memory slot to store the result of or operation.
-                // Since the result is actually a temporary value, we don't need to add it to the symbol table.
-                let result_name = nsc.inc_and_get_named_symbol("%or")?;
-                append_line(lines, &format!("  {} = alloc i32", result_name));
-
-                // left-hand side
-                let mut pre1 = String::new();
-                let var1 = exp1.generate(&mut pre1, scopes, tsm, nsc)?;
-                append_line(lines, &pre1);
-                let lvar1 = tsm.new_temp_symbol();
-                append_line(lines, &format!("  {} = ne {}, 0", lvar1, var1));
-                append_line(lines, &format!("  store {}, {}", lvar1, result_name));
-                append_line(
-                    lines,
-                    &format!("  br {}, {}, {}", lvar1, end_label, rhs_label),
-                );
-
-                // right-hand side
-                append_line(lines, &format!("\n{}:", rhs_label));
-                let mut pre2 = String::new();
-                let var2 = exp2.generate(&mut pre2, scopes, tsm, nsc)?;
-                append_line(lines, &pre2);
-                let lvar2 = tsm.new_temp_symbol();
-                append_line(lines, &format!("  {} = ne {}, 0", lvar2, var2));
-                append_line(lines, &format!("  store {}, {}", lvar2, result_name));
-                append_line(lines, &format!("  jump {}", end_label));
-
-                // end
-                append_line(lines, &format!("\n{}:", end_label));
-                let new_var = tsm.new_temp_symbol();
-                append_line(lines, &format!("  {} = load {}", new_var, result_name));
-                Ok(new_var)
+                generate_short_circuit(program, scopes, nsc, exp1.as_ref(), exp2, true)
             }
         }
     }
 }
 
-impl KoopaTextGenerate for LAndExp {
+impl KoopaGenerate for LAndExp {
+    type Ret = Value;
+
     fn generate(
         &self,
-        lines: &mut String,
+        program: &mut Program,
         scopes: &mut Scopes,
-        tsm: &mut TempSymbolManager,
         nsc: &mut NamedSymbolCounter,
-    ) -> Result<String, ()> {
+    ) -> Result<Self::Ret, Diagnostic> {
         match self {
-            Self::Eq(exp) => {
-                let mut pre = String::new();
-                let var = exp.generate(&mut pre, scopes, tsm, nsc)?;
-                append_line(lines, &pre);
-                Ok(var)
-            }
+            Self::Eq(exp) => exp.generate(program, scopes, nsc),
             Self::LAndEq(exp1, exp2) => {
-                // Prepare the labels
-                let rhs_label = nsc.inc_and_get_named_symbol("%and_rhs")?;
-                let end_label = nsc.inc_and_get_named_symbol("%and_end")?;
-
-                // Koopa has "SSA" feature, so we have to allocate a memory slot to store the result of or operation.
-                // Since the result is actually a temporary value, we don't need to add it to the symbol table.
-                let result_name = nsc.inc_and_get_named_symbol("%and")?;
-                append_line(lines, &format!("  {} = alloc i32", result_name));
-
-                // left-hand side
-                let mut pre1 = String::new();
-                let var1 = exp1.generate(&mut pre1, scopes, tsm, nsc)?;
-                append_line(lines, &pre1);
-                let lvar1 = tsm.new_temp_symbol();
-                append_line(lines, &format!("  {} = ne {}, 0", lvar1, var1));
-                append_line(lines, &format!("  store {}, {}", lvar1, result_name));
-                append_line(
-                    lines,
-                    &format!("  br {}, {}, {}", lvar1, rhs_label, end_label),
-                );
-
-                // right-hand side
-                append_line(lines, &format!("\n{}:", rhs_label));
-                let mut pre2 = String::new();
-                let var2 = exp2.generate(&mut pre2, scopes, tsm, nsc)?;
-                append_line(lines, &pre2);
-                let lvar2 = tsm.new_temp_symbol();
-                append_line(lines, &format!("  {} = ne {}, 0", lvar2, var2));
-                append_line(lines, &format!("  store {}, {}", lvar2, result_name));
-                append_line(lines, &format!("  jump {}", end_label));
-
-                // end
-                append_line(lines, &format!("\n{}:", end_label));
-                let new_var = tsm.new_temp_symbol();
-                append_line(lines, &format!("  {} = load {}", new_var, result_name));
-                Ok(new_var)
+                generate_short_circuit(program, scopes, nsc, exp1.as_ref(), exp2, false)
             }
         }
     }
 }
 
-impl KoopaTextGenerate for EqExp {
+impl KoopaGenerate for EqExp {
+    type Ret = Value;
+
     fn generate(
         &self,
-        lines: &mut String,
+        program: &mut Program,
         scopes: &mut Scopes,
-        tsm: &mut TempSymbolManager,
         nsc: &mut NamedSymbolCounter,
-    ) -> Result<String, ()> {
+    ) -> Result<Self::Ret, Diagnostic> {
         match self {
-            Self::Rel(exp) => {
-                let mut pre = String::new();
-                let var = exp.generate(&mut pre, scopes, tsm, nsc)?;
-                append_line(lines, &pre);
-                Ok(var)
-            }
+            Self::Rel(exp) => exp.generate(program, scopes, nsc),
             Self::EqRel(exp1, op, exp2) => {
-                let mut pre1 = String::new();
-                let mut pre2 = String::new();
-                let var1 = exp1.generate(&mut pre1, scopes, tsm, nsc)?;
-                let var2 = exp2.generate(&mut pre2, scopes, tsm, nsc)?;
-                append_line(lines, &pre1);
-                append_line(lines, &pre2);
-
-                let new_var = tsm.new_temp_symbol();
-                let op_str = match *op {
-                    EqExpOp::Eq => "eq",
-                    EqExpOp::Neq => "ne",
+                let var1 = exp1.generate(program, scopes, nsc)?;
+                let var2 = exp2.generate(program, scopes, nsc)?;
+                let op = match *op {
+                    EqExpOp::Eq => BinaryOp::Eq,
+                    EqExpOp::Neq => BinaryOp::NotEq,
                 };
-                let new_line = format!("  {} = {} {}, {}", new_var, op_str, var1, var2);
-                append_line(lines, &new_line);
-                Ok(new_var)
+                emit(program, scopes, nsc, |b| b.binary(op, var1, var2))
             }
         }
     }
 }
 
-impl KoopaTextGenerate for RelExp {
+impl KoopaGenerate for RelExp {
+    type Ret = Value;
+
     fn generate(
         &self,
-        lines: &mut String,
+        program: &mut Program,
         scopes: &mut Scopes,
-        tsm: &mut TempSymbolManager,
         nsc: &mut NamedSymbolCounter,
-    ) -> Result<String, ()> {
+    ) -> Result<Self::Ret, Diagnostic> {
         match self {
-            Self::Add(exp) => {
-                let mut pre = String::new();
-                let var = exp.generate(&mut pre, scopes, tsm, nsc)?;
-                append_line(lines, &pre);
-                Ok(var)
-            }
+            Self::Add(exp) => exp.generate(program, scopes, nsc),
             Self::RelAdd(exp1, op, exp2) => {
-                let mut pre1 = String::new();
-                let mut pre2 = String::new();
-                let var1 = exp1.generate(&mut pre1, scopes, tsm, nsc)?;
-                let var2 = exp2.generate(&mut pre2, scopes, tsm, nsc)?;
-                append_line(lines, &pre1);
-                append_line(lines, &pre2);
-
-                let new_var = tsm.new_temp_symbol();
-                let op_str = match *op {
-                    RelExpOp::Le => "le",
-                    RelExpOp::Ge => "ge",
-                    RelExpOp::Lt => "lt",
-                    RelExpOp::Gt => "gt",
+                let var1 = exp1.generate(program, scopes, nsc)?;
+                let var2 = exp2.generate(program, scopes, nsc)?;
+                let op = match *op {
+                    RelExpOp::Le => BinaryOp::Le,
+                    RelExpOp::Ge => BinaryOp::Ge,
+                    RelExpOp::Lt => BinaryOp::Lt,
+                    RelExpOp::Gt => BinaryOp::Gt,
                 };
-                let new_line = format!("  {} = {} {}, {}", new_var, op_str, var1, var2);
-                append_line(lines, &new_line);
-                Ok(new_var)
+                emit(program, scopes, nsc, |b| b.binary(op, var1, var2))
             }
         }
     }
 }
 
-impl KoopaTextGenerate for AddExp {
+impl KoopaGenerate for AddExp {
+    type Ret = Value;
+
     fn generate(
         &self,
-        lines: &mut String,
+        program: &mut Program,
         scopes: &mut Scopes,
-        tsm: &mut TempSymbolManager,
         nsc: &mut NamedSymbolCounter,
-    ) -> Result<String, ()> {
+    ) -> Result<Self::Ret, Diagnostic> {
         match self {
-            Self::Mul(exp) => {
-                let mut pre = String::new();
-                let var = exp.generate(&mut pre, scopes, tsm, nsc)?;
-                append_line(lines, &pre);
-                Ok(var)
-            }
+            Self::Mul(exp) => exp.generate(program, scopes, nsc),
             Self::AddMul(exp1, op, exp2) => {
-                let mut pre1 = String::new();
-                let mut pre2 = String::new();
-                let var1 = exp1.generate(&mut pre1, scopes, tsm, nsc)?;
-                let var2 = exp2.generate(&mut pre2, scopes, tsm, nsc)?;
-                append_line(lines, &pre1);
-                append_line(lines, &pre2);
-
-                let new_var = tsm.new_temp_symbol();
-                let op_str = match *op {
-                    AddExpOp::Add => "add",
-                    AddExpOp::Sub => "sub",
+                let var1 = exp1.generate(program, scopes, nsc)?;
+                let var2 = exp2.generate(program, scopes, nsc)?;
+                let op = match *op {
+                    AddExpOp::Add => BinaryOp::Add,
+                    AddExpOp::Sub => BinaryOp::Sub,
                 };
-                let new_line = format!("  {} = {} {}, {}", new_var, op_str, var1, var2);
-                append_line(lines, &new_line);
-                Ok(new_var)
+                emit(program, scopes, nsc, |b| b.binary(op, var1, var2))
             }
         }
     }
 }
 
-impl KoopaTextGenerate for MulExp {
+impl KoopaGenerate for MulExp {
+    type Ret = Value;
+
     fn generate(
         &self,
-        lines: &mut String,
+        program: &mut Program,
         scopes: &mut Scopes,
-        tsm: &mut TempSymbolManager,
         nsc: &mut NamedSymbolCounter,
-    ) -> Result<String, ()> {
+    ) -> Result<Self::Ret, Diagnostic> {
         match self {
-            Self::Unary(exp) => {
-                let mut pre = String::new();
-                let var = exp.generate(&mut pre, scopes, tsm, nsc)?;
-                append_line(lines, &pre);
-                Ok(var)
-            }
+            Self::Unary(exp) => exp.generate(program, scopes, nsc),
             Self::MulUnary(exp1, op, exp2) => {
-                let mut pre1 = String::new();
-                let mut pre2 = String::new();
-                let var1 = exp1.generate(&mut pre1, scopes, tsm, nsc)?;
-                let var2 = exp2.generate(&mut pre2, scopes, tsm, nsc)?;
-                append_line(lines, &pre1);
-                append_line(lines, &pre2);
-
-                let new_var = tsm.new_temp_symbol();
-                let op_str = match *op {
-                    MulExpOp::Mul => "mul",
-                    MulExpOp::Div => "div",
-                    MulExpOp::Mod => "mod",
+                let var1 = exp1.generate(program, scopes, nsc)?;
+                let var2 = exp2.generate(program, scopes, nsc)?;
+                let op = match *op {
+                    MulExpOp::Mul => BinaryOp::Mul,
+                    MulExpOp::Div => BinaryOp::Div,
+                    MulExpOp::Mod => BinaryOp::Mod,
                 };
-                let new_line = format!("  {} = {} {}, {}", new_var, op_str, var1, var2);
-                append_line(lines, &new_line);
-                Ok(new_var)
+                emit(program, scopes, nsc, |b| b.binary(op, var1, var2))
             }
         }
     }
 }
 
-impl KoopaTextGenerate for UnaryExp {
+impl KoopaGenerate for UnaryExp {
+    type Ret = Value;
+
+    /// Returns the result of the expression,
+    /// which is a value of the unit type for a call to a `void` function.
     fn generate(
         &self,
-        lines: &mut String,
+        program: &mut Program,
         scopes: &mut Scopes,
-        tsm: &mut TempSymbolManager,
         nsc: &mut NamedSymbolCounter,
-    ) -> Result<String, ()> {
-        let mut pre = String::new();
+    ) -> Result<Self::Ret, Diagnostic> {
         match self {
-            Self::Primary(pexp) => {
-                let var = pexp.generate(&mut pre, scopes, tsm, nsc)?;
-                append_line(lines, &pre);
-                Ok(var)
-            }
-            Self::FuncCall(ident, params) => {
+            Self::Primary(pexp) => pexp.generate(program, scopes, nsc),
+            Self::FuncCall(ident, params, _) => {
                 let FunctionInfo {
-                    symbol: func_symbol,
-                    return_void,
+                    handle,
                     array_param,
-                } = scopes.get_function(&ident)?;
+                    ..
+                } = scopes.get_function(ident)?;
 
-                let mut param_text = String::new();
+                let mut args = Vec::new();
                 for (i, param) in params.iter().enumerate() {
-                    let mut param_generation_text = String::new();
-                    let param_var = param.generate(&mut param_generation_text, scopes, tsm, nsc)?;
-                    let real_param_var = if array_param[i] {
+                    let param_var = param.generate(program, scopes, nsc)?;
+                    let real_param_var = if array_param.get(i) == Some(&true) {
                         // If the parameter is an array, we need to pass a pointer to it.
                         // That is, we should pass the address of its first element.
-                        if scopes.has_cur_func_param(&param_var) {
-                            // the direct use of a function array parameter
-                            // use `load` and `getptr`
-                            let load_result = tsm.new_temp_symbol();
-                            let get_result = tsm.new_temp_symbol();
-                            append_line(&mut param_generation_text, &format!("  {} = load {}", load_result, param_var));
-                            append_line(&mut param_generation_text, &format!("  {} = getptr {}, 0", get_result, load_result));
-                            get_result
-                        } else { 
-                            // use `getelemptr`
-                            let new_var = tsm.new_temp_symbol();
-                            append_line(&mut param_generation_text, &format!("  {} = getelemptr {}, 0", new_var, param_var));
-                            new_var
+                        if scopes.has_cur_func_param(param_var) {
+                            // the direct use of a function array parameter, which holds the pointer
+                            emit(program, scopes, nsc, |b| b.load(param_var))?
+                        } else {
+                            let zero = integer(program, scopes, 0);
+                            emit(program, scopes, nsc, |b| b.get_elem_ptr(param_var, zero))?
                         }
                     } else {
                         // scalar function parameter
                         param_var
                     };
-            
-                    append_line(lines, &param_generation_text);
-                    if i > 0 {
-                        param_text.push_str(", ");
-                    }
-                    param_text.push_str(&real_param_var);
+                    args.push(real_param_var);
                 }
 
-                if return_void {
-                    append_line(lines, &format!("  call {}({})", func_symbol, param_text));
-                    Ok(String::new())
-                } else {
-                    let result_symbol = tsm.new_temp_symbol();
-                    append_line(
-                        lines,
-                        &format!("  {} = call {}({})", result_symbol, func_symbol, param_text),
-                    );
-                    Ok(result_symbol)
-                }
+                emit(program, scopes, nsc, |b| b.call(handle, args))
             }
-            Self::Unary(op, uexp) => {
-                let var = uexp.generate(&mut pre, scopes, tsm, nsc)?;
-                append_line(lines, &pre);
+            // `2147483648` is out of range, so `-2147483648` must be folded in advance
+            Self::Unary(UnaryExpOp::Neg, uexp, _) if uexp.is_int_min_magnitude() => {
+                Ok(integer(program, scopes, i32::MIN))
+            }
+            Self::Unary(op, uexp, _) => {
+                let var = uexp.generate(program, scopes, nsc)?;
+                let zero = integer(program, scopes, 0);
                 match *op {
                     UnaryExpOp::Pos => Ok(var),
                     UnaryExpOp::Neg => {
-                        let new_var = tsm.new_temp_symbol();
-                        let new_line = format!("  {} = sub 0, {}", new_var, var);
-                        append_line(lines, &new_line);
-                        Ok(new_var)
+                        emit(program, scopes, nsc, |b| b.binary(BinaryOp::Sub, zero, var))
                     }
                     UnaryExpOp::Not => {
-                        let new_var = tsm.new_temp_symbol();
-                        let new_line = format!("  {} = eq 0, {}", new_var, var);
-                        append_line(lines, &new_line);
-                        Ok(new_var)
+                        emit(program, scopes, nsc, |b| b.binary(BinaryOp::Eq, zero, var))
                     }
                 }
             }
@@ -994,43 +831,31 @@ impl KoopaTextGenerate for UnaryExp {
     }
 }
 
-impl KoopaTextGenerate for PrimaryExp {
+impl KoopaGenerate for PrimaryExp {
+    type Ret = Value;
+
     fn generate(
         &self,
-        lines: &mut String,
+        program: &mut Program,
         scopes: &mut Scopes,
-        tsm: &mut TempSymbolManager,
         nsc: &mut NamedSymbolCounter,
-    ) -> Result<String, ()> {
+    ) -> Result<Self::Ret, Diagnostic> {
         match self {
-            Self::Exp(exp) => {
-                let mut pre = String::new();
-                let var = exp.generate(&mut pre, scopes, tsm, nsc)?;
-                append_line(lines, &pre);
-                Ok(var)
-            }
-            Self::Num(num) => Ok(format!("{}", num)),
+            Self::Exp(exp) => exp.generate(program, scopes, nsc),
+            Self::Num(num, span) => Ok(integer(program, scopes, check_int_literal(*num, *span)?)),
             Self::LVal(lval) => {
-                let mut pre = String::new();
-                let symbol = lval.generate(&mut pre, scopes, tsm, nsc)?;
-                append_line(lines, &pre);
+                let symbol = lval.generate(program, scopes, nsc)?;
 
                 match scopes.get_value(&lval.ident)? {
                     SymbolTableValue::Const(_) => Ok(symbol),
-                    SymbolTableValue::Var(_) => {
-                        let new_temp_symbol = tsm.new_temp_symbol();
-                        append_line(lines, &format!("  {} = load {}", new_temp_symbol, symbol));
-                        Ok(new_temp_symbol)
-                    }
+                    SymbolTableValue::Var(_) => emit(program, scopes, nsc, |b| b.load(symbol)),
                     SymbolTableValue::Array(_, nd) => {
-                        if lval.idx.len() < nd { 
+                        if lval.idx.len() < nd {
                             // Must be a parameter when calling a function.
                             // We don't have to load the data in this case!
                             Ok(symbol)
                         } else {
-                            let new_temp_symbol = tsm.new_temp_symbol();
-                            append_line(lines, &format!("  {} = load {}", new_temp_symbol, symbol));
-                            Ok(new_temp_symbol)
+                            emit(program, scopes, nsc, |b| b.load(symbol))
                         }
                     }
                 }
@@ -1039,24 +864,25 @@ impl KoopaTextGenerate for PrimaryExp {
     }
 }
 
-impl KoopaTextGenerate for LVal {
-    /// Return the symbol corresponding to the identifier.
+impl KoopaGenerate for LVal {
+    type Ret = Value;
+
+    /// Return the value corresponding to the identifier.
     ///
     /// If the identifier is a constant, return the constant value.
     /// If the identifier is a variable or an array element, return a pointer to it.
-    /// Notice that lines will be changed if the identifier is an array.
+    /// Notice that instructions will be generated if the identifier is an array.
     fn generate(
         &self,
-        lines: &mut String,
+        program: &mut Program,
         scopes: &mut Scopes,
-        tsm: &mut TempSymbolManager,
         nsc: &mut NamedSymbolCounter,
-    ) -> Result<String, ()> {
+    ) -> Result<Self::Ret, Diagnostic> {
         match scopes.get_value(&self.ident)? {
             SymbolTableValue::Var(v) => Ok(v),
-            SymbolTableValue::Const(c) => Ok(c),
+            SymbolTableValue::Const(c) => Ok(integer(program, scopes, c)),
             SymbolTableValue::Array(a, _) => {
-                get_pointer_to_element_exp_idx(lines, &a, &self.idx, scopes, tsm, nsc)
+                get_pointer_to_element_exp_idx(program, a, &self.idx, scopes, nsc)
             }
         }
     }
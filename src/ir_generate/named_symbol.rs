@@ -26,7 +26,7 @@ impl NamedSymbolCounter {
 
     /// Get the counter of the symbol with the given name.
     pub fn get_count(&self, id: &str) -> Option<usize> {
-        self.counter.get(id).map(|&x| x)
+        self.counter.get(id).copied()
     }
 
     /// Get the full name of the symbol with the given name.
@@ -34,22 +34,17 @@ impl NamedSymbolCounter {
     /// The full symbol name has the form "{id}_{counter}".
     /// `id` should be prefixed with `@` or `%`.
     pub fn get_named_symbol(&self, id: &str) -> Option<String> {
-        match self.get_count(id) {
-            Some(c) => Some(format!("{}_{}", id, c)),
-            None => None,
-        }
+        self.get_count(id).map(|c| format!("{}_{}", id, c))
     }
 
     /// Increase the counter of the symbol with the given id, and return the full name of the symbol.
     ///
     /// The full symbol name has the form "{id}_{counter}"
     /// `id` should be prefixed with `@` or `%`.
-    pub fn inc_and_get_named_symbol(&mut self, id: &str) -> Result<String, ()> {
+    pub fn inc_and_get_named_symbol(&mut self, id: &str) -> String {
         self.inc(id);
-        let Some(sym) = self.get_named_symbol(id) else {
-            return Err(());
-        };
-        Ok(sym)
+        self.get_named_symbol(id)
+            .expect("the counter has just been increased")
     }
 }
 
@@ -1,54 +1,66 @@
+use crate::ast_generate::ast::{Ident, Span};
+use crate::diagnostic::Diagnostic;
+use koopa::ir::{BasicBlock, Function, Value};
 use std::collections::HashMap;
 
-/// Information about a function that will be used during Koopa text generation.
+/// Information about a function that will be used during Koopa IR generation.
 #[derive(Clone)]
 pub struct FunctionInfo {
-    pub symbol: String,
+    pub handle: Function,
     pub return_void: bool,
     pub array_param: Vec<bool>,
     // there's no need to store detailed parameter types!!!
+    /// Where the function is defined, `None` for SysY library functions.
+    pub span: Option<Span>,
 }
 
 /// The value of a symbol in the symbol table.
 ///
 /// In a symbol table (an element of the `values` field of the struct `Scopes`),
 /// a value identifier (a `String`) is mapped to a symbol, which is an instance of this struct.
-/// A symbol can be either a constant (a `String` indicating its literal value)
-/// or a variable (a `String` representing a Koopa symbol),
+/// A symbol can be either a constant (its value),
+/// or a variable (the Koopa `alloc` holding it),
 /// or an array (constant and variable arrays are treated equally).
 #[derive(Clone)]
 pub enum SymbolTableValue {
-    Const(String),
-    Var(String),
-    Array(String, usize),
+    Const(i32),
+    Var(Value),
+    Array(Value, usize),
 }
 
-/// The three labels defined for a `while` loop.
+/// The three basic blocks created for a `while` loop.
 #[derive(Clone)]
+#[allow(dead_code)]
 pub struct LoopLabel {
-    pub entry: String,
-    pub body: String,
-    pub end: String,
+    pub entry: BasicBlock,
+    pub body: BasicBlock,
+    pub end: BasicBlock,
 }
 
 #[allow(dead_code)]
 pub struct Scopes {
     /// All the functions defined in the program.
     ///
-    /// identifier -> koopa symbol name
+    /// identifier -> koopa function
     functions: HashMap<String, FunctionInfo>,
     /// Stacked symbol tables.
     ///
-    /// identifier -> koopa symbol name / const value
-    values: Vec<HashMap<String, SymbolTableValue>>,
+    /// identifier -> (koopa value / const value, where the identifier is defined)
+    values: Vec<HashMap<String, (SymbolTableValue, Span)>>,
     /// Contents of `values_buffer` will be inserted into the scope entered next time.
     ///
     /// This field is used to put function parameters into the symbol table of the function body.
-    values_buffer: Vec<(String, SymbolTableValue)>,
+    values_buffer: Vec<(Ident, SymbolTableValue)>,
     /// Stacked loop information.
     loops: Vec<LoopLabel>,
     /// The parameter list of the current function.
-    cur_func_params: Vec<String>,
+    cur_func_params: Vec<Value>,
+    /// The function whose body is being generated.
+    cur_func: Option<Function>,
+    /// The basic block that new instructions are appended to.
+    ///
+    /// It's `None` after a terminator, until the next basic block is entered.
+    pub cur_bb: Option<BasicBlock>,
 }
 
 #[allow(dead_code)]
@@ -61,61 +73,80 @@ impl Scopes {
             values_buffer: Vec::new(),
             loops: Vec::new(),
             cur_func_params: Vec::new(),
+            cur_func: None,
+            cur_bb: None,
         }
     }
 
-    pub fn get_function(&self, identifier: &str) -> Result<FunctionInfo, ()> {
-        let Some(res) = self.functions.get(identifier) else {
-            return Err(());
+    pub fn get_function(&self, ident: &Ident) -> Result<FunctionInfo, Diagnostic> {
+        let Some(res) = self.functions.get(&ident.name) else {
+            return Err(Diagnostic::error(format!(
+                "cannot find function `{}` in this scope",
+                ident.name
+            ))
+            .with_primary(ident.span, "not found in this scope"));
         };
         Ok(res.clone())
     }
 
+    /// Return whether a function with the given name has been added.
+    pub fn has_function(&self, identifier: &str) -> bool {
+        self.functions.contains_key(identifier)
+    }
+
+    /// Add a function to the global scope.
+    ///
+    /// `span` is `None` for SysY library functions, which are not defined in the source code.
     pub fn add_function(
         &mut self,
         identifier: &str,
-        symbol: &str,
+        handle: Function,
         return_void: bool,
         array_param: Vec<bool>,
-    ) -> Result<(), ()> {
-        if let Some(_) = self.functions.insert(
+        span: Option<Span>,
+    ) -> Result<(), Diagnostic> {
+        if let Some(old) = self.functions.get(identifier) {
+            let mut diag = Diagnostic::error(format!(
+                "the function `{}` is defined multiple times",
+                identifier
+            ));
+            if let Some(span) = span {
+                diag = diag.with_primary(span, format!("`{}` redefined here", identifier));
+            }
+            diag = match old.span {
+                Some(old_span) => diag.with_secondary(
+                    old_span,
+                    format!("previous definition of `{}` here", identifier),
+                ),
+                None => diag.with_note(format!("`{}` is a SysY library function", identifier)),
+            };
+            return Err(diag);
+        }
+        self.functions.insert(
             identifier.into(),
             FunctionInfo {
-                symbol: symbol.into(),
+                handle,
                 return_void,
                 array_param,
+                span,
             },
-        ) {
-            return Err(());
-        };
+        );
         Ok(())
     }
 
-    pub fn add_value_to_buffer(&mut self, identifier: &str, symbol: &str, is_const: bool, n_array_dim: Option<usize>) {
-        let v = if let Some(nd) = n_array_dim {
-            SymbolTableValue::Array(symbol.into(), nd)
-        } else {
-            if is_const {
-                SymbolTableValue::Const(symbol.into())
-            } else {
-                SymbolTableValue::Var(symbol.into())
-            }
-        };
-        self.values_buffer.push((identifier.into(), v));
+    pub fn add_value_to_buffer(&mut self, ident: &Ident, v: SymbolTableValue) {
+        self.values_buffer.push((ident.clone(), v));
     }
 
     /// Enter a new scope.
     ///
     /// Contents in `values_buffer` will be added to the new scope.
-    pub fn enter(&mut self) -> Result<(), ()> {
-        let mut map: HashMap<String, SymbolTableValue> = HashMap::new();
-        for (k, v) in self.values_buffer.iter() {
-            if let Some(_) = map.insert(k.clone(), v.clone()) {
-                return Err(());
-            };
+    pub fn enter(&mut self) -> Result<(), Diagnostic> {
+        self.values.push(HashMap::new());
+        let buffer = std::mem::take(&mut self.values_buffer);
+        for (ident, v) in buffer.iter() {
+            self.insert_value(ident, v.clone())?;
         }
-        self.values.push(map);
-        self.values_buffer.clear();
         Ok(())
     }
 
@@ -124,58 +155,64 @@ impl Scopes {
         self.values.pop();
     }
 
-    /// Get the value (symbol name) of the given identifier.
+    /// Get the value (Koopa value or constant) of the given identifier.
     ///
     /// If the identifier is not found in the current scope,
     /// search in the outer one, and then in the outer one of the outer one, and so on.
-    /// `Err(())` is returned if the identifier is not found in all the scopes.
-    pub fn get_value(&self, identifier: &str) -> Result<SymbolTableValue, ()> {
-        let l = self.values.len();
-        for i_ in 1..=l {
-            let i = l - i_;
-            if let Some(res) = self.values[i].get(identifier) {
+    /// An error is returned if the identifier is not found in all the scopes.
+    pub fn get_value(&self, ident: &Ident) -> Result<SymbolTableValue, Diagnostic> {
+        for symtab in self.values.iter().rev() {
+            if let Some((res, _)) = symtab.get(&ident.name) {
                 return Ok(res.clone());
             }
         }
-        Err(())
+        Err(
+            Diagnostic::error(format!("cannot find value `{}` in this scope", ident.name))
+                .with_primary(ident.span, "not found in this scope"),
+        )
+    }
+
+    /// Get the place where the given identifier is defined.
+    ///
+    /// The scopes are searched in the same order as `get_value`.
+    pub fn get_value_span(&self, identifier: &str) -> Option<Span> {
+        self.values
+            .iter()
+            .rev()
+            .find_map(|symtab| symtab.get(identifier).map(|(_, span)| *span))
     }
 
     /// Add a new value to the current scope.
     ///
     /// The entry is added to the symbol table at the top of the stack.
-    pub fn add_value(
-        &mut self,
-        identifier: &str,
-        symbol: &str,
-        is_const: bool,
-        n_array_dim: Option<usize>,
-    ) -> Result<(), ()> {
+    pub fn add_value(&mut self, ident: &Ident, v: SymbolTableValue) -> Result<(), Diagnostic> {
+        self.insert_value(ident, v)
+    }
+
+    fn insert_value(&mut self, ident: &Ident, v: SymbolTableValue) -> Result<(), Diagnostic> {
         let Some(symtab) = self.values.last_mut() else {
-            return Err(());
+            return Err(Diagnostic::error("no scope to define a value in"));
         };
 
-        let v = if let Some(nd) = n_array_dim {
-            SymbolTableValue::Array(symbol.into(), nd)
-        } else {
-            if is_const {
-                SymbolTableValue::Const(symbol.into())
-            } else {
-                SymbolTableValue::Var(symbol.into())
-            }
-        };
-        if let Some(_) = symtab.insert(identifier.into(), v) {
-            return Err(()); // defined multiple times
-        };
+        if let Some((_, old_span)) = symtab.get(&ident.name) {
+            // defined multiple times
+            return Err(Diagnostic::error(format!(
+                "the name `{}` is defined multiple times in the same scope",
+                ident.name
+            ))
+            .with_primary(ident.span, format!("`{}` redefined here", ident.name))
+            .with_secondary(
+                *old_span,
+                format!("previous definition of `{}` here", ident.name),
+            ));
+        }
+        symtab.insert(ident.name.clone(), (v, ident.span));
         Ok(())
     }
 
-    /// Enter a new loop with the given labels.
-    pub fn enter_loop(&mut self, entry_label: &str, body_label: &str, end_label: &str) {
-        self.loops.push(LoopLabel {
-            entry: entry_label.to_string(),
-            body: body_label.to_string(),
-            end: end_label.to_string(),
-        })
+    /// Enter a new loop with the given basic blocks.
+    pub fn enter_loop(&mut self, entry: BasicBlock, body: BasicBlock, end: BasicBlock) {
+        self.loops.push(LoopLabel { entry, body, end })
     }
 
     /// Exit the current loop.
@@ -183,14 +220,12 @@ impl Scopes {
         self.loops.pop();
     }
 
-    /// Get the labels for the current loop.
+    /// Get the basic blocks of the current loop.
     ///
     /// The current loop information is located at the top of the stack.
-    pub fn get_cur_loop_labels(&self) -> Result<LoopLabel, ()> {
-        let Some(res) = self.loops.last() else {
-            return Err(());
-        };
-        Ok(res.clone())
+    /// `None` is returned if we're not in a loop.
+    pub fn get_cur_loop_labels(&self) -> Option<LoopLabel> {
+        self.loops.last().cloned()
     }
 
     /// Return whether we're now in the global scope.
@@ -201,18 +236,37 @@ impl Scopes {
     }
 
     /// Add a record of a parameter of the current function.
-    /// 
-    /// The record is stored in the `cur_func_params` field, which is a `Vec<String>`.
-    /// Each record should be a Koopa symbol name, rather than a SysY identifier.
-    pub fn add_cur_func_param(&mut self, param: &str) {
-        self.cur_func_params.push(param.into());
+    ///
+    /// The record is stored in the `cur_func_params` field, which is a `Vec<Value>`.
+    /// Each record should be the `alloc` the parameter is moved into, rather than a SysY identifier.
+    pub fn add_cur_func_param(&mut self, param: Value) {
+        self.cur_func_params.push(param);
     }
 
     pub fn clear_cur_func_params(&mut self) {
         self.cur_func_params.clear();
     }
 
-    pub fn has_cur_func_param(&self, param: &str) -> bool {
-        self.cur_func_params.contains(&param.into())
+    pub fn has_cur_func_param(&self, param: Value) -> bool {
+        self.cur_func_params.contains(&param)
+    }
+
+    /// Start generating the body of the given function.
+    pub fn enter_func(&mut self, func: Function) {
+        self.cur_func = Some(func);
+        self.cur_bb = None;
+        self.clear_cur_func_params();
+    }
+
+    /// Finish generating the body of the current function.
+    pub fn exit_func(&mut self) {
+        self.cur_func = None;
+        self.cur_bb = None;
+    }
+
+    /// Get the function whose body is being generated.
+    pub fn cur_func(&self) -> Result<Function, Diagnostic> {
+        self.cur_func
+            .ok_or_else(|| Diagnostic::error("an instruction is generated outside of any function"))
     }
 }
@@ -0,0 +1,196 @@
+mod interpreter;
+mod memory;
+
+use crate::diagnostic::Diagnostic;
+use interpreter::Interpreter;
+use koopa::ir::Program;
+
+/// Options of the Koopa interpreter.
+#[derive(Debug, Clone)]
+pub struct InterpretOptions {
+    /// Stop with an error after executing this many instructions, `None` for no limit.
+    pub max_instructions: Option<u64>,
+    /// The size of the stack in bytes, which holds the `alloc`s of the running functions.
+    pub stack_size: usize,
+}
+
+impl Default for InterpretOptions {
+    fn default() -> Self {
+        Self {
+            max_instructions: None,
+            stack_size: 8 << 20,
+        }
+    }
+}
+
+/// The result of running a Koopa program in the interpreter.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InterpretResult {
+    /// Everything written by `putint`, `putch` and `putarray`.
+    pub stdout: String,
+    /// The return value of `@main`, which is not truncated to 8 bits.
+    pub exit_code: i32,
+    /// The number of executed Koopa instructions.
+    pub instruction_count: u64,
+}
+
+/// Run a Koopa program directly, as a reference for the RISC-V backend.
+///
+/// `@main` is called with the given standard input.
+/// The functions of the SysY runtime library (`@getint`, `@putint`, etc.) are emulated,
+/// like in the RISC-V simulator.
+///
+/// # Errors
+/// An error is returned if the program is not valid, or it crashes (e.g. divides by zero).
+pub fn interpret_koopa(program: &Program, stdin: &str) -> Result<InterpretResult, Diagnostic> {
+    interpret_koopa_with_options(program, stdin, &InterpretOptions::default())
+}
+
+/// Run a Koopa program directly, with the given options.
+///
+/// # Errors
+/// An error is returned if the program is not valid, it crashes, or it runs for too long.
+pub fn interpret_koopa_with_options(
+    program: &Program,
+    stdin: &str,
+    options: &InterpretOptions,
+) -> Result<InterpretResult, Diagnostic> {
+    let mut interpreter = Interpreter::new(program, stdin, options.stack_size)?;
+    let exit_code = interpreter.run(options.max_instructions)?;
+    Ok(InterpretResult {
+        instruction_count: interpreter.instruction_count,
+        stdout: interpreter.io.into_stdout(),
+        exit_code,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ir_generate::get_koopa_program;
+
+    fn interpret(text: &str, stdin: &str) -> Result<InterpretResult, Diagnostic> {
+        interpret_koopa(&get_koopa_program(text).unwrap(), stdin)
+    }
+
+    #[test]
+    fn interpret_block_params_test() {
+        // the sum of the integers read until a zero, with the loop variables in block parameters
+        let text = r"
+decl @getint(): i32
+decl @putint(i32)
+
+fun @main(): i32 {
+%entry:
+  jump %loop(0, 0)
+
+%loop(%sum: i32, %count: i32):
+  %x = call @getint()
+  br %x, %body, %end
+
+%body:
+  %sum_1 = add %sum, %x
+  %count_1 = add %count, 1
+  jump %loop(%sum_1, %count_1)
+
+%end:
+  call @putint(%sum)
+  ret %count
+}
+";
+        let result = interpret(text, "3 -1 10 0 5").unwrap();
+        assert_eq!(result.stdout, "12");
+        assert_eq!(result.exit_code, 3);
+    }
+
+    #[test]
+    fn interpret_memory_test() {
+        let text = r"
+global @g = alloc [[i32, 2], 2], {{1, 2}, zeroinit}
+decl @putarray(i32, *i32)
+
+fun @fill(%p: *i32, %n: i32) {
+%entry:
+  %i = alloc i32
+  store 0, %i
+  jump %cond
+
+%cond:
+  %0 = load %i
+  %1 = lt %0, %n
+  br %1, %body, %end
+
+%body:
+  %2 = getptr %p, %0
+  %3 = mul %0, %0
+  store %3, %2
+  %4 = add %0, 1
+  store %4, %i
+  jump %cond
+
+%end:
+  ret
+}
+
+fun @main(): i32 {
+%entry:
+  %arr = alloc [i32, 4]
+  %0 = getelemptr %arr, 0
+  call @fill(%0, 4)
+  call @putarray(4, %0)
+  %1 = getelemptr @g, 1
+  %2 = getelemptr %1, 0
+  store 9, %2
+  %3 = getelemptr @g, 0
+  %4 = getelemptr %3, 0
+  call @putarray(4, %4)
+  %5 = getelemptr %arr, 3
+  %6 = load %5
+  ret %6
+}
+";
+        let result = interpret(text, "").unwrap();
+        assert_eq!(result.stdout, "4: 0 1 4 9\n4: 1 2 9 0\n");
+        assert_eq!(result.exit_code, 9);
+    }
+
+    #[test]
+    fn interpret_error_test() {
+        let diag = interpret(
+            "fun @main(): i32 {\n%entry:\n  %0 = div 1, 0\n  ret %0\n}\n",
+            "",
+        )
+        .unwrap_err();
+        assert_eq!(diag.message, "the program divides by zero");
+        assert_eq!(diag.notes, vec!["in function `@main`"]);
+
+        let text = "fun @f(): i32 {\n%entry:\n  %0 = call @f()\n  ret %0\n}\n";
+        let diag = interpret(text, "").unwrap_err();
+        assert_eq!(diag.message, "the Koopa program has no `@main`");
+
+        let options = InterpretOptions {
+            max_instructions: Some(50),
+            stack_size: 4096,
+        };
+        let text = "fun @main(): i32 {\n%entry:\n  %0 = call @main()\n  ret %0\n}\n";
+        let program = get_koopa_program(text).unwrap();
+        let diag = interpret_koopa_with_options(
+            &program,
+            "",
+            &InterpretOptions {
+                max_instructions: None,
+                ..options
+            },
+        )
+        .unwrap_err();
+        assert_eq!(diag.message, "the program overflows the stack");
+
+        let text = "fun @main(): i32 {\n%entry:\n  jump %loop\n\n%loop:\n  jump %loop\n}\n";
+        let program = get_koopa_program(text).unwrap();
+        let diag = interpret_koopa_with_options(&program, "", &options).unwrap_err();
+        assert_eq!(
+            diag.message,
+            "the program does not finish within 50 instructions"
+        );
+    }
+}
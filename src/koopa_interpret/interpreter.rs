@@ -0,0 +1,429 @@
+use super::memory::Memory;
+use crate::diagnostic::Diagnostic;
+use crate::sysy_runtime::{SysyIo, RUNTIME_FUNCTIONS};
+use koopa::ir::entities::ValueData;
+use koopa::ir::*;
+use std::collections::HashMap;
+
+/// The state of a function being executed.
+struct Frame {
+    func: Function,
+    bb: BasicBlock,
+    /// The index of the next instruction in `bb`.
+    next: usize,
+    /// The values of the arguments, the basic block parameters and the executed instructions.
+    values: HashMap<Value, u32>,
+    /// The stack top when the function is called.
+    stack_mark: usize,
+    /// The `call` instruction of the caller, which receives the return value.
+    call: Option<Value>,
+}
+
+/// The state of a running Koopa program.
+pub struct Interpreter<'a> {
+    program: &'a Program,
+    /// The instructions of every basic block, in the layout order.
+    insts: HashMap<Function, HashMap<BasicBlock, Vec<Value>>>,
+    /// The addresses of the global variables.
+    globals: HashMap<Value, u32>,
+    memory: Memory,
+    frames: Vec<Frame>,
+    pub io: SysyIo,
+    pub instruction_count: u64,
+}
+
+impl<'a> Interpreter<'a> {
+    /// Construct an interpreter about to call `@main`, with global variables initialized.
+    pub fn new(program: &'a Program, stdin: &str, stack_size: usize) -> Result<Self, Diagnostic> {
+        Type::set_ptr_size(4);
+
+        let mut memory = Memory::new(stack_size);
+        let mut globals = HashMap::new();
+        for &global in program.inst_layout() {
+            let data = program.borrow_value(global);
+            let ValueKind::GlobalAlloc(alloc) = data.kind() else {
+                continue;
+            };
+            let mut init = Vec::new();
+            flatten(
+                alloc.init(),
+                &|v| program.borrow_value(v).clone(),
+                &mut init,
+            );
+            globals.insert(global, memory.alloc_global(&init));
+        }
+
+        let insts = program
+            .funcs()
+            .iter()
+            .map(|(&func, data)| {
+                let blocks = data
+                    .layout()
+                    .bbs()
+                    .iter()
+                    .map(|(&bb, node)| (bb, node.insts().keys().copied().collect()))
+                    .collect();
+                (func, blocks)
+            })
+            .collect();
+
+        let mut interpreter = Self {
+            program,
+            insts,
+            globals,
+            memory,
+            frames: Vec::new(),
+            io: SysyIo::new(stdin),
+            instruction_count: 0,
+        };
+        let main = program
+            .funcs()
+            .iter()
+            .find(|(_, data)| data.name() == "@main")
+            .map(|(&func, _)| func)
+            .ok_or_else(|| Diagnostic::error("the Koopa program has no `@main`"))?;
+        if !program.func(main).params().is_empty() {
+            return Err(Diagnostic::error("`@main` must not have parameters"));
+        }
+        interpreter.enter(main, &[], None)?;
+        Ok(interpreter)
+    }
+
+    /// Run until `@main` returns, and return its return value (0 if it returns nothing).
+    ///
+    /// # Errors
+    /// An error is returned if the program accesses an invalid address, divides by zero,
+    /// or it executes more than `max_instructions` instructions.
+    pub fn run(&mut self, max_instructions: Option<u64>) -> Result<i32, Diagnostic> {
+        loop {
+            if max_instructions.is_some_and(|max| self.instruction_count >= max) {
+                return Err(Diagnostic::error(format!(
+                    "the program does not finish within {} instructions",
+                    self.instruction_count
+                )));
+            }
+            if let Some(ret) = self.step().map_err(|diag| self.locate(diag))? {
+                return Ok(ret as i32);
+            }
+        }
+    }
+
+    fn frame(&self) -> &Frame {
+        self.frames.last().unwrap()
+    }
+
+    fn frame_mut(&mut self) -> &mut Frame {
+        self.frames.last_mut().unwrap()
+    }
+
+    fn func_data(&self) -> &'a FunctionData {
+        self.program.func(self.frame().func)
+    }
+
+    /// Attach the current function to an error.
+    fn locate(&self, diag: Diagnostic) -> Diagnostic {
+        match self.frames.last() {
+            Some(frame) => diag.with_note(format!(
+                "in function `{}`",
+                self.program.func(frame.func).name()
+            )),
+            None => diag,
+        }
+    }
+
+    /// Call a function defined in the program.
+    fn enter(
+        &mut self,
+        func: Function,
+        args: &[u32],
+        call: Option<Value>,
+    ) -> Result<(), Diagnostic> {
+        let data = self.program.func(func);
+        let Some(entry) = data.layout().entry_bb() else {
+            return Err(Diagnostic::error(format!(
+                "the function `{}` is declared but not defined",
+                data.name()
+            )));
+        };
+        let stack_mark = self.memory.mark();
+        // every call takes a word of the stack, so that infinite recursion overflows it
+        self.memory.alloc(4)?;
+        self.frames.push(Frame {
+            func,
+            bb: entry,
+            next: 0,
+            values: data
+                .params()
+                .iter()
+                .copied()
+                .zip(args.iter().copied())
+                .collect(),
+            stack_mark,
+            call,
+        });
+        Ok(())
+    }
+
+    /// Get the value of an operand in the current function.
+    fn value(&self, value: Value) -> Result<u32, Diagnostic> {
+        if value.is_global() {
+            return Ok(self.globals[&value]);
+        }
+        match self.func_data().dfg().value(value).kind() {
+            ValueKind::Integer(i) => Ok(i.value() as u32),
+            ValueKind::ZeroInit(_) | ValueKind::Undef(_) => Ok(0),
+            _ => self
+                .frame()
+                .values
+                .get(&value)
+                .copied()
+                .ok_or_else(|| Diagnostic::error("a value is used before it's defined")),
+        }
+    }
+
+    fn value_type(&self, value: Value) -> Type {
+        if value.is_global() {
+            self.program.borrow_value(value).ty().clone()
+        } else {
+            self.func_data().dfg().value(value).ty().clone()
+        }
+    }
+
+    /// Move to the target basic block, passing the arguments to its parameters.
+    fn jump(&mut self, target: BasicBlock, args: &[Value]) -> Result<(), Diagnostic> {
+        let args = args
+            .iter()
+            .map(|&arg| self.value(arg))
+            .collect::<Result<Vec<_>, _>>()?;
+        let params = self.func_data().dfg().bb(target).params();
+        let frame = self.frame_mut();
+        frame.values.extend(params.iter().copied().zip(args));
+        frame.bb = target;
+        frame.next = 0;
+        Ok(())
+    }
+
+    /// Execute one instruction, and return the return value of `@main` if it returns.
+    fn step(&mut self) -> Result<Option<u32>, Diagnostic> {
+        let frame = self.frame();
+        let Some(&inst) = self.insts[&frame.func][&frame.bb].get(frame.next) else {
+            return Err(Diagnostic::error("a basic block ends without a terminator"));
+        };
+        self.frame_mut().next += 1;
+        self.instruction_count += 1;
+
+        let data = self.func_data().dfg().value(inst);
+        let result = match data.kind() {
+            ValueKind::Alloc(_) => {
+                let TypeKind::Pointer(base) = data.ty().kind() else {
+                    unreachable!("`alloc` always returns a pointer")
+                };
+                Some(self.memory.alloc(base.size())?)
+            }
+            ValueKind::Load(load) => {
+                if data.ty().size() != 4 {
+                    return Err(Diagnostic::error(format!(
+                        "loading a value of type `{}` is not supported",
+                        data.ty()
+                    )));
+                }
+                Some(self.memory.load(self.value(load.src())?)?)
+            }
+            ValueKind::Store(store) => {
+                let dest = self.value(store.dest())?;
+                let value = store.value();
+                if value.is_global() {
+                    self.memory.store(dest, self.value(value)?)?;
+                } else {
+                    let dfg = self.func_data().dfg();
+                    let mut words = Vec::new();
+                    match dfg.value(value).kind() {
+                        ValueKind::Aggregate(_) | ValueKind::ZeroInit(_) | ValueKind::Undef(_) => {
+                            flatten(value, &|v| dfg.value(v).clone(), &mut words)
+                        }
+                        _ => words.push(self.value(value)?),
+                    }
+                    for (i, word) in words.into_iter().enumerate() {
+                        self.memory.store(dest.wrapping_add(4 * i as u32), word)?;
+                    }
+                }
+                None
+            }
+            ValueKind::GetPtr(get_ptr) => {
+                let TypeKind::Pointer(base) = self.value_type(get_ptr.src()).kind().clone() else {
+                    unreachable!("the source of `getptr` is always a pointer")
+                };
+                let src = self.value(get_ptr.src())?;
+                let index = self.value(get_ptr.index())?;
+                Some(src.wrapping_add(index.wrapping_mul(base.size() as u32)))
+            }
+            ValueKind::GetElemPtr(get_elem_ptr) => {
+                let elem_size = match self.value_type(get_elem_ptr.src()).kind() {
+                    TypeKind::Pointer(base) => match base.kind() {
+                        TypeKind::Array(elem, _) => elem.size(),
+                        _ => unreachable!("the source of `getelemptr` always points to an array"),
+                    },
+                    _ => unreachable!("the source of `getelemptr` is always a pointer"),
+                };
+                let src = self.value(get_elem_ptr.src())?;
+                let index = self.value(get_elem_ptr.index())?;
+                Some(src.wrapping_add(index.wrapping_mul(elem_size as u32)))
+            }
+            ValueKind::Binary(binary) => {
+                let lhs = self.value(binary.lhs())? as i32;
+                let rhs = self.value(binary.rhs())? as i32;
+                Some(binary_op(binary.op(), lhs, rhs)? as u32)
+            }
+            ValueKind::Branch(branch) => {
+                if self.value(branch.cond())? != 0 {
+                    self.jump(branch.true_bb(), branch.true_args())?;
+                } else {
+                    self.jump(branch.false_bb(), branch.false_args())?;
+                }
+                None
+            }
+            ValueKind::Jump(jump) => {
+                self.jump(jump.target(), jump.args())?;
+                None
+            }
+            ValueKind::Call(call) => {
+                let args = call
+                    .args()
+                    .iter()
+                    .map(|&arg| self.value(arg))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let callee = self.program.func(call.callee());
+                let name = callee.name().trim_start_matches('@');
+                if callee.layout().entry_bb().is_none() && RUNTIME_FUNCTIONS.contains(&name) {
+                    self.call_runtime(name, &args)?
+                } else {
+                    self.enter(call.callee(), &args, Some(inst))?;
+                    None
+                }
+            }
+            ValueKind::Return(ret) => {
+                let value = ret.value().map(|v| self.value(v)).transpose()?;
+                let frame = self.frames.pop().unwrap();
+                self.memory.release(frame.stack_mark);
+                if self.frames.is_empty() {
+                    return Ok(Some(value.unwrap_or(0)));
+                }
+                if let (Some(call), Some(value)) = (frame.call, value) {
+                    self.frame_mut().values.insert(call, value);
+                }
+                None
+            }
+            kind => {
+                return Err(Diagnostic::error(format!(
+                    "`{:?}` is not an instruction",
+                    kind
+                )))
+            }
+        };
+        if let Some(result) = result {
+            self.frame_mut().values.insert(inst, result);
+        }
+        Ok(None)
+    }
+
+    /// Run a function of the SysY runtime, and return its return value.
+    fn call_runtime(&mut self, name: &str, args: &[u32]) -> Result<Option<u32>, Diagnostic> {
+        let arg = |i: usize| {
+            args.get(i).copied().ok_or_else(|| {
+                Diagnostic::error(format!("`@{}` is called with too few arguments", name))
+            })
+        };
+        Ok(match name {
+            "getint" => Some(self.io.get_int() as u32),
+            "getch" => Some(self.io.get_ch() as u32),
+            "getarray" => {
+                let array = arg(0)?;
+                let n = self.io.get_int();
+                for i in 0..n.max(0) as u32 {
+                    let value = self.io.get_int();
+                    self.memory.store(array.wrapping_add(4 * i), value as u32)?;
+                }
+                Some(n as u32)
+            }
+            "putint" => {
+                self.io.put_int(arg(0)? as i32);
+                None
+            }
+            "putch" => {
+                self.io.put_ch(arg(0)? as i32);
+                None
+            }
+            "putarray" => {
+                let (n, array) = (arg(0)? as i32, arg(1)?);
+                let values = (0..n.max(0) as u32)
+                    .map(|i| {
+                        self.memory
+                            .load(array.wrapping_add(4 * i))
+                            .map(|v| v as i32)
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                self.io.put_array(&values);
+                None
+            }
+            // the timer is not simulated
+            _ => None,
+        })
+    }
+}
+
+/// Append the words of a constant (an integer, `zeroinit`, `undef` or an aggregate) to `words`.
+///
+/// `value_data` looks up a value, since global constants and local constants live in different places.
+fn flatten(value: Value, value_data: &dyn Fn(Value) -> ValueData, words: &mut Vec<u32>) {
+    let data = value_data(value);
+    match data.kind() {
+        ValueKind::Integer(i) => words.push(i.value() as u32),
+        ValueKind::Aggregate(aggregate) => {
+            for &elem in aggregate.elems() {
+                flatten(elem, value_data, words);
+            }
+        }
+        _ => words.resize(words.len() + data.ty().size() / 4, 0),
+    }
+}
+
+/// Compute a binary operation of Koopa. The arithmetic wraps around on overflow.
+fn binary_op(op: BinaryOp, lhs: i32, rhs: i32) -> Result<i32, Diagnostic> {
+    Ok(match op {
+        BinaryOp::NotEq => (lhs != rhs) as i32,
+        BinaryOp::Eq => (lhs == rhs) as i32,
+        BinaryOp::Gt => (lhs > rhs) as i32,
+        BinaryOp::Lt => (lhs < rhs) as i32,
+        BinaryOp::Ge => (lhs >= rhs) as i32,
+        BinaryOp::Le => (lhs <= rhs) as i32,
+        BinaryOp::Add => lhs.wrapping_add(rhs),
+        BinaryOp::Sub => lhs.wrapping_sub(rhs),
+        BinaryOp::Mul => lhs.wrapping_mul(rhs),
+        BinaryOp::Div | BinaryOp::Mod if rhs == 0 => {
+            return Err(Diagnostic::error("the program divides by zero"))
+        }
+        BinaryOp::Div => lhs.wrapping_div(rhs),
+        BinaryOp::Mod => lhs.wrapping_rem(rhs),
+        BinaryOp::And => lhs & rhs,
+        BinaryOp::Or => lhs | rhs,
+        BinaryOp::Xor => lhs ^ rhs,
+        BinaryOp::Shl => lhs.wrapping_shl(rhs as u32),
+        BinaryOp::Shr => (lhs as u32).wrapping_shr(rhs as u32) as i32,
+        BinaryOp::Sar => lhs.wrapping_shr(rhs as u32),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn binary_op_test() {
+        assert_eq!(binary_op(BinaryOp::Mod, -7, 2).unwrap(), -1);
+        assert_eq!(binary_op(BinaryOp::Div, i32::MIN, -1).unwrap(), i32::MIN);
+        assert_eq!(binary_op(BinaryOp::Shr, -8, 1).unwrap(), 0x7fff_fffc);
+        assert_eq!(binary_op(BinaryOp::Sar, -8, 1).unwrap(), -4);
+        assert_eq!(binary_op(BinaryOp::Le, 3, 3).unwrap(), 1);
+        assert!(binary_op(BinaryOp::Mod, 1, 0).is_err());
+    }
+}
@@ -0,0 +1,97 @@
+use crate::diagnostic::Diagnostic;
+
+/// The memory of the interpreter, made of 32-bit words.
+///
+/// Global variables are placed first, and the stack grows upwards after them.
+/// Address 0 is never allocated, so that it can be used as a null pointer.
+pub struct Memory {
+    words: Vec<u32>,
+    /// The number of words the memory may grow to.
+    limit: usize,
+}
+
+impl Memory {
+    /// Construct a memory with a stack of the given size in bytes.
+    pub fn new(stack_size: usize) -> Self {
+        Self {
+            words: vec![0],
+            limit: 1 + stack_size / 4,
+        }
+    }
+
+    /// Allocate a global variable with the initial words, and return its address.
+    pub fn alloc_global(&mut self, init: &[u32]) -> u32 {
+        let address = self.words.len() as u32 * 4;
+        self.words.extend_from_slice(init);
+        self.limit += init.len();
+        address
+    }
+
+    /// The mark of the stack top, which is restored by `release`.
+    pub fn mark(&self) -> usize {
+        self.words.len()
+    }
+
+    /// Free everything allocated after the mark.
+    pub fn release(&mut self, mark: usize) {
+        self.words.truncate(mark);
+    }
+
+    /// Allocate zeroed memory of the given size in bytes on the stack, and return its address.
+    pub fn alloc(&mut self, size: usize) -> Result<u32, Diagnostic> {
+        let len = size.div_ceil(4).max(1);
+        if self.words.len() + len > self.limit {
+            return Err(Diagnostic::error("the program overflows the stack"));
+        }
+        let address = self.words.len() as u32 * 4;
+        self.words.resize(self.words.len() + len, 0);
+        Ok(address)
+    }
+
+    fn index(&self, address: u32) -> Result<usize, Diagnostic> {
+        let index = address as usize / 4;
+        if !address.is_multiple_of(4) || index == 0 || index >= self.words.len() {
+            return Err(Diagnostic::error(format!(
+                "the program accesses an invalid address {:#x}",
+                address
+            )));
+        }
+        Ok(index)
+    }
+
+    pub fn load(&self, address: u32) -> Result<u32, Diagnostic> {
+        Ok(self.words[self.index(address)?])
+    }
+
+    pub fn store(&mut self, address: u32, value: u32) -> Result<(), Diagnostic> {
+        let index = self.index(address)?;
+        self.words[index] = value;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn memory_test() {
+        let mut memory = Memory::new(16);
+        let global = memory.alloc_global(&[7, 8]);
+        assert_eq!(global, 4);
+        assert_eq!(memory.load(global + 4).unwrap(), 8);
+
+        let mark = memory.mark();
+        let a = memory.alloc(8).unwrap();
+        memory.store(a + 4, 5).unwrap();
+        assert_eq!(memory.load(a + 4).unwrap(), 5);
+        assert!(memory.alloc(12).is_err());
+        memory.release(mark);
+
+        assert!(memory.load(a).is_err());
+        assert!(memory.load(0).is_err());
+        assert!(memory.load(global + 1).is_err());
+        let b = memory.alloc(4).unwrap();
+        assert_eq!(memory.load(b).unwrap(), 0);
+    }
+}
@@ -1,11 +1,27 @@
 pub mod ast_generate;
+pub mod ast_interpret;
+pub mod diagnostic;
+pub mod fuzz;
+pub mod harness;
 pub mod ir_generate;
+pub mod koopa_interpret;
+pub mod opt;
+pub mod reduce;
+pub mod riscv_simulate;
+pub mod semantic;
+mod session;
+mod sysy_runtime;
 pub mod target_generate;
 mod tools;
+use diagnostic::Diagnostic;
+use opt::{OptLevel, OptOptions};
+pub use session::{Compiler, Session};
 use std::fs;
+use std::path::Path;
+use std::str::FromStr;
 
 /// The running mode of the compiler.
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Mode {
     /// Convert the SysY source code to Koopa code.
     Koopa,
@@ -15,6 +31,16 @@ pub enum Mode {
     Perf,
 }
 
+impl Mode {
+    /// The optimization level used when it's not given explicitly.
+    pub fn default_opt_level(&self) -> OptLevel {
+        match self {
+            Self::Koopa | Self::Riscv => OptLevel::O0,
+            Self::Perf => OptLevel::O2,
+        }
+    }
+}
+
 /// The error type of the compiler.
 #[derive(Debug)]
 pub enum RunError {
@@ -22,66 +48,225 @@ pub enum RunError {
     ReadFileError,
     /// The output file (Koopa or RISC-V) cannot be written.
     WriteFileError,
-    /// The SysY source code cannot be parsed to AST.
-    Sysy2AstError,
-    /// The AST cannot be parsed to Koopa text.
-    Ast2KoopaTextError,
-    /// The Koopa text cannot be parsed to Koopa program.
-    KoopaText2ProgramError,
+    /// The SysY source code cannot be parsed to AST, because of the syntax errors.
+    Sysy2AstError(Vec<Diagnostic>),
+    /// The AST is not a valid SysY program, e.g. a name is not defined.
+    SemanticError(Vec<Diagnostic>),
+    /// The AST cannot be converted to Koopa program.
+    Ast2KoopaError(Diagnostic),
+    /// The input Koopa text cannot be parsed to Koopa program.
+    KoopaText2ProgramError(Diagnostic),
+    /// The optimization pipeline cannot be built, or a pass breaks the Koopa program.
+    OptimizeError(Diagnostic),
+    /// The Koopa program cannot be printed to Koopa text.
+    KoopaProgram2TextError(Diagnostic),
     /// The Koopa program cannot be parsed to RISC-V text.
-    KoopaProgram2RiscvError,
+    KoopaProgram2RiscvError(Diagnostic),
     /// The feature is not implemented.
     NotImplementedError,
 }
 
+impl RunError {
+    /// Get the diagnostics carried by the error.
+    ///
+    /// Their spans refer to the SysY source code.
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        match self {
+            Self::Sysy2AstError(ds) | Self::SemanticError(ds) => ds,
+            Self::Ast2KoopaError(d)
+            | Self::KoopaText2ProgramError(d)
+            | Self::OptimizeError(d)
+            | Self::KoopaProgram2TextError(d)
+            | Self::KoopaProgram2RiscvError(d) => std::slice::from_ref(d),
+            _ => &[],
+        }
+    }
+
+    /// Get the name of the variant, e.g. `SemanticError`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::ReadFileError => "ReadFileError",
+            Self::WriteFileError => "WriteFileError",
+            Self::Sysy2AstError(_) => "Sysy2AstError",
+            Self::SemanticError(_) => "SemanticError",
+            Self::Ast2KoopaError(_) => "Ast2KoopaError",
+            Self::KoopaText2ProgramError(_) => "KoopaText2ProgramError",
+            Self::OptimizeError(_) => "OptimizeError",
+            Self::KoopaProgram2TextError(_) => "KoopaProgram2TextError",
+            Self::KoopaProgram2RiscvError(_) => "KoopaProgram2RiscvError",
+            Self::NotImplementedError => "NotImplementedError",
+        }
+    }
+}
+
+/// A stage of the compilation whose result can be emitted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Stage {
+    /// The tokens of the SysY source code, one per line.
+    Tokens,
+    /// The AST, in the form of `{:#?}`.
+    Ast,
+    /// The Koopa text, after the optimization passes.
+    Koopa,
+    /// The RISC-V text.
+    Riscv,
+}
+
+impl Stage {
+    /// The extension of the file the stage is written to.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Tokens => "tokens",
+            Self::Ast => "ast",
+            Self::Koopa => "koopa",
+            Self::Riscv => "S",
+        }
+    }
+}
+
+impl FromStr for Stage {
+    type Err = ();
+
+    /// Parse the stage given by `--emit=STAGE`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "tokens" => Ok(Self::Tokens),
+            "ast" => Ok(Self::Ast),
+            "koopa" => Ok(Self::Koopa),
+            "riscv" => Ok(Self::Riscv),
+            _ => Err(()),
+        }
+    }
+}
+
+/// The language of the input of the compiler.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Language {
+    /// SysY source code, which goes through all the stages.
+    Sysy,
+    /// Koopa text, which is parsed to Koopa program and then optimized and lowered to RISC-V.
+    Koopa,
+}
+
+impl Language {
+    /// Guess the language from the extension of the input file, which is Koopa for `.koopa` and SysY otherwise.
+    pub fn from_path(path: &str) -> Self {
+        match Path::new(path).extension() {
+            Some(ext) if ext == "koopa" => Self::Koopa,
+            _ => Self::Sysy,
+        }
+    }
+
+    /// The first stage whose result can be emitted from the input.
+    pub fn first_stage(&self) -> Stage {
+        match self {
+            Self::Sysy => Stage::Tokens,
+            Self::Koopa => Stage::Koopa,
+        }
+    }
+}
+
+impl FromStr for Language {
+    type Err = ();
+
+    /// Parse the language given by `-x LANG`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "sysy" => Ok(Self::Sysy),
+            "koopa" => Ok(Self::Koopa),
+            _ => Err(()),
+        }
+    }
+}
+
+impl Mode {
+    /// The stage whose result is the output of the mode.
+    pub fn stage(&self) -> Stage {
+        match self {
+            Self::Koopa => Stage::Koopa,
+            Self::Riscv | Self::Perf => Stage::Riscv,
+        }
+    }
+}
+
+impl RunError {
+    /// The exit code of the driver when the compilation fails with the error.
+    ///
+    /// - 1: the input or the output file cannot be accessed.
+    /// - 2: the SysY source code or the input Koopa text has syntax errors.
+    /// - 3: the SysY source code has semantic errors.
+    /// - 4: the compiler fails on a valid program, which is a bug of the compiler.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Self::ReadFileError | Self::WriteFileError => 1,
+            Self::Sysy2AstError(_) | Self::KoopaText2ProgramError(_) => 2,
+            Self::SemanticError(_) => 3,
+            Self::Ast2KoopaError(_)
+            | Self::OptimizeError(_)
+            | Self::KoopaProgram2TextError(_)
+            | Self::KoopaProgram2RiscvError(_)
+            | Self::NotImplementedError => 4,
+        }
+    }
+}
+
+/// Compile the input in the language, and return the result of each stage, in the order of the stages.
+///
+/// Only the stages needed by the given ones run, e.g. emitting only the tokens doesn't parse the source code.
+/// The mode selects the optimization level when it's not given explicitly.
+/// See `Session::emitted` for the forms of the results.
+///
+/// # Panics
+/// Panics if a stage comes before the first stage of the language, e.g. the tokens of a Koopa text.
+pub fn emit(
+    language: Language,
+    mode: Mode,
+    source: &str,
+    stages: &[Stage],
+    options: &OptOptions,
+) -> Result<Vec<String>, RunError> {
+    let mut session = Compiler::new(mode)
+        .language(language)
+        .options(options.clone())
+        .stop_after(stages.iter().max().copied().unwrap_or(Stage::Tokens))
+        .keep_tokens(stages.contains(&Stage::Tokens))
+        .compile(source);
+    if let Some(e) = session.error.take() {
+        return Err(e);
+    }
+    Ok(stages
+        .iter()
+        .map(|&stage| session.emitted(stage).unwrap())
+        .collect())
+}
+
+/// Compile the SysY source code in the given mode, and return the Koopa or RISC-V text.
+///
+/// Unlike `run_with_options`, nothing is read or written.
+pub fn compile(mode: Mode, source: &str, options: &OptOptions) -> Result<String, RunError> {
+    Ok(emit(Language::Sysy, mode, source, &[mode.stage()], options)?.remove(0))
+}
+
 /// Run the compiler in the given mode.
 ///
 /// The first argument is the running mode of the compiler.
 /// The second and third arguments are the input and output file paths, respectively.
 pub fn run(mode: Mode, input: &str, output: &str) -> Result<(), RunError> {
-    // read the SysY input source file
-    let Ok(input_content) = fs::read_to_string(input) else {
-        return Err(RunError::ReadFileError);
-    };
-
-    // parse the SysY file and generate the AST
-    let Ok(ast) = ast_generate::parse_sysy_to_ast(&input_content) else {
-        return Err(RunError::Sysy2AstError);
-    };
-    println!("{}\nAST:\n", "=====".repeat(20));
-    println!("{:#?}", &ast);
-
-    // scan the AST and get the Koopa text
-    let Ok(text) = ir_generate::parse_ast_to_koopa_text(&ast) else {
-        return Err(RunError::Ast2KoopaTextError);
-    };
-    println!("{}\nKoopa:\n", "=====".repeat(20));
-    println!("{}", &text);
-
-    // write Koopa text to file
-    if let Mode::Koopa = mode {
-        let Ok(_) = fs::write(output, text) else {
-            return Err(RunError::WriteFileError);
-        };
-        return Ok(());
-    }
-
-    // convert the Koopa text to Koopa program
-    let Ok(program) = ir_generate::get_koopa_program(&text) else {
-        return Err(RunError::KoopaText2ProgramError);
-    };
-
-    // convert the Koopa program to RISC-V text
-    let Ok(rvtext) = target_generate::parse_koopa_program_to_riscv(&program) else {
-        return Err(RunError::KoopaProgram2RiscvError);
-    };
-    println!("{}\nRISC-V:\n", "=====".repeat(20));
-    println!("{}", &rvtext);
-
-    // write RISC-V text to file
-    let Ok(_) = fs::write(output, rvtext) else {
-        return Err(RunError::WriteFileError);
-    };
+    run_with_options(mode, input, output, &OptOptions::default())
+}
 
-    Ok(())
+/// Run the compiler in the given mode, with the given options of the optimizer.
+///
+/// The input is a Koopa text if its extension is `.koopa`, or SysY source code otherwise.
+/// The passes run on the Koopa program before it's written (`Mode::Koopa`) or lowered to RISC-V.
+pub fn run_with_options(
+    mode: Mode,
+    input: &str,
+    output: &str,
+    options: &OptOptions,
+) -> Result<(), RunError> {
+    let source = fs::read_to_string(input).map_err(|_| RunError::ReadFileError)?;
+    let language = Language::from_path(input);
+    let text = emit(language, mode, &source, &[mode.stage()], options)?.remove(0);
+    fs::write(output, text).map_err(|_| RunError::WriteFileError)
 }
@@ -1,86 +1,260 @@
 use std::env;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::panic;
+use std::path::Path;
 use std::process::exit;
-use sysy_compiler::{Mode, RunError};
+use sysy_compiler::opt::{self, OptLevel, OptOptions};
+use sysy_compiler::{Language, Mode, RunError, Stage};
+
+/// The exit code of an invalid command line, or of an input or output file that cannot be accessed.
+const EXIT_USAGE: i32 = 1;
+/// The exit code of a panic, which is an internal error like `RunError::Ast2KoopaError`.
+const EXIT_PANIC: i32 = 4;
 
 struct Cli {
+    /// The mode given by `-koopa`, `-riscv` or `-perf`, which selects the default stage and optimization level.
     mode: Mode,
+    /// The stages given by `--emit`, in the order they are given.
+    stages: Vec<Stage>,
+    /// `-` for stdin.
     input: String,
-    output: String,
+    /// The language given by `-x`, or guessed from the extension of the input.
+    language: Language,
+    /// `-` for stdout, `None` if not given.
+    output: Option<String>,
+    verbose: bool,
+    options: OptOptions,
 }
 
 impl Cli {
-    fn parse() -> Result<Self, ()> {
-        let mut args = env::args();
-        args.next();
-        let mode = args.next();
-        let input = args.next();
-        let o = args.next();
-        let output = args.next();
-
-        match (mode, input, o, output) {
-            (Some(mode_), Some(input_), Some(o_), Some(output_)) if o_ == "-o" => {
-                let mode_converted = match mode_.as_str() {
-                    "-koopa" => Mode::Koopa,
-                    "-riscv" => Mode::Riscv,
-                    "-perf" => Mode::Perf,
+    /// Parse the command line, which is `None` if the help is requested.
+    fn parse() -> Result<Option<Self>, ()> {
+        let mut cli = Self {
+            mode: Mode::Riscv,
+            stages: Vec::new(),
+            input: String::new(),
+            language: Language::Sysy,
+            output: None,
+            verbose: false,
+            options: OptOptions::default(),
+        };
+        let mut mode = None;
+        let mut language = None;
+        let mut input = None;
+        let mut args = env::args().skip(1);
+        while let Some(arg) = args.next() {
+            if let Some(stages) = arg.strip_prefix("--emit=") {
+                for stage in stages.split(',') {
+                    let stage = stage.parse()?;
+                    if !cli.stages.contains(&stage) {
+                        cli.stages.push(stage);
+                    }
+                }
+            } else if let Some(level) = arg.strip_prefix("-O") {
+                cli.options.level = Some(level.parse()?);
+            } else if let Some(name) = arg.strip_prefix("--enable-pass=") {
+                cli.options.enabled_passes.push(name.into());
+            } else if let Some(name) = arg.strip_prefix("--disable-pass=") {
+                cli.options.disabled_passes.push(name.into());
+            } else {
+                match arg.as_str() {
+                    "-koopa" | "-riscv" | "-perf" if mode.is_none() => {
+                        mode = Some(match arg.as_str() {
+                            "-koopa" => Mode::Koopa,
+                            "-riscv" => Mode::Riscv,
+                            _ => Mode::Perf,
+                        })
+                    }
+                    "-o" if cli.output.is_none() => cli.output = Some(args.next().ok_or(())?),
+                    "-x" if language.is_none() => language = Some(args.next().ok_or(())?.parse()?),
+                    "-v" => cli.verbose = true,
+                    "--verify-each" => cli.options.verify_each = true,
+                    "-h" | "--help" => return Ok(None),
+                    _ if (arg == "-" || !arg.starts_with('-')) && input.is_none() => {
+                        input = Some(arg)
+                    }
                     _ => return Err(()),
-                };
-                Ok(Self {
-                    mode: mode_converted,
-                    input: input_,
-                    output: output_,
-                })
+                }
             }
-            _ => Err(()),
         }
+        cli.input = input.ok_or(())?;
+        cli.language = language.unwrap_or_else(|| Language::from_path(&cli.input));
+        cli.mode = mode.unwrap_or(Mode::Riscv);
+        if cli.stages.is_empty() {
+            cli.stages.push(cli.mode.stage());
+        }
+        // e.g. there are no tokens of a Koopa text
+        if cli
+            .stages
+            .iter()
+            .any(|&stage| stage < cli.language.first_stage())
+        {
+            return Err(());
+        }
+        Ok(Some(cli))
+    }
+
+    /// Get where each stage is written, `None` for stdout.
+    fn destinations(&self) -> Vec<Option<String>> {
+        let stem = match (&self.output, self.input.as_str()) {
+            (Some(output), _) if output == "-" => return vec![None; self.stages.len()],
+            (Some(output), _) if self.stages.len() == 1 => return vec![Some(output.clone())],
+            (Some(output), _) => output.as_str(),
+            (None, "-") => return vec![None; self.stages.len()],
+            (None, input) => input,
+        };
+        self.stages
+            .iter()
+            .map(|stage| {
+                Some(
+                    Path::new(stem)
+                        .with_extension(stage.extension())
+                        .to_string_lossy()
+                        .into_owned(),
+                )
+            })
+            .collect()
     }
 }
 
 const CLI_HELP: &str = r#"
 sysy compiler: Yifan Huang <1900012913@pku.edu.cn>
 
-Usage 1: cargo run -- MODE INPUT -o OUTPUT
-Usage 2: <path-to-sysy_compiler> MODE INPUT -o OUTPUT
+Usage 1: <path-to-sysy_compiler> MODE INPUT -o OUTPUT [OPTIONS]
     MODE: "-koopa", "-riscv" or "-perf"
     INPUT: the input SysY source file
     OUTPUT: the output file
+Usage 2: <path-to-sysy_compiler> [--emit=STAGES] [-o OUTPUT] [OPTIONS] INPUT
+    STAGES: comma-separated stages among "tokens", "ast", "koopa" and "riscv" (default: "riscv")
+    INPUT: the input SysY source file (or Koopa file with the extension ".koopa"), "-" for stdin
+    OUTPUT: the output file, "-" for stdout (default: stdout if INPUT is "-")
+        With several stages (or without OUTPUT), each stage is written to OUTPUT (or INPUT)
+        with the extension replaced by ".tokens", ".ast", ".koopa" or ".S".
+OPTIONS:
+    -x LANG: the language of INPUT, "sysy" or "koopa" (default: "koopa" for ".koopa" files, "sysy" otherwise)
+        A Koopa input starts at the stage "koopa", where the optimization passes run.
+    -O0, -O1, -O2: the optimization level (default: -O2 for "-perf", -O0 otherwise)
+    --enable-pass=NAME: run the pass even if it's not in the pipeline of the level
+    --disable-pass=NAME: remove the pass from the pipeline
+    --verify-each: verify the Koopa program after each pass
+    -v: print the AST, the Koopa text and the RISC-V text of the compilation to stderr
+    -h, --help: print this help
+EXIT CODES:
+    0: success
+    1: invalid command line, or the input or output file cannot be accessed
+    2: syntax errors of the SysY source code or the Koopa text
+    3: semantic errors
+    4: internal errors of the compiler
 "#;
 
+/// Print the usage and the available passes.
+fn print_help() {
+    eprintln!("{}", CLI_HELP);
+    let passes: Vec<_> = opt::available_passes().collect();
+    if !passes.is_empty() {
+        eprintln!("PASSES:");
+        for (name, description) in passes {
+            eprintln!("    {}: {}", name, description);
+        }
+    }
+}
+
 fn main() {
     // parse the command line arguments
-    let Ok(Cli{mode, input, output}) = Cli::parse() else {
-        eprintln!("Error: invalid command line argument!\n{}", CLI_HELP);
-        exit(-1)
+    let cli = match Cli::parse() {
+        Ok(Some(cli)) => cli,
+        Ok(None) => {
+            print_help();
+            return;
+        }
+        Err(()) => {
+            eprintln!("Error: invalid command line argument!");
+            print_help();
+            exit(EXIT_USAGE)
+        }
     };
-    println!("mode={:?}, input={}, output={}", mode, input, output);
 
-    match sysy_compiler::run(mode, &input, &output) {
-        Err(e) => {
-            match e {
-                RunError::ReadFileError => {
-                    eprintln!("Error: cannot read input file {}!", &input);
-                }
-                RunError::WriteFileError => {
-                    eprintln!("Error: cannot write file {}!", &output)
-                }
-                RunError::Sysy2AstError => {
-                    eprintln!("Error: cannot convert SysY to AST!");
-                }
-                RunError::Ast2KoopaTextError => {
-                    eprintln!("Error: cannot convert AST to Koopa text!");
-                }
-                RunError::KoopaText2ProgramError => {
-                    eprintln!("Error: cannot convert Koopa text to program!");
-                }
-                RunError::KoopaProgram2RiscvError => {
-                    eprintln!("Error: cannot convert Koopa program to RISC-V");
-                }
-                RunError::NotImplementedError => {
-                    eprintln!("Error: not implemented");
-                }
+    // an unknown pass is an error of the command line rather than of the compilation
+    if let Err(diag) = opt::build_pass_manager(OptLevel::O0, &cli.options) {
+        eprintln!("{}", diag.render("", ""));
+        exit(EXIT_USAGE)
+    }
+
+    let destinations = cli.destinations();
+    // e.g. `--emit=koopa a.koopa` would write to `a.koopa` itself
+    if destinations.contains(&Some(cli.input.clone())) {
+        eprintln!("Error: the output file {} is the input file!", &cli.input);
+        exit(EXIT_USAGE)
+    }
+
+    // read the SysY source code or the Koopa text
+    let (source, file_name) = if cli.input == "-" {
+        let mut source = String::new();
+        if io::stdin().read_to_string(&mut source).is_err() {
+            eprintln!("Error: cannot read stdin!");
+            exit(EXIT_USAGE)
+        }
+        (source, "<stdin>")
+    } else {
+        let Ok(source) = fs::read_to_string(&cli.input) else {
+            eprintln!("Error: cannot read input file {}!", &cli.input);
+            exit(EXIT_USAGE)
+        };
+        (source, cli.input.as_str())
+    };
+
+    // the dumps of `-v` are the stages from the AST (or the Koopa text) to the last one requested
+    let mut stages = cli.stages.clone();
+    if cli.verbose {
+        let last = *stages.iter().max().unwrap();
+        for stage in [Stage::Ast, Stage::Koopa, Stage::Riscv] {
+            if stage >= cli.language.first_stage() && stage <= last && !stages.contains(&stage) {
+                stages.push(stage);
+            }
+        }
+    }
+
+    // a panic is reported like the other internal errors, after its message is printed by the default hook
+    let results = panic::catch_unwind(|| {
+        sysy_compiler::emit(cli.language, cli.mode, &source, &stages, &cli.options)
+    });
+    let results = match results {
+        Ok(Ok(results)) => results,
+        Ok(Err(e)) => exit(report(&e, &source, file_name)),
+        Err(_) => exit(EXIT_PANIC),
+    };
+
+    if cli.verbose {
+        for (stage, text) in stages.iter().zip(&results) {
+            eprintln!("{}\n{:?}:\n", "=====".repeat(20), stage);
+            eprintln!("{}", text);
+        }
+    }
+    for (text, destination) in results.iter().zip(destinations) {
+        let written = match &destination {
+            Some(path) => fs::write(path, text),
+            None => io::stdout().write_all(text.as_bytes()),
+        };
+        if written.is_err() {
+            eprintln!(
+                "Error: cannot write file {}!",
+                destination.as_deref().unwrap_or("<stdout>")
+            );
+            exit(RunError::WriteFileError.exit_code());
+        }
+    }
+}
+
+/// Print the error, and return the exit code.
+fn report(e: &RunError, source: &str, file_name: &str) -> i32 {
+    match e {
+        RunError::NotImplementedError => eprintln!("Error: not implemented"),
+        e => {
+            for diag in e.diagnostics() {
+                eprintln!("{}\n", diag.render(source, file_name));
             }
-            exit(-1);
         }
-        Ok(_) => (),
     }
+    e.exit_code()
 }
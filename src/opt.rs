@@ -0,0 +1,252 @@
+mod alias;
+mod cfg;
+mod dce;
+mod gvn;
+mod licm;
+mod mem2reg;
+mod pass_manager;
+mod sccp;
+mod simplify_cfg;
+mod utils;
+mod verify;
+
+use crate::diagnostic::Diagnostic;
+use dce::Dce;
+use gvn::Gvn;
+use koopa::ir::Program;
+use koopa::opt::Pass;
+use licm::Licm;
+use mem2reg::Mem2Reg;
+pub use pass_manager::PassManager;
+use sccp::Sccp;
+use simplify_cfg::SimplifyCfg;
+use std::str::FromStr;
+pub use verify::verify_program;
+
+/// The optimization level, which selects the pipeline of passes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OptLevel {
+    /// No optimization.
+    #[default]
+    O0,
+    /// Cheap optimizations.
+    O1,
+    /// All the optimizations.
+    O2,
+}
+
+impl FromStr for OptLevel {
+    type Err = ();
+
+    /// Parse the level given by `-O0`, `-O1` or `-O2` (without the `-O` prefix).
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "0" => Ok(Self::O0),
+            "1" => Ok(Self::O1),
+            "2" => Ok(Self::O2),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Options of the optimizer.
+#[derive(Debug, Clone, Default)]
+pub struct OptOptions {
+    /// The optimization level, `None` for the default level of the running mode.
+    pub level: Option<OptLevel>,
+    /// Passes to run after the pipeline of the level, if they are not in it.
+    pub enabled_passes: Vec<String>,
+    /// Passes to remove from the pipeline.
+    pub disabled_passes: Vec<String>,
+    /// Verify the program after each pass.
+    pub verify_each: bool,
+}
+
+/// A pass that can be enabled or disabled by its name.
+struct PassInfo {
+    name: &'static str,
+    description: &'static str,
+    create: fn() -> Pass,
+}
+
+/// All the passes, in the order they are listed by `available_passes`.
+///
+/// This is not the order they are run in: the pipeline of the level runs first,
+/// followed by the enabled passes not in it, in the order they are given (see `build_pass_manager`).
+const PASSES: &[PassInfo] = &[
+    PassInfo {
+        name: "mem2reg",
+        description: "promote scalar allocs to SSA values and basic block parameters",
+        create: || Pass::Function(Box::new(Mem2Reg)),
+    },
+    PassInfo {
+        name: "sccp",
+        description: "fold constants and branches on constants by sparse conditional constant propagation",
+        create: || Pass::Function(Box::new(Sccp)),
+    },
+    PassInfo {
+        name: "gvn",
+        description: "remove redundant computations and loads by global value numbering",
+        create: || Pass::Function(Box::new(Gvn)),
+    },
+    PassInfo {
+        name: "licm",
+        description: "hoist loop-invariant computations and loads out of loops",
+        create: || Pass::Module(Box::new(Licm)),
+    },
+    PassInfo {
+        name: "dce",
+        description: "remove unreachable basic blocks, and unused instructions without side effects",
+        create: || Pass::Module(Box::new(Dce)),
+    },
+    PassInfo {
+        name: "simplify-cfg",
+        description: "merge straight-line basic blocks, thread jumps through empty basic blocks and fold trivial branches",
+        create: || Pass::Function(Box::new(SimplifyCfg)),
+    },
+];
+
+/// Get the names of the passes run at the given level, in order.
+fn pipeline(level: OptLevel) -> Vec<&'static str> {
+    match level {
+        OptLevel::O0 => Vec::new(),
+        OptLevel::O1 => vec!["mem2reg", "sccp", "dce", "simplify-cfg"],
+        OptLevel::O2 => vec!["mem2reg", "sccp", "gvn", "licm", "dce", "simplify-cfg"],
+    }
+}
+
+/// Get the names and descriptions of all the passes.
+pub fn available_passes() -> impl Iterator<Item = (&'static str, &'static str)> {
+    PASSES.iter().map(|p| (p.name, p.description))
+}
+
+fn find_pass(name: &str) -> Result<&'static PassInfo, Diagnostic> {
+    PASSES.iter().find(|p| p.name == name).ok_or_else(|| {
+        let names: Vec<String> = PASSES.iter().map(|p| format!("`{}`", p.name)).collect();
+        let note = if names.is_empty() {
+            String::from("no pass is available")
+        } else {
+            format!("the available passes are {}", names.join(", "))
+        };
+        Diagnostic::error(format!("unknown pass `{}`", name)).with_note(note)
+    })
+}
+
+/// Build the pass manager for the given level and options.
+///
+/// # Errors
+/// An error is returned if an enabled or disabled pass does not exist.
+pub fn build_pass_manager(
+    level: OptLevel,
+    options: &OptOptions,
+) -> Result<PassManager, Diagnostic> {
+    for name in options
+        .enabled_passes
+        .iter()
+        .chain(options.disabled_passes.iter())
+    {
+        find_pass(name)?;
+    }
+
+    let mut names = pipeline(level);
+    for name in options.enabled_passes.iter() {
+        if !names.contains(&name.as_str()) {
+            names.push(find_pass(name)?.name);
+        }
+    }
+    names.retain(|name| !options.disabled_passes.iter().any(|d| d == name));
+
+    let mut pm = PassManager::new();
+    pm.set_verify_each(options.verify_each);
+    for name in names {
+        pm.register(name, (find_pass(name)?.create)());
+    }
+    Ok(pm)
+}
+
+/// Optimize the Koopa program in place.
+///
+/// # Errors
+/// An error is returned if the pipeline cannot be built,
+/// or if verification is enabled and a pass breaks the program.
+pub fn optimize(
+    program: &mut Program,
+    level: OptLevel,
+    options: &OptOptions,
+) -> Result<(), Diagnostic> {
+    build_pass_manager(level, options)?.run(program)
+}
+
+/// The program optimized by `run_passes`.
+#[cfg(test)]
+struct PassesRun {
+    /// The Koopa text of the program.
+    text: String,
+    /// The number of instructions run before the last pass.
+    before: u64,
+    /// The number of instructions run after the last pass.
+    after: u64,
+}
+
+/// Compile the SysY source and run the passes on it in order, verifying the program after each one.
+///
+/// Check that the passes keep the behavior of the program, and that its text can be parsed again.
+#[cfg(test)]
+fn run_passes(input: &str, passes: &[&str]) -> PassesRun {
+    use crate::ast_generate::parse_sysy_to_ast;
+    use crate::ir_generate::{
+        get_koopa_program, koopa_program_to_text, parse_ast_to_koopa_program,
+    };
+    use crate::koopa_interpret::{interpret_koopa, InterpretResult};
+
+    let ast = parse_sysy_to_ast(input).unwrap();
+    let mut program = parse_ast_to_koopa_program(&ast).unwrap();
+    let expected = interpret_koopa(&program, "").unwrap();
+    let mut run = |passes: &[&str]| -> InterpretResult {
+        let options = OptOptions {
+            enabled_passes: passes.iter().map(|&p| String::from(p)).collect(),
+            verify_each: true,
+            ..OptOptions::default()
+        };
+        optimize(&mut program, OptLevel::O0, &options).unwrap();
+        let result = interpret_koopa(&program, "").unwrap();
+        assert_eq!(
+            (&result.stdout, result.exit_code),
+            (&expected.stdout, expected.exit_code)
+        );
+        result
+    };
+    let (last, init) = passes.split_last().unwrap();
+    let before = run(init).instruction_count;
+    let after = run(&[last]).instruction_count;
+
+    let text = koopa_program_to_text(&program).unwrap();
+    get_koopa_program(&text).unwrap();
+    PassesRun {
+        text,
+        before,
+        after,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn opt_level_test() {
+        assert_eq!("2".parse::<OptLevel>(), Ok(OptLevel::O2));
+        assert!("3".parse::<OptLevel>().is_err());
+        assert!(pipeline(OptLevel::O0).is_empty());
+    }
+
+    #[test]
+    fn unknown_pass_test() {
+        let options = OptOptions {
+            disabled_passes: vec![String::from("no-such-pass")],
+            ..OptOptions::default()
+        };
+        let diag = build_pass_manager(OptLevel::O2, &options).err().unwrap();
+        assert_eq!(diag.message, "unknown pass `no-such-pass`");
+    }
+}
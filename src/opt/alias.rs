@@ -0,0 +1,83 @@
+use koopa::ir::*;
+use std::collections::HashSet;
+
+/// The memory object a pointer points into.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Root {
+    /// An `alloc` of the function.
+    Alloc(Value),
+    /// A global value.
+    Global(Value),
+    /// A pointer from a parameter or from memory, which may point to any object except the `alloc`s that never escape.
+    Unknown,
+}
+
+impl Root {
+    /// Find the memory object a pointer points into.
+    pub fn of(data: &FunctionData, mut ptr: Value) -> Self {
+        loop {
+            // a global value is not in the data flow graph of the function
+            match data.dfg().values().get(&ptr).map(|d| d.kind()) {
+                Some(ValueKind::GetElemPtr(gep)) => ptr = gep.src(),
+                Some(ValueKind::GetPtr(gp)) => ptr = gp.src(),
+                Some(ValueKind::Alloc(_)) => return Self::Alloc(ptr),
+                Some(_) => return Self::Unknown,
+                None => return Self::Global(ptr),
+            }
+        }
+    }
+}
+
+/// Which pointers of a function may point to the same memory.
+///
+/// Pointers into different `alloc`s or global values never do,
+/// and an `alloc` can only be reached through a pointer of unknown origin if its address escapes,
+/// i.e. is passed to a function, stored, returned, or passed to a basic block.
+pub struct Aliases {
+    escaped: HashSet<Value>,
+}
+
+impl Aliases {
+    pub fn new(data: &FunctionData) -> Self {
+        let mut escaped = HashSet::new();
+        for (_, node) in data.layout().bbs() {
+            for &inst in node.insts().keys() {
+                let escaping: Vec<Value> = match data.dfg().value(inst).kind() {
+                    ValueKind::Store(store) => vec![store.value()],
+                    ValueKind::Call(call) => call.args().to_vec(),
+                    ValueKind::Branch(br) => br
+                        .true_args()
+                        .iter()
+                        .chain(br.false_args())
+                        .copied()
+                        .collect(),
+                    ValueKind::Jump(jump) => jump.args().to_vec(),
+                    ValueKind::Return(ret) => ret.value().into_iter().collect(),
+                    _ => Vec::new(),
+                };
+                for v in escaping {
+                    if let Root::Alloc(alloc) = Root::of(data, v) {
+                        escaped.insert(alloc);
+                    }
+                }
+            }
+        }
+        Self { escaped }
+    }
+
+    /// Return whether two pointers into the given objects may point to the same memory.
+    pub fn may_alias(&self, a: Root, b: Root) -> bool {
+        match (a, b) {
+            (Root::Alloc(x) | Root::Global(x), Root::Alloc(y) | Root::Global(y)) => x == y,
+            (Root::Alloc(x), Root::Unknown) | (Root::Unknown, Root::Alloc(x)) => {
+                self.escaped.contains(&x)
+            }
+            _ => true,
+        }
+    }
+
+    /// Return whether a called function may access the memory of the object.
+    pub fn call_may_access(&self, root: Root) -> bool {
+        !matches!(root, Root::Alloc(alloc) if !self.escaped.contains(&alloc))
+    }
+}
@@ -0,0 +1,197 @@
+use super::utils::successors;
+use koopa::ir::{BasicBlock, FunctionData};
+use std::collections::{HashMap, HashSet};
+
+/// The control flow graph of a function definition.
+///
+/// Only the basic blocks in the layout are considered.
+pub struct Cfg {
+    /// The entry basic block.
+    pub entry: BasicBlock,
+    /// The predecessors of each basic block, with duplicates for a `br` to the same block twice.
+    pub preds: HashMap<BasicBlock, Vec<BasicBlock>>,
+    /// The basic blocks reachable from the entry, in reverse post-order.
+    pub rpo: Vec<BasicBlock>,
+}
+
+impl Cfg {
+    /// Build the control flow graph of a function definition.
+    ///
+    /// Return `None` if the function is a declaration.
+    pub fn new(data: &FunctionData) -> Option<Self> {
+        let entry = data.layout().entry_bb()?;
+        let mut succs = HashMap::new();
+        let mut preds: HashMap<BasicBlock, Vec<BasicBlock>> = HashMap::new();
+        for (&bb, node) in data.layout().bbs() {
+            preds.entry(bb).or_default();
+            let targets = node
+                .insts()
+                .back_key()
+                .map(|&term| successors(data.dfg(), term))
+                .unwrap_or_default();
+            for &target in targets.iter() {
+                preds.entry(target).or_default().push(bb);
+            }
+            succs.insert(bb, targets);
+        }
+
+        // iterative DFS, so that deep control flow does not overflow the stack
+        let mut post_order = Vec::new();
+        let mut visited = HashSet::from([entry]);
+        let mut stack = vec![(entry, 0usize)];
+        while let Some((bb, i)) = stack.pop() {
+            match succs.get(&bb).and_then(|s: &Vec<BasicBlock>| s.get(i)) {
+                Some(&next) => {
+                    stack.push((bb, i + 1));
+                    if visited.insert(next) {
+                        stack.push((next, 0));
+                    }
+                }
+                None => post_order.push(bb),
+            }
+        }
+        post_order.reverse();
+
+        Some(Self {
+            entry,
+            preds,
+            rpo: post_order,
+        })
+    }
+
+    /// Get the predecessors of a basic block.
+    pub fn preds(&self, bb: BasicBlock) -> &[BasicBlock] {
+        self.preds.get(&bb).map_or(&[], |p| p)
+    }
+}
+
+/// The dominator tree of the reachable part of a control flow graph.
+pub struct DomTree {
+    /// The immediate dominator of each reachable basic block, except the entry.
+    idom: HashMap<BasicBlock, BasicBlock>,
+    /// The children of each basic block in the dominator tree, in reverse post-order.
+    children: HashMap<BasicBlock, Vec<BasicBlock>>,
+    /// The position of each reachable basic block in the reverse post-order.
+    rpo_index: HashMap<BasicBlock, usize>,
+}
+
+impl DomTree {
+    /// Compute the dominator tree by the iterative algorithm of Cooper, Harvey and Kennedy.
+    pub fn new(cfg: &Cfg) -> Self {
+        let rpo_index: HashMap<BasicBlock, usize> =
+            cfg.rpo.iter().enumerate().map(|(i, &bb)| (bb, i)).collect();
+        let mut idom: HashMap<BasicBlock, BasicBlock> = HashMap::from([(cfg.entry, cfg.entry)]);
+
+        let intersect =
+            |idom: &HashMap<BasicBlock, BasicBlock>, mut a: BasicBlock, mut b: BasicBlock| {
+                while a != b {
+                    while rpo_index[&a] > rpo_index[&b] {
+                        a = idom[&a];
+                    }
+                    while rpo_index[&b] > rpo_index[&a] {
+                        b = idom[&b];
+                    }
+                }
+                a
+            };
+
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for &bb in cfg.rpo.iter().skip(1) {
+                let new_idom = cfg
+                    .preds(bb)
+                    .iter()
+                    .filter(|p| idom.contains_key(p))
+                    .copied()
+                    .reduce(|a, b| intersect(&idom, a, b));
+                if let Some(new_idom) = new_idom {
+                    if idom.get(&bb) != Some(&new_idom) {
+                        idom.insert(bb, new_idom);
+                        changed = true;
+                    }
+                }
+            }
+        }
+        idom.remove(&cfg.entry);
+
+        let mut children: HashMap<BasicBlock, Vec<BasicBlock>> = HashMap::new();
+        for &bb in cfg.rpo.iter() {
+            if let Some(&parent) = idom.get(&bb) {
+                children.entry(parent).or_default().push(bb);
+            }
+        }
+
+        Self {
+            idom,
+            children,
+            rpo_index,
+        }
+    }
+
+    /// Return whether the basic block is reachable from the entry.
+    pub fn is_reachable(&self, bb: BasicBlock) -> bool {
+        self.rpo_index.contains_key(&bb)
+    }
+
+    /// Get the immediate dominator of a basic block, `None` for the entry and unreachable blocks.
+    pub fn idom(&self, bb: BasicBlock) -> Option<BasicBlock> {
+        self.idom.get(&bb).copied()
+    }
+
+    /// Return whether `a` dominates `b`, where both are reachable.
+    pub fn dominates(&self, a: BasicBlock, mut b: BasicBlock) -> bool {
+        loop {
+            if a == b {
+                return true;
+            }
+            match self.idom(b) {
+                Some(idom) => b = idom,
+                None => return false,
+            }
+        }
+    }
+
+    /// Get the children of a basic block in the dominator tree.
+    pub fn children(&self, bb: BasicBlock) -> &[BasicBlock] {
+        self.children.get(&bb).map_or(&[], |c| c)
+    }
+
+    /// Compute the dominance frontier of every reachable basic block.
+    pub fn frontiers(&self, cfg: &Cfg) -> HashMap<BasicBlock, HashSet<BasicBlock>> {
+        let mut frontiers: HashMap<BasicBlock, HashSet<BasicBlock>> = HashMap::new();
+        for &bb in cfg.rpo.iter() {
+            let preds: Vec<BasicBlock> = cfg
+                .preds(bb)
+                .iter()
+                .copied()
+                .filter(|&p| self.is_reachable(p))
+                .collect();
+            if preds.len() < 2 {
+                continue;
+            }
+            let Some(idom) = self.idom(bb) else {
+                // the entry has predecessors, so it's in the frontier of them all
+                for p in preds {
+                    let mut runner = Some(p);
+                    while let Some(r) = runner {
+                        frontiers.entry(r).or_default().insert(bb);
+                        runner = self.idom(r);
+                    }
+                }
+                continue;
+            };
+            for p in preds {
+                let mut runner = p;
+                while runner != idom {
+                    frontiers.entry(runner).or_default().insert(bb);
+                    match self.idom(runner) {
+                        Some(parent) => runner = parent,
+                        None => break,
+                    }
+                }
+            }
+        }
+        frontiers
+    }
+}
@@ -0,0 +1,344 @@
+use super::cfg::Cfg;
+use super::utils::{is_terminator, may_trap, remove_values, successors, use_counts};
+use koopa::ir::builder::ValueBuilder;
+use koopa::ir::*;
+use koopa::opt::ModulePass;
+use std::collections::{HashMap, HashSet};
+
+/// Remove the basic blocks unreachable from the entry,
+/// and the instructions and basic block parameters that have no side effects and whose results are never used.
+///
+/// A `call` has no side effects if the callee is pure (see `pure_functions`),
+/// while a division by a value that may be zero is kept, since it may trap at runtime.
+pub struct Dce;
+
+impl ModulePass for Dce {
+    fn run_on(&mut self, program: &mut Program) {
+        let pure = pure_functions(program);
+        for func in program.func_layout().to_vec() {
+            let data = program.func_mut(func);
+            // a declaration has no blocks to clean up
+            if data.layout().entry_bb().is_some() {
+                remove_unreachable_bbs(data);
+                remove_dead_values(data, &pure);
+            }
+        }
+    }
+}
+
+/// Return whether the instruction has side effects, so that it's kept even if its result is never used.
+fn has_side_effects(data: &FunctionData, inst: Value, pure: &HashSet<Function>) -> bool {
+    match data.dfg().value(inst).kind() {
+        ValueKind::Store(_) => true,
+        ValueKind::Call(call) => !pure.contains(&call.callee()),
+        kind if is_terminator(kind) => true,
+        _ => may_trap(data, inst),
+    }
+}
+
+/// Find the pure function definitions, whose calls can be removed if their results are never used.
+///
+/// A function is pure if it only stores to its own `alloc`s, only calls pure functions,
+/// cannot trap by a division, and always returns, which is ensured by having no loop.
+/// The functions are checked until nothing changes, so a recursive function is never pure.
+/// The library functions are all impure.
+fn pure_functions(program: &Program) -> HashSet<Function> {
+    let mut pure = HashSet::new();
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for &func in program.func_layout() {
+            if !pure.contains(&func) && is_pure(program.func(func), &pure) {
+                pure.insert(func);
+                changed = true;
+            }
+        }
+    }
+    pure
+}
+
+fn is_pure(data: &FunctionData, pure: &HashSet<Function>) -> bool {
+    let Some(cfg) = Cfg::new(data) else {
+        return false;
+    };
+    // an edge to a basic block that comes no later in the reverse post-order closes a loop
+    let rpo_index: HashMap<BasicBlock, usize> =
+        cfg.rpo.iter().enumerate().map(|(i, &bb)| (bb, i)).collect();
+    for &bb in cfg.rpo.iter() {
+        if cfg
+            .preds(bb)
+            .iter()
+            .any(|p| rpo_index.get(p).is_some_and(|&i| i >= rpo_index[&bb]))
+        {
+            return false;
+        }
+    }
+
+    // the definitions dominate the uses, so the pointers into the `alloc`s are found in the reverse post-order
+    let mut local = HashSet::new();
+    for &bb in cfg.rpo.iter() {
+        for &inst in data.layout().bbs().node(&bb).unwrap().insts().keys() {
+            match data.dfg().value(inst).kind() {
+                ValueKind::Alloc(_) => {
+                    local.insert(inst);
+                }
+                ValueKind::GetElemPtr(gep) if local.contains(&gep.src()) => {
+                    local.insert(inst);
+                }
+                ValueKind::GetPtr(gp) if local.contains(&gp.src()) => {
+                    local.insert(inst);
+                }
+                ValueKind::Store(store) if !local.contains(&store.dest()) => return false,
+                ValueKind::Call(call) if !pure.contains(&call.callee()) => return false,
+                _ if may_trap(data, inst) => return false,
+                _ => {}
+            }
+        }
+    }
+    true
+}
+
+/// Remove the basic blocks unreachable from the entry.
+fn remove_unreachable_bbs(data: &mut FunctionData) {
+    let Some(cfg) = Cfg::new(data) else {
+        return;
+    };
+    let reachable: HashSet<BasicBlock> = cfg.rpo.iter().copied().collect();
+    let unreachable: Vec<BasicBlock> = data
+        .layout()
+        .bbs()
+        .keys()
+        .copied()
+        .filter(|bb| !reachable.contains(bb))
+        .collect();
+    if unreachable.is_empty() {
+        return;
+    }
+
+    let mut insts = Vec::new();
+    let mut defined = HashSet::new();
+    for &bb in unreachable.iter() {
+        defined.extend(data.dfg().bb(bb).params().iter().copied());
+        for &inst in data.layout().bbs().node(&bb).unwrap().insts().keys() {
+            insts.push(inst);
+            defined.insert(inst);
+        }
+    }
+    // A reachable basic block cannot use the values of an unreachable one if the definitions dominate the uses,
+    // but the dominance is not verified, so such a function is left as it is.
+    for &bb in cfg.rpo.iter() {
+        for &inst in data.layout().bbs().node(&bb).unwrap().insts().keys() {
+            if data
+                .dfg()
+                .value(inst)
+                .kind()
+                .value_uses()
+                .any(|v| defined.contains(&v))
+            {
+                return;
+            }
+        }
+    }
+
+    for &bb in unreachable.iter() {
+        let node = data.layout_mut().bb_mut(bb);
+        while node.insts_mut().pop_back().is_some() {}
+        data.layout_mut().bbs_mut().remove(&bb);
+    }
+    remove_values(data, insts);
+    let counts = use_counts(data);
+    for bb in unreachable {
+        let bb_data = data.dfg().bb(bb);
+        // an instruction kept by `remove_values` may still use a parameter, and `remove_bb` panics then
+        let unused =
+            |v: &Value| !counts.contains_key(v) && data.dfg().value(*v).used_by().is_empty();
+        if bb_data.used_by().is_empty() && bb_data.params().iter().all(unused) {
+            data.dfg_mut().remove_bb(bb);
+        }
+    }
+}
+
+/// Get the arguments a branch or a jump passes to the `j`-th parameter of the target.
+fn args_to(data: &FunctionData, term: Value, target: BasicBlock, j: usize) -> Vec<Value> {
+    match data.dfg().value(term).kind() {
+        ValueKind::Branch(br) => {
+            let mut args = Vec::new();
+            if br.true_bb() == target {
+                args.push(br.true_args()[j]);
+            }
+            if br.false_bb() == target {
+                args.push(br.false_args()[j]);
+            }
+            args
+        }
+        ValueKind::Jump(jump) => vec![jump.args()[j]],
+        _ => Vec::new(),
+    }
+}
+
+/// Remove the instructions and the basic block parameters that are not needed by any side effect.
+///
+/// The live values are marked from the instructions with side effects,
+/// and an argument of a basic block is only live if the parameter it's passed to is.
+fn remove_dead_values(data: &mut FunctionData, pure: &HashSet<Function>) {
+    let mut params = HashMap::new();
+    let mut in_terms: HashMap<BasicBlock, Vec<Value>> = HashMap::new();
+    let mut live = HashSet::new();
+    let mut work = Vec::new();
+    for (&bb, node) in data.layout().bbs() {
+        for (j, &param) in data.dfg().bb(bb).params().iter().enumerate() {
+            params.insert(param, (bb, j));
+        }
+        for &inst in node.insts().keys() {
+            if has_side_effects(data, inst, pure) {
+                live.insert(inst);
+                work.push(inst);
+            }
+        }
+        if let Some(&term) = node.insts().back_key() {
+            let mut targets = successors(data.dfg(), term);
+            targets.dedup();
+            for target in targets {
+                in_terms.entry(target).or_default().push(term);
+            }
+        }
+    }
+
+    while let Some(v) = work.pop() {
+        let uses: Vec<Value> = match params.get(&v) {
+            Some(&(bb, j)) => in_terms
+                .get(&bb)
+                .into_iter()
+                .flatten()
+                .flat_map(|&term| args_to(data, term, bb, j))
+                .collect(),
+            None => match data.dfg().value(v).kind() {
+                // the arguments are marked along with the parameters
+                ValueKind::Branch(br) => vec![br.cond()],
+                ValueKind::Jump(_) => Vec::new(),
+                kind => kind.value_uses().collect(),
+            },
+        };
+        for u in uses {
+            // the constants, the global values and the function parameters are never removed
+            let removable = params.contains_key(&u) || data.layout().parent_bb(u).is_some();
+            if removable && live.insert(u) {
+                work.push(u);
+            }
+        }
+    }
+
+    // remove the dead instructions from the layout
+    let mut dead = Vec::new();
+    let bbs: Vec<BasicBlock> = data.layout().bbs().keys().copied().collect();
+    for &bb in bbs.iter() {
+        let insts: Vec<Value> = data
+            .layout()
+            .bbs()
+            .node(&bb)
+            .unwrap()
+            .insts()
+            .keys()
+            .copied()
+            .collect();
+        for inst in insts {
+            if !live.contains(&inst) {
+                data.layout_mut().bb_mut(bb).insts_mut().remove(&inst);
+                dead.push(inst);
+            }
+        }
+    }
+
+    // remove the dead parameters, along with their arguments
+    for &bb in bbs.iter() {
+        let bb_params = data.dfg().bb(bb).params().to_vec();
+        let keep: Vec<bool> = bb_params.iter().map(|p| live.contains(p)).collect();
+        if keep.iter().all(|&k| k) {
+            continue;
+        }
+        for &term in in_terms.get(&bb).into_iter().flatten() {
+            let mut term_data = data.dfg().value(term).clone();
+            let retain = |args: &mut Vec<Value>| {
+                let mut i = 0;
+                args.retain(|_| {
+                    i += 1;
+                    keep[i - 1]
+                });
+            };
+            match term_data.kind_mut() {
+                ValueKind::Branch(br) => {
+                    if br.true_bb() == bb {
+                        retain(br.true_args_mut());
+                    }
+                    if br.false_bb() == bb {
+                        retain(br.false_args_mut());
+                    }
+                }
+                ValueKind::Jump(jump) => retain(jump.args_mut()),
+                _ => continue,
+            }
+            data.dfg_mut().replace_value_with(term).raw(term_data);
+        }
+        data.dfg_mut()
+            .bb_mut(bb)
+            .params_mut()
+            .retain(|p| live.contains(p));
+        dead.extend(bb_params.into_iter().filter(|p| !live.contains(p)));
+    }
+
+    remove_values(data, dead);
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::opt::run_passes;
+
+    #[test]
+    fn dce_unreachable_test() {
+        let text = run_passes("int main() { return 1; int a = 2; return a; }", &["dce"]).text;
+        assert!(
+            !text.contains("ret %") && !text.contains("alloc"),
+            "{}",
+            text
+        );
+        // the branch folded by `sccp` leaves the other target unreachable
+        let text = run_passes(
+            "int main() { int x = 0; if (x) { putint(1); } else { putint(2); } return 0; }",
+            &["mem2reg", "sccp", "dce"],
+        )
+        .text;
+        assert!(!text.contains("%if_then"), "{}", text);
+        // the `mul` rewritten by `mem2reg` is removed after the `call` using it
+        let text = run_passes(
+            "void f(int a) {}\nint main() { return 0; f(0 * (7 && 0)); }",
+            &["mem2reg", "dce"],
+        )
+        .text;
+        assert!(!text.contains("mul"), "{}", text);
+    }
+
+    #[test]
+    fn dce_pure_call_test() {
+        let text = run_passes(
+            "int sq(int x) { int a[2]; a[0] = x; return a[0] * x; }\n\
+             int f(int n) { if (n) return f(n - 1); return 0; }\n\
+             int main() { sq(3); f(2); putint(sq(2)); return 0; }",
+            &["dce"],
+        )
+        .text;
+        // the recursive function is not pure
+        assert_eq!(text.matches("call @sq").count(), 1, "{}", text);
+        assert!(text.contains("call @f"), "{}", text);
+    }
+
+    #[test]
+    fn dce_dead_param_test() {
+        // `s` is computed around the loop, but never used after it
+        let text = run_passes(
+            "int main() { int i = 0, s = 0; while (i < 10) { s = s + i; i = i + 1; } return i; }",
+            &["mem2reg", "dce"],
+        )
+        .text;
+        assert_eq!(text.matches(" add ").count(), 1, "{}", text);
+    }
+}
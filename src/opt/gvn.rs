@@ -0,0 +1,280 @@
+use super::alias::{Aliases, Root};
+use super::cfg::{Cfg, DomTree};
+use super::utils::{remove_values, replace_operands};
+use koopa::ir::*;
+use koopa::opt::FunctionPass;
+use std::collections::HashMap;
+
+/// Remove the redundant computations by global value numbering on the dominator tree.
+///
+/// A `binary`, `getelemptr` or `getptr` is replaced by an equal one that dominates it.
+/// A `load` is replaced by the value last loaded from or stored to the same pointer,
+/// if no `store` to memory that may be the same and no `call` happen in between.
+/// The memory is only followed into a basic block whose only predecessor is its immediate dominator,
+/// so that every path to the `load` passes the earlier access.
+pub struct Gvn;
+
+impl FunctionPass for Gvn {
+    fn run_on(&mut self, _func: Function, data: &mut FunctionData) {
+        let Some(cfg) = Cfg::new(data) else {
+            return;
+        };
+        let dom = DomTree::new(&cfg);
+        let mut numbering = Numbering::new(data);
+        numbering.walk(data, &cfg, &dom);
+        numbering.finish(data, &dom);
+    }
+}
+
+/// An operand of an expression, where the integers are compared by their values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Operand {
+    Const(i32),
+    Value(Value),
+}
+
+/// A pure computation, which gives the same result for the same operands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Expr {
+    Binary(BinaryOp, Operand, Operand),
+    GetElemPtr(Value, Operand),
+    GetPtr(Value, Operand),
+}
+
+/// The known contents of memory, from pointers to the values they point to.
+type Memory = HashMap<Value, Value>;
+
+struct Numbering {
+    /// The expressions computed along the current path of the dominator tree.
+    exprs: HashMap<Expr, Value>,
+    /// The pointers that may point to the same memory.
+    aliases: Aliases,
+    /// The memory at the end of each basic block along the current path of the dominator tree.
+    memory: HashMap<BasicBlock, Memory>,
+    /// The value each removed instruction is replaced by.
+    replaced: HashMap<Value, Value>,
+    /// The removed instructions, which are still in the layout until `finish`.
+    removed: Vec<Value>,
+}
+
+impl Numbering {
+    fn new(data: &FunctionData) -> Self {
+        Self {
+            exprs: HashMap::new(),
+            aliases: Aliases::new(data),
+            memory: HashMap::new(),
+            replaced: HashMap::new(),
+            removed: Vec::new(),
+        }
+    }
+
+    /// Number the reachable basic blocks in the pre-order of the dominator tree.
+    fn walk(&mut self, data: &mut FunctionData, cfg: &Cfg, dom: &DomTree) {
+        // walk the dominator tree without recursion, so that deep nesting does not overflow the stack
+        let mut stack = vec![(cfg.entry, None)];
+        while let Some((bb, pushed)) = stack.pop() {
+            match pushed {
+                Some(pushed) => {
+                    for expr in pushed {
+                        self.exprs.remove(&expr);
+                    }
+                    self.memory.remove(&bb);
+                }
+                None => {
+                    let idom = dom.idom(bb);
+                    let preds = cfg.preds(bb);
+                    let memory = match idom {
+                        Some(idom) if preds.iter().all(|&p| p == idom) => {
+                            self.memory[&idom].clone()
+                        }
+                        _ => Memory::new(),
+                    };
+                    let pushed = self.number_block(data, bb, memory);
+                    stack.push((bb, Some(pushed)));
+                    for &child in dom.children(bb).iter().rev() {
+                        stack.push((child, None));
+                    }
+                }
+            }
+        }
+    }
+
+    /// Number the instructions of a basic block, given the memory at its start.
+    ///
+    /// Return the expressions it adds.
+    fn number_block(
+        &mut self,
+        data: &mut FunctionData,
+        bb: BasicBlock,
+        mut memory: Memory,
+    ) -> Vec<Expr> {
+        let mut pushed = Vec::new();
+        let insts: Vec<Value> = data
+            .layout()
+            .bbs()
+            .node(&bb)
+            .unwrap()
+            .insts()
+            .keys()
+            .copied()
+            .collect();
+        for inst in insts {
+            replace_operands(data.dfg_mut(), inst, |v| {
+                *self.replaced.get(&v).unwrap_or(&v)
+            });
+            let mut leader = None;
+            match data.dfg().value(inst).kind() {
+                ValueKind::Load(load) => match memory.get(&load.src()) {
+                    Some(&value) => leader = Some(value),
+                    None => {
+                        memory.insert(load.src(), inst);
+                    }
+                },
+                ValueKind::Store(store) => {
+                    let dest = Root::of(data, store.dest());
+                    memory.retain(|&ptr, _| !self.aliases.may_alias(dest, Root::of(data, ptr)));
+                    memory.insert(store.dest(), store.value());
+                }
+                ValueKind::Call(_) => {
+                    memory.retain(|&ptr, _| !self.aliases.call_may_access(Root::of(data, ptr)));
+                }
+                _ => {
+                    if let Some(expr) = expr(data, inst) {
+                        match self.lookup(expr) {
+                            Some(value) => leader = Some(value),
+                            None => {
+                                self.exprs.insert(expr, inst);
+                                pushed.push(expr);
+                            }
+                        }
+                    }
+                }
+            }
+            if let Some(leader) = leader {
+                self.replaced.insert(inst, leader);
+                self.removed.push(inst);
+            }
+        }
+        self.memory.insert(bb, memory);
+        pushed
+    }
+
+    /// Find an equal expression computed before, trying both orders of the operands of a commutative operator.
+    fn lookup(&self, expr: Expr) -> Option<Value> {
+        if let Some(&value) = self.exprs.get(&expr) {
+            return Some(value);
+        }
+        match expr {
+            Expr::Binary(op, lhs, rhs) if is_commutative(op) => {
+                self.exprs.get(&Expr::Binary(op, rhs, lhs)).copied()
+            }
+            _ => None,
+        }
+    }
+
+    /// Replace the uses of the removed instructions in the unreachable basic blocks, and remove them.
+    fn finish(self, data: &mut FunctionData, dom: &DomTree) {
+        for &inst in self.removed.iter() {
+            let bb = data.layout().parent_bb(inst).unwrap();
+            data.layout_mut().bb_mut(bb).insts_mut().remove(&inst);
+        }
+        let unreachable: Vec<BasicBlock> = data
+            .layout()
+            .bbs()
+            .keys()
+            .copied()
+            .filter(|&bb| !dom.is_reachable(bb))
+            .collect();
+        for bb in unreachable {
+            let insts: Vec<Value> = data
+                .layout()
+                .bbs()
+                .node(&bb)
+                .unwrap()
+                .insts()
+                .keys()
+                .copied()
+                .collect();
+            for inst in insts {
+                replace_operands(data.dfg_mut(), inst, |v| {
+                    *self.replaced.get(&v).unwrap_or(&v)
+                });
+            }
+        }
+        remove_values(data, self.removed);
+    }
+}
+
+fn is_commutative(op: BinaryOp) -> bool {
+    use BinaryOp::*;
+    matches!(op, NotEq | Eq | Add | Mul | And | Or | Xor)
+}
+
+/// Get the operand of an expression, which is a global value if it's not in the data flow graph of the function.
+fn operand(data: &FunctionData, v: Value) -> Operand {
+    match data.dfg().values().get(&v).map(|d| d.kind()) {
+        Some(ValueKind::Integer(i)) => Operand::Const(i.value()),
+        _ => Operand::Value(v),
+    }
+}
+
+/// Get the expression computed by the instruction, `None` if it's not a pure computation.
+fn expr(data: &FunctionData, inst: Value) -> Option<Expr> {
+    match data.dfg().value(inst).kind() {
+        ValueKind::Binary(bin) => Some(Expr::Binary(
+            bin.op(),
+            operand(data, bin.lhs()),
+            operand(data, bin.rhs()),
+        )),
+        ValueKind::GetElemPtr(gep) => Some(Expr::GetElemPtr(gep.src(), operand(data, gep.index()))),
+        ValueKind::GetPtr(gp) => Some(Expr::GetPtr(gp.src(), operand(data, gp.index()))),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::opt::run_passes;
+
+    #[test]
+    fn gvn_array_test() {
+        let text = run_passes(
+            "int a[4][4], b[4][4];\n\
+             int main() { int i = 1, j = 2; b[i][j] = 3; a[i][j] = a[i][j] + b[i][j]; putint(a[i][j]); return 0; }",
+            &["gvn"],
+        )
+        .text;
+        // one address of each element, and only `a[i][j]` is loaded,
+        // since the indexes and `b[i][j]` are taken from the stores
+        assert_eq!(text.matches("getelemptr").count(), 4, "{}", text);
+        assert_eq!(text.matches("load").count(), 1, "{}", text);
+        // the commuted expression is the same
+        let text = run_passes(
+            "int f(int x, int y) { return (x + y) * (y + x); }\nint main() { return f(2, 3); }",
+            &["mem2reg", "gvn"],
+        )
+        .text;
+        assert_eq!(text.matches(" add ").count(), 1, "{}", text);
+    }
+
+    #[test]
+    fn gvn_memory_test() {
+        // the global value is loaded again after the call, and after the `if`
+        let text = run_passes(
+            "int g;\nvoid set() { g = 2; }\n\
+             int main() { g = 1; int x = g; set(); x = x + g + g; if (x) { x = x + 1; } return x + g; }",
+            &["gvn"],
+        )
+        .text;
+        assert_eq!(text.matches("load @g").count(), 2, "{}", text);
+        // the array escapes to the callee, which changes it
+        let text = run_passes(
+            "void f(int a[]) { a[0] = 3; }\n\
+             int main() { int a[2], b[2]; a[0] = 1; b[0] = 2; f(a); return a[0] * 10 + b[0]; }",
+            &["gvn"],
+        )
+        .text;
+        // `a[0]` is loaded after the call, but `b[0]` is not
+        assert_eq!(text.matches("load").count(), 1, "{}", text);
+    }
+}
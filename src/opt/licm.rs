@@ -0,0 +1,316 @@
+use super::alias::{Aliases, Root};
+use super::cfg::{Cfg, DomTree};
+use super::utils::{may_trap, redirect_branch};
+use koopa::ir::builder::{BasicBlockBuilder, LocalInstBuilder};
+use koopa::ir::*;
+use koopa::opt::ModulePass;
+use std::collections::{HashMap, HashSet};
+
+/// Hoist the loop-invariant instructions of the natural loops into their preheaders.
+///
+/// A `binary` that cannot trap, a `getelemptr` and a `getptr` are hoisted if their operands are defined outside the loop.
+/// A `load` is also hoisted if the loop never writes the memory (by a `store` or a `call`),
+/// and if the pointer is always valid, since the loop body may not run at all.
+/// The inner loops are handled first, so that an instruction may be hoisted out of several loops.
+pub struct Licm;
+
+impl ModulePass for Licm {
+    fn run_on(&mut self, program: &mut Program) {
+        // the global values are not in the data flow graphs of the functions
+        let globals: HashMap<Value, Type> = program
+            .inst_layout()
+            .iter()
+            .map(|&v| (v, program.borrow_value(v).ty().clone()))
+            .collect();
+        for func in program.func_layout().to_vec() {
+            let data = program.func_mut(func);
+            // a declaration has no loops to hoist out of
+            if data.layout().entry_bb().is_some() {
+                licm(data, &globals);
+            }
+        }
+    }
+}
+
+/// A natural loop.
+struct Loop {
+    header: BasicBlock,
+    /// The basic blocks of the loop (including the header) in reverse post-order.
+    bbs: Vec<BasicBlock>,
+}
+
+fn licm(data: &mut FunctionData, globals: &HashMap<Value, Type>) {
+    let cfg = Cfg::new(data).unwrap();
+    let loops = find_loops(&cfg, &DomTree::new(&cfg));
+    if loops.is_empty() {
+        return;
+    }
+    let preheaders: HashMap<BasicBlock, BasicBlock> = loops
+        .iter()
+        .filter_map(|l| Some((l.header, insert_preheader(data, &cfg, l)?)))
+        .collect();
+
+    // the loops are found again, since the preheaders may belong to outer loops
+    let cfg = Cfg::new(data).unwrap();
+    let aliases = Aliases::new(data);
+    for l in find_loops(&cfg, &DomTree::new(&cfg)) {
+        if let Some(&preheader) = preheaders.get(&l.header) {
+            hoist(data, &l, preheader, &aliases, globals);
+        }
+    }
+}
+
+/// Find the natural loops, where the loops with the same header are merged, from the inner ones to the outer ones.
+fn find_loops(cfg: &Cfg, dom: &DomTree) -> Vec<Loop> {
+    let mut loops = Vec::new();
+    for &header in cfg.rpo.iter() {
+        // the source of a back edge is dominated by the target
+        let latches: Vec<BasicBlock> = cfg
+            .preds(header)
+            .iter()
+            .copied()
+            .filter(|&p| dom.is_reachable(p) && dom.dominates(header, p))
+            .collect();
+        if latches.is_empty() {
+            continue;
+        }
+        let mut body = HashSet::from([header]);
+        let mut work = latches;
+        while let Some(bb) = work.pop() {
+            if body.insert(bb) {
+                work.extend(
+                    cfg.preds(bb)
+                        .iter()
+                        .copied()
+                        .filter(|&p| dom.is_reachable(p)),
+                );
+            }
+        }
+        let bbs = cfg
+            .rpo
+            .iter()
+            .copied()
+            .filter(|bb| body.contains(bb))
+            .collect();
+        loops.push(Loop { header, bbs });
+    }
+    // an inner loop is smaller than the loops containing it
+    loops.sort_by_key(|l| l.bbs.len());
+    loops
+}
+
+/// Get the preheader of the loop, the only basic block outside the loop that jumps to the header.
+///
+/// If there's none, a new basic block is inserted before the header,
+/// which takes the arguments of the edges from outside the loop and passes them to the header.
+/// Return `None` if the header is the entry, which cannot have a preheader.
+fn insert_preheader(data: &mut FunctionData, cfg: &Cfg, l: &Loop) -> Option<BasicBlock> {
+    let header = l.header;
+    let mut outside: Vec<BasicBlock> = cfg
+        .preds(header)
+        .iter()
+        .copied()
+        .filter(|p| !l.bbs.contains(p))
+        .collect();
+    outside.dedup();
+    if outside.is_empty() {
+        return None;
+    }
+    let terms: Vec<Value> = outside
+        .iter()
+        .map(|bb| {
+            *data
+                .layout()
+                .bbs()
+                .node(bb)
+                .unwrap()
+                .insts()
+                .back_key()
+                .unwrap()
+        })
+        .collect();
+    if let [term] = terms[..] {
+        if matches!(data.dfg().value(term).kind(), ValueKind::Jump(_)) {
+            return Some(outside[0]);
+        }
+    }
+
+    let header_data = data.dfg().bb(header);
+    let name = header_data
+        .name()
+        .as_ref()
+        .map(|name| format!("{}_preheader", name));
+    let tys: Vec<Type> = header_data
+        .params()
+        .iter()
+        .map(|&p| data.dfg().value(p).ty().clone())
+        .collect();
+    let preheader = data.dfg_mut().new_bb().basic_block_with_params(name, tys);
+    let params = data.dfg().bb(preheader).params().to_vec();
+    let jump = data.dfg_mut().new_value().jump_with_args(header, params);
+    data.layout_mut()
+        .bbs_mut()
+        .cursor_mut(header)
+        .insert_key_before(preheader)
+        .unwrap();
+    data.layout_mut()
+        .bb_mut(preheader)
+        .insts_mut()
+        .push_key_back(jump)
+        .unwrap();
+    for term in terms {
+        redirect_branch(data.dfg_mut(), term, header, preheader, |_| {});
+    }
+    Some(preheader)
+}
+
+/// Return whether the pointer is always valid to load from,
+/// i.e. it points to an `alloc` or a global value, or to an element of them by indexes in bounds.
+fn is_dereferenceable(data: &FunctionData, ptr: Value, globals: &HashMap<Value, Type>) -> bool {
+    let Some(ptr_data) = data.dfg().values().get(&ptr) else {
+        return true;
+    };
+    match ptr_data.kind() {
+        ValueKind::Alloc(_) => true,
+        ValueKind::GetElemPtr(gep) => {
+            let src = gep.src();
+            let src_ty = match data.dfg().values().get(&src) {
+                Some(src_data) => src_data.ty().clone(),
+                None => globals[&src].clone(),
+            };
+            let index = data.dfg().values().get(&gep.index()).map(|d| d.kind());
+            let in_bounds = match (src_ty.kind(), index) {
+                (TypeKind::Pointer(base), Some(ValueKind::Integer(i))) => match base.kind() {
+                    TypeKind::Array(_, len) => (0..*len as i64).contains(&(i.value() as i64)),
+                    _ => false,
+                },
+                _ => false,
+            };
+            in_bounds && is_dereferenceable(data, src, globals)
+        }
+        _ => false,
+    }
+}
+
+/// Hoist the invariant instructions of the loop to the end of the preheader, before its terminator.
+fn hoist(
+    data: &mut FunctionData,
+    l: &Loop,
+    preheader: BasicBlock,
+    aliases: &Aliases,
+    globals: &HashMap<Value, Type>,
+) {
+    let mut defined = HashSet::new();
+    let mut written = Vec::new();
+    let mut has_call = false;
+    for &bb in l.bbs.iter() {
+        defined.extend(data.dfg().bb(bb).params().iter().copied());
+        for &inst in data.layout().bbs().node(&bb).unwrap().insts().keys() {
+            defined.insert(inst);
+            match data.dfg().value(inst).kind() {
+                ValueKind::Store(store) => written.push(Root::of(data, store.dest())),
+                ValueKind::Call(_) => has_call = true,
+                _ => {}
+            }
+        }
+    }
+
+    let term = *data
+        .layout()
+        .bbs()
+        .node(&preheader)
+        .unwrap()
+        .insts()
+        .back_key()
+        .unwrap();
+    // the definitions come before the uses in reverse post-order, except the basic block parameters
+    for &bb in l.bbs.iter() {
+        let insts: Vec<Value> = data
+            .layout()
+            .bbs()
+            .node(&bb)
+            .unwrap()
+            .insts()
+            .keys()
+            .copied()
+            .collect();
+        for inst in insts {
+            let kind = data.dfg().value(inst).kind();
+            if kind.value_uses().any(|v| defined.contains(&v)) {
+                continue;
+            }
+            let invariant = match kind {
+                ValueKind::Binary(_) => !may_trap(data, inst),
+                ValueKind::GetElemPtr(_) | ValueKind::GetPtr(_) => true,
+                ValueKind::Load(load) => {
+                    let root = Root::of(data, load.src());
+                    is_dereferenceable(data, load.src(), globals)
+                        && !written.iter().any(|&w| aliases.may_alias(w, root))
+                        && !(has_call && aliases.call_may_access(root))
+                }
+                _ => false,
+            };
+            if invariant {
+                data.layout_mut().bb_mut(bb).insts_mut().remove(&inst);
+                let mut cursor = data
+                    .layout_mut()
+                    .bb_mut(preheader)
+                    .insts_mut()
+                    .cursor_mut(term);
+                cursor.insert_key_before(inst).unwrap();
+                defined.remove(&inst);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::opt::run_passes;
+
+    #[test]
+    fn licm_nested_loop_test() {
+        let run = run_passes(
+            "int n = 8; int a[8][8];\n\
+             int main() {\n\
+               int i = 0;\n\
+               if (n > 5) i = 1;\n\
+               while (i < n) { int j = 0; while (j < n) { a[i][j] = i * n + j; j = j + 1; } i = i + 1; }\n\
+               return a[3][4];\n\
+             }",
+            &["mem2reg", "simplify-cfg", "licm"],
+        );
+        // `load @n` is hoisted out of both loops, and `a[i]` and `i * n` out of the inner one
+        assert!(
+            run.after + 3 * 56 <= run.before,
+            "{} {}\n{}",
+            run.before,
+            run.after,
+            run.text
+        );
+        // both the entry and the `if` jump to the outer loop
+        assert!(run.text.contains("_preheader"), "{}", run.text);
+    }
+
+    #[test]
+    fn licm_memory_test() {
+        // the loop writes `a[0]`, and the call may write `g`, so they are loaded in every iteration
+        let text = run_passes(
+            "int g = 1; int a[2];\n\
+             void f() { g = g + 1; }\n\
+             int main() { int i = 0; while (i < 4) { a[0] = a[0] + a[1] + i; f(); i = i + g; } return a[0]; }",
+            &["mem2reg", "licm"],
+        )
+        .text;
+        let body = text
+            .split("\n\n")
+            .find(|bb| bb.contains("call @f"))
+            .unwrap();
+        assert_eq!(body.matches("load").count(), 3, "{}", text);
+        // the division by zero is not hoisted out of the loop, which never runs
+        run_passes(
+            "int main() { int i = 0, x = 0, z = 0; while (i < 0) { x = x + 1 / z; i = i + 1; } return x; }",
+            &["mem2reg", "licm"],
+        );
+    }
+}
@@ -0,0 +1,381 @@
+use super::cfg::{Cfg, DomTree};
+use super::utils::{push_bb_param, push_branch_args, remove_values, replace_operands, successors};
+use koopa::ir::builder::ValueBuilder;
+use koopa::ir::*;
+use koopa::opt::FunctionPass;
+use std::collections::{HashMap, HashSet};
+
+/// Promote scalar `alloc`s to SSA values and basic block parameters.
+///
+/// An `alloc` is promotable if it does not allocate an array
+/// and it's only used as the source of `load`s and the destination of `store`s.
+/// Other `alloc`s (arrays, and the ones whose pointer escapes) are left alone.
+///
+/// The parameters are placed at the iterated dominance frontiers of the `store`s,
+/// and a `load` before any `store` reads `undef`.
+pub struct Mem2Reg;
+
+impl FunctionPass for Mem2Reg {
+    fn run_on(&mut self, _func: Function, data: &mut FunctionData) {
+        let Some(cfg) = Cfg::new(data) else {
+            return;
+        };
+        // the entry basic block cannot have parameters
+        if !cfg.preds(cfg.entry).is_empty() {
+            return;
+        }
+        let allocs = promotable_allocs(data);
+        if allocs.is_empty() {
+            return;
+        }
+        let dom = DomTree::new(&cfg);
+        let params = place_params(data, &cfg, &dom, &allocs);
+
+        let mut renamer = Renamer {
+            vars: allocs
+                .iter()
+                .enumerate()
+                .map(|(i, &alloc)| (alloc, i))
+                .collect(),
+            types: allocs.iter().map(|&alloc| pointee(data, alloc)).collect(),
+            params,
+            stacks: vec![Vec::new(); allocs.len()],
+            undefs: vec![None; allocs.len()],
+            replaced: HashMap::new(),
+            removed: Vec::new(),
+        };
+        renamer.rename(data, &dom, cfg.entry);
+        // the unreachable basic blocks are not in the dominator tree, and only see `undef`
+        let unreachable: Vec<BasicBlock> = data
+            .layout()
+            .bbs()
+            .keys()
+            .copied()
+            .filter(|&bb| !dom.is_reachable(bb))
+            .collect();
+        for bb in unreachable {
+            let pushed = renamer.rename_block(data, bb);
+            renamer.pop(pushed);
+        }
+        renamer.finish(data);
+    }
+}
+
+/// Get the type of the value an `alloc` allocates.
+fn pointee(data: &FunctionData, alloc: Value) -> Type {
+    match data.dfg().value(alloc).ty().kind() {
+        TypeKind::Pointer(base) => base.clone(),
+        _ => unreachable!("an `alloc` is a pointer"),
+    }
+}
+
+/// Find the promotable `alloc`s, in the order of the layout.
+fn promotable_allocs(data: &FunctionData) -> Vec<Value> {
+    let mut allocs = Vec::new();
+    let mut escaped = HashSet::new();
+    for (_, node) in data.layout().bbs() {
+        for &inst in node.insts().keys() {
+            match data.dfg().value(inst).kind() {
+                ValueKind::Alloc(_) => {
+                    if !matches!(pointee(data, inst).kind(), TypeKind::Array(..)) {
+                        allocs.push(inst);
+                    }
+                }
+                ValueKind::Load(_) => {}
+                ValueKind::Store(store) => {
+                    escaped.insert(store.value());
+                }
+                kind => escaped.extend(kind.value_uses()),
+            }
+        }
+    }
+    allocs.retain(|alloc| !escaped.contains(alloc));
+    allocs
+}
+
+/// Add the basic block parameters of the promoted `alloc`s.
+///
+/// Return the parameters of each basic block along with the index of their `alloc`s.
+fn place_params(
+    data: &mut FunctionData,
+    cfg: &Cfg,
+    dom: &DomTree,
+    allocs: &[Value],
+) -> HashMap<BasicBlock, Vec<(usize, Value)>> {
+    let vars: HashMap<Value, usize> = allocs
+        .iter()
+        .enumerate()
+        .map(|(i, &alloc)| (alloc, i))
+        .collect();
+    let mut def_bbs: Vec<HashSet<BasicBlock>> = vec![HashSet::new(); allocs.len()];
+    for &bb in cfg.rpo.iter() {
+        for &inst in data.layout().bbs().node(&bb).unwrap().insts().keys() {
+            if let ValueKind::Store(store) = data.dfg().value(inst).kind() {
+                if let Some(&var) = vars.get(&store.dest()) {
+                    def_bbs[var].insert(bb);
+                }
+            }
+        }
+    }
+
+    let frontiers = dom.frontiers(cfg);
+    let mut param_bbs: Vec<HashSet<BasicBlock>> = vec![HashSet::new(); allocs.len()];
+    for (var, defs) in def_bbs.into_iter().enumerate() {
+        let mut work: Vec<BasicBlock> = defs.into_iter().collect();
+        while let Some(bb) = work.pop() {
+            for &df in frontiers.get(&bb).into_iter().flatten() {
+                // a parameter is a definition as well
+                if param_bbs[var].insert(df) {
+                    work.push(df);
+                }
+            }
+        }
+    }
+
+    // add the parameters in the order of the layout and of the `alloc`s, so that the output is stable
+    let mut params: HashMap<BasicBlock, Vec<(usize, Value)>> = HashMap::new();
+    let bbs: Vec<BasicBlock> = data.layout().bbs().keys().copied().collect();
+    for bb in bbs {
+        for (var, &alloc) in allocs.iter().enumerate() {
+            if param_bbs[var].contains(&bb) {
+                let name = data
+                    .dfg()
+                    .value(alloc)
+                    .name()
+                    .as_ref()
+                    .map(|name| format!("%{}", &name[1..]));
+                let ty = pointee(data, alloc);
+                let param = push_bb_param(data.dfg_mut(), bb, ty, name);
+                params.entry(bb).or_default().push((var, param));
+            }
+        }
+    }
+    params
+}
+
+/// The state of renaming the `load`s and `store`s of the promoted `alloc`s.
+struct Renamer {
+    /// The index of each promoted `alloc`.
+    vars: HashMap<Value, usize>,
+    /// The type of the value each `alloc` holds.
+    types: Vec<Type>,
+    /// The parameters of each basic block along with the index of their `alloc`s.
+    params: HashMap<BasicBlock, Vec<(usize, Value)>>,
+    /// The definitions of each `alloc` along the current path of the dominator tree.
+    stacks: Vec<Vec<Value>>,
+    /// The `undef` of each `alloc`, created when first needed.
+    undefs: Vec<Option<Value>>,
+    /// The value each removed `load` is replaced by.
+    replaced: HashMap<Value, Value>,
+    /// The removed instructions, which are still in the layout until `finish`.
+    removed: Vec<Value>,
+}
+
+impl Renamer {
+    /// Rename the basic block and the ones it dominates.
+    fn rename(&mut self, data: &mut FunctionData, dom: &DomTree, bb: BasicBlock) {
+        // walk the dominator tree without recursion, so that deep nesting does not overflow the stack
+        let mut stack = vec![(bb, None)];
+        while let Some((bb, pushed)) = stack.pop() {
+            match pushed {
+                Some(pushed) => self.pop(pushed),
+                None => {
+                    let pushed = self.rename_block(data, bb);
+                    stack.push((bb, Some(pushed)));
+                    for &child in dom.children(bb).iter().rev() {
+                        stack.push((child, None));
+                    }
+                }
+            }
+        }
+    }
+
+    /// Rename a single basic block, and pass the current definitions to its successors.
+    ///
+    /// Return the `alloc`s whose definitions are pushed.
+    fn rename_block(&mut self, data: &mut FunctionData, bb: BasicBlock) -> Vec<usize> {
+        let mut pushed = Vec::new();
+        for &(var, param) in self.params.get(&bb).into_iter().flatten() {
+            self.stacks[var].push(param);
+            pushed.push(var);
+        }
+
+        let insts: Vec<Value> = data
+            .layout()
+            .bbs()
+            .node(&bb)
+            .unwrap()
+            .insts()
+            .keys()
+            .copied()
+            .collect();
+        for &inst in insts.iter() {
+            match data.dfg().value(inst).kind().clone() {
+                ValueKind::Alloc(_) if self.vars.contains_key(&inst) => self.removed.push(inst),
+                ValueKind::Load(load) => {
+                    if let Some(&var) = self.vars.get(&load.src()) {
+                        let value = self.current(data, var);
+                        self.replaced.insert(inst, value);
+                        self.removed.push(inst);
+                    }
+                }
+                ValueKind::Store(store) => {
+                    if let Some(&var) = self.vars.get(&store.dest()) {
+                        self.stacks[var].push(store.value());
+                        pushed.push(var);
+                        self.removed.push(inst);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        if let Some(&term) = insts.last() {
+            let mut targets = successors(data.dfg(), term);
+            targets.dedup();
+            for target in targets {
+                let Some(params) = self.params.get(&target) else {
+                    continue;
+                };
+                let vars: Vec<usize> = params.iter().map(|&(var, _)| var).collect();
+                let args: Vec<Value> = vars
+                    .into_iter()
+                    .map(|var| self.current(data, var))
+                    .collect();
+                push_branch_args(data.dfg_mut(), term, target, &args);
+            }
+        }
+        pushed
+    }
+
+    /// Pop the definitions pushed by a basic block.
+    fn pop(&mut self, pushed: Vec<usize>) {
+        for var in pushed {
+            self.stacks[var].pop();
+        }
+    }
+
+    /// Get the current definition of an `alloc`.
+    fn current(&mut self, data: &mut FunctionData, var: usize) -> Value {
+        if let Some(&value) = self.stacks[var].last() {
+            return value;
+        }
+        *self.undefs[var]
+            .get_or_insert_with(|| data.dfg_mut().new_value().undef(self.types[var].clone()))
+    }
+
+    /// Replace the uses of the removed `load`s, and remove the promoted instructions.
+    fn finish(self, data: &mut FunctionData) {
+        for &inst in self.removed.iter() {
+            let bb = data.layout().parent_bb(inst).unwrap();
+            data.layout_mut().bb_mut(bb).insts_mut().remove(&inst);
+        }
+
+        // a stored value may be a removed `load` itself, so follow the replacements to the end
+        let resolve = |mut v: Value| {
+            while let Some(&next) = self.replaced.get(&v) {
+                v = next;
+            }
+            v
+        };
+        let insts: Vec<Value> = data
+            .layout()
+            .bbs()
+            .nodes()
+            .flat_map(|node| node.insts().keys().copied())
+            .collect();
+        for inst in insts {
+            replace_operands(data.dfg_mut(), inst, resolve);
+        }
+
+        remove_values(data, self.removed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast_generate::parse_sysy_to_ast;
+    use crate::ir_generate::{
+        get_koopa_program, koopa_program_to_text, parse_ast_to_koopa_program,
+    };
+    use crate::opt::verify_program;
+
+    fn mem2reg(input: &str) -> Program {
+        let ast = parse_sysy_to_ast(input).unwrap();
+        let mut program = parse_ast_to_koopa_program(&ast).unwrap();
+        for &func in program.func_layout().to_vec().iter() {
+            let data = program.func_mut(func);
+            if data.layout().entry_bb().is_some() {
+                Mem2Reg.run_on(func, data);
+            }
+        }
+        verify_program(&program).unwrap();
+        program
+    }
+
+    /// Count the `alloc`s, `load`s and `store`s in the layout.
+    fn count_memory_insts(program: &Program) -> (usize, usize, usize) {
+        let (mut allocs, mut loads, mut stores) = (0, 0, 0);
+        for &func in program.func_layout() {
+            let data = program.func(func);
+            for node in data.layout().bbs().nodes() {
+                for &inst in node.insts().keys() {
+                    match data.dfg().value(inst).kind() {
+                        ValueKind::Alloc(_) => allocs += 1,
+                        ValueKind::Load(_) => loads += 1,
+                        ValueKind::Store(_) => stores += 1,
+                        _ => {}
+                    }
+                }
+            }
+        }
+        (allocs, loads, stores)
+    }
+
+    #[test]
+    fn mem2reg_loop_test() {
+        let program = mem2reg(
+            "int main() { int i = 0, s = 0; while (i < 10) { if (i % 2) s = s + i; i = i + 1; } return s; }",
+        );
+        assert_eq!(count_memory_insts(&program), (0, 0, 0));
+
+        let text = koopa_program_to_text(&program).unwrap();
+        assert!(text.contains("%while_entry"), "{}", text);
+        assert!(text.contains(": i32)"), "{}", text);
+        // the Koopa front end checks the types of the block arguments
+        get_koopa_program(&text).unwrap();
+    }
+
+    #[test]
+    fn mem2reg_undef_test() {
+        let program = mem2reg("int main() { int a; if (getint()) a = 1; return a; }");
+        let text = koopa_program_to_text(&program).unwrap();
+        assert!(text.contains("undef"), "{}", text);
+        get_koopa_program(&text).unwrap();
+    }
+
+    #[test]
+    fn mem2reg_arrays_and_escapes_test() {
+        // the arrays are left alone, while the pointer parameter is only loaded and gets promoted
+        let program = mem2reg(
+            "int f(int a[]) { int b[2] = {1, 2}; a[0] = b[1]; return a[0]; }\n\
+             int main() { int x[3]; return f(x); }",
+        );
+        let (allocs, _, _) = count_memory_insts(&program);
+        assert_eq!(allocs, 2);
+        get_koopa_program(&koopa_program_to_text(&program).unwrap()).unwrap();
+    }
+
+    #[test]
+    fn mem2reg_generated_test() {
+        for input in [
+            "tests/sysy_scripts/lv6-2.c",
+            "tests/sysy_scripts/lv7-2.c",
+            "tests/sysy_scripts/lv9-3.c",
+        ] {
+            let program = mem2reg(&std::fs::read_to_string(input).unwrap());
+            get_koopa_program(&koopa_program_to_text(&program).unwrap()).unwrap();
+        }
+    }
+}
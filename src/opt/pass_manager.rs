@@ -0,0 +1,153 @@
+use super::verify::verify_program;
+use crate::diagnostic::Diagnostic;
+use koopa::ir::Program;
+use koopa::opt::Pass;
+
+/// An ordered list of passes to run on a Koopa program.
+///
+/// Unlike `koopa::opt::PassManager`, the passes are named,
+/// function passes only run on function definitions (in the order of the program),
+/// and the program can be verified after each pass to find out which pass breaks it.
+pub struct PassManager {
+    passes: Vec<(String, Pass)>,
+    verify_each: bool,
+}
+
+impl PassManager {
+    /// Construct an empty pass manager.
+    pub fn new() -> Self {
+        Self {
+            passes: Vec::new(),
+            verify_each: false,
+        }
+    }
+
+    /// Append a pass to the pipeline.
+    pub fn register(&mut self, name: impl Into<String>, pass: Pass) {
+        self.passes.push((name.into(), pass));
+    }
+
+    /// Verify the program before running any pass and after each pass.
+    pub fn set_verify_each(&mut self, verify_each: bool) {
+        self.verify_each = verify_each;
+    }
+
+    /// Get the names of the registered passes, in the order they run.
+    pub fn pass_names(&self) -> Vec<&str> {
+        self.passes.iter().map(|(name, _)| name.as_str()).collect()
+    }
+
+    /// Run all the registered passes on the program.
+    ///
+    /// # Errors
+    /// If verification is enabled, an error is returned as soon as the program becomes invalid,
+    /// with a note naming the pass that broke it.
+    pub fn run(&mut self, program: &mut Program) -> Result<(), Diagnostic> {
+        if self.verify_each {
+            verify_program(program)
+                .map_err(|d| d.with_note("the program is invalid before running any pass"))?;
+        }
+
+        for (name, pass) in self.passes.iter_mut() {
+            match pass {
+                Pass::Module(p) => p.run_on(program),
+                Pass::Function(p) => {
+                    let funcs = program.func_layout().to_vec();
+                    for func in funcs {
+                        let data = program.func_mut(func);
+                        // function passes never see declarations, so they don't have to check for them
+                        if data.layout().entry_bb().is_some() {
+                            p.run_on(func, data);
+                        }
+                    }
+                }
+            }
+
+            if self.verify_each {
+                verify_program(program).map_err(|d| {
+                    d.with_note(format!("the program is broken by the pass `{}`", name))
+                })?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for PassManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast_generate::parse_sysy_to_ast;
+    use crate::ir_generate::parse_ast_to_koopa_program;
+    use koopa::ir::{Function, FunctionData};
+    use koopa::opt::FunctionPass;
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    /// Count the function definitions it runs on.
+    struct CountFunctions(Rc<Cell<usize>>);
+
+    impl FunctionPass for CountFunctions {
+        fn run_on(&mut self, _func: Function, _data: &mut FunctionData) {
+            self.0.set(self.0.get() + 1);
+        }
+    }
+
+    /// Remove the last instruction of every entry basic block.
+    struct DropTerminators;
+
+    impl FunctionPass for DropTerminators {
+        fn run_on(&mut self, _func: Function, data: &mut FunctionData) {
+            let entry = data.layout().entry_bb().unwrap();
+            data.layout_mut().bb_mut(entry).insts_mut().pop_back();
+        }
+    }
+
+    fn program() -> Program {
+        let ast = parse_sysy_to_ast("int f() { return 1; }\nint main() { return f(); }").unwrap();
+        parse_ast_to_koopa_program(&ast).unwrap()
+    }
+
+    #[test]
+    fn pass_manager_function_pass_test() {
+        let count = Rc::new(Cell::new(0));
+        let mut pm = PassManager::new();
+        pm.register(
+            "count",
+            Pass::Function(Box::new(CountFunctions(count.clone()))),
+        );
+        pm.register(
+            "count-again",
+            Pass::Function(Box::new(CountFunctions(count.clone()))),
+        );
+        assert_eq!(pm.pass_names(), vec!["count", "count-again"]);
+
+        pm.run(&mut program()).unwrap();
+        // the library declarations are skipped
+        assert_eq!(count.get(), 4);
+    }
+
+    #[test]
+    fn pass_manager_verify_each_test() {
+        let mut pm = PassManager::new();
+        pm.register(
+            "drop-terminators",
+            Pass::Function(Box::new(DropTerminators)),
+        );
+        pm.run(&mut program()).unwrap();
+
+        pm.set_verify_each(true);
+        let diag = pm.run(&mut program()).unwrap_err();
+        assert!(
+            diag.notes.iter().any(|n| n.contains("`drop-terminators`")),
+            "{}",
+            diag
+        );
+    }
+}
@@ -0,0 +1,358 @@
+use super::cfg::Cfg;
+use super::utils::{remove_values, replace_operands, successors, use_counts};
+use koopa::ir::builder::{LocalInstBuilder, ValueBuilder};
+use koopa::ir::*;
+use koopa::opt::FunctionPass;
+use std::collections::{HashMap, HashSet};
+
+/// Propagate and fold constants by sparse conditional constant propagation (Wegman and Zadeck).
+///
+/// The results of `binary`s and the basic block parameters that are constant on every executable path
+/// are replaced by integers, and a `br` on a constant condition becomes a `jump`,
+/// which may leave basic blocks unreachable (they are left to the dead code elimination).
+///
+/// An `undef` is treated as an unknown value rather than any constant,
+/// and a division by zero is never folded, so that it still happens at runtime.
+pub struct Sccp;
+
+impl FunctionPass for Sccp {
+    fn run_on(&mut self, _func: Function, data: &mut FunctionData) {
+        let Some(cfg) = Cfg::new(data) else {
+            return;
+        };
+        let mut solver = Solver::new(data);
+        solver.solve(data, cfg.entry);
+        solver.rewrite(data);
+    }
+}
+
+/// The lattice of the value of an SSA value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Lattice {
+    /// Not known yet, e.g. the definition is not executable (yet).
+    Top,
+    /// The same constant on every executable path.
+    Const(i32),
+    /// Not a constant.
+    Bottom,
+}
+
+impl Lattice {
+    fn meet(self, other: Self) -> Self {
+        match (self, other) {
+            (Self::Top, x) | (x, Self::Top) => x,
+            (Self::Const(a), Self::Const(b)) if a == b => self,
+            _ => Self::Bottom,
+        }
+    }
+}
+
+/// Fold a binary operation, which wraps around on overflow.
+///
+/// Return `None` for a division by zero.
+fn fold_binary(op: BinaryOp, lhs: i32, rhs: i32) -> Option<i32> {
+    Some(match op {
+        BinaryOp::NotEq => (lhs != rhs) as i32,
+        BinaryOp::Eq => (lhs == rhs) as i32,
+        BinaryOp::Gt => (lhs > rhs) as i32,
+        BinaryOp::Lt => (lhs < rhs) as i32,
+        BinaryOp::Ge => (lhs >= rhs) as i32,
+        BinaryOp::Le => (lhs <= rhs) as i32,
+        BinaryOp::Add => lhs.wrapping_add(rhs),
+        BinaryOp::Sub => lhs.wrapping_sub(rhs),
+        BinaryOp::Mul => lhs.wrapping_mul(rhs),
+        BinaryOp::Div | BinaryOp::Mod if rhs == 0 => return None,
+        BinaryOp::Div => lhs.wrapping_div(rhs),
+        BinaryOp::Mod => lhs.wrapping_rem(rhs),
+        BinaryOp::And => lhs & rhs,
+        BinaryOp::Or => lhs | rhs,
+        BinaryOp::Xor => lhs ^ rhs,
+        BinaryOp::Shl => lhs.wrapping_shl(rhs as u32),
+        BinaryOp::Shr => (lhs as u32).wrapping_shr(rhs as u32) as i32,
+        BinaryOp::Sar => lhs.wrapping_shr(rhs as u32),
+    })
+}
+
+/// The state of the propagation.
+struct Solver {
+    /// The lattice of each `binary` and basic block parameter, the other values are never constants.
+    lattice: HashMap<Value, Lattice>,
+    /// The instructions using each value, computed from the layout.
+    users: HashMap<Value, Vec<Value>>,
+    /// The edges (the source, and the index of the target among its successors) into each basic block.
+    in_edges: HashMap<BasicBlock, Vec<(BasicBlock, usize)>>,
+    executable_bbs: HashSet<BasicBlock>,
+    executable_edges: HashSet<(BasicBlock, usize)>,
+    /// The edges that just became executable.
+    flow_work: Vec<(BasicBlock, usize)>,
+    /// The values whose lattice just changed.
+    ssa_work: Vec<Value>,
+}
+
+impl Solver {
+    fn new(data: &FunctionData) -> Self {
+        let mut lattice = HashMap::new();
+        let mut users: HashMap<Value, Vec<Value>> = HashMap::new();
+        let mut in_edges: HashMap<BasicBlock, Vec<(BasicBlock, usize)>> = HashMap::new();
+        for (&bb, node) in data.layout().bbs() {
+            for &param in data.dfg().bb(bb).params() {
+                lattice.insert(param, Lattice::Top);
+            }
+            for &inst in node.insts().keys() {
+                let kind = data.dfg().value(inst).kind();
+                if matches!(kind, ValueKind::Binary(_)) {
+                    lattice.insert(inst, Lattice::Top);
+                }
+                for v in kind.value_uses() {
+                    users.entry(v).or_default().push(inst);
+                }
+            }
+            if let Some(&term) = node.insts().back_key() {
+                for (i, target) in successors(data.dfg(), term).into_iter().enumerate() {
+                    in_edges.entry(target).or_default().push((bb, i));
+                }
+            }
+        }
+        Self {
+            lattice,
+            users,
+            in_edges,
+            executable_bbs: HashSet::new(),
+            executable_edges: HashSet::new(),
+            flow_work: Vec::new(),
+            ssa_work: Vec::new(),
+        }
+    }
+
+    /// Get the lattice of a value, which is an operand or a basic block argument.
+    fn value_of(&self, data: &FunctionData, v: Value) -> Lattice {
+        if let Some(&l) = self.lattice.get(&v) {
+            return l;
+        }
+        // the global values are not in the data flow graph of the function
+        match data.dfg().values().get(&v).map(|d| d.kind()) {
+            Some(ValueKind::Integer(i)) => Lattice::Const(i.value()),
+            _ => Lattice::Bottom,
+        }
+    }
+
+    fn update(&mut self, v: Value, new: Lattice) {
+        let old = self.lattice[&v];
+        let new = old.meet(new);
+        if new != old {
+            self.lattice.insert(v, new);
+            self.ssa_work.push(v);
+        }
+    }
+
+    /// Run the propagation from the entry until nothing changes.
+    fn solve(&mut self, data: &FunctionData, entry: BasicBlock) {
+        self.executable_bbs.insert(entry);
+        self.visit_block(data, entry);
+        loop {
+            if let Some((bb, i)) = self.flow_work.pop() {
+                let term = *data
+                    .layout()
+                    .bbs()
+                    .node(&bb)
+                    .unwrap()
+                    .insts()
+                    .back_key()
+                    .unwrap();
+                let target = successors(data.dfg(), term)[i];
+                if self.executable_bbs.insert(target) {
+                    self.visit_block(data, target);
+                } else {
+                    self.visit_params(data, target);
+                }
+            } else if let Some(v) = self.ssa_work.pop() {
+                for user in self.users.get(&v).cloned().unwrap_or_default() {
+                    let bb = data.layout().parent_bb(user).unwrap();
+                    if self.executable_bbs.contains(&bb) {
+                        self.visit_inst(data, bb, user);
+                    }
+                }
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn visit_block(&mut self, data: &FunctionData, bb: BasicBlock) {
+        self.visit_params(data, bb);
+        let insts: Vec<Value> = data
+            .layout()
+            .bbs()
+            .node(&bb)
+            .unwrap()
+            .insts()
+            .keys()
+            .copied()
+            .collect();
+        for inst in insts {
+            self.visit_inst(data, bb, inst);
+        }
+    }
+
+    /// Meet the arguments of the executable edges into the basic block.
+    fn visit_params(&mut self, data: &FunctionData, bb: BasicBlock) {
+        let params = data.dfg().bb(bb).params().to_vec();
+        for (j, &param) in params.iter().enumerate() {
+            let mut value = Lattice::Top;
+            for &(pred, i) in self.in_edges.get(&bb).into_iter().flatten() {
+                if !self.executable_edges.contains(&(pred, i)) {
+                    continue;
+                }
+                let term = *data
+                    .layout()
+                    .bbs()
+                    .node(&pred)
+                    .unwrap()
+                    .insts()
+                    .back_key()
+                    .unwrap();
+                let arg = match data.dfg().value(term).kind() {
+                    ValueKind::Branch(br) if i == 0 => br.true_args()[j],
+                    ValueKind::Branch(br) => br.false_args()[j],
+                    ValueKind::Jump(jump) => jump.args()[j],
+                    _ => unreachable!("an edge starts from a branch or a jump"),
+                };
+                value = value.meet(self.value_of(data, arg));
+            }
+            self.update(param, value);
+        }
+    }
+
+    fn visit_inst(&mut self, data: &FunctionData, bb: BasicBlock, inst: Value) {
+        match data.dfg().value(inst).kind() {
+            ValueKind::Binary(bin) => {
+                let value = match (
+                    self.value_of(data, bin.lhs()),
+                    self.value_of(data, bin.rhs()),
+                ) {
+                    (Lattice::Bottom, _) | (_, Lattice::Bottom) => Lattice::Bottom,
+                    (Lattice::Const(l), Lattice::Const(r)) => {
+                        fold_binary(bin.op(), l, r).map_or(Lattice::Bottom, Lattice::Const)
+                    }
+                    _ => Lattice::Top,
+                };
+                self.update(inst, value);
+            }
+            ValueKind::Branch(br) => match self.value_of(data, br.cond()) {
+                Lattice::Top => {}
+                Lattice::Const(c) => self.mark_edge(bb, if c != 0 { 0 } else { 1 }),
+                Lattice::Bottom => {
+                    self.mark_edge(bb, 0);
+                    self.mark_edge(bb, 1);
+                }
+            },
+            ValueKind::Jump(_) => self.mark_edge(bb, 0),
+            _ => {}
+        }
+    }
+
+    /// Mark the edge executable, or re-evaluate the parameters of the target if it already is,
+    /// since the arguments may have changed.
+    fn mark_edge(&mut self, bb: BasicBlock, i: usize) {
+        // the parameters are re-evaluated when the edge is popped
+        if self.executable_edges.insert((bb, i)) || !self.flow_work.contains(&(bb, i)) {
+            self.flow_work.push((bb, i));
+        }
+    }
+
+    /// Replace the constants by integers, fold the branches on constant conditions,
+    /// and remove the folded `binary`s.
+    fn rewrite(self, data: &mut FunctionData) {
+        // the branches of the executable basic blocks
+        let bbs: Vec<BasicBlock> = data.layout().bbs().keys().copied().collect();
+        for &bb in bbs.iter().filter(|bb| self.executable_bbs.contains(bb)) {
+            let term = *data
+                .layout()
+                .bbs()
+                .node(&bb)
+                .unwrap()
+                .insts()
+                .back_key()
+                .unwrap();
+            let ValueKind::Branch(br) = data.dfg().value(term).kind().clone() else {
+                continue;
+            };
+            let Lattice::Const(c) = self.value_of(data, br.cond()) else {
+                continue;
+            };
+            let (target, args) = if c != 0 {
+                (br.true_bb(), br.true_args().to_vec())
+            } else {
+                (br.false_bb(), br.false_args().to_vec())
+            };
+            data.dfg_mut()
+                .replace_value_with(term)
+                .jump_with_args(target, args);
+        }
+
+        let mut constants = HashMap::new();
+        for (&v, &l) in self.lattice.iter() {
+            if let Lattice::Const(c) = l {
+                constants.insert(v, data.dfg_mut().new_value().integer(c));
+            }
+        }
+        if constants.is_empty() {
+            return;
+        }
+        let insts: Vec<Value> = data
+            .layout()
+            .bbs()
+            .nodes()
+            .flat_map(|node| node.insts().keys().copied())
+            .collect();
+        for &inst in insts.iter() {
+            replace_operands(data.dfg_mut(), inst, |v| {
+                constants.get(&v).copied().unwrap_or(v)
+            });
+        }
+        let counts = use_counts(data);
+        let mut folded = Vec::new();
+        for inst in insts {
+            if constants.contains_key(&inst) && !counts.contains_key(&inst) {
+                let bb = data.layout().parent_bb(inst).unwrap();
+                data.layout_mut().bb_mut(bb).insts_mut().remove(&inst);
+                folded.push(inst);
+            }
+        }
+        remove_values(data, folded);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::opt::run_passes;
+
+    /// Run `mem2reg` and `sccp`, and check that the behavior is kept.
+    fn sccp(input: &str) -> String {
+        run_passes(input, &["mem2reg", "sccp"]).text
+    }
+
+    #[test]
+    fn sccp_fold_test() {
+        let text = sccp("int main() { int n = 10; int a = n * 4; return a - 1; }");
+        assert!(!text.contains("mul") && !text.contains("sub"), "{}", text);
+        assert!(text.contains("ret 39"), "{}", text);
+        // a division by zero is left to the runtime
+        let text = sccp("int g;\nint main() { int z = 0; if (g) return 1 / z; return 0; }");
+        assert!(text.contains("div 1, 0"), "{}", text);
+    }
+
+    #[test]
+    fn sccp_branch_test() {
+        let text = sccp("int main() { int x = 1; if (x > 0) return 2; return 3; }");
+        assert!(!text.contains(" br "), "{}", text);
+        // `c` stays 5 around the loop, since it's only assigned 5 on the executable paths
+        let run = run_passes(
+            "int main() { int i = 0, c = 5; while (i < c) { if (c != 5) c = 1; i = i + 1; } return c; }",
+            &["mem2reg", "sccp"],
+        );
+        assert!(run.text.contains("ret 5"), "{}", run.text);
+        // the `if` is folded, so every iteration runs fewer instructions
+        assert!(run.after < run.before, "{} {}", run.before, run.after);
+    }
+}
@@ -0,0 +1,216 @@
+use super::utils::{redirect_branch, replace_operands};
+use koopa::ir::builder::LocalInstBuilder;
+use koopa::ir::*;
+use koopa::opt::FunctionPass;
+use std::collections::HashMap;
+
+/// Simplify the control flow graph, until nothing changes:
+/// fold a `br` whose two edges are the same into a `jump`,
+/// thread the jumps through basic blocks that only jump to another one and remove them,
+/// and merge a basic block into its only predecessor if the predecessor jumps to it.
+pub struct SimplifyCfg;
+
+impl FunctionPass for SimplifyCfg {
+    fn run_on(&mut self, _func: Function, data: &mut FunctionData) {
+        loop {
+            let mut changed = fold_branches(data);
+            changed |= thread_jumps(data);
+            changed |= merge_bbs(data);
+            if !changed {
+                break;
+            }
+        }
+    }
+}
+
+/// Get the terminator of the basic block.
+fn terminator(data: &FunctionData, bb: BasicBlock) -> Option<Value> {
+    data.layout().bbs().node(&bb)?.insts().back_key().copied()
+}
+
+/// Get the branches and jumps to the basic block,
+/// or `None` if one of them is not in the layout.
+fn predecessors(data: &FunctionData, bb: BasicBlock) -> Option<Vec<Value>> {
+    let users: Vec<Value> = data.dfg().bb(bb).used_by().iter().copied().collect();
+    users
+        .iter()
+        .all(|&term| data.layout().parent_bb(term).is_some())
+        .then_some(users)
+}
+
+/// Fold every `br` whose two edges have the same target and arguments into a `jump`.
+fn fold_branches(data: &mut FunctionData) -> bool {
+    let mut changed = false;
+    let bbs: Vec<BasicBlock> = data.layout().bbs().keys().copied().collect();
+    for bb in bbs {
+        let Some(term) = terminator(data, bb) else {
+            continue;
+        };
+        let ValueKind::Branch(br) = data.dfg().value(term).kind() else {
+            continue;
+        };
+        if br.true_bb() == br.false_bb() && br.true_args() == br.false_args() {
+            let (target, args) = (br.true_bb(), br.true_args().to_vec());
+            data.dfg_mut()
+                .replace_value_with(term)
+                .jump_with_args(target, args);
+            changed = true;
+        }
+    }
+    changed
+}
+
+/// Redirect the edges to every basic block (except the entry) that only jumps to another one,
+/// to the target of its jump, and remove it.
+///
+/// The arguments of the jump may be the parameters of the forwarding basic block,
+/// which are replaced by the arguments of each edge,
+/// so the parameters must not be used anywhere else.
+/// The other arguments dominate the forwarding basic block, so they also dominate its predecessors.
+fn thread_jumps(data: &mut FunctionData) -> bool {
+    let mut changed = false;
+    let entry = data.layout().entry_bb().unwrap();
+    let bbs: Vec<BasicBlock> = data.layout().bbs().keys().copied().collect();
+    for bb in bbs {
+        let node = data.layout().bbs().node(&bb).unwrap();
+        if bb == entry || node.insts().len() != 1 {
+            continue;
+        }
+        let jump = *node.insts().front_key().unwrap();
+        let ValueKind::Jump(j) = data.dfg().value(jump).kind() else {
+            continue;
+        };
+        let (target, jump_args) = (j.target(), j.args().to_vec());
+        let params = data.dfg().bb(bb).params().to_vec();
+        let params_only_in_jump = params.iter().all(|&p| {
+            let users = data.dfg().value(p).used_by();
+            users.iter().all(|&u| u == jump)
+        });
+        if target == bb || !params_only_in_jump {
+            continue;
+        }
+        let Some(preds) = predecessors(data, bb) else {
+            continue;
+        };
+
+        for pred in preds {
+            redirect_branch(data.dfg_mut(), pred, bb, target, |args| {
+                let map: HashMap<Value, Value> =
+                    params.iter().copied().zip(args.iter().copied()).collect();
+                *args = jump_args.iter().map(|a| *map.get(a).unwrap_or(a)).collect();
+            });
+        }
+
+        data.layout_mut().bb_mut(bb).insts_mut().pop_back();
+        data.layout_mut().bbs_mut().remove(&bb);
+        data.dfg_mut().remove_value(jump);
+        data.dfg_mut().remove_bb(bb);
+        changed = true;
+    }
+    changed
+}
+
+/// Merge every basic block (except the entry) into its only predecessor, if the predecessor jumps to it.
+///
+/// The parameters of the merged basic block are replaced by the arguments of the jump.
+fn merge_bbs(data: &mut FunctionData) -> bool {
+    let mut changed = false;
+    let entry = data.layout().entry_bb().unwrap();
+    let bbs: Vec<BasicBlock> = data.layout().bbs().keys().copied().collect();
+    for bb in bbs {
+        if bb == entry || !data.layout().bbs().contains_key(&bb) {
+            continue;
+        }
+        let Some(preds) = predecessors(data, bb) else {
+            continue;
+        };
+        let [jump] = preds[..] else {
+            continue;
+        };
+        let ValueKind::Jump(j) = data.dfg().value(jump).kind() else {
+            continue;
+        };
+        let pred = data.layout().parent_bb(jump).unwrap();
+        if pred == bb {
+            continue;
+        }
+
+        let params = data.dfg().bb(bb).params().to_vec();
+        let map: HashMap<Value, Value> = params
+            .iter()
+            .copied()
+            .zip(j.args().iter().copied())
+            .collect();
+        for &param in params.iter() {
+            let users: Vec<Value> = data.dfg().value(param).used_by().iter().copied().collect();
+            for user in users {
+                replace_operands(data.dfg_mut(), user, |v| *map.get(&v).unwrap_or(&v));
+            }
+        }
+
+        data.layout_mut().bb_mut(pred).insts_mut().remove(&jump);
+        let insts: Vec<Value> = data
+            .layout()
+            .bbs()
+            .node(&bb)
+            .unwrap()
+            .insts()
+            .keys()
+            .copied()
+            .collect();
+        for inst in insts {
+            data.layout_mut().bb_mut(bb).insts_mut().remove(&inst);
+            data.layout_mut()
+                .bb_mut(pred)
+                .insts_mut()
+                .push_key_back(inst)
+                .unwrap();
+        }
+        data.layout_mut().bbs_mut().remove(&bb);
+        data.dfg_mut().remove_value(jump);
+        data.dfg_mut().remove_bb(bb);
+        changed = true;
+    }
+    changed
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::opt::run_passes;
+
+    #[test]
+    fn simplify_cfg_thread_test() {
+        // the `else` block of an `if` without `else` only jumps to the end
+        let text = run_passes(
+            "int f(int a) { if (a) { putint(1); } putint(2); return 0; }\nint main() { return f(1); }",
+            &["simplify-cfg"],
+        )
+        .text;
+        assert!(!text.contains("%if_else"), "{}", text);
+        // the parameter of the end block is passed through the forwarding block
+        let text = run_passes(
+            "int f(int a) { int b = 0; if (a) { b = 1; } else { b = 2; } return b; }\nint main() { return f(0); }",
+            &["mem2reg", "simplify-cfg"],
+        )
+        .text;
+        assert!(!text.contains("jump"), "{}", text);
+    }
+
+    #[test]
+    fn simplify_cfg_merge_test() {
+        let text = run_passes(
+            "int main() { int s = 0; { s = 1; } while (s < 10) s = s * 2; return s; }",
+            &["mem2reg", "simplify-cfg"],
+        )
+        .text;
+        // only the entry and the blocks of the loop are left
+        assert_eq!(text.matches("\n%").count(), 4, "{}", text);
+        // both edges of the `br` go to the end block after the jumps are threaded
+        let text = run_passes(
+            "int f(int x) { if (x) { } else { } return x; }\nint main() { return f(2); }",
+            &["simplify-cfg"],
+        )
+        .text;
+        assert!(!text.contains("br") && !text.contains("jump"), "{}", text);
+    }
+}
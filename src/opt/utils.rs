@@ -0,0 +1,207 @@
+use koopa::ir::builder::{BasicBlockBuilder, ValueBuilder};
+use koopa::ir::dfg::DataFlowGraph;
+use koopa::ir::{BasicBlock, BinaryOp, FunctionData, Type, Value, ValueKind};
+use std::collections::HashMap;
+
+/// Return whether the instruction ends a basic block.
+pub fn is_terminator(kind: &ValueKind) -> bool {
+    matches!(
+        kind,
+        ValueKind::Branch(_) | ValueKind::Jump(_) | ValueKind::Return(_)
+    )
+}
+
+/// Return whether the division may trap, i.e. its divisor is not a nonzero constant.
+pub fn may_trap(data: &FunctionData, inst: Value) -> bool {
+    let ValueKind::Binary(bin) = data.dfg().value(inst).kind() else {
+        return false;
+    };
+    if !matches!(bin.op(), BinaryOp::Div | BinaryOp::Mod) {
+        return false;
+    }
+    // a global value is not in the data flow graph of the function
+    !matches!(
+        data.dfg().values().get(&bin.rhs()).map(|d| d.kind()),
+        Some(ValueKind::Integer(i)) if i.value() != 0
+    )
+}
+
+/// Get the branch targets of an instruction, in order (empty if it's not a branch or jump).
+pub fn successors(dfg: &DataFlowGraph, inst: Value) -> Vec<BasicBlock> {
+    match dfg.value(inst).kind() {
+        ValueKind::Branch(br) => vec![br.true_bb(), br.false_bb()],
+        ValueKind::Jump(jump) => vec![jump.target()],
+        _ => Vec::new(),
+    }
+}
+
+/// Replace every operand `v` of an instruction by `f(v)`, including the arguments of branch targets.
+///
+/// The instruction keeps its handle, but `DataFlowGraph::replace_value_with` clears its own `used_by` set,
+/// since the new `ValueData` is a clone, which never has users.
+/// The same holds for the other helpers that rewrite an instruction.
+/// The `used_by` sets of the operands, of the basic block parameters and of the basic blocks stay right,
+/// but the uses of an instruction must be counted from the layout by `use_counts`.
+pub fn replace_operands(dfg: &mut DataFlowGraph, inst: Value, mut f: impl FnMut(Value) -> Value) {
+    let mut data = dfg.value(inst).clone();
+    let mut changed = false;
+    let mut replace = |v: &mut Value| {
+        let new = f(*v);
+        if new != *v {
+            *v = new;
+            changed = true;
+        }
+    };
+    match data.kind_mut() {
+        ValueKind::Load(load) => replace(load.src_mut()),
+        ValueKind::Store(store) => {
+            replace(store.value_mut());
+            replace(store.dest_mut());
+        }
+        ValueKind::GetPtr(gp) => {
+            replace(gp.src_mut());
+            replace(gp.index_mut());
+        }
+        ValueKind::GetElemPtr(gep) => {
+            replace(gep.src_mut());
+            replace(gep.index_mut());
+        }
+        ValueKind::Binary(bin) => {
+            replace(bin.lhs_mut());
+            replace(bin.rhs_mut());
+        }
+        ValueKind::Branch(br) => {
+            replace(br.cond_mut());
+            br.true_args_mut().iter_mut().for_each(&mut replace);
+            br.false_args_mut().iter_mut().for_each(&mut replace);
+        }
+        ValueKind::Jump(jump) => jump.args_mut().iter_mut().for_each(&mut replace),
+        ValueKind::Call(call) => call.args_mut().iter_mut().for_each(&mut replace),
+        ValueKind::Return(ret) => {
+            if let Some(v) = ret.value_mut() {
+                replace(v);
+            }
+        }
+        _ => {}
+    }
+    if changed {
+        dfg.replace_value_with(inst).raw(data);
+    }
+}
+
+/// Append arguments to every edge from a branch or jump to the target.
+pub fn push_branch_args(dfg: &mut DataFlowGraph, inst: Value, target: BasicBlock, args: &[Value]) {
+    let mut data = dfg.value(inst).clone();
+    match data.kind_mut() {
+        ValueKind::Branch(br) => {
+            if br.true_bb() == target {
+                br.true_args_mut().extend_from_slice(args);
+            }
+            if br.false_bb() == target {
+                br.false_args_mut().extend_from_slice(args);
+            }
+        }
+        ValueKind::Jump(jump) if jump.target() == target => jump.args_mut().extend_from_slice(args),
+        _ => return,
+    }
+    dfg.replace_value_with(inst).raw(data);
+}
+
+/// Redirect every edge from a branch or jump to `from` to the basic block `to`,
+/// where `f` changes the arguments passed on each edge.
+pub fn redirect_branch(
+    dfg: &mut DataFlowGraph,
+    inst: Value,
+    from: BasicBlock,
+    to: BasicBlock,
+    mut f: impl FnMut(&mut Vec<Value>),
+) {
+    let mut data = dfg.value(inst).clone();
+    match data.kind_mut() {
+        ValueKind::Branch(br) => {
+            if br.true_bb() == from {
+                *br.true_bb_mut() = to;
+                f(br.true_args_mut());
+            }
+            if br.false_bb() == from {
+                *br.false_bb_mut() = to;
+                f(br.false_args_mut());
+            }
+        }
+        ValueKind::Jump(jump) if jump.target() == from => {
+            *jump.target_mut() = to;
+            f(jump.args_mut());
+        }
+        _ => return,
+    }
+    dfg.replace_value_with(inst).raw(data);
+}
+
+/// Append a parameter to an existing basic block.
+pub fn push_bb_param(
+    dfg: &mut DataFlowGraph,
+    bb: BasicBlock,
+    ty: Type,
+    name: Option<String>,
+) -> Value {
+    // the builder only creates parameters along with a new basic block,
+    // so build a temporary one whose last parameter has the right index and move that parameter
+    let index = dfg.bb(bb).params().len();
+    let tmp = dfg
+        .new_bb()
+        .basic_block_with_params(None, vec![ty; index + 1]);
+    let param = dfg.bb_mut(tmp).params_mut().pop().unwrap();
+    dfg.remove_bb(tmp);
+    dfg.bb_mut(bb).params_mut().push(param);
+    dfg.set_value_name(param, name);
+    param
+}
+
+/// Count the uses of each value by the instructions in the layout,
+/// including the arguments passed to basic blocks.
+///
+/// Unlike `used_by`, the counts are right for the instructions rewritten by `replace_operands`.
+pub fn use_counts(data: &FunctionData) -> HashMap<Value, usize> {
+    let mut counts = HashMap::new();
+    for node in data.layout().bbs().nodes() {
+        for &inst in node.insts().keys() {
+            for v in data.dfg().value(inst).kind().value_uses() {
+                *counts.entry(v).or_insert(0) += 1;
+            }
+        }
+    }
+    counts
+}
+
+/// Remove values (instructions already taken out of the layout, or basic block parameters) from the data flow graph,
+/// removing the users before the values they use.
+///
+/// The uses are counted from the layout and from the values to remove (see `use_counts`),
+/// and a value still used by an instruction in the layout is kept.
+/// A value whose `used_by` is not empty is also kept, since `DataFlowGraph::remove_value` would panic,
+/// which only happens if one of its users was taken out of the layout but never removed.
+pub fn remove_values(data: &mut FunctionData, mut values: Vec<Value>) {
+    let mut counts = use_counts(data);
+    for &v in values.iter() {
+        for u in data.dfg().value(v).kind().value_uses() {
+            *counts.entry(u).or_insert(0) += 1;
+        }
+    }
+    loop {
+        let before = values.len();
+        values.retain(|&v| {
+            let dfg = data.dfg_mut();
+            if counts.get(&v).copied().unwrap_or(0) > 0 || !dfg.value(v).used_by().is_empty() {
+                return true;
+            }
+            for u in dfg.value(v).kind().value_uses() {
+                *counts.get_mut(&u).unwrap() -= 1;
+            }
+            dfg.remove_value(v);
+            false
+        });
+        if values.is_empty() || values.len() == before {
+            break;
+        }
+    }
+}
@@ -0,0 +1,204 @@
+use super::utils::is_terminator;
+use crate::diagnostic::Diagnostic;
+use koopa::ir::*;
+
+/// Check the structural invariants of a Koopa program.
+///
+/// The checks are the ones every pass relies on:
+/// 1. every basic block in a function definition is non-empty and ends with exactly one terminator;
+/// 2. every branch target is a basic block in the layout, and gets as many arguments as its parameters;
+/// 3. every operand is a global value or a value of the same function,
+///    and an operand instruction is placed in some basic block;
+/// 4. every call passes as many arguments as the callee's parameters;
+/// 5. a `ret` carries a value if and only if the function returns one.
+///
+/// Dominance of definitions over uses is not checked.
+pub fn verify_program(program: &Program) -> Result<(), Diagnostic> {
+    for &func in program.func_layout() {
+        verify_function(program, program.func(func))?;
+    }
+    Ok(())
+}
+
+fn verify_function(program: &Program, data: &FunctionData) -> Result<(), Diagnostic> {
+    // a declaration has no body to verify
+    let Some(entry) = data.layout().entry_bb() else {
+        return Ok(());
+    };
+    let error = |message: String| {
+        Err(Diagnostic::error(format!(
+            "invalid Koopa IR in function `{}`: {}",
+            data.name(),
+            message
+        )))
+    };
+    let bb_name = |bb: BasicBlock| {
+        data.dfg()
+            .bbs()
+            .get(&bb)
+            .and_then(|bbd| bbd.name().clone())
+            .unwrap_or_else(|| String::from("%<unnamed>"))
+    };
+
+    if !data.dfg().bb(entry).params().is_empty() {
+        return error(String::from("the entry basic block has parameters"));
+    }
+
+    let TypeKind::Function(_, ret_ty) = data.ty().kind() else {
+        return error(String::from(
+            "the type of the function is not a function type",
+        ));
+    };
+
+    for (&bb, node) in data.layout().bbs() {
+        let Some(&last) = node.insts().back_key() else {
+            return error(format!("basic block `{}` is empty", bb_name(bb)));
+        };
+
+        for (&inst, _) in node.insts() {
+            let Some(inst_data) = data.dfg().values().get(&inst) else {
+                return error(format!(
+                    "basic block `{}` contains a missing instruction",
+                    bb_name(bb)
+                ));
+            };
+            let kind = inst_data.kind();
+            if !kind.is_local_inst() {
+                return error(format!(
+                    "basic block `{}` contains a value that is not an instruction",
+                    bb_name(bb)
+                ));
+            }
+
+            let is_terminator = is_terminator(kind);
+            if is_terminator && inst != last {
+                return error(format!(
+                    "basic block `{}` has a terminator in the middle",
+                    bb_name(bb)
+                ));
+            }
+            if !is_terminator && inst == last {
+                return error(format!(
+                    "basic block `{}` does not end with a terminator",
+                    bb_name(bb)
+                ));
+            }
+
+            // operands
+            for v in kind.value_uses() {
+                if program.borrow_values().contains_key(&v) {
+                    continue;
+                }
+                let Some(v_data) = data.dfg().values().get(&v) else {
+                    return error(format!(
+                        "basic block `{}` uses a value that does not exist",
+                        bb_name(bb)
+                    ));
+                };
+                if v_data.kind().is_local_inst() && data.layout().parent_bb(v).is_none() {
+                    return error(format!(
+                        "basic block `{}` uses an instruction that is not in any basic block",
+                        bb_name(bb)
+                    ));
+                }
+            }
+
+            // branch targets
+            let targets: Vec<(BasicBlock, usize)> = match kind {
+                ValueKind::Jump(jump) => vec![(jump.target(), jump.args().len())],
+                ValueKind::Branch(br) => vec![
+                    (br.true_bb(), br.true_args().len()),
+                    (br.false_bb(), br.false_args().len()),
+                ],
+                _ => Vec::new(),
+            };
+            for (target, n_args) in targets {
+                if !data.layout().bbs().contains_key(&target) {
+                    return error(format!(
+                        "basic block `{}` jumps to a basic block that is not in the layout",
+                        bb_name(bb)
+                    ));
+                }
+                if data.dfg().bb(target).params().len() != n_args {
+                    return error(format!(
+                        "basic block `{}` passes {} argument(s) to `{}`, which has {} parameter(s)",
+                        bb_name(bb),
+                        n_args,
+                        bb_name(target),
+                        data.dfg().bb(target).params().len()
+                    ));
+                }
+            }
+
+            match kind {
+                ValueKind::Call(call) => {
+                    let Some(callee) = program.funcs().get(&call.callee()) else {
+                        return error(format!(
+                            "basic block `{}` calls a function that does not exist",
+                            bb_name(bb)
+                        ));
+                    };
+                    let TypeKind::Function(params_ty, _) = callee.ty().kind() else {
+                        return error(format!("`{}` is not a function", callee.name()));
+                    };
+                    if params_ty.len() != call.args().len() {
+                        return error(format!(
+                            "basic block `{}` passes {} argument(s) to `{}`, which takes {}",
+                            bb_name(bb),
+                            call.args().len(),
+                            callee.name(),
+                            params_ty.len()
+                        ));
+                    }
+                }
+                ValueKind::Return(ret) if ret.value().is_some() == ret_ty.is_unit() => {
+                    return error(format!(
+                        "basic block `{}` returns a value that does not match the return type",
+                        bb_name(bb)
+                    ));
+                }
+                _ => {}
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast_generate::parse_sysy_to_ast;
+    use crate::ir_generate::parse_ast_to_koopa_program;
+
+    fn verify_test(input: &str) {
+        let input_content = std::fs::read_to_string(input).unwrap();
+        let ast = parse_sysy_to_ast(&input_content).unwrap();
+        let program = parse_ast_to_koopa_program(&ast).unwrap();
+        verify_program(&program).unwrap();
+    }
+
+    #[test]
+    fn verify_generated_test() {
+        verify_test("tests/sysy_scripts/lv6-2.c");
+        verify_test("tests/sysy_scripts/lv7-2.c");
+        verify_test("tests/sysy_scripts/lv9-3.c");
+    }
+
+    #[test]
+    fn verify_missing_terminator_test() {
+        let ast = parse_sysy_to_ast("int main() { int a = 1; return a; }").unwrap();
+        let mut program = parse_ast_to_koopa_program(&ast).unwrap();
+        let main = program.func_layout()[program.func_layout().len() - 1];
+        let data = program.func_mut(main);
+        let entry = data.layout().entry_bb().unwrap();
+        data.layout_mut().bb_mut(entry).insts_mut().pop_back();
+
+        let diag = verify_program(&program).unwrap_err();
+        assert!(
+            diag.message.contains("does not end with a terminator"),
+            "{}",
+            diag
+        );
+    }
+}
@@ -0,0 +1,625 @@
+use crate::ast_generate::ast::*;
+use crate::ast_generate::parse_sysy_to_ast;
+use crate::fuzz::{run_at, Level};
+use crate::opt::OptOptions;
+use crate::{compile, semantic, Mode};
+use std::collections::HashMap;
+use std::panic::{self, AssertUnwindSafe};
+
+/// The property a reduced program must keep.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Predicate {
+    /// The compiler panics in the mode.
+    Panic(Mode),
+    /// The compiler fails in the mode, with the `RunError` of the name (e.g. `KoopaProgram2RiscvError`).
+    Error(Mode, String),
+    /// The program is valid and runs well at the first level, but runs differently or fails at the second level.
+    ///
+    /// The first level should be `Level::Ast`, which rejects the programs with undefined behavior.
+    Differ(Level, Level),
+}
+
+impl Predicate {
+    /// Check whether the SysY program satisfies the predicate, with the standard input for running it.
+    pub fn test(&self, source: &str, stdin: &str) -> bool {
+        match self {
+            Self::Panic(mode) => {
+                catch_silently(|| compile(*mode, source, &OptOptions::default())).is_none()
+            }
+            Self::Error(mode, name) => matches!(
+                catch_silently(|| compile(*mode, source, &OptOptions::default())),
+                Some(Err(e)) if e.name() == name
+            ),
+            Self::Differ(reference, level) => {
+                let Ok(ast) = parse_sysy_to_ast(source) else {
+                    return false;
+                };
+                if semantic::check_ast(&ast).is_err() {
+                    return false;
+                }
+                let Some(Ok(expected)) = catch_silently(|| run_at(*reference, &ast, stdin)) else {
+                    return false;
+                };
+                match catch_silently(|| run_at(*level, &ast, stdin)) {
+                    Some(Ok(outcome)) => outcome != expected,
+                    Some(Err(_)) | None => true,
+                }
+            }
+        }
+    }
+}
+
+/// Run the function, and return `None` if it panics, without printing the panic message.
+fn catch_silently<T>(f: impl FnOnce() -> T) -> Option<T> {
+    let hook = panic::take_hook();
+    panic::set_hook(Box::new(|_| {}));
+    let result = panic::catch_unwind(AssertUnwindSafe(f)).ok();
+    panic::set_hook(hook);
+    result
+}
+
+/// A kind of change made to the program, from the coarsest to the finest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Change {
+    /// Drop a function or a global declaration.
+    RemoveItem,
+    /// Delete a statement or a declaration from a block.
+    RemoveBlockItem,
+    /// Drop a definition from a declaration of several ones.
+    RemoveDef,
+    /// Replace `if` or `while` with its body, or drop the `else` branch.
+    UnwrapStmt,
+    /// Drop the initializer of a variable, or an element of an initializer list.
+    RemoveInit,
+    /// Replace a name of a constant with its value.
+    InlineConst,
+    /// Replace an operation with one of its operands, or remove the parentheses.
+    TakeOperand,
+    /// Replace an expression with `0`.
+    Zero,
+}
+
+const CHANGES: [Change; 8] = [
+    Change::RemoveItem,
+    Change::RemoveBlockItem,
+    Change::RemoveDef,
+    Change::UnwrapStmt,
+    Change::RemoveInit,
+    Change::InlineConst,
+    Change::TakeOperand,
+    Change::Zero,
+];
+
+/// Reduce the program to a smaller one that still satisfies the predicate.
+///
+/// Every kind of change is tried at every place of the AST, and a candidate is kept
+/// if it's smaller and satisfies the predicate, until no change is kept.
+/// The size of a program is measured by its tokens, then its identifiers, then its length,
+/// so that the reduction always terminates.
+///
+/// # Errors
+/// An error is returned if the program cannot be parsed, or it doesn't satisfy the predicate at first.
+pub fn reduce(source: &str, stdin: &str, predicate: &Predicate) -> Result<CompUnit, String> {
+    let mut ast = parse_sysy_to_ast(source).map_err(|diags| diags[0].message.clone())?;
+    if !predicate.test(&ast.to_string(), stdin) {
+        return Err(String::from("the program doesn't satisfy the predicate"));
+    }
+    let mut size = measure(&ast.to_string());
+    loop {
+        let mut changed = false;
+        for change in CHANGES {
+            let mut target = 0;
+            loop {
+                let mut candidate = ast.clone();
+                let mut editor = Editor::new(change, target, &ast);
+                editor.comp_unit(&mut candidate);
+                if !editor.done {
+                    break;
+                }
+                let text = candidate.to_string();
+                let candidate_size = measure(&text);
+                if candidate_size < size && predicate.test(&text, stdin) {
+                    // the place is occupied by the next candidate now, so the target stays
+                    ast = candidate;
+                    size = candidate_size;
+                    changed = true;
+                } else {
+                    target += 1;
+                }
+            }
+        }
+        if !changed {
+            return Ok(ast);
+        }
+    }
+}
+
+/// The size of the program: the numbers of tokens and identifiers, and the length.
+fn measure(text: &str) -> (usize, usize, usize) {
+    let (mut tokens, mut idents) = (0, 0);
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c.is_whitespace() {
+            continue;
+        }
+        tokens += 1;
+        if c.is_ascii_alphanumeric() || c == '_' {
+            if !c.is_ascii_digit() {
+                idents += 1;
+            }
+            while chars
+                .next_if(|c| c.is_ascii_alphanumeric() || *c == '_')
+                .is_some()
+            {}
+        }
+    }
+    (tokens, idents, text.len())
+}
+
+/// Make one change to the AST: the `target`-th place (counting from 0) where the change can be made.
+struct Editor {
+    change: Change,
+    target: usize,
+    /// The number of places visited.
+    count: usize,
+    done: bool,
+    /// The values of the scalar constants initialized by literals.
+    consts: HashMap<String, i64>,
+}
+
+impl Editor {
+    fn new(change: Change, target: usize, ast: &CompUnit) -> Self {
+        let mut consts = HashMap::new();
+        if change == Change::InlineConst {
+            collect_consts(ast, &mut consts);
+        }
+        Self {
+            change,
+            target,
+            count: 0,
+            done: false,
+            consts,
+        }
+    }
+
+    /// Visit a place for the change, and return whether to make the change here.
+    fn hit(&mut self, change: Change) -> bool {
+        if self.done || change != self.change {
+            return false;
+        }
+        self.count += 1;
+        self.done = self.count - 1 == self.target;
+        self.done
+    }
+
+    fn comp_unit(&mut self, ast: &mut CompUnit) {
+        for i in 0..ast.items.len() {
+            if self.hit(Change::RemoveItem) {
+                ast.items.remove(i);
+                return;
+            }
+        }
+        for item in ast.items.iter_mut() {
+            match item {
+                CompUnitItem::GlobalDecl(global_decl) => self.decl(&mut global_decl.decl),
+                CompUnitItem::FuncDef(func_def) => self.block(&mut func_def.block),
+                CompUnitItem::FuncDecl(_) => {}
+            }
+        }
+    }
+
+    fn block(&mut self, block: &mut Block) {
+        for i in 0..block.items.len() {
+            if self.hit(Change::RemoveBlockItem) {
+                block.items.remove(i);
+                return;
+            }
+        }
+        for item in block.items.iter_mut() {
+            match item {
+                BlockItem::Decl(decl) => self.decl(decl),
+                BlockItem::Stmt(stmt) => self.stmt(stmt),
+            }
+        }
+    }
+
+    fn decl(&mut self, decl: &mut Decl) {
+        let len = match decl {
+            Decl::Const(const_decl) => const_decl.defs.len(),
+            Decl::Var(var_decl) => var_decl.defs.len(),
+        };
+        for i in 0..len {
+            if len > 1 && self.hit(Change::RemoveDef) {
+                match decl {
+                    Decl::Const(const_decl) => {
+                        const_decl.defs.remove(i);
+                    }
+                    Decl::Var(var_decl) => {
+                        var_decl.defs.remove(i);
+                    }
+                }
+                return;
+            }
+        }
+        match decl {
+            Decl::Const(const_decl) => {
+                for def in const_decl.defs.iter_mut() {
+                    // the sizes of arrays are left alone, which must be positive
+                    self.const_init(&mut def.init);
+                }
+            }
+            Decl::Var(var_decl) => {
+                for def in var_decl.defs.iter_mut() {
+                    if def.init.is_some() && self.hit(Change::RemoveInit) {
+                        def.init = None;
+                        return;
+                    }
+                    if let Some(init) = &mut def.init {
+                        self.init(init);
+                    }
+                }
+            }
+        }
+    }
+
+    fn const_init(&mut self, init: &mut ConstInitVal) {
+        match init {
+            ConstInitVal::Exp(const_exp) => self.exp(&mut const_exp.exp),
+            ConstInitVal::Array(inits, _) => {
+                for i in 0..inits.len() {
+                    if self.hit(Change::RemoveInit) {
+                        inits.remove(i);
+                        return;
+                    }
+                }
+                inits.iter_mut().for_each(|init| self.const_init(init));
+            }
+        }
+    }
+
+    fn init(&mut self, init: &mut InitVal) {
+        match init {
+            InitVal::Exp(exp) => self.exp(exp),
+            InitVal::Array(inits, _) => {
+                for i in 0..inits.len() {
+                    if self.hit(Change::RemoveInit) {
+                        inits.remove(i);
+                        return;
+                    }
+                }
+                inits.iter_mut().for_each(|init| self.init(init));
+            }
+        }
+    }
+
+    fn stmt(&mut self, stmt: &mut Stmt) {
+        match &mut stmt.kind {
+            StmtKind::If(_, then, otherwise) => {
+                if self.hit(Change::UnwrapStmt) {
+                    stmt.kind = then.kind.clone();
+                    return;
+                }
+                if let Some(otherwise) = otherwise.take_if(|_| self.hit(Change::UnwrapStmt)) {
+                    stmt.kind = otherwise.kind;
+                    return;
+                }
+                if otherwise.is_some() && self.hit(Change::UnwrapStmt) {
+                    *otherwise = None;
+                    return;
+                }
+            }
+            StmtKind::While(_, body) if self.hit(Change::UnwrapStmt) => {
+                stmt.kind = body.kind.clone();
+                return;
+            }
+            _ => {}
+        }
+        match &mut stmt.kind {
+            StmtKind::Assign(lval, exp) => {
+                self.lval(lval);
+                self.exp(exp);
+            }
+            StmtKind::Exp(Some(exp)) | StmtKind::Return(Some(exp)) => self.exp(exp),
+            StmtKind::Block(block) => self.block(block),
+            StmtKind::If(cond, then, otherwise) => {
+                self.exp(cond);
+                self.stmt(then);
+                if let Some(otherwise) = otherwise {
+                    self.stmt(otherwise);
+                }
+            }
+            StmtKind::While(cond, body) => {
+                self.exp(cond);
+                self.stmt(body);
+            }
+            StmtKind::Exp(None) | StmtKind::Break | StmtKind::Continue | StmtKind::Return(None) => {
+            }
+        }
+    }
+
+    fn lval(&mut self, lval: &mut LVal) {
+        lval.idx.iter_mut().for_each(|exp| self.exp(exp));
+    }
+
+    fn exp(&mut self, exp: &mut Exp) {
+        if literal(exp) != Some(0) && self.hit(Change::Zero) {
+            exp.exp = LOrExp::from_unary(zero(exp.span));
+            return;
+        }
+        self.lor(&mut exp.exp);
+    }
+
+    fn lor(&mut self, exp: &mut LOrExp) {
+        if let LOrExp::LOrLAnd(exp1, exp2) = exp {
+            if self.hit(Change::TakeOperand) {
+                *exp = (**exp1).clone();
+                return;
+            }
+            if self.hit(Change::TakeOperand) {
+                *exp = LOrExp::LAnd(exp2.clone());
+                return;
+            }
+        }
+        match exp {
+            LOrExp::LAnd(exp) => self.land(exp),
+            LOrExp::LOrLAnd(exp1, exp2) => {
+                self.lor(exp1);
+                self.land(exp2);
+            }
+        }
+    }
+
+    fn land(&mut self, exp: &mut LAndExp) {
+        if let LAndExp::LAndEq(exp1, exp2) = exp {
+            if self.hit(Change::TakeOperand) {
+                *exp = (**exp1).clone();
+                return;
+            }
+            if self.hit(Change::TakeOperand) {
+                *exp = LAndExp::Eq(exp2.clone());
+                return;
+            }
+        }
+        match exp {
+            LAndExp::Eq(exp) => self.eq(exp),
+            LAndExp::LAndEq(exp1, exp2) => {
+                self.land(exp1);
+                self.eq(exp2);
+            }
+        }
+    }
+
+    fn eq(&mut self, exp: &mut EqExp) {
+        if let EqExp::EqRel(exp1, _, exp2) = exp {
+            if self.hit(Change::TakeOperand) {
+                *exp = (**exp1).clone();
+                return;
+            }
+            if self.hit(Change::TakeOperand) {
+                *exp = EqExp::Rel(exp2.clone());
+                return;
+            }
+        }
+        match exp {
+            EqExp::Rel(exp) => self.rel(exp),
+            EqExp::EqRel(exp1, _, exp2) => {
+                self.eq(exp1);
+                self.rel(exp2);
+            }
+        }
+    }
+
+    fn rel(&mut self, exp: &mut RelExp) {
+        if let RelExp::RelAdd(exp1, _, exp2) = exp {
+            if self.hit(Change::TakeOperand) {
+                *exp = (**exp1).clone();
+                return;
+            }
+            if self.hit(Change::TakeOperand) {
+                *exp = RelExp::Add(exp2.clone());
+                return;
+            }
+        }
+        match exp {
+            RelExp::Add(exp) => self.add(exp),
+            RelExp::RelAdd(exp1, _, exp2) => {
+                self.rel(exp1);
+                self.add(exp2);
+            }
+        }
+    }
+
+    fn add(&mut self, exp: &mut AddExp) {
+        if let AddExp::AddMul(exp1, _, exp2) = exp {
+            if self.hit(Change::TakeOperand) {
+                *exp = (**exp1).clone();
+                return;
+            }
+            if self.hit(Change::TakeOperand) {
+                *exp = AddExp::Mul(exp2.clone());
+                return;
+            }
+        }
+        match exp {
+            AddExp::Mul(exp) => self.mul(exp),
+            AddExp::AddMul(exp1, _, exp2) => {
+                self.add(exp1);
+                self.mul(exp2);
+            }
+        }
+    }
+
+    fn mul(&mut self, exp: &mut MulExp) {
+        if let MulExp::MulUnary(exp1, _, exp2) = exp {
+            if self.hit(Change::TakeOperand) {
+                *exp = (**exp1).clone();
+                return;
+            }
+            if self.hit(Change::TakeOperand) {
+                *exp = MulExp::Unary(exp2.clone());
+                return;
+            }
+        }
+        match exp {
+            MulExp::Unary(exp) => self.unary(exp),
+            MulExp::MulUnary(exp1, _, exp2) => {
+                self.mul(exp1);
+                self.unary(exp2);
+            }
+        }
+    }
+
+    fn unary(&mut self, exp: &mut UnaryExp) {
+        let span = exp.span();
+        match exp {
+            UnaryExp::Primary(PrimaryExp::Num(0, _)) => return,
+            UnaryExp::Primary(PrimaryExp::LVal(lval)) if lval.idx.is_empty() => {
+                if let Some(&value) = self.consts.get(&lval.ident.name) {
+                    if self.hit(Change::InlineConst) {
+                        *exp = UnaryExp::Primary(PrimaryExp::Num(value, span));
+                        return;
+                    }
+                }
+            }
+            UnaryExp::Primary(PrimaryExp::Exp(inner)) => {
+                if let Some(inner) = as_unary(inner).filter(|_| self.hit(Change::TakeOperand)) {
+                    *exp = inner.clone();
+                    return;
+                }
+            }
+            // `-2147483648` cannot be unwrapped, since the literal is out of the range of `int`
+            UnaryExp::Unary(_, operand, _)
+                if !operand.is_int_min_magnitude() && self.hit(Change::TakeOperand) =>
+            {
+                *exp = (**operand).clone();
+                return;
+            }
+            _ => {}
+        }
+        if self.hit(Change::Zero) {
+            *exp = zero(span);
+            return;
+        }
+        match exp {
+            UnaryExp::Primary(PrimaryExp::Exp(exp)) => self.exp(exp),
+            UnaryExp::Primary(PrimaryExp::LVal(lval)) => self.lval(lval),
+            UnaryExp::Primary(PrimaryExp::Num(..)) => {}
+            UnaryExp::FuncCall(_, args, _) => args.iter_mut().for_each(|arg| self.exp(arg)),
+            UnaryExp::Unary(_, exp, _) => self.unary(exp),
+        }
+    }
+}
+
+fn zero(span: Span) -> UnaryExp {
+    UnaryExp::Primary(PrimaryExp::Num(0, span))
+}
+
+impl LOrExp {
+    fn from_unary(exp: UnaryExp) -> Self {
+        Self::LAnd(LAndExp::Eq(EqExp::Rel(RelExp::Add(AddExp::Mul(
+            MulExp::Unary(exp),
+        )))))
+    }
+}
+
+/// Get the unary expression if the expression is nothing but it.
+fn as_unary(exp: &Exp) -> Option<&UnaryExp> {
+    match &exp.exp {
+        LOrExp::LAnd(LAndExp::Eq(EqExp::Rel(RelExp::Add(AddExp::Mul(MulExp::Unary(exp)))))) => {
+            Some(exp)
+        }
+        _ => None,
+    }
+}
+
+/// Get the value of the expression if it's a literal.
+fn literal(exp: &Exp) -> Option<i64> {
+    match as_unary(exp) {
+        Some(UnaryExp::Primary(PrimaryExp::Num(num, _))) => Some(*num),
+        _ => None,
+    }
+}
+
+/// Collect the scalar constants initialized by literals, ignoring the scopes.
+///
+/// A wrong value may be inlined if a constant is shadowed, and then the predicate decides.
+fn collect_consts(ast: &CompUnit, consts: &mut HashMap<String, i64>) {
+    fn decl(decl: &Decl, consts: &mut HashMap<String, i64>) {
+        if let Decl::Const(const_decl) = decl {
+            for def in &const_decl.defs {
+                if let (true, ConstInitVal::Exp(const_exp)) = (def.dims.is_empty(), &def.init) {
+                    if let Some(value) = literal(&const_exp.exp) {
+                        consts.insert(def.ident.name.clone(), value);
+                    }
+                }
+            }
+        }
+    }
+    fn block(block: &Block, consts: &mut HashMap<String, i64>) {
+        for item in &block.items {
+            match item {
+                BlockItem::Decl(d) => decl(d, consts),
+                BlockItem::Stmt(s) => stmt(s, consts),
+            }
+        }
+    }
+    fn stmt(s: &Stmt, consts: &mut HashMap<String, i64>) {
+        match &s.kind {
+            StmtKind::Block(b) => block(b, consts),
+            StmtKind::If(_, then, otherwise) => {
+                stmt(then, consts);
+                if let Some(otherwise) = otherwise {
+                    stmt(otherwise, consts);
+                }
+            }
+            StmtKind::While(_, body) => stmt(body, consts),
+            _ => {}
+        }
+    }
+    for item in &ast.items {
+        match item {
+            CompUnitItem::GlobalDecl(global_decl) => decl(&global_decl.decl, consts),
+            CompUnitItem::FuncDef(func_def) => block(&func_def.block, consts),
+            CompUnitItem::FuncDecl(_) => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn measure_test() {
+        assert_eq!(measure("int a1 = 10;"), (5, 2, 12));
+        assert!(measure("x = c;") > measure("x = 5;"));
+    }
+
+    #[test]
+    fn reduce_test() {
+        let source = r#"
+            const int K = 3;
+            int g[4] = {1, 2, 3, 4};
+            int f(int a[]) {
+                int s = 0, i = 0;
+                while (i < K) { s = s + a[i]; i = i + 1; }
+                return s;
+            }
+            int main() {
+                int x = f(g) * 2;
+                if (x > 0) { putint(x / 7); } else { putint(x); }
+                return 0;
+            }
+        "#;
+        // everything but the use of the undefined name is removed
+        let predicate = Predicate::Error(Mode::Koopa, String::from("SemanticError"));
+        assert!(reduce(source, "", &predicate).is_err());
+        let reduced = reduce(
+            &format!("{}\nint h() {{ return y; }}", source),
+            "",
+            &predicate,
+        )
+        .unwrap();
+        assert_eq!(reduced.to_string(), "int h() {\n    return y;\n}\n");
+    }
+}
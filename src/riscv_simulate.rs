@@ -0,0 +1,115 @@
+mod assemble;
+mod machine;
+
+use crate::diagnostic::Diagnostic;
+use assemble::assemble;
+use machine::Machine;
+
+/// Options of the RISC-V simulator.
+#[derive(Debug, Clone)]
+pub struct SimulateOptions {
+    /// Stop with an error after executing this many instructions, `None` for no limit.
+    pub max_instructions: Option<u64>,
+    /// The size of the stack in bytes.
+    pub stack_size: usize,
+}
+
+impl Default for SimulateOptions {
+    fn default() -> Self {
+        Self {
+            max_instructions: None,
+            stack_size: 8 << 20,
+        }
+    }
+}
+
+/// The result of running a program in the simulator.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SimulateResult {
+    /// Everything written by `putint`, `putch` and `putarray`.
+    pub stdout: String,
+    /// The return value of `main`, which is not truncated to 8 bits.
+    pub exit_code: i32,
+    /// The number of executed instructions, not counting the functions of the SysY runtime.
+    pub instruction_count: u64,
+}
+
+/// Run the RISC-V text emitted by the compiler on an RV32IM simulator.
+///
+/// The text is assembled, and `main` is called with the given standard input.
+/// The SysY runtime library (`getint`, `putint`, etc.) is emulated by the simulator.
+///
+/// # Errors
+/// An error is returned if the text cannot be assembled, or the program crashes.
+pub fn simulate_riscv(text: &str, stdin: &str) -> Result<SimulateResult, Diagnostic> {
+    simulate_riscv_with_options(text, stdin, &SimulateOptions::default())
+}
+
+/// Run the RISC-V text on an RV32IM simulator, with the given options.
+///
+/// # Errors
+/// An error is returned if the text cannot be assembled, the program crashes,
+/// or it runs for too long.
+pub fn simulate_riscv_with_options(
+    text: &str,
+    stdin: &str,
+    options: &SimulateOptions,
+) -> Result<SimulateResult, Diagnostic> {
+    let exe = assemble(text)?;
+    let mut machine = Machine::new(&exe, stdin, options.stack_size)?;
+    let exit_code = machine.run(options.max_instructions)?;
+    Ok(SimulateResult {
+        instruction_count: machine.instruction_count,
+        stdout: machine.io.into_stdout(),
+        exit_code,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn simulate_loop_test() {
+        // sum of 1..=10, printed and returned
+        let text = "  .text\n  .globl main\nmain:\n  addi sp, sp, -16\n  sw ra, 12(sp)\n  li t0, 0\n  li t1, 1\n\
+                    loop:\n  add t0, t0, t1\n  addi t1, t1, 1\n  li t2, 10\n  ble t1, t2, loop\n  sw t0, 0(sp)\n\
+                    \x20 mv a0, t0\n  call putint\n  li a0, 10\n  call putch\n  lw a0, 0(sp)\n  lw ra, 12(sp)\n\
+                    \x20 addi sp, sp, 16\n  ret";
+        let result = simulate_riscv(text, "").unwrap();
+        assert_eq!(result.stdout, "55\n");
+        assert_eq!(result.exit_code, 55);
+        assert!(result.instruction_count > 40);
+    }
+
+    #[test]
+    fn simulate_runtime_test() {
+        // read an array, print it, and return its first element plus a character
+        let text = "  .data\narr:\n  .zero 40\n  .text\nmain:\n  addi sp, sp, -16\n  sw ra, 12(sp)\n  la a0, arr\n\
+                    \x20 call getarray\n  la a1, arr\n  call putarray\n  call getch\n  call getch\n  la t0, arr\n\
+                    \x20 lw t1, 0(t0)\n  add a0, a0, t1\n  lw ra, 12(sp)\n  addi sp, sp, 16\n  ret";
+        let result = simulate_riscv(text, "3 4 5 6\nA").unwrap();
+        assert_eq!(result.stdout, "3: 4 5 6\n");
+        assert_eq!(result.exit_code, 4 + 'A' as i32);
+    }
+
+    #[test]
+    fn simulate_error_test() {
+        let diag = simulate_riscv("main:\n  lw a0, 0(zero)\n  ret", "").unwrap_err();
+        assert_eq!(diag.message, "the program accesses an invalid address 0x0");
+        assert_eq!(diag.notes, vec!["at line 2 of the RISC-V text"]);
+
+        let options = SimulateOptions {
+            max_instructions: Some(100),
+            ..SimulateOptions::default()
+        };
+        let diag = simulate_riscv_with_options("main:\n  j main", "", &options).unwrap_err();
+        assert_eq!(
+            diag.message,
+            "the program does not finish within 100 instructions"
+        );
+
+        let diag = simulate_riscv("  .text\nf:\n  ret", "").unwrap_err();
+        assert_eq!(diag.message, "the RISC-V text has no `main`");
+    }
+}
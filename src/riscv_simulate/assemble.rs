@@ -0,0 +1,704 @@
+use crate::diagnostic::Diagnostic;
+use crate::sysy_runtime::RUNTIME_FUNCTIONS;
+use std::collections::HashMap;
+
+/// The address of the first instruction.
+pub const TEXT_BASE: u32 = 0x0001_0000;
+/// The address of the first byte of the `.data` section.
+pub const DATA_BASE: u32 = 0x1000_0000;
+/// The address of the first function of the SysY runtime, each of which takes 4 bytes.
+pub const RUNTIME_BASE: u32 = 0x0000_1000;
+
+/// An integer register, `x0` to `x31`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Reg(pub u8);
+
+impl Reg {
+    pub const ZERO: Self = Self(0);
+    pub const RA: Self = Self(1);
+    pub const SP: Self = Self(2);
+    pub const A0: Self = Self(10);
+    pub const A1: Self = Self(11);
+}
+
+/// An operation on two registers, or on a register and an immediate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AluOp {
+    Add,
+    Sub,
+    Sll,
+    Slt,
+    Sltu,
+    Xor,
+    Srl,
+    Sra,
+    Or,
+    And,
+    Mul,
+    Mulh,
+    Mulhsu,
+    Mulhu,
+    Div,
+    Divu,
+    Rem,
+    Remu,
+}
+
+/// The width of a memory access.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Width {
+    Byte,
+    Half,
+    Word,
+}
+
+/// The condition of a conditional branch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BranchCond {
+    Eq,
+    Ne,
+    Lt,
+    Ge,
+    Ltu,
+    Geu,
+}
+
+/// An RV32IM instruction, with the pseudo instructions expanded and the symbols resolved.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Inst {
+    Op {
+        op: AluOp,
+        rd: Reg,
+        rs1: Reg,
+        rs2: Reg,
+    },
+    OpImm {
+        op: AluOp,
+        rd: Reg,
+        rs1: Reg,
+        imm: i32,
+    },
+    /// `li` and `la`, which may need two real instructions.
+    Li {
+        rd: Reg,
+        imm: i32,
+    },
+    Lui {
+        rd: Reg,
+        imm: i32,
+    },
+    Auipc {
+        rd: Reg,
+        imm: i32,
+    },
+    Load {
+        width: Width,
+        signed: bool,
+        rd: Reg,
+        rs1: Reg,
+        offset: i32,
+    },
+    Store {
+        width: Width,
+        rs2: Reg,
+        rs1: Reg,
+        offset: i32,
+    },
+    Branch {
+        cond: BranchCond,
+        rs1: Reg,
+        rs2: Reg,
+        target: u32,
+    },
+    Jal {
+        rd: Reg,
+        target: u32,
+    },
+    Jalr {
+        rd: Reg,
+        rs1: Reg,
+        offset: i32,
+    },
+}
+
+/// An assembled program.
+pub struct Executable {
+    /// The instructions, where the i-th one is at address `TEXT_BASE + 4 * i`.
+    pub insts: Vec<Inst>,
+    /// The line number (starting from 1) of each instruction in the assembly.
+    pub lines: Vec<usize>,
+    /// The initial content of the `.data` section.
+    pub data: Vec<u8>,
+    /// The address of each label.
+    pub symbols: HashMap<String, u32>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Section {
+    Text,
+    Data,
+}
+
+/// A line of assembly after the labels are taken away.
+struct Statement<'a> {
+    line: usize,
+    mnemonic: &'a str,
+    operands: Vec<&'a str>,
+}
+
+fn error_at(line: usize, message: impl Into<String>) -> Diagnostic {
+    Diagnostic::error(message).with_note(format!("at line {} of the RISC-V text", line))
+}
+
+/// Assemble the RISC-V text emitted by the compiler (or written by hand in the same dialect).
+///
+/// The symbols of the SysY runtime are defined implicitly, unless the text defines them.
+///
+/// # Errors
+/// An error is returned if an instruction or directive is not supported, or a symbol is undefined.
+pub fn assemble(text: &str) -> Result<Executable, Diagnostic> {
+    // the first pass collects the labels and the data, and the second one encodes the instructions
+    let mut section = Section::Text;
+    let mut symbols = HashMap::new();
+    let mut data = Vec::new();
+    let mut text_statements = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        let line_no = i + 1;
+        let mut rest = line.split('#').next().unwrap_or_default().trim();
+        while let Some((label, after)) = split_label(rest) {
+            let address = match section {
+                Section::Text => TEXT_BASE + 4 * text_statements.len() as u32,
+                Section::Data => DATA_BASE + data.len() as u32,
+            };
+            if symbols.insert(label.to_string(), address).is_some() {
+                return Err(error_at(
+                    line_no,
+                    format!("label `{}` is defined more than once", label),
+                ));
+            }
+            rest = after.trim();
+        }
+        if rest.is_empty() {
+            continue;
+        }
+
+        let (mnemonic, operands) = match rest.split_once(char::is_whitespace) {
+            Some((m, ops)) => (m, ops.split(',').map(str::trim).collect()),
+            None => (rest, Vec::new()),
+        };
+        let statement = Statement {
+            line: line_no,
+            mnemonic,
+            operands,
+        };
+
+        match mnemonic {
+            ".text" => section = Section::Text,
+            ".data" | ".bss" | ".rodata" => section = Section::Data,
+            ".section" => {
+                section = match statement.operands.first() {
+                    Some(name) if name.starts_with(".text") => Section::Text,
+                    _ => Section::Data,
+                }
+            }
+            ".globl" | ".global" | ".type" | ".size" | ".file" | ".option" | ".attribute"
+            | ".p2align" => {}
+            ".align" | ".balign" => {
+                let n = parse_imm(&statement, 0)? as u32;
+                let align = if mnemonic == ".align" {
+                    1 << n
+                } else {
+                    n.max(1)
+                };
+                if section == Section::Data {
+                    while !(data.len() as u32).is_multiple_of(align) {
+                        data.push(0);
+                    }
+                }
+            }
+            ".word" | ".half" | ".byte" => {
+                let size = match mnemonic {
+                    ".word" => 4,
+                    ".half" => 2,
+                    _ => 1,
+                };
+                for i in 0..statement.operands.len() {
+                    let value = parse_imm(&statement, i)?;
+                    data.extend_from_slice(&value.to_le_bytes()[..size]);
+                }
+            }
+            ".zero" | ".space" => {
+                let n = parse_imm(&statement, 0)?;
+                data.resize(data.len() + n as usize, 0);
+            }
+            _ if mnemonic.starts_with('.') => {
+                return Err(error_at(
+                    line_no,
+                    format!("unsupported directive `{}`", mnemonic),
+                ));
+            }
+            _ => {
+                if section != Section::Text {
+                    return Err(error_at(
+                        line_no,
+                        "an instruction is placed outside the `.text` section",
+                    ));
+                }
+                text_statements.push(statement);
+            }
+        }
+    }
+
+    for (i, name) in RUNTIME_FUNCTIONS.iter().enumerate() {
+        symbols
+            .entry(name.to_string())
+            .or_insert(RUNTIME_BASE + 4 * i as u32);
+    }
+
+    let mut insts = Vec::with_capacity(text_statements.len());
+    let mut lines = Vec::with_capacity(text_statements.len());
+    for statement in text_statements.iter() {
+        insts.push(encode(statement, &symbols)?);
+        lines.push(statement.line);
+    }
+
+    Ok(Executable {
+        insts,
+        lines,
+        data,
+        symbols,
+    })
+}
+
+/// Split `label: rest` into the label and the rest.
+fn split_label(s: &str) -> Option<(&str, &str)> {
+    let (label, rest) = s.split_once(':')?;
+    let is_symbol = !label.is_empty()
+        && label
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.' || c == '$');
+    is_symbol.then_some((label, rest))
+}
+
+fn parse_reg(statement: &Statement, i: usize) -> Result<Reg, Diagnostic> {
+    let s = operand(statement, i)?;
+    let abi = [
+        "zero", "ra", "sp", "gp", "tp", "t0", "t1", "t2", "s0", "s1", "a0", "a1", "a2", "a3", "a4",
+        "a5", "a6", "a7", "s2", "s3", "s4", "s5", "s6", "s7", "s8", "s9", "s10", "s11", "t3", "t4",
+        "t5", "t6",
+    ];
+    if let Some(n) = abi.iter().position(|&r| r == s) {
+        return Ok(Reg(n as u8));
+    }
+    if s == "fp" {
+        return Ok(Reg(8));
+    }
+    match s.strip_prefix('x').and_then(|n| n.parse::<u8>().ok()) {
+        Some(n) if n < 32 => Ok(Reg(n)),
+        _ => Err(error_at(
+            statement.line,
+            format!("`{}` is not a register", s),
+        )),
+    }
+}
+
+fn parse_imm(statement: &Statement, i: usize) -> Result<i32, Diagnostic> {
+    let s = operand(statement, i)?;
+    parse_int(s).ok_or_else(|| error_at(statement.line, format!("`{}` is not an integer", s)))
+}
+
+/// Parse a decimal or hexadecimal integer, which wraps around to 32 bits.
+fn parse_int(s: &str) -> Option<i32> {
+    let (negative, digits) = match s.strip_prefix('-') {
+        Some(d) => (true, d),
+        None => (false, s),
+    };
+    let value = match digits
+        .strip_prefix("0x")
+        .or_else(|| digits.strip_prefix("0X"))
+    {
+        Some(hex) => i64::from_str_radix(hex, 16).ok()?,
+        None => digits.parse::<i64>().ok()?,
+    };
+    let value = if negative { -value } else { value };
+    Some(value as i32)
+}
+
+/// Parse `offset(reg)`, where the offset may be omitted.
+fn parse_mem(statement: &Statement, i: usize) -> Result<(i32, Reg), Diagnostic> {
+    let s = operand(statement, i)?;
+    let error = || error_at(statement.line, format!("`{}` is not a memory operand", s));
+    let (offset, reg) = s
+        .strip_suffix(')')
+        .and_then(|s| s.split_once('('))
+        .ok_or_else(error)?;
+    let offset = if offset.is_empty() {
+        0
+    } else {
+        parse_int(offset).ok_or_else(error)?
+    };
+    let reg_statement = Statement {
+        line: statement.line,
+        mnemonic: statement.mnemonic,
+        operands: vec![reg.trim()],
+    };
+    Ok((offset, parse_reg(&reg_statement, 0)?))
+}
+
+fn parse_symbol(
+    statement: &Statement,
+    i: usize,
+    symbols: &HashMap<String, u32>,
+) -> Result<u32, Diagnostic> {
+    let s = operand(statement, i)?;
+    symbols
+        .get(s)
+        .copied()
+        .ok_or_else(|| error_at(statement.line, format!("undefined symbol `{}`", s)))
+}
+
+fn operand<'a>(statement: &Statement<'a>, i: usize) -> Result<&'a str, Diagnostic> {
+    statement.operands.get(i).copied().ok_or_else(|| {
+        error_at(
+            statement.line,
+            format!(
+                "`{}` expects at least {} operand(s)",
+                statement.mnemonic,
+                i + 1
+            ),
+        )
+    })
+}
+
+/// Encode an instruction, expanding the pseudo instructions.
+fn encode(s: &Statement, symbols: &HashMap<String, u32>) -> Result<Inst, Diagnostic> {
+    let n_operands = match s.mnemonic {
+        "ret" | "nop" => 0,
+        "j" | "call" | "tail" | "jr" => 1,
+        "jal" | "jalr" => s.operands.len().clamp(1, 3),
+        "lui" | "auipc" | "li" | "la" | "mv" | "not" | "neg" | "seqz" | "snez" | "sltz"
+        | "sgtz" | "beqz" | "bnez" | "blez" | "bgez" | "bltz" | "bgtz" | "lw" | "lh" | "lhu"
+        | "lb" | "lbu" | "sw" | "sh" | "sb" => 2,
+        "add" | "sub" | "sll" | "slt" | "sltu" | "xor" | "srl" | "sra" | "or" | "and" | "mul"
+        | "mulh" | "mulhsu" | "mulhu" | "div" | "divu" | "rem" | "remu" | "sgt" | "sgtu"
+        | "addi" | "slti" | "sltiu" | "xori" | "ori" | "andi" | "slli" | "srli" | "srai"
+        | "beq" | "bne" | "blt" | "bge" | "bltu" | "bgeu" | "bgt" | "ble" | "bgtu" | "bleu" => 3,
+        m => return Err(error_at(s.line, format!("unsupported instruction `{}`", m))),
+    };
+    if s.operands.len() != n_operands {
+        return Err(error_at(
+            s.line,
+            format!(
+                "`{}` expects {} operand(s), found {}",
+                s.mnemonic,
+                n_operands,
+                s.operands.len()
+            ),
+        ));
+    }
+
+    let rd = || parse_reg(s, 0);
+    let r = |i| parse_reg(s, i);
+    let imm = |i| parse_imm(s, i);
+    let target = |i| parse_symbol(s, i, symbols);
+    let op = |op| {
+        Ok(Inst::Op {
+            op,
+            rd: rd()?,
+            rs1: r(1)?,
+            rs2: r(2)?,
+        })
+    };
+    let op_imm = |op| {
+        Ok(Inst::OpImm {
+            op,
+            rd: rd()?,
+            rs1: r(1)?,
+            imm: imm(2)?,
+        })
+    };
+    let branch = |cond, swap: bool| {
+        let (rs1, rs2) = if swap { (r(1)?, r(0)?) } else { (r(0)?, r(1)?) };
+        Ok(Inst::Branch {
+            cond,
+            rs1,
+            rs2,
+            target: target(2)?,
+        })
+    };
+    // compare with zero: `zero_first` puts `zero` as the first operand
+    let branch_zero = |cond, zero_first: bool| {
+        let (rs1, rs2) = if zero_first {
+            (Reg::ZERO, r(0)?)
+        } else {
+            (r(0)?, Reg::ZERO)
+        };
+        Ok(Inst::Branch {
+            cond,
+            rs1,
+            rs2,
+            target: target(1)?,
+        })
+    };
+    let load = |width, signed| {
+        let (offset, rs1) = parse_mem(s, 1)?;
+        Ok(Inst::Load {
+            width,
+            signed,
+            rd: rd()?,
+            rs1,
+            offset,
+        })
+    };
+    let store = |width| {
+        let (offset, rs1) = parse_mem(s, 1)?;
+        Ok(Inst::Store {
+            width,
+            rs2: r(0)?,
+            rs1,
+            offset,
+        })
+    };
+
+    match s.mnemonic {
+        "add" => op(AluOp::Add),
+        "sub" => op(AluOp::Sub),
+        "sll" => op(AluOp::Sll),
+        "slt" => op(AluOp::Slt),
+        "sltu" => op(AluOp::Sltu),
+        "xor" => op(AluOp::Xor),
+        "srl" => op(AluOp::Srl),
+        "sra" => op(AluOp::Sra),
+        "or" => op(AluOp::Or),
+        "and" => op(AluOp::And),
+        "mul" => op(AluOp::Mul),
+        "mulh" => op(AluOp::Mulh),
+        "mulhsu" => op(AluOp::Mulhsu),
+        "mulhu" => op(AluOp::Mulhu),
+        "div" => op(AluOp::Div),
+        "divu" => op(AluOp::Divu),
+        "rem" => op(AluOp::Rem),
+        "remu" => op(AluOp::Remu),
+        "sgt" => Ok(Inst::Op {
+            op: AluOp::Slt,
+            rd: rd()?,
+            rs1: r(2)?,
+            rs2: r(1)?,
+        }),
+        "sgtu" => Ok(Inst::Op {
+            op: AluOp::Sltu,
+            rd: rd()?,
+            rs1: r(2)?,
+            rs2: r(1)?,
+        }),
+        "addi" => op_imm(AluOp::Add),
+        "slti" => op_imm(AluOp::Slt),
+        "sltiu" => op_imm(AluOp::Sltu),
+        "xori" => op_imm(AluOp::Xor),
+        "ori" => op_imm(AluOp::Or),
+        "andi" => op_imm(AluOp::And),
+        "slli" => op_imm(AluOp::Sll),
+        "srli" => op_imm(AluOp::Srl),
+        "srai" => op_imm(AluOp::Sra),
+        "lui" => Ok(Inst::Lui {
+            rd: rd()?,
+            imm: imm(1)?,
+        }),
+        "auipc" => Ok(Inst::Auipc {
+            rd: rd()?,
+            imm: imm(1)?,
+        }),
+        "li" => Ok(Inst::Li {
+            rd: rd()?,
+            imm: imm(1)?,
+        }),
+        "la" => Ok(Inst::Li {
+            rd: rd()?,
+            imm: target(1)? as i32,
+        }),
+        "mv" => Ok(Inst::OpImm {
+            op: AluOp::Add,
+            rd: rd()?,
+            rs1: r(1)?,
+            imm: 0,
+        }),
+        "not" => Ok(Inst::OpImm {
+            op: AluOp::Xor,
+            rd: rd()?,
+            rs1: r(1)?,
+            imm: -1,
+        }),
+        "neg" => Ok(Inst::Op {
+            op: AluOp::Sub,
+            rd: rd()?,
+            rs1: Reg::ZERO,
+            rs2: r(1)?,
+        }),
+        "seqz" => Ok(Inst::OpImm {
+            op: AluOp::Sltu,
+            rd: rd()?,
+            rs1: r(1)?,
+            imm: 1,
+        }),
+        "snez" => Ok(Inst::Op {
+            op: AluOp::Sltu,
+            rd: rd()?,
+            rs1: Reg::ZERO,
+            rs2: r(1)?,
+        }),
+        "sltz" => Ok(Inst::Op {
+            op: AluOp::Slt,
+            rd: rd()?,
+            rs1: r(1)?,
+            rs2: Reg::ZERO,
+        }),
+        "sgtz" => Ok(Inst::Op {
+            op: AluOp::Slt,
+            rd: rd()?,
+            rs1: Reg::ZERO,
+            rs2: r(1)?,
+        }),
+        "lw" => load(Width::Word, true),
+        "lh" => load(Width::Half, true),
+        "lhu" => load(Width::Half, false),
+        "lb" => load(Width::Byte, true),
+        "lbu" => load(Width::Byte, false),
+        "sw" => store(Width::Word),
+        "sh" => store(Width::Half),
+        "sb" => store(Width::Byte),
+        "beq" => branch(BranchCond::Eq, false),
+        "bne" => branch(BranchCond::Ne, false),
+        "blt" => branch(BranchCond::Lt, false),
+        "bge" => branch(BranchCond::Ge, false),
+        "bltu" => branch(BranchCond::Ltu, false),
+        "bgeu" => branch(BranchCond::Geu, false),
+        "bgt" => branch(BranchCond::Lt, true),
+        "ble" => branch(BranchCond::Ge, true),
+        "bgtu" => branch(BranchCond::Ltu, true),
+        "bleu" => branch(BranchCond::Geu, true),
+        "beqz" => branch_zero(BranchCond::Eq, false),
+        "bnez" => branch_zero(BranchCond::Ne, false),
+        "blez" => branch_zero(BranchCond::Ge, true),
+        "bgez" => branch_zero(BranchCond::Ge, false),
+        "bltz" => branch_zero(BranchCond::Lt, false),
+        "bgtz" => branch_zero(BranchCond::Lt, true),
+        "j" | "tail" => Ok(Inst::Jal {
+            rd: Reg::ZERO,
+            target: target(0)?,
+        }),
+        "call" => Ok(Inst::Jal {
+            rd: Reg::RA,
+            target: target(0)?,
+        }),
+        "jal" => match s.operands.len() {
+            1 => Ok(Inst::Jal {
+                rd: Reg::RA,
+                target: target(0)?,
+            }),
+            _ => Ok(Inst::Jal {
+                rd: rd()?,
+                target: target(1)?,
+            }),
+        },
+        "jr" => Ok(Inst::Jalr {
+            rd: Reg::ZERO,
+            rs1: r(0)?,
+            offset: 0,
+        }),
+        "jalr" => match s.operands.len() {
+            1 => Ok(Inst::Jalr {
+                rd: Reg::RA,
+                rs1: r(0)?,
+                offset: 0,
+            }),
+            2 => {
+                let (offset, rs1) = parse_mem(s, 1)?;
+                Ok(Inst::Jalr {
+                    rd: rd()?,
+                    rs1,
+                    offset,
+                })
+            }
+            _ => Ok(Inst::Jalr {
+                rd: rd()?,
+                rs1: r(1)?,
+                offset: imm(2)?,
+            }),
+        },
+        "ret" => Ok(Inst::Jalr {
+            rd: Reg::ZERO,
+            rs1: Reg::RA,
+            offset: 0,
+        }),
+        "nop" => Ok(Inst::OpImm {
+            op: AluOp::Add,
+            rd: Reg::ZERO,
+            rs1: Reg::ZERO,
+            imm: 0,
+        }),
+        _ => unreachable!("the mnemonic is checked above"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn assemble_test() {
+        let exe = assemble(
+            "  .data\n  .globl x\nx:\n  .word 1, -2\n  .zero 8\n \n  .text\nmain:  # entry\n  la t0, x\n  lw a0, 4(t0)\n  bnez a0, main\n  call putint\n  ret",
+        )
+        .unwrap();
+        assert_eq!(
+            exe.data,
+            vec![1, 0, 0, 0, 0xfe, 0xff, 0xff, 0xff, 0, 0, 0, 0, 0, 0, 0, 0]
+        );
+        assert_eq!(exe.symbols["x"], DATA_BASE);
+        assert_eq!(exe.symbols["main"], TEXT_BASE);
+        assert_eq!(exe.lines, vec![9, 10, 11, 12, 13]);
+        assert_eq!(
+            exe.insts[0],
+            Inst::Li {
+                rd: Reg(5),
+                imm: DATA_BASE as i32
+            }
+        );
+        assert_eq!(
+            exe.insts[1],
+            Inst::Load {
+                width: Width::Word,
+                signed: true,
+                rd: Reg::A0,
+                rs1: Reg(5),
+                offset: 4
+            }
+        );
+        assert_eq!(
+            exe.insts[2],
+            Inst::Branch {
+                cond: BranchCond::Ne,
+                rs1: Reg::A0,
+                rs2: Reg::ZERO,
+                target: TEXT_BASE
+            }
+        );
+        assert_eq!(
+            exe.insts[3],
+            Inst::Jal {
+                rd: Reg::RA,
+                target: RUNTIME_BASE + 12
+            }
+        );
+    }
+
+    #[test]
+    fn assemble_error_test() {
+        let diag = assemble("main:\n  frobnicate a0").err().unwrap();
+        assert_eq!(diag.message, "unsupported instruction `frobnicate`");
+        assert_eq!(diag.notes, vec!["at line 2 of the RISC-V text"]);
+
+        let diag = assemble("main:\n  j nowhere").err().unwrap();
+        assert_eq!(diag.message, "undefined symbol `nowhere`");
+    }
+}
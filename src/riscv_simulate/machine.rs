@@ -0,0 +1,307 @@
+use super::assemble::*;
+use crate::diagnostic::Diagnostic;
+use crate::sysy_runtime::{SysyIo, RUNTIME_FUNCTIONS};
+
+/// The address `main` returns to, which ends the simulation.
+const EXIT_ADDRESS: u32 = 0;
+/// The initial stack pointer, which is also the end of the stack.
+const STACK_TOP: u32 = 0x8000_0000;
+
+/// The state of a running program.
+pub struct Machine<'a> {
+    exe: &'a Executable,
+    regs: [u32; 32],
+    pc: u32,
+    data: Vec<u8>,
+    stack: Vec<u8>,
+    pub io: SysyIo,
+    pub instruction_count: u64,
+}
+
+impl<'a> Machine<'a> {
+    /// Construct a machine about to call `main` with the given stack size in bytes.
+    pub fn new(exe: &'a Executable, stdin: &str, stack_size: usize) -> Result<Self, Diagnostic> {
+        let Some(&main) = exe.symbols.get("main") else {
+            return Err(Diagnostic::error("the RISC-V text has no `main`"));
+        };
+        let mut regs = [0u32; 32];
+        regs[Reg::RA.0 as usize] = EXIT_ADDRESS;
+        regs[Reg::SP.0 as usize] = STACK_TOP;
+        Ok(Self {
+            exe,
+            regs,
+            pc: main,
+            data: exe.data.clone(),
+            stack: vec![0; stack_size],
+            io: SysyIo::new(stdin),
+            instruction_count: 0,
+        })
+    }
+
+    /// Run until `main` returns, and return its return value.
+    ///
+    /// # Errors
+    /// An error is returned if the program jumps to or accesses an invalid address,
+    /// or it executes more than `max_instructions` instructions.
+    pub fn run(&mut self, max_instructions: Option<u64>) -> Result<i32, Diagnostic> {
+        loop {
+            if self.pc == EXIT_ADDRESS {
+                return Ok(self.regs[Reg::A0.0 as usize] as i32);
+            }
+            if max_instructions.is_some_and(|max| self.instruction_count >= max) {
+                return Err(Diagnostic::error(format!(
+                    "the program does not finish within {} instructions",
+                    self.instruction_count
+                )));
+            }
+            self.step()?;
+        }
+    }
+
+    fn reg(&self, r: Reg) -> u32 {
+        self.regs[r.0 as usize]
+    }
+
+    fn set_reg(&mut self, r: Reg, value: u32) {
+        if r != Reg::ZERO {
+            self.regs[r.0 as usize] = value;
+        }
+    }
+
+    /// Attach the location of the current instruction to an error.
+    fn error(&self, message: impl Into<String>) -> Diagnostic {
+        let diag = Diagnostic::error(message);
+        match self
+            .pc
+            .checked_sub(TEXT_BASE)
+            .map(|offset| offset as usize / 4)
+        {
+            Some(i) if i < self.exe.lines.len() => {
+                diag.with_note(format!("at line {} of the RISC-V text", self.exe.lines[i]))
+            }
+            _ => diag.with_note(format!("at address {:#x}", self.pc)),
+        }
+    }
+
+    /// Execute one instruction, or one function of the SysY runtime.
+    fn step(&mut self) -> Result<(), Diagnostic> {
+        if let Some(offset) = self.pc.checked_sub(RUNTIME_BASE) {
+            let index = offset as usize / 4;
+            if offset % 4 == 0 && index < RUNTIME_FUNCTIONS.len() {
+                self.call_runtime(RUNTIME_FUNCTIONS[index])?;
+                self.pc = self.reg(Reg::RA);
+                return Ok(());
+            }
+        }
+        let inst = match self.pc.checked_sub(TEXT_BASE) {
+            Some(offset) if offset % 4 == 0 && (offset as usize / 4) < self.exe.insts.len() => {
+                self.exe.insts[offset as usize / 4]
+            }
+            _ => return Err(self.error("the program jumps to an invalid address")),
+        };
+        self.instruction_count += 1;
+
+        let mut next_pc = self.pc.wrapping_add(4);
+        match inst {
+            Inst::Op { op, rd, rs1, rs2 } => {
+                let value = alu(op, self.reg(rs1), self.reg(rs2));
+                self.set_reg(rd, value);
+            }
+            Inst::OpImm { op, rd, rs1, imm } => {
+                let value = alu(op, self.reg(rs1), imm as u32);
+                self.set_reg(rd, value);
+            }
+            Inst::Li { rd, imm } => self.set_reg(rd, imm as u32),
+            Inst::Lui { rd, imm } => self.set_reg(rd, (imm as u32) << 12),
+            Inst::Auipc { rd, imm } => self.set_reg(rd, self.pc.wrapping_add((imm as u32) << 12)),
+            Inst::Load {
+                width,
+                signed,
+                rd,
+                rs1,
+                offset,
+            } => {
+                let address = self.reg(rs1).wrapping_add(offset as u32);
+                let value = self.load(address, width)?;
+                let value = match (width, signed) {
+                    (Width::Byte, true) => value as u8 as i8 as u32,
+                    (Width::Half, true) => value as u16 as i16 as u32,
+                    _ => value,
+                };
+                self.set_reg(rd, value);
+            }
+            Inst::Store {
+                width,
+                rs2,
+                rs1,
+                offset,
+            } => {
+                let address = self.reg(rs1).wrapping_add(offset as u32);
+                self.store(address, width, self.reg(rs2))?;
+            }
+            Inst::Branch {
+                cond,
+                rs1,
+                rs2,
+                target,
+            } => {
+                let (a, b) = (self.reg(rs1), self.reg(rs2));
+                let taken = match cond {
+                    BranchCond::Eq => a == b,
+                    BranchCond::Ne => a != b,
+                    BranchCond::Lt => (a as i32) < (b as i32),
+                    BranchCond::Ge => (a as i32) >= (b as i32),
+                    BranchCond::Ltu => a < b,
+                    BranchCond::Geu => a >= b,
+                };
+                if taken {
+                    next_pc = target;
+                }
+            }
+            Inst::Jal { rd, target } => {
+                self.set_reg(rd, next_pc);
+                next_pc = target;
+            }
+            Inst::Jalr { rd, rs1, offset } => {
+                let target = self.reg(rs1).wrapping_add(offset as u32) & !1;
+                self.set_reg(rd, next_pc);
+                next_pc = target;
+            }
+        }
+        self.pc = next_pc;
+        Ok(())
+    }
+
+    /// Find the memory backing `[address, address + size)`.
+    fn memory(&mut self, address: u32, size: u32) -> Result<&mut [u8], Diagnostic> {
+        let stack_base = STACK_TOP - self.stack.len() as u32;
+        let in_stack = address >= stack_base;
+        let (base, len) = if in_stack {
+            (stack_base, self.stack.len())
+        } else {
+            (DATA_BASE, self.data.len())
+        };
+        let start = address.wrapping_sub(base) as usize;
+        if address < base || start + size as usize > len {
+            return Err(self.error(format!(
+                "the program accesses an invalid address {:#x}",
+                address
+            )));
+        }
+        let memory = if in_stack {
+            &mut self.stack
+        } else {
+            &mut self.data
+        };
+        Ok(&mut memory[start..start + size as usize])
+    }
+
+    fn load(&mut self, address: u32, width: Width) -> Result<u32, Diagnostic> {
+        let bytes = self.memory(address, size_of(width))?;
+        let mut buf = [0u8; 4];
+        buf[..bytes.len()].copy_from_slice(bytes);
+        Ok(u32::from_le_bytes(buf))
+    }
+
+    fn store(&mut self, address: u32, width: Width, value: u32) -> Result<(), Diagnostic> {
+        let bytes = self.memory(address, size_of(width))?;
+        let len = bytes.len();
+        bytes.copy_from_slice(&value.to_le_bytes()[..len]);
+        Ok(())
+    }
+
+    /// Run a function of the SysY runtime, following the calling convention.
+    fn call_runtime(&mut self, name: &str) -> Result<(), Diagnostic> {
+        let a0 = self.reg(Reg::A0);
+        let a1 = self.reg(Reg::A1);
+        match name {
+            "getint" => {
+                let value = self.io.get_int();
+                self.set_reg(Reg::A0, value as u32);
+            }
+            "getch" => {
+                let value = self.io.get_ch();
+                self.set_reg(Reg::A0, value as u32);
+            }
+            "getarray" => {
+                let n = self.io.get_int();
+                for i in 0..n.max(0) as u32 {
+                    let value = self.io.get_int();
+                    self.store(a0.wrapping_add(4 * i), Width::Word, value as u32)?;
+                }
+                self.set_reg(Reg::A0, n as u32);
+            }
+            "putint" => self.io.put_int(a0 as i32),
+            "putch" => self.io.put_ch(a0 as i32),
+            "putarray" => {
+                let mut values = Vec::new();
+                for i in 0..(a0 as i32).max(0) as u32 {
+                    values.push(self.load(a1.wrapping_add(4 * i), Width::Word)? as i32);
+                }
+                self.io.put_array(&values);
+            }
+            // the timer is not simulated
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+fn size_of(width: Width) -> u32 {
+    match width {
+        Width::Byte => 1,
+        Width::Half => 2,
+        Width::Word => 4,
+    }
+}
+
+/// Compute an arithmetic or logic operation as RV32IM does, including division by zero and overflow.
+fn alu(op: AluOp, a: u32, b: u32) -> u32 {
+    let (sa, sb) = (a as i32, b as i32);
+    match op {
+        AluOp::Add => a.wrapping_add(b),
+        AluOp::Sub => a.wrapping_sub(b),
+        AluOp::Sll => a << (b & 31),
+        AluOp::Slt => (sa < sb) as u32,
+        AluOp::Sltu => (a < b) as u32,
+        AluOp::Xor => a ^ b,
+        AluOp::Srl => a >> (b & 31),
+        AluOp::Sra => (sa >> (b & 31)) as u32,
+        AluOp::Or => a | b,
+        AluOp::And => a & b,
+        AluOp::Mul => a.wrapping_mul(b),
+        AluOp::Mulh => ((sa as i64 * sb as i64) >> 32) as u32,
+        AluOp::Mulhsu => ((sa as i64 * b as i64) >> 32) as u32,
+        AluOp::Mulhu => ((a as u64 * b as u64) >> 32) as u32,
+        AluOp::Div => match sb {
+            0 => u32::MAX,
+            _ => sa.wrapping_div(sb) as u32,
+        },
+        AluOp::Divu => a.checked_div(b).unwrap_or(u32::MAX),
+        AluOp::Rem => match sb {
+            0 => a,
+            _ => sa.wrapping_rem(sb) as u32,
+        },
+        AluOp::Remu => a.checked_rem(b).unwrap_or(a),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn alu_test() {
+        assert_eq!(alu(AluOp::Div, 7, 0), u32::MAX);
+        assert_eq!(alu(AluOp::Rem, 7, 0), 7);
+        assert_eq!(
+            alu(AluOp::Div, i32::MIN as u32, -1i32 as u32),
+            i32::MIN as u32
+        );
+        assert_eq!(alu(AluOp::Rem, i32::MIN as u32, -1i32 as u32), 0);
+        assert_eq!(alu(AluOp::Rem, -7i32 as u32, 2), -1i32 as u32);
+        assert_eq!(alu(AluOp::Sra, -8i32 as u32, 1), -4i32 as u32);
+        assert_eq!(alu(AluOp::Srl, -8i32 as u32, 1), 0x7fff_fffc);
+        assert_eq!(alu(AluOp::Mulh, -2i32 as u32, 3), u32::MAX);
+    }
+}
@@ -0,0 +1,1025 @@
+use crate::ast_generate::ast::*;
+use crate::diagnostic::Diagnostic;
+use crate::ir_generate::exp_solve::{check_int_literal, ConstTable, ExpSolve};
+use std::collections::HashMap;
+use std::fmt;
+
+/// Check the AST before generating Koopa IR.
+///
+/// Names are resolved and the types of expressions are checked,
+/// so that the IR generator only sees valid programs.
+/// All the problems found are reported at once.
+///
+/// # Errors
+/// The diagnostics are returned if there's any problem.
+pub fn check_ast(ast: &CompUnit) -> Result<(), Vec<Diagnostic>> {
+    let mut checker = Checker::new();
+    checker.check_comp_unit(ast);
+    if checker.diagnostics.is_empty() {
+        Ok(())
+    } else {
+        Err(checker.diagnostics)
+    }
+}
+
+/// The type of an expression.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Type {
+    Int,
+    Void,
+    /// An array (or a pointer to one) with the lengths of its dimensions.
+    ///
+    /// A length is `None` if it's unknown, e.g. the first dimension of an array parameter.
+    Array(Vec<Option<i32>>),
+    /// The type of an expression that has already been reported as invalid.
+    Error,
+}
+
+impl Type {
+    /// Return whether an argument of type `self` can be passed to a parameter of type `param`.
+    ///
+    /// The first dimension of an array is never checked, since arrays are passed as pointers.
+    fn fits(&self, param: &Type) -> bool {
+        match (self, param) {
+            (Type::Error, _) | (_, Type::Error) => true,
+            (Type::Int, Type::Int) => true,
+            (Type::Array(arg), Type::Array(param)) => {
+                arg.len() == param.len()
+                    && arg
+                        .iter()
+                        .zip(param.iter())
+                        .skip(1)
+                        .all(|(a, p)| match (a, p) {
+                            (Some(a), Some(p)) => a == p,
+                            _ => true,
+                        })
+            }
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Type::Int => write!(f, "int"),
+            Type::Void => write!(f, "void"),
+            Type::Array(dims) => {
+                write!(f, "int")?;
+                for d in dims.iter() {
+                    match d {
+                        Some(d) => write!(f, "[{}]", d)?,
+                        None => write!(f, "[]")?,
+                    }
+                }
+                Ok(())
+            }
+            Type::Error => write!(f, "{{error}}"),
+        }
+    }
+}
+
+/// A name defined by a declaration or a function parameter.
+#[derive(Debug, Clone)]
+enum Symbol {
+    /// A constant scalar and its value.
+    Const(i32),
+    Var,
+    Array {
+        dims: Vec<Option<i32>>,
+        is_const: bool,
+    },
+}
+
+/// The signature of a function.
+struct Signature {
+    return_void: bool,
+    params: Vec<Type>,
+    /// Where the function is defined or first declared, `None` for SysY library functions.
+    span: Option<Span>,
+    /// Whether the function has a definition in the source code.
+    defined: bool,
+}
+
+struct Checker {
+    functions: HashMap<String, Signature>,
+    /// Stacked symbol tables, the same as the ones used in IR generation.
+    ///
+    /// identifier -> (symbol, where the identifier is defined)
+    values: Vec<HashMap<String, (Symbol, Span)>>,
+    /// Function parameters, which will be put into the scope of the function body.
+    params_buffer: Vec<(Ident, Symbol)>,
+    /// Whether the function being checked returns `void`.
+    cur_func_return_void: bool,
+    /// The number of loops enclosing the current statement.
+    loop_depth: usize,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Checker {
+    fn new() -> Self {
+        let mut functions = HashMap::new();
+        let array = || Type::Array(vec![None]);
+        let library: [(&str, bool, Vec<Type>); 8] = [
+            ("getint", false, vec![]),
+            ("getch", false, vec![]),
+            ("getarray", false, vec![array()]),
+            ("putint", true, vec![Type::Int]),
+            ("putch", true, vec![Type::Int]),
+            ("putarray", true, vec![Type::Int, array()]),
+            ("starttime", true, vec![]),
+            ("stoptime", true, vec![]),
+        ];
+        for (name, return_void, params) in library {
+            functions.insert(
+                name.to_string(),
+                Signature {
+                    return_void,
+                    params,
+                    span: None,
+                    defined: false,
+                },
+            );
+        }
+        Self {
+            functions,
+            values: vec![HashMap::new()],
+            params_buffer: Vec::new(),
+            cur_func_return_void: false,
+            loop_depth: 0,
+            diagnostics: Vec::new(),
+        }
+    }
+
+    fn report(&mut self, diag: Diagnostic) {
+        self.diagnostics.push(diag);
+    }
+
+    ////////////////////////////////////////////////////////////////////////
+    // Symbol tables                                                      //
+    ////////////////////////////////////////////////////////////////////////
+
+    fn enter(&mut self) {
+        self.values.push(HashMap::new());
+        for (ident, symbol) in std::mem::take(&mut self.params_buffer) {
+            self.define(&ident, symbol);
+        }
+    }
+
+    fn exit(&mut self) {
+        self.values.pop();
+    }
+
+    /// Define a name in the current scope, and report it if the name is already defined there.
+    fn define(&mut self, ident: &Ident, symbol: Symbol) {
+        let symtab = self
+            .values
+            .last_mut()
+            .expect("the global scope is never exited");
+        if let Some((_, old_span)) = symtab.get(&ident.name) {
+            let diag = Diagnostic::error(format!(
+                "the name `{}` is defined multiple times in the same scope",
+                ident.name
+            ))
+            .with_primary(ident.span, format!("`{}` redefined here", ident.name))
+            .with_secondary(
+                *old_span,
+                format!("previous definition of `{}` here", ident.name),
+            );
+            self.report(diag);
+            return;
+        }
+        symtab.insert(ident.name.clone(), (symbol, ident.span));
+    }
+
+    fn lookup(&self, identifier: &str) -> Option<&(Symbol, Span)> {
+        self.values
+            .iter()
+            .rev()
+            .find_map(|symtab| symtab.get(identifier))
+    }
+
+    fn not_found(ident: &Ident) -> Diagnostic {
+        Diagnostic::error(format!("cannot find value `{}` in this scope", ident.name))
+            .with_primary(ident.span, "not found in this scope")
+    }
+
+    /// Evaluate a constant expression, or report why it's not a constant.
+    fn solve(&mut self, exp: &ConstExp) -> Option<i32> {
+        match exp.solve(self) {
+            Ok(v) => Some(v),
+            Err(diag) => {
+                self.report(diag);
+                None
+            }
+        }
+    }
+
+    /// Solve the lengths of the dimensions of an array, which must be positive constants.
+    fn solve_dims(&mut self, dims: &[ConstExp]) -> Vec<Option<i32>> {
+        dims.iter()
+            .map(|d| {
+                let v = self.solve(d)?;
+                if v <= 0 {
+                    self.report(
+                        Diagnostic::error(format!(
+                            "the length of an array dimension must be positive, but it is {}",
+                            v
+                        ))
+                        .with_primary(d.span(), "invalid array length"),
+                    );
+                    return None;
+                }
+                Some(v)
+            })
+            .collect()
+    }
+
+    ////////////////////////////////////////////////////////////////////////
+    // Declarations and statements                                        //
+    ////////////////////////////////////////////////////////////////////////
+
+    fn check_comp_unit(&mut self, comp_unit: &CompUnit) {
+        // global values are visible in all the functions, just like in IR generation
+        for item in comp_unit.items.iter() {
+            if let CompUnitItem::GlobalDecl(global_decl) = item {
+                self.check_decl(&global_decl.decl);
+            }
+        }
+
+        // collect the signatures of all the functions before checking any function body,
+        // so that a function can be called before it's defined
+        let mut param_types = Vec::new();
+        for item in comp_unit.items.iter() {
+            match item {
+                CompUnitItem::FuncDef(func_def) => {
+                    let params = self.check_params(&func_def.params);
+                    self.declare_function(&func_def.ident, &func_def.func_type, &params, true);
+                    param_types.push(params);
+                }
+                CompUnitItem::FuncDecl(func_decl) => {
+                    let params = self.check_params(&func_decl.params);
+                    self.declare_function(&func_decl.ident, &func_decl.func_type, &params, false);
+                }
+                CompUnitItem::GlobalDecl(_) => (),
+            }
+        }
+
+        let func_defs = comp_unit.items.iter().filter_map(|item| match item {
+            CompUnitItem::FuncDef(func_def) => Some(func_def),
+            _ => None,
+        });
+        for (func_def, params) in func_defs.zip(param_types) {
+            self.check_func_def(func_def, params);
+        }
+    }
+
+    /// Get the types of function parameters.
+    fn check_params(&mut self, params: &[FuncFParam]) -> Vec<Type> {
+        params
+            .iter()
+            .map(|param| match param.dims {
+                None => Type::Int,
+                Some(ref dims) => {
+                    let mut dims = self.solve_dims(dims);
+                    dims.insert(0, None);
+                    Type::Array(dims)
+                }
+            })
+            .collect()
+    }
+
+    /// Record the signature of a function definition (`is_definition`) or prototype.
+    ///
+    /// A function can be declared many times, but the signatures must be the same.
+    fn declare_function(
+        &mut self,
+        ident: &Ident,
+        func_type: &FuncType,
+        params: &[Type],
+        is_definition: bool,
+    ) {
+        let name = &ident.name;
+        let return_void = matches!(func_type, FuncType::Void);
+        let Some(old) = self.functions.get_mut(name) else {
+            self.functions.insert(
+                name.clone(),
+                Signature {
+                    return_void,
+                    params: params.to_vec(),
+                    span: Some(ident.span),
+                    defined: is_definition,
+                },
+            );
+            return;
+        };
+
+        let previous = match old.span {
+            Some(_) if old.defined => "previous definition",
+            Some(_) => "previous declaration",
+            None => "",
+        };
+        let diag = if is_definition && (old.defined || old.span.is_none()) {
+            Diagnostic::error(format!("the function `{}` is defined multiple times", name))
+                .with_primary(ident.span, format!("`{}` redefined here", name))
+        } else if old.return_void != return_void || old.params != params {
+            Diagnostic::error(format!("conflicting types for `{}`", name))
+                .with_primary(ident.span, "the signature differs from the previous one")
+        } else {
+            if is_definition {
+                // point at the definition in later diagnostics
+                old.span = Some(ident.span);
+                old.defined = true;
+            }
+            return;
+        };
+        let diag = match old.span {
+            Some(old_span) => {
+                diag.with_secondary(old_span, format!("{} of `{}` here", previous, name))
+            }
+            None => diag.with_note(format!("`{}` is a SysY library function", name)),
+        };
+        self.report(diag);
+    }
+
+    fn check_func_def(&mut self, func_def: &FuncDef, params: Vec<Type>) {
+        for (param, ty) in func_def.params.iter().zip(params) {
+            let symbol = match ty {
+                Type::Array(dims) => Symbol::Array {
+                    dims,
+                    is_const: false,
+                },
+                _ => Symbol::Var,
+            };
+            self.params_buffer.push((param.ident.clone(), symbol));
+        }
+        self.cur_func_return_void = matches!(func_def.func_type, FuncType::Void);
+        self.check_block(&func_def.block);
+    }
+
+    fn check_block(&mut self, block: &Block) {
+        self.enter();
+        for item in block.items.iter() {
+            match item {
+                BlockItem::Stmt(stmt) => self.check_stmt(stmt),
+                BlockItem::Decl(decl) => self.check_decl(decl),
+            }
+        }
+        self.exit();
+    }
+
+    fn check_stmt(&mut self, stmt: &Stmt) {
+        match &stmt.kind {
+            StmtKind::Assign(lval, exp) => {
+                self.check_assignee(lval);
+                self.expect_int(exp);
+            }
+            StmtKind::Exp(exp) => {
+                if let Some(exp) = exp {
+                    // the result is discarded, so a `void` call is fine here
+                    self.check_exp(exp);
+                }
+            }
+            StmtKind::Block(block) => self.check_block(block),
+            StmtKind::If(cond, then, otherwise) => {
+                self.expect_int(cond);
+                self.check_stmt(then);
+                if let Some(otherwise) = otherwise {
+                    self.check_stmt(otherwise);
+                }
+            }
+            StmtKind::While(cond, body) => {
+                self.expect_int(cond);
+                self.loop_depth += 1;
+                self.check_stmt(body);
+                self.loop_depth -= 1;
+            }
+            StmtKind::Break => {
+                if self.loop_depth == 0 {
+                    self.report(
+                        Diagnostic::error("`break` outside of a loop")
+                            .with_primary(stmt.span, "cannot `break` outside of a loop"),
+                    );
+                }
+            }
+            StmtKind::Continue => {
+                if self.loop_depth == 0 {
+                    self.report(
+                        Diagnostic::error("`continue` outside of a loop")
+                            .with_primary(stmt.span, "cannot `continue` outside of a loop"),
+                    );
+                }
+            }
+            StmtKind::Return(exp) => match (exp, self.cur_func_return_void) {
+                (Some(exp), false) => self.expect_int(exp),
+                (None, true) => (),
+                (Some(exp), true) => {
+                    self.check_exp(exp);
+                    self.report(
+                        Diagnostic::error("mismatched types")
+                            .with_primary(exp.span, "expected `void`, found `int`")
+                            .with_note("the function returns nothing, so `return` takes no value"),
+                    );
+                }
+                (None, false) => self.report(
+                    Diagnostic::error("`return;` in a function whose return type is `int`")
+                        .with_primary(stmt.span, "return type is not `void`"),
+                ),
+            },
+        }
+    }
+
+    /// Check the left-hand side of an assignment, which must be a variable or an array element.
+    fn check_assignee(&mut self, lval: &LVal) {
+        let ty = self.check_lval(lval);
+        let Some((symbol, def_span)) = self.lookup(&lval.ident.name).cloned() else {
+            return;
+        };
+        if matches!(
+            symbol,
+            Symbol::Const(_) | Symbol::Array { is_const: true, .. }
+        ) {
+            self.report(
+                Diagnostic::error(format!("cannot assign to constant `{}`", lval.ident.name))
+                    .with_primary(lval.span, "cannot assign to a constant")
+                    .with_secondary(
+                        def_span,
+                        format!("`{}` is defined as a constant here", lval.ident.name),
+                    ),
+            );
+        } else if let Type::Array(_) = ty {
+            self.report(
+                Diagnostic::error(format!("cannot assign to array `{}`", lval.ident.name))
+                    .with_primary(lval.span, format!("expected `int`, found `{}`", ty)),
+            );
+        }
+    }
+
+    fn check_decl(&mut self, decl: &Decl) {
+        match decl {
+            Decl::Const(const_decl) => {
+                for def in const_decl.defs.iter() {
+                    self.check_const_def(def);
+                }
+            }
+            Decl::Var(var_decl) => {
+                for def in var_decl.defs.iter() {
+                    self.check_var_def(def);
+                }
+            }
+        }
+    }
+
+    fn check_const_def(&mut self, def: &ConstDef) {
+        if def.dims.is_empty() {
+            let value = match &def.init {
+                ConstInitVal::Exp(exp) => self.solve(exp),
+                ConstInitVal::Array(_, span) => {
+                    self.report(
+                        Diagnostic::error("invalid initializer for a scalar constant")
+                            .with_primary(
+                                *span,
+                                "expected an expression, found an initializer list",
+                            ),
+                    );
+                    None
+                }
+            };
+            // the problem has been reported, so pretend the value is 0 to avoid more errors
+            self.define(&def.ident, Symbol::Const(value.unwrap_or(0)));
+        } else {
+            let dims = self.solve_dims(&def.dims);
+            match &def.init {
+                ConstInitVal::Exp(exp) => self.report(
+                    Diagnostic::error("invalid initializer for an array")
+                        .with_primary(exp.span(), "expected an initializer list"),
+                ),
+                init => {
+                    self.check_const_init_elements(init);
+                    self.check_initializer_shape(init, &dims);
+                }
+            }
+            self.define(
+                &def.ident,
+                Symbol::Array {
+                    dims,
+                    is_const: true,
+                },
+            );
+        }
+    }
+
+    fn check_const_init_elements(&mut self, init: &ConstInitVal) {
+        match init {
+            ConstInitVal::Exp(exp) => {
+                self.solve(exp);
+            }
+            ConstInitVal::Array(inits, _) => {
+                for init in inits.iter() {
+                    self.check_const_init_elements(init);
+                }
+            }
+        }
+    }
+
+    fn check_var_def(&mut self, def: &VarDef) {
+        let is_global = self.values.len() <= 1;
+        let dims = self.solve_dims(&def.dims);
+        if let Some(ref init) = def.init {
+            match (init, dims.is_empty()) {
+                (InitVal::Exp(_), true) | (InitVal::Array(..), false) => {
+                    self.check_var_init_elements(init, is_global);
+                    if !dims.is_empty() {
+                        self.check_initializer_shape(init, &dims);
+                    }
+                }
+                (InitVal::Array(_, span), true) => self.report(
+                    Diagnostic::error("invalid initializer for a scalar variable")
+                        .with_primary(*span, "expected an expression, found an initializer list"),
+                ),
+                (InitVal::Exp(exp), false) => self.report(
+                    Diagnostic::error("invalid initializer for an array")
+                        .with_primary(exp.span, "expected an initializer list"),
+                ),
+            }
+        }
+        // the name is visible only after its initializer
+        let symbol = if dims.is_empty() {
+            Symbol::Var
+        } else {
+            Symbol::Array {
+                dims,
+                is_const: false,
+            }
+        };
+        self.define(&def.ident, symbol);
+    }
+
+    /// Check the elements of a variable initializer.
+    ///
+    /// The initial values of global variables must be constants.
+    fn check_var_init_elements(&mut self, init: &InitVal, is_global: bool) {
+        match init {
+            InitVal::Exp(exp) if is_global => {
+                if let Err(diag) = exp.solve(self) {
+                    self.report(
+                        diag.with_note("global variables must be initialized by constants"),
+                    );
+                }
+            }
+            InitVal::Exp(exp) => self.expect_int(exp),
+            InitVal::Array(inits, _) => {
+                for init in inits.iter() {
+                    self.check_var_init_elements(init, is_global);
+                }
+            }
+        }
+    }
+
+    /// Check that the initializer list fits in an array with the given dimensions.
+    ///
+    /// The elements are placed in the same way as the IR generator does.
+    fn check_initializer_shape(&mut self, init: &impl Initializer, dims: &[Option<i32>]) {
+        // the lengths of invalid dimensions have been reported, so the shape is not checked
+        let Some(dims) = dims.iter().copied().collect::<Option<Vec<i32>>>() else {
+            return;
+        };
+        let mut backward_prod = vec![1usize; dims.len()];
+        for j in (0..dims.len().saturating_sub(1)).rev() {
+            backward_prod[j] = backward_prod[j + 1] * (dims[j + 1] as usize);
+        }
+        let n_total = backward_prod[0] * (dims[0] as usize);
+        if let Err(diag) = walk_initializer(init, &backward_prod, 0, 0, n_total) {
+            self.report(diag);
+        }
+    }
+
+    ////////////////////////////////////////////////////////////////////////
+    // Expressions                                                        //
+    ////////////////////////////////////////////////////////////////////////
+
+    /// Check an expression whose value must be an `int`.
+    fn expect_int(&mut self, exp: &Exp) {
+        let ty = self.check_exp(exp);
+        self.expect_int_type(ty, exp.span);
+    }
+
+    fn expect_int_type(&mut self, ty: Type, span: Span) {
+        match ty {
+            Type::Int | Type::Error => (),
+            Type::Void => self.report(
+                Diagnostic::error("the result of a `void` function is used as a value")
+                    .with_primary(span, "this call returns nothing"),
+            ),
+            ty @ Type::Array(_) => self.report(
+                Diagnostic::error("mismatched types")
+                    .with_primary(span, format!("expected `int`, found `{}`", ty)),
+            ),
+        }
+    }
+
+    fn check_exp(&mut self, exp: &Exp) -> Type {
+        self.check_lor_exp(&exp.exp)
+    }
+
+    fn check_lor_exp(&mut self, exp: &LOrExp) -> Type {
+        match exp {
+            LOrExp::LAnd(exp) => self.check_land_exp(exp),
+            LOrExp::LOrLAnd(exp1, exp2) => {
+                let ty = self.check_lor_exp(exp1);
+                self.expect_int_type(ty, exp1.span());
+                let ty = self.check_land_exp(exp2);
+                self.expect_int_type(ty, exp2.span());
+                Type::Int
+            }
+        }
+    }
+
+    fn check_land_exp(&mut self, exp: &LAndExp) -> Type {
+        match exp {
+            LAndExp::Eq(exp) => self.check_eq_exp(exp),
+            LAndExp::LAndEq(exp1, exp2) => {
+                let ty = self.check_land_exp(exp1);
+                self.expect_int_type(ty, exp1.span());
+                let ty = self.check_eq_exp(exp2);
+                self.expect_int_type(ty, exp2.span());
+                Type::Int
+            }
+        }
+    }
+
+    fn check_eq_exp(&mut self, exp: &EqExp) -> Type {
+        match exp {
+            EqExp::Rel(exp) => self.check_rel_exp(exp),
+            EqExp::EqRel(exp1, _, exp2) => {
+                let ty = self.check_eq_exp(exp1);
+                self.expect_int_type(ty, exp1.span());
+                let ty = self.check_rel_exp(exp2);
+                self.expect_int_type(ty, exp2.span());
+                Type::Int
+            }
+        }
+    }
+
+    fn check_rel_exp(&mut self, exp: &RelExp) -> Type {
+        match exp {
+            RelExp::Add(exp) => self.check_add_exp(exp),
+            RelExp::RelAdd(exp1, _, exp2) => {
+                let ty = self.check_rel_exp(exp1);
+                self.expect_int_type(ty, exp1.span());
+                let ty = self.check_add_exp(exp2);
+                self.expect_int_type(ty, exp2.span());
+                Type::Int
+            }
+        }
+    }
+
+    fn check_add_exp(&mut self, exp: &AddExp) -> Type {
+        match exp {
+            AddExp::Mul(exp) => self.check_mul_exp(exp),
+            AddExp::AddMul(exp1, _, exp2) => {
+                let ty = self.check_add_exp(exp1);
+                self.expect_int_type(ty, exp1.span());
+                let ty = self.check_mul_exp(exp2);
+                self.expect_int_type(ty, exp2.span());
+                Type::Int
+            }
+        }
+    }
+
+    fn check_mul_exp(&mut self, exp: &MulExp) -> Type {
+        match exp {
+            MulExp::Unary(exp) => self.check_unary_exp(exp),
+            MulExp::MulUnary(exp1, _, exp2) => {
+                let ty = self.check_mul_exp(exp1);
+                self.expect_int_type(ty, exp1.span());
+                let ty = self.check_unary_exp(exp2);
+                self.expect_int_type(ty, exp2.span());
+                Type::Int
+            }
+        }
+    }
+
+    fn check_unary_exp(&mut self, exp: &UnaryExp) -> Type {
+        match exp {
+            UnaryExp::Primary(exp) => self.check_primary_exp(exp),
+            UnaryExp::Unary(UnaryExpOp::Neg, exp, _) if exp.is_int_min_magnitude() => Type::Int,
+            UnaryExp::Unary(_, exp, _) => {
+                let ty = self.check_unary_exp(exp);
+                self.expect_int_type(ty, exp.span());
+                Type::Int
+            }
+            UnaryExp::FuncCall(ident, args, span) => self.check_func_call(ident, args, *span),
+        }
+    }
+
+    fn check_primary_exp(&mut self, exp: &PrimaryExp) -> Type {
+        match exp {
+            PrimaryExp::Exp(exp) => self.check_exp(exp),
+            PrimaryExp::LVal(lval) => self.check_lval(lval),
+            PrimaryExp::Num(num, span) => match check_int_literal(*num, *span) {
+                Ok(_) => Type::Int,
+                Err(diag) => {
+                    self.report(diag);
+                    Type::Error
+                }
+            },
+        }
+    }
+
+    /// Check the indices of an l-value, and return the type of the element it refers to.
+    fn check_lval(&mut self, lval: &LVal) -> Type {
+        for idx in lval.idx.iter() {
+            self.expect_int(idx);
+        }
+        let Some((symbol, _)) = self.lookup(&lval.ident.name) else {
+            self.report(Self::not_found(&lval.ident));
+            return Type::Error;
+        };
+        let dims = match symbol {
+            Symbol::Const(_) | Symbol::Var => Vec::new(),
+            Symbol::Array { dims, .. } => dims.clone(),
+        };
+        if lval.idx.len() > dims.len() {
+            let ty = if dims.is_empty() {
+                Type::Int
+            } else {
+                Type::Array(dims.clone())
+            };
+            self.report(
+                Diagnostic::error(format!("too many indices for `{}`", lval.ident.name))
+                    .with_primary(
+                        lval.span,
+                        format!("`{}` has type `{}`", lval.ident.name, ty),
+                    ),
+            );
+            return Type::Error;
+        }
+        if lval.idx.len() == dims.len() {
+            Type::Int
+        } else {
+            Type::Array(dims[lval.idx.len()..].to_vec())
+        }
+    }
+
+    fn check_func_call(&mut self, ident: &Ident, args: &[Exp], span: Span) -> Type {
+        let arg_types: Vec<Type> = args.iter().map(|arg| self.check_exp(arg)).collect();
+        let Some(signature) = self.functions.get(&ident.name) else {
+            self.report(
+                Diagnostic::error(format!(
+                    "cannot find function `{}` in this scope",
+                    ident.name
+                ))
+                .with_primary(ident.span, "not found in this scope"),
+            );
+            return Type::Error;
+        };
+        let return_type = if signature.return_void {
+            Type::Void
+        } else {
+            Type::Int
+        };
+
+        let mut diags = Vec::new();
+        if args.len() != signature.params.len() {
+            let plural = |n: usize| if n == 1 { "" } else { "s" };
+            let mut diag = Diagnostic::error(format!(
+                "this function takes {} argument{} but {} argument{} supplied",
+                signature.params.len(),
+                plural(signature.params.len()),
+                args.len(),
+                if args.len() == 1 { " was" } else { "s were" },
+            ))
+            .with_primary(
+                span,
+                format!("wrong number of arguments to `{}`", ident.name),
+            );
+            if let Some(def_span) = signature.span {
+                diag = diag.with_secondary(def_span, format!("`{}` is defined here", ident.name));
+            }
+            diags.push(diag);
+        }
+        for ((arg, ty), param) in args.iter().zip(arg_types).zip(signature.params.iter()) {
+            if ty == Type::Void {
+                diags.push(
+                    Diagnostic::error("the result of a `void` function is used as a value")
+                        .with_primary(arg.span, "this call returns nothing"),
+                );
+            } else if !ty.fits(param) {
+                diags.push(
+                    Diagnostic::error("mismatched types")
+                        .with_primary(arg.span, format!("expected `{}`, found `{}`", param, ty)),
+                );
+            }
+        }
+
+        self.diagnostics.extend(diags);
+        return_type
+    }
+}
+
+impl ConstTable for Checker {
+    fn get_const(&self, lval: &LVal) -> Result<i32, Diagnostic> {
+        let Some((symbol, def_span)) = self.lookup(&lval.ident.name) else {
+            return Err(Self::not_found(&lval.ident));
+        };
+        match symbol {
+            Symbol::Const(v) if lval.idx.is_empty() => Ok(*v),
+            _ => Err(Diagnostic::error(format!(
+                "`{}` cannot be used in a constant expression",
+                lval.ident.name
+            ))
+            .with_primary(lval.span, "not a constant")
+            .with_secondary(*def_span, format!("`{}` is defined here", lval.ident.name))),
+        }
+    }
+}
+
+/// Walk an initializer list whose elements are placed in `[idx, end)` of the flattened array.
+///
+/// A nested list initializes the largest sub-array aligned with its position,
+/// which is the same rule as `array_utils::skip_to_next_aligned`.
+fn walk_initializer(
+    init: &impl Initializer,
+    backward_prod: &[usize],
+    level: usize,
+    idx: usize,
+    end: usize,
+) -> Result<(), Diagnostic> {
+    let Some(elements) = init.elements() else {
+        return Ok(());
+    };
+    let mut current_idx = idx;
+    for element in elements.iter() {
+        let next_idx = match element.elements() {
+            None => current_idx + 1,
+            Some(_) => match backward_prod[level.min(backward_prod.len())..]
+                .iter()
+                .find(|&&p| current_idx.is_multiple_of(p))
+            {
+                Some(p) => current_idx + p,
+                None => {
+                    return Err(
+                        Diagnostic::error("the initializer list is nested too deeply")
+                            .with_primary(element.span(), "no sub-array to initialize"),
+                    );
+                }
+            },
+        };
+        if next_idx > end {
+            return Err(
+                Diagnostic::error("excess elements in the array initializer")
+                    .with_primary(element.span(), "this element does not fit in the array"),
+            );
+        }
+        walk_initializer(element, backward_prod, level + 1, current_idx, next_idx)?;
+        current_idx = next_idx;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast_generate::parse_sysy_to_ast;
+
+    fn check(input: &str) -> Vec<String> {
+        let ast = parse_sysy_to_ast(input).unwrap();
+        match check_ast(&ast) {
+            Ok(()) => Vec::new(),
+            Err(diags) => diags.iter().map(|d| d.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn semantic_valid_test() {
+        let input = r#"
+const int N = 3;
+int g[N][2] = {1, 2, {3}};
+int sum(int a[][2], int n) {
+    int s = 0, i = 0;
+    while (i < n) {
+        if (i == 2) break;
+        s = s + a[i][0] + a[i][1];
+        i = i + 1;
+    }
+    return s;
+}
+void show(int x) {
+    putint(x);
+    return;
+}
+int main() {
+    int arr[4] = {};
+    show(sum(g, N));
+    putarray(4, arr);
+    return -2147483648 + sum(g, 1);
+}
+"#;
+        assert_eq!(check(input), Vec::<String>::new());
+    }
+
+    #[test]
+    fn semantic_report_all_test() {
+        let input = r#"
+const int c = 1;
+int f(int a, int b[]) { return a; }
+void g() {}
+int main() {
+    int x[2][3];
+    int y;
+    int y;
+    c = 2;
+    f(1);
+    f(x, 1);
+    f(1, x);
+    f(1, x[0], 2);
+    y = g();
+    break;
+    return z;
+}
+"#;
+        assert_eq!(
+            check(input),
+            vec![
+                "error: the name `y` is defined multiple times in the same scope (at 8:9)",
+                "error: cannot assign to constant `c` (at 9:5)",
+                "error: this function takes 2 arguments but 1 argument was supplied (at 10:5)",
+                "error: mismatched types (at 11:7)",
+                "error: mismatched types (at 11:10)",
+                "error: mismatched types (at 12:10)",
+                "error: this function takes 2 arguments but 3 arguments were supplied (at 13:5)",
+                "error: the result of a `void` function is used as a value (at 14:9)",
+                "error: `break` outside of a loop (at 15:5)",
+                "error: cannot find value `z` in this scope (at 16:12)",
+            ]
+        );
+    }
+
+    #[test]
+    fn semantic_array_dims_test() {
+        let input = r#"
+void f(int a[][3]) {}
+int main() {
+    int x[2][4];
+    int y[5][3];
+    int z[2][3] = {1, 2, 3, 4, 5, 6, 7};
+    f(y);
+    f(x);
+    f(y[0]);
+    return x[0][0][0];
+}
+"#;
+        assert_eq!(
+            check(input),
+            vec![
+                "error: excess elements in the array initializer (at 6:38)",
+                "error: mismatched types (at 8:7)",
+                "error: mismatched types (at 9:7)",
+                "error: too many indices for `x` (at 10:12)",
+            ]
+        );
+    }
+
+    #[test]
+    fn semantic_prototype_test() {
+        let input = r#"
+int is_odd(int n);
+int is_even(int n) {
+    if (n == 0) return 1;
+    return is_odd(n - 1);
+}
+int is_odd(int n) {
+    if (n == 0) return 0;
+    return is_even(n - 1);
+}
+int main() {
+    return later(is_even(4)) + is_odd(3);
+}
+int later(int x) { return x; }
+"#;
+        assert_eq!(check(input), Vec::<String>::new());
+    }
+
+    #[test]
+    fn semantic_conflicting_prototype_test() {
+        let input = r#"
+int f(int a[]);
+void g();
+int getint();
+void putint(int a[]);
+int f(int a) { return a; }
+void g() {}
+void g() {}
+int main() { return 0; }
+"#;
+        assert_eq!(
+            check(input),
+            vec![
+                "error: conflicting types for `putint` (at 5:6)",
+                "error: conflicting types for `f` (at 6:5)",
+                "error: the function `g` is defined multiple times (at 8:6)",
+            ]
+        );
+    }
+}
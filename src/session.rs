@@ -0,0 +1,289 @@
+use crate::ast_generate::ast::CompUnit;
+use crate::ast_generate::parse_sysy_to_partial_ast;
+use crate::ast_generate::token::{tokenize, SpannedToken};
+use crate::diagnostic::Diagnostic;
+use crate::opt::{self, OptLevel, OptOptions};
+use crate::{ir_generate, semantic, target_generate, Language, Mode, RunError, Stage};
+use koopa::ir::Program;
+
+/// A builder of compilations, which compiles SysY source code (or Koopa text) in memory.
+///
+/// # Examples
+/// ```
+/// use sysy_compiler::{Compiler, Mode, Stage};
+/// let session = Compiler::new(Mode::Riscv).stop_after(Stage::Koopa).compile("int main() { return 0; }");
+/// assert!(session.is_ok());
+/// assert!(session.koopa.unwrap().contains("ret 0"));
+/// assert!(session.riscv.is_none());
+/// ```
+#[derive(Debug, Clone)]
+pub struct Compiler {
+    mode: Mode,
+    language: Language,
+    options: OptOptions,
+    stop_after: Stage,
+    tokens: bool,
+}
+
+impl Compiler {
+    /// Construct a compiler of SysY source code in the mode, which stops after the stage of the mode
+    /// (Koopa or RISC-V), and optimizes at the default level of the mode.
+    pub fn new(mode: Mode) -> Self {
+        Self {
+            mode,
+            language: Language::Sysy,
+            options: OptOptions::default(),
+            stop_after: mode.stage(),
+            tokens: false,
+        }
+    }
+
+    /// Set the language of the input.
+    ///
+    /// A Koopa text skips the stages before `Stage::Koopa`, whose artifacts are always `None`.
+    pub fn language(mut self, language: Language) -> Self {
+        self.language = language;
+        self
+    }
+
+    /// Set the options of the optimizer.
+    pub fn options(mut self, options: OptOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Set the optimization level, overriding the default level of the mode.
+    pub fn opt_level(mut self, level: OptLevel) -> Self {
+        self.options.level = Some(level);
+        self
+    }
+
+    /// Stop the compilation after the stage.
+    pub fn stop_after(mut self, stage: Stage) -> Self {
+        self.stop_after = stage;
+        self
+    }
+
+    /// Keep the tokens of the source code in the session, which are always kept when stopping after `Stage::Tokens`.
+    pub fn keep_tokens(mut self, keep: bool) -> Self {
+        self.tokens = keep;
+        self
+    }
+
+    /// Compile the SysY source code, or the Koopa text.
+    ///
+    /// The compilation stops at the first stage that fails, and the artifacts of the previous stages are kept.
+    pub fn compile(&self, source: &str) -> Session {
+        let mut session = Session {
+            source: source.into(),
+            tokens: None,
+            ast: None,
+            program: None,
+            koopa: None,
+            riscv: None,
+            error: None,
+        };
+        if let Err(e) = self.run(&mut session) {
+            session.error = Some(e);
+        }
+        session
+    }
+
+    fn run(&self, session: &mut Session) -> Result<(), RunError> {
+        let program = match self.language {
+            Language::Sysy => match self.run_frontend(session)? {
+                Some(program) => program,
+                None => return Ok(()),
+            },
+            // the Koopa text may use any feature of the `koopa` crate, e.g. basic block parameters and `undef`
+            Language::Koopa if self.stop_after >= Stage::Koopa => {
+                ir_generate::get_koopa_program(&session.source)
+                    .map_err(RunError::KoopaText2ProgramError)?
+            }
+            Language::Koopa => return Ok(()),
+        };
+        self.run_backend(session, program)
+    }
+
+    /// Run the stages from the tokens to the generation of Koopa program,
+    /// which returns `None` if the compilation stops before the Koopa program is generated.
+    fn run_frontend(&self, session: &mut Session) -> Result<Option<Program>, RunError> {
+        // split the SysY source code into tokens
+        if self.tokens || self.stop_after == Stage::Tokens {
+            let tokens = tokenize(&session.source).map_err(|d| RunError::Sysy2AstError(vec![d]))?;
+            session.tokens = Some(tokens);
+        }
+        if self.stop_after == Stage::Tokens {
+            return Ok(None);
+        }
+
+        // parse the SysY source code and generate the AST, which is partial if there are syntax errors
+        let (ast, errors) = parse_sysy_to_partial_ast(&session.source);
+        session.ast = ast;
+        if !errors.is_empty() {
+            return Err(RunError::Sysy2AstError(errors));
+        }
+        let ast = session.ast.as_ref().unwrap();
+        if self.stop_after == Stage::Ast {
+            return Ok(None);
+        }
+
+        // check the AST before generating IR
+        semantic::check_ast(ast).map_err(RunError::SemanticError)?;
+
+        // scan the AST and build the Koopa program
+        let program =
+            ir_generate::parse_ast_to_koopa_program(ast).map_err(RunError::Ast2KoopaError)?;
+        Ok(Some(program))
+    }
+
+    /// Run the optimization passes on the Koopa program, and then the stages after it.
+    fn run_backend(&self, session: &mut Session, mut program: Program) -> Result<(), RunError> {
+        let level = self
+            .options
+            .level
+            .unwrap_or_else(|| self.mode.default_opt_level());
+        opt::optimize(&mut program, level, &self.options).map_err(RunError::OptimizeError)?;
+        let program = session.program.insert(program);
+        session.koopa = Some(
+            ir_generate::koopa_program_to_text(program)
+                .map_err(RunError::KoopaProgram2TextError)?,
+        );
+        if self.stop_after == Stage::Koopa {
+            return Ok(());
+        }
+
+        // convert the Koopa program to RISC-V text
+        let riscv = target_generate::parse_koopa_program_to_riscv(program)
+            .map_err(RunError::KoopaProgram2RiscvError)?;
+        session.riscv = Some(riscv);
+        Ok(())
+    }
+}
+
+/// The artifacts of a compilation, from the tokens to the RISC-V text.
+///
+/// An artifact is `None` if its stage doesn't run, because the compilation stops before it or fails.
+pub struct Session {
+    /// The SysY source code, or the Koopa text.
+    pub source: String,
+    /// The tokens, only kept if requested.
+    pub tokens: Option<Vec<SpannedToken>>,
+    /// The AST, which is partial if the source code has syntax errors.
+    pub ast: Option<CompUnit>,
+    /// The optimized Koopa program.
+    pub program: Option<Program>,
+    /// The text of the optimized Koopa program.
+    pub koopa: Option<String>,
+    pub riscv: Option<String>,
+    /// The error that fails the compilation.
+    pub error: Option<RunError>,
+}
+
+impl Session {
+    pub fn is_ok(&self) -> bool {
+        self.error.is_none()
+    }
+
+    /// Get the diagnostics of the compilation, whose spans refer to the source code.
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        self.error.as_ref().map_or(&[], |e| e.diagnostics())
+    }
+
+    /// Render the diagnostics, as if the source code was in the file.
+    pub fn render_diagnostics(&self, file_name: &str) -> String {
+        let rendered: Vec<String> = self
+            .diagnostics()
+            .iter()
+            .map(|diag| diag.render(&self.source, file_name))
+            .collect();
+        rendered.join("\n\n")
+    }
+
+    /// Get the result of the stage in the form emitted by the driver, `None` if the stage doesn't run.
+    ///
+    /// The tokens are one per line, and the AST is in the form of `{:#?}`.
+    pub fn emitted(&self, stage: Stage) -> Option<String> {
+        match stage {
+            Stage::Tokens => self
+                .tokens
+                .as_ref()
+                .map(|tokens| tokens.iter().map(|t| format!("{}\n", t)).collect()),
+            Stage::Ast => self.ast.as_ref().map(|ast| format!("{:#?}\n", ast)),
+            Stage::Koopa => self.koopa.clone(),
+            Stage::Riscv => self.riscv.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn session_test() {
+        let source = "int main() { int a = 1; return a + 2; }";
+        let session = Compiler::new(Mode::Koopa).keep_tokens(true).compile(source);
+        assert!(session.is_ok());
+        assert_eq!(session.tokens.as_ref().unwrap().len(), 16);
+        assert!(session.ast.is_some() && session.program.is_some());
+        assert!(session.riscv.is_none());
+
+        let session = Compiler::new(Mode::Riscv)
+            .opt_level(OptLevel::O1)
+            .compile(source);
+        assert!(session.riscv.as_ref().unwrap().contains("main:"));
+        assert!(session.emitted(Stage::Tokens).is_none());
+
+        let session = Compiler::new(Mode::Riscv)
+            .stop_after(Stage::Ast)
+            .compile(source);
+        assert!(session
+            .emitted(Stage::Ast)
+            .unwrap()
+            .starts_with("CompUnit {"));
+        assert!(session.program.is_none());
+    }
+
+    #[test]
+    fn session_error_test() {
+        // the AST is kept without the statement that cannot be parsed
+        let session =
+            Compiler::new(Mode::Riscv).compile("int f() { return 1 }\nint main() { return x; }");
+        assert!(matches!(session.error, Some(RunError::Sysy2AstError(_))));
+        assert_eq!(session.ast.as_ref().unwrap().items.len(), 2);
+        assert!(session.render_diagnostics("a.sy").contains("--> a.sy:1:20"));
+
+        let session = Compiler::new(Mode::Riscv).compile("int main() { return x; }");
+        assert!(matches!(session.error, Some(RunError::SemanticError(_))));
+        assert_eq!(session.diagnostics().len(), 1);
+        assert!(session.ast.is_some() && session.koopa.is_none());
+    }
+
+    #[test]
+    fn session_koopa_test() {
+        // the Koopa text may use basic block parameters and `undef`
+        let source = r#"
+fun @main(): i32 {
+%entry:
+  jump %end(undef, 1)
+%end(%a: i32, %b: i32):
+  ret %b
+}
+"#;
+        let session = Compiler::new(Mode::Koopa)
+            .language(Language::Koopa)
+            .compile(source);
+        assert!(session.is_ok());
+        assert!(session.ast.is_none());
+        assert!(session.koopa.as_ref().unwrap().contains("%end("));
+
+        let session = Compiler::new(Mode::Koopa)
+            .language(Language::Koopa)
+            .compile("fun @main(): i32 {");
+        assert!(matches!(
+            session.error,
+            Some(RunError::KoopaText2ProgramError(_))
+        ));
+    }
+}
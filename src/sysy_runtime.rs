@@ -0,0 +1,125 @@
+/// The functions of the SysY runtime library.
+///
+/// The RISC-V simulator places them at consecutive addresses in this order.
+///
+/// `_sysy_starttime` and `_sysy_stoptime` are what the `starttime` and `stoptime` macros expand to.
+pub const RUNTIME_FUNCTIONS: [&str; 10] = [
+    "getint",
+    "getch",
+    "getarray",
+    "putint",
+    "putch",
+    "putarray",
+    "starttime",
+    "stoptime",
+    "_sysy_starttime",
+    "_sysy_stoptime",
+];
+
+/// The standard input and output seen by the SysY runtime library.
+pub struct SysyIo {
+    stdin: Vec<u8>,
+    pos: usize,
+    stdout: Vec<u8>,
+}
+
+impl SysyIo {
+    /// Construct the I/O with the whole standard input.
+    pub fn new(stdin: &str) -> Self {
+        Self {
+            stdin: stdin.as_bytes().to_vec(),
+            pos: 0,
+            stdout: Vec::new(),
+        }
+    }
+
+    /// Read an integer like `scanf("%d", ...)`.
+    ///
+    /// Leading whitespace is skipped. If there's no integer, return 0 and consume nothing else.
+    pub fn get_int(&mut self) -> i32 {
+        while self.pos < self.stdin.len() && self.stdin[self.pos].is_ascii_whitespace() {
+            self.pos += 1;
+        }
+        let start = self.pos;
+        let mut negative = false;
+        if self.pos < self.stdin.len() && matches!(self.stdin[self.pos], b'+' | b'-') {
+            negative = self.stdin[self.pos] == b'-';
+            self.pos += 1;
+        }
+        let digits_start = self.pos;
+        let mut value = 0i32;
+        while self.pos < self.stdin.len() && self.stdin[self.pos].is_ascii_digit() {
+            value = value
+                .wrapping_mul(10)
+                .wrapping_add((self.stdin[self.pos] - b'0') as i32);
+            self.pos += 1;
+        }
+        if self.pos == digits_start {
+            self.pos = start;
+            return 0;
+        }
+        if negative {
+            value.wrapping_neg()
+        } else {
+            value
+        }
+    }
+
+    /// Read a character like `getchar()`, which returns -1 at the end of the input.
+    pub fn get_ch(&mut self) -> i32 {
+        match self.stdin.get(self.pos) {
+            Some(&c) => {
+                self.pos += 1;
+                c as i32
+            }
+            None => -1,
+        }
+    }
+
+    /// Write an integer like `printf("%d", ...)`.
+    pub fn put_int(&mut self, value: i32) {
+        self.stdout.extend_from_slice(value.to_string().as_bytes());
+    }
+
+    /// Write a character like `putchar(...)`.
+    pub fn put_ch(&mut self, c: i32) {
+        self.stdout.push(c as u8);
+    }
+
+    /// Write an array like `putarray(n, a)`: the length, a colon, the elements and a new line.
+    pub fn put_array(&mut self, values: &[i32]) {
+        self.put_int(values.len() as i32);
+        self.put_ch(b':' as i32);
+        for &value in values {
+            self.put_ch(b' ' as i32);
+            self.put_int(value);
+        }
+        self.put_ch(b'\n' as i32);
+    }
+
+    /// Take the standard output, with invalid UTF-8 replaced.
+    pub fn into_stdout(self) -> String {
+        String::from_utf8_lossy(&self.stdout).into_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sysy_io_test() {
+        let mut io = SysyIo::new("  12 -3\nx");
+        assert_eq!(io.get_int(), 12);
+        assert_eq!(io.get_int(), -3);
+        assert_eq!(io.get_int(), 0);
+        assert_eq!(io.get_ch(), b'x' as i32);
+        assert_eq!(io.get_ch(), -1);
+
+        io.put_int(-42);
+        io.put_ch(b'\n' as i32);
+        io.put_array(&[1, -2]);
+        io.put_array(&[]);
+        assert_eq!(io.into_stdout(), "-42\n2: 1 -2\n0:\n");
+    }
+}
@@ -1,9 +1,12 @@
 mod context;
 mod function_call;
 mod function_scan;
+mod machine;
+mod register_allocation;
 mod riscv_generate;
 mod value_location;
 
+use crate::diagnostic::Diagnostic;
 use context::ProgramContext;
 use koopa::ir::*;
 use riscv_generate::RiscvGenerate;
@@ -11,13 +14,81 @@ use riscv_generate::RiscvGenerate;
 /// Convert the Koopa program to RISC-V text.
 ///
 /// The only argument is a reference to the Koopa program (i.e. `&Program`), which is defined in the `koopa` crate.
-/// If an error occurs, `Err(())` is returned.
+/// If an error occurs, a `Diagnostic` without any source span is returned.
 /// Otherwise, return the RISC-V text wrapped by `Ok`.
 ///
 /// # Errors
 /// An error may occur when the Koopa program is not valid.
-pub fn parse_koopa_program_to_riscv(program: &Program) -> Result<String, ()> {
-    let mut text = String::new();
-    program.generate(&mut text, &mut ProgramContext::new(program))?;
-    Ok(text)
+pub fn parse_koopa_program_to_riscv(program: &Program) -> Result<String, Diagnostic> {
+    let machine_program = program.generate(&mut Vec::new(), &mut ProgramContext::new(program))?;
+    Ok(machine_program.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ir_generate::get_koopa_program;
+    use crate::koopa_interpret::interpret_koopa;
+    use crate::riscv_simulate::simulate_riscv;
+
+    #[test]
+    fn lower_ssa_koopa_test() {
+        // basic block parameters on both edges of a branch, `undef`, arrays stored as a whole,
+        // pointers passed as arguments, and basic block names that are reused or are not valid labels
+        let text = r#"
+decl @putint(i32)
+decl @putch(i32)
+global @g = alloc [i32, 3], undef
+
+fun @swap(%n: i32): i32 {
+%entry:
+  jump %0(1, 2, 0)
+%0(%a: i32, %b: i32, %i: i32):
+  %c = lt %i, %n
+  br %c, %1(%b, %a), %end(%a, %b)
+%1(%x: i32, %y: i32):
+  %i2 = add %i, 1
+  jump %0(%x, %y, %i2)
+%end(%p: i32, %q: i32):
+  %r = mul %p, 10
+  %s = add %r, %q
+  ret %s
+}
+
+fun @main(): i32 {
+%entry:
+  %arr = alloc [i32, 4]
+  store {1, 2, 3, 4}, %arr
+  %z = alloc [i32, 2]
+  store zeroinit, %z
+  jump %swap(%arr, undef)
+%swap(%p: *[i32, 4], %u: i32):
+  %e = getelemptr %p, 2
+  %v = load %e
+  call @putint(%v)
+  %q = getptr %z, 0
+  %f = getelemptr %q, 1
+  %w = load %f
+  call @putint(%w)
+  %s = call @swap(3)
+  call @putint(%s)
+  %g1 = getelemptr @g, 1
+  store %u, %g1
+  store 5, %g1
+  %gv = load %g1
+  call @putint(%gv)
+  call @putch(10)
+  ret %v
+}
+"#;
+        let program = get_koopa_program(text).unwrap();
+        let riscv = parse_koopa_program_to_riscv(&program).unwrap();
+        let expected = interpret_koopa(&program, "").unwrap();
+        let result = simulate_riscv(&riscv, "").unwrap();
+        assert_eq!(expected.stdout, "30215\n");
+        assert_eq!(
+            (result.stdout, result.exit_code),
+            (expected.stdout, expected.exit_code)
+        );
+    }
 }
@@ -1,7 +1,9 @@
 use super::function_scan::*;
+use super::machine::{MachineBlock, MachineInst, Reg};
 use super::value_location::*;
+use crate::diagnostic::Diagnostic;
 use koopa::ir::entities::*;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 /// Context information used during RISC-V assembly generation.
 pub struct ProgramContext<'a> {
@@ -11,6 +13,16 @@ pub struct ProgramContext<'a> {
     pub func: Option<FunctionScanResult>,
     // The locations of the global values.
     pub global_values: HashMap<Value, ValueLocation>,
+    /// The labels in use, including the names of the functions and the global values.
+    pub labels: HashSet<String>,
+    /// The labels of the basic blocks in the current function.
+    pub bb_labels: HashMap<BasicBlock, String>,
+    /// The register the result of the current instruction is computed to.
+    pub target: Reg,
+    /// The epilogue of the current function, which is inserted before each `ret`.
+    pub epilogue: Vec<MachineInst>,
+    /// The edges split by the branch of the current basic block, which are placed after it.
+    pub edge_blocks: Vec<MachineBlock>,
 }
 
 #[allow(dead_code)]
@@ -21,15 +33,55 @@ impl<'a> ProgramContext<'a> {
             program,
             func: None,
             global_values: HashMap::new(),
+            labels: HashSet::new(),
+            bb_labels: HashMap::new(),
+            target: Reg::T(0),
+            epilogue: Vec::new(),
+            edge_blocks: Vec::new(),
+        }
+    }
+
+    /// Reserve a label that is not in use yet, which is `base` itself if possible.
+    pub fn fresh_label(&mut self, base: &str) -> String {
+        let mut label = String::from(base);
+        let mut i = 0;
+        while self.labels.contains(&label) {
+            label = format!("{}_{}", base, i);
+            i += 1;
+        }
+        self.labels.insert(label.clone());
+        label
+    }
+
+    /// Give a label to each basic block of the function.
+    ///
+    /// The label is the Koopa name of the basic block without its "%" or "@" prefix,
+    /// unless the name is already used (e.g. by another function of a Koopa text) or it's not a valid label.
+    pub fn assign_basic_block_labels(&mut self, func_data: &FunctionData) {
+        self.bb_labels.clear();
+        for &bb in func_data.layout().bbs().keys() {
+            let name = func_data
+                .dfg()
+                .bb(bb)
+                .name()
+                .as_deref()
+                .map(|name| &name[1..]);
+            let base = match name {
+                Some(name) if name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') => {
+                    String::from(name)
+                }
+                Some(name) => format!("bb{}", name),
+                None => String::from("bb"),
+            };
+            let label = self.fresh_label(&base);
+            self.bb_labels.insert(bb, label);
         }
     }
 
     /// Get the `FunctionData` corresponding to the `func` field of the struct.
     pub fn get_current_function_data(&self) -> Option<&FunctionData> {
-        let Some(FunctionScanResult{func: cur_func, .. }) = self.func else {
-            return None;
-        };
-        Some(self.program.func(cur_func))
+        let FunctionScanResult { func: cur_func, .. } = self.func.as_ref()?;
+        Some(self.program.func(*cur_func))
     }
 
     /// Get the `FunctionData` corresponding to the given `Function` handler.
@@ -42,10 +94,7 @@ impl<'a> ProgramContext<'a> {
     /// 1. The current function is stored in `self.func`.
     /// 2. The return value has been ceiled up to 16 bytes.
     pub fn get_current_stack_frame_size(&self) -> Option<usize> {
-        let Some(FunctionScanResult{stack_frame_size, .. }) = self.func else {
-            return None;
-        };
-        Some(stack_frame_size)
+        self.func.as_ref().map(|f| f.stack_frame_size)
     }
 
     /// Given a `Value` handler, return the corresponding `ValueData` in the current program context or that of a global value.
@@ -57,9 +106,7 @@ impl<'a> ProgramContext<'a> {
             return Some(self.program.borrow_value(val).clone());
         }
 
-        let Some(cur_func_data) = self.get_current_function_data() else {
-            return None;
-        };
+        let cur_func_data = self.get_current_function_data()?;
         Some(cur_func_data.dfg().value(val).clone())
     }
 
@@ -70,51 +117,67 @@ impl<'a> ProgramContext<'a> {
         &self,
         bb: BasicBlock,
     ) -> Option<&BasicBlockData> {
-        let Some(cur_func_data) = self.get_current_function_data() else {
-            return None;
-        };
+        let cur_func_data = self.get_current_function_data()?;
         Some(cur_func_data.dfg().bb(bb))
     }
 
+    /// Given a `BasicBlock` handler, return the label of the basic block in the RISC-V code.
+    ///
+    /// The labels are assigned by `assign_basic_block_labels`.
+    pub fn get_basic_block_name_in_current_function(
+        &self,
+        bb: BasicBlock,
+    ) -> Result<String, Diagnostic> {
+        match self.bb_labels.get(&bb) {
+            Some(label) => Ok(label.clone()),
+            None => Err(Diagnostic::error(
+                "a basic block is missing from the current function",
+            )),
+        }
+    }
+
     /// Given a `Value`, get its location in the "current function" or that of a global value.
     ///
     /// First, search in the current function. If the value is not found, search in the global values.
     /// Actually, the order of searching does not matter, since the `Value` handler is unique.
     pub fn get_value_location_local_or_global(&self, val: Value) -> Option<ValueLocation> {
-        let Some(cur_func) = &self.func else {
-            return None;
-        };
-        let op_loc = cur_func.value_locations.get(&val);
-        match op_loc {
-            Some(loc) => Some(loc.clone()),
-            None => self.global_values.get(&val).map(|loc| loc.clone()),
-        }
+        let cur_func = self.func.as_ref()?;
+        cur_func
+            .value_locations
+            .get(&val)
+            .or_else(|| self.global_values.get(&val))
+            .cloned()
     }
 
-    pub fn add_global_value(&mut self, val: Value, loc: ValueLocation) -> Result<(), ()> {
-        if let Some(_) = self.global_values.insert(val, loc) {
-            return Err(());
+    pub fn add_global_value(&mut self, val: Value, loc: ValueLocation) -> Result<(), Diagnostic> {
+        if self.global_values.insert(val, loc).is_some() {
+            return Err(Diagnostic::error("a global value is allocated twice"));
         };
         Ok(())
     }
 
     /// Whether the `Value`'s `ValueLocation` contains a **pointer** to:
     /// 1. the data that a Koopa symbol refers to
-    /// or
+    ///    or
     /// 2. the data that a Koopa pointer points to
+    ///
     /// rather than containing the **data themselves**.
     ///
     /// If true, we cannot load or store the `Value` directly.
-    pub fn location_of_value_contain_pointer(&self, val: Value) -> Result<bool, ()> {
+    pub fn location_of_value_contain_pointer(&self, val: Value) -> Result<bool, Diagnostic> {
         if self.global_values.contains_key(&val) {
             return Ok(false);
         };
 
         let Some(ref f) = self.func else {
-            return Err(());
+            return Err(Diagnostic::error(
+                "a local value is used outside of any function",
+            ));
         };
         let Some(&ans) = f.contain_pointer.get(&val) else {
-            return Err(());
+            return Err(Diagnostic::error(
+                "the value is used, but it is neither an instruction nor a global value",
+            ));
         };
 
         Ok(ans)
@@ -1,19 +1,23 @@
+use super::machine::{Mem, Reg};
 use super::value_location::ValueLocation;
 
 /// Get the location of the i-th argument of a function.
-/// 
+///
 /// If `call_another` is true, find the location of the parameter of another function being called now.
 /// If false, find the location of the parameter of the current function.
-pub fn function_arg_location(i: usize, stack_frame_size: usize, call_another: bool) -> ValueLocation {
+pub fn function_arg_location(
+    i: usize,
+    stack_frame_size: usize,
+    call_another: bool,
+) -> ValueLocation {
     if i < 8 {
-        ValueLocation::Reg(format!("a{}", i))
+        ValueLocation::Reg(Reg::A(i as u8))
     } else {
         if call_another {
-            ValueLocation::Stack(format!("{}(sp)", 4 * (i - 8)))
+            ValueLocation::Stack(Mem::sp(4 * (i - 8)))
         } else {
-            ValueLocation::Stack(format!("{}(sp)", 4 * (i - 8) + stack_frame_size))
+            ValueLocation::Stack(Mem::sp(4 * (i - 8) + stack_frame_size))
         }
-        
     }
 }
 
@@ -25,61 +29,61 @@ mod tests {
     fn function_arg_location_test() {
         assert_eq!(
             function_arg_location(0, 16, true),
-            ValueLocation::Reg("a0".into())
+            ValueLocation::Reg(Reg::A(0))
         );
         assert_eq!(
             function_arg_location(1, 16, false),
-            ValueLocation::Reg("a1".into())
+            ValueLocation::Reg(Reg::A(1))
         );
         assert_eq!(
             function_arg_location(2, 16, false),
-            ValueLocation::Reg("a2".into())
+            ValueLocation::Reg(Reg::A(2))
         );
         assert_eq!(
             function_arg_location(3, 16, true),
-            ValueLocation::Reg("a3".into())
+            ValueLocation::Reg(Reg::A(3))
         );
         assert_eq!(
             function_arg_location(4, 16, true),
-            ValueLocation::Reg("a4".into())
+            ValueLocation::Reg(Reg::A(4))
         );
         assert_eq!(
             function_arg_location(5, 16, false),
-            ValueLocation::Reg("a5".into())
+            ValueLocation::Reg(Reg::A(5))
         );
         assert_eq!(
             function_arg_location(6, 16, false),
-            ValueLocation::Reg("a6".into())
+            ValueLocation::Reg(Reg::A(6))
         );
         assert_eq!(
             function_arg_location(7, 16, false),
-            ValueLocation::Reg("a7".into())
+            ValueLocation::Reg(Reg::A(7))
         );
 
         assert_eq!(
             function_arg_location(8, 16, false),
-            ValueLocation::Stack("16(sp)".into())
+            ValueLocation::Stack(Mem::sp(16))
         );
         assert_eq!(
             function_arg_location(9, 16, false),
-            ValueLocation::Stack("20(sp)".into())
+            ValueLocation::Stack(Mem::sp(20))
         );
         assert_eq!(
             function_arg_location(10, 16, false),
-            ValueLocation::Stack("24(sp)".into())
+            ValueLocation::Stack(Mem::sp(24))
         );
 
         assert_eq!(
             function_arg_location(8, 16, true),
-            ValueLocation::Stack("0(sp)".into())
+            ValueLocation::Stack(Mem::sp(0))
         );
         assert_eq!(
             function_arg_location(9, 16, true),
-            ValueLocation::Stack("4(sp)".into())
+            ValueLocation::Stack(Mem::sp(4))
         );
         assert_eq!(
             function_arg_location(10, 16, true),
-            ValueLocation::Stack("8(sp)".into())
+            ValueLocation::Stack(Mem::sp(8))
         );
     }
 }
@@ -3,8 +3,10 @@ use koopa::ir::entities::*;
 use koopa::ir::*;
 use std::collections::HashMap;
 
-use super::function_call::function_arg_location;
+use super::machine::{Mem, Reg};
+use super::register_allocation::*;
 use super::value_location::*;
+use crate::diagnostic::Diagnostic;
 
 /// The result of function scanning.
 pub struct FunctionScanResult {
@@ -14,14 +16,16 @@ pub struct FunctionScanResult {
     pub func: Function,
     /// The locations of the values in the function.
     ///
-    /// During scanning, the addresses of all the variables are decided.
-    /// Each of them has a **unique** location on the stack!
+    /// During scanning, the homes of all the values are decided.
+    /// Each `alloc` has a stack slot of its own,
+    /// and every other value with a result lives in a register or, under register pressure, in a spill slot.
     /// Notice that `Value` has implemented the `Copy` trait!
     pub value_locations: HashMap<Value, ValueLocation>,
     /// Whether the `Value`'s `ValueLocation` contains a pointer to:
     /// 1. the data that a Koopa symbol refers to
-    /// or
+    ///    or
     /// 2. the data that a Koopa pointer points to
+    ///
     /// rather than containing these data themselves.
     ///
     /// If true, we cannot load or store the `Value` directly.
@@ -34,11 +38,17 @@ pub struct FunctionScanResult {
     ///
     /// `None` if the function does not call other functions.
     pub ra_slot_location: Option<ValueLocation>,
+    /// The callee-saved registers used by the function, and the slots they are saved in.
+    pub callee_saved_slots: Vec<(Reg, ValueLocation)>,
 }
 
 impl FunctionScanResult {
     /// Scan the function and yield a `FunctionScanResult`.
-    pub fn try_from(func: Function, func_data: &FunctionData) -> Result<Self, ()> {
+    ///
+    /// The stack frame is laid out from the bottom as:
+    /// the arguments of the callees passed on the stack, the `alloc`s, the spill slots,
+    /// the saved callee-saved registers and the return address.
+    pub fn try_from(func: Function, func_data: &FunctionData) -> Result<Self, Diagnostic> {
         let mut n_local_var = 0usize;
         let mut n_param_on_stack = 0usize;
         let mut has_call = false;
@@ -52,29 +62,50 @@ impl FunctionScanResult {
             &mut has_call,
             None,
         )?;
+        let allocation = allocate_registers(func_data);
+        let n_callee_saved = allocation.used_callee_saved.len();
 
         let stack_frame_size = ceil_to_k(
-            (n_param_on_stack + n_local_var + (has_call as usize)) * 4,
+            (n_param_on_stack
+                + n_local_var
+                + allocation.n_spill_slots
+                + n_callee_saved
+                + (has_call as usize))
+                * 4,
             16usize,
         );
         let mut ra_slot_location = None;
         if has_call {
-            ra_slot_location = Some(ValueLocation::Stack(format!(
-                "{}(sp)",
-                stack_frame_size - 4
-            )));
+            ra_slot_location = Some(ValueLocation::Stack(Mem::sp(stack_frame_size - 4)));
         }
+        let slot_location =
+            |slot: usize| ValueLocation::Stack(Mem::sp(4 * (n_param_on_stack + slot)));
+
         let mut value_locations: HashMap<Value, ValueLocation> = value_slots
             .into_iter()
-            .map(|(k, v)| {
-                let loc = 4 * (v + n_param_on_stack);
-                (k, ValueLocation::Stack(format!("{}(sp)", loc)))
-            })
+            .map(|(k, v)| (k, slot_location(v)))
             .collect();
-        // we have to add the function parameters to the `value_locations` map
-        for (i, param) in func_data.params().iter().enumerate() {
-            value_locations.insert(*param, function_arg_location(i, stack_frame_size, false));
+        for (&val, &home) in allocation.homes.iter() {
+            let loc = match home {
+                Home::Reg(reg) => ValueLocation::Reg(reg),
+                Home::Spill(i) => slot_location(n_local_var + i),
+            };
+            value_locations.insert(val, loc);
+            // the value itself is in its home, which is a pointer if the value is one
+            let is_pointer = matches!(func_data.dfg().value(val).ty().kind(), TypeKind::Pointer(_));
+            contain_pointer.insert(val, is_pointer);
         }
+        let callee_saved_slots = allocation
+            .used_callee_saved
+            .iter()
+            .enumerate()
+            .map(|(i, &reg)| {
+                (
+                    reg,
+                    slot_location(n_local_var + allocation.n_spill_slots + i),
+                )
+            })
+            .collect();
 
         Ok(Self {
             func,
@@ -82,6 +113,7 @@ impl FunctionScanResult {
             contain_pointer,
             stack_frame_size,
             ra_slot_location,
+            callee_saved_slots,
         })
     }
 }
@@ -89,10 +121,14 @@ impl FunctionScanResult {
 /// Scan the function and update some fields.
 ///
 /// The implementation should update the following fields:
-/// * `value_slots`: the slot id (order number) of each value. This field should be updated by `FunctionData`'s implementation only!
-/// * `n_local_var`: the number of local variables.
+/// * `value_slots`: the slot id (order number) of each `alloc`. This field should be updated by `FunctionData`'s implementation only!
+/// * `contain_pointer`: whether the location of each `alloc` contains a pointer (which is always false).
+/// * `n_local_var`: the number of slots of local variables.
 /// * `n_param_on_stack`: the number of function parameters on the stack. Updated only by the implementation of `Call`.
 /// * `has_call`: whether the function calls other functions. Updated only by the implementation of `Call`.
+///
+/// Only the `alloc`s get stack slots here.
+/// The results of the other instructions are allocated a register (or a spill slot) by register allocation.
 trait FunctionScan {
     type Ret;
 
@@ -104,7 +140,7 @@ trait FunctionScan {
         n_param_on_stack: &mut usize,
         has_call: &mut bool,
         value_data: Option<&ValueData>,
-    ) -> Result<Self::Ret, ()>;
+    ) -> Result<Self::Ret, Diagnostic>;
 }
 
 impl FunctionScan for FunctionData {
@@ -118,7 +154,7 @@ impl FunctionScan for FunctionData {
         n_param_on_stack: &mut usize,
         has_call: &mut bool,
         _value_data: Option<&ValueData>,
-    ) -> Result<Self::Ret, ()> {
+    ) -> Result<Self::Ret, Diagnostic> {
         for (_bb, node) in self.layout().bbs() {
             for &inst_val in node.insts().keys() {
                 let inst_val_data = self.dfg().value(inst_val);
@@ -130,20 +166,10 @@ impl FunctionScan for FunctionData {
                     has_call,
                     None,
                 )?;
-                match loc {
-                    Some(o) => {
-                        // the instruction yields a new value
-                        value_slots.insert(inst_val, o);
-                        match inst_val_data.kind() {
-                            ValueKind::GetElemPtr(_) | ValueKind::GetPtr(_) => {
-                                contain_pointer.insert(inst_val, true);
-                            }
-                            _ => {
-                                contain_pointer.insert(inst_val, false);
-                            }
-                        }
-                    }
-                    None => (),
+                if let Some(o) = loc {
+                    // the instruction is an `alloc`, whose slots contain the data
+                    value_slots.insert(inst_val, o);
+                    contain_pointer.insert(inst_val, false);
                 }
             }
         }
@@ -163,7 +189,7 @@ impl FunctionScan for ValueData {
         n_param_on_stack: &mut usize,
         has_call: &mut bool,
         _value_data: Option<&ValueData>,
-    ) -> Result<Self::Ret, ()> {
+    ) -> Result<Self::Ret, Diagnostic> {
         match self.kind() {
             ValueKind::Alloc(val) => val.scan(
                 value_slots,
@@ -247,7 +273,10 @@ impl FunctionScan for ValueData {
             ),
 
             // others: unreachable
-            _ => Err(()),
+            kind => Err(
+                Diagnostic::error("unsupported Koopa instruction in a function body")
+                    .with_note(format!("found {:?}", kind)),
+            ),
         }
     }
 }
@@ -263,9 +292,11 @@ impl FunctionScan for values::Alloc {
         _n_param_on_stack: &mut usize,
         _has_call: &mut bool,
         value_data: Option<&ValueData>,
-    ) -> Result<Self::Ret, ()> {
+    ) -> Result<Self::Ret, Diagnostic> {
         let Some(value_data) = value_data else {
-            return Err(());
+            return Err(Diagnostic::error(
+                "`alloc` is scanned without its value data",
+            ));
         };
         let old_n_local_var = *n_local_var;
 
@@ -286,13 +317,12 @@ impl FunctionScan for values::Load {
         &self,
         _value_slots: &mut HashMap<Value, usize>,
         _contain_pointer: &mut HashMap<Value, bool>,
-        n_local_var: &mut usize,
+        _n_local_var: &mut usize,
         _n_param_on_stack: &mut usize,
         _has_call: &mut bool,
         _value_data: Option<&ValueData>,
-    ) -> Result<Self::Ret, ()> {
-        *n_local_var += 1;
-        Ok(Some(*n_local_var - 1))
+    ) -> Result<Self::Ret, Diagnostic> {
+        Ok(None)
     }
 }
 
@@ -307,7 +337,7 @@ impl FunctionScan for values::Store {
         _n_param_on_stack: &mut usize,
         _has_call: &mut bool,
         _value_data: Option<&ValueData>,
-    ) -> Result<Self::Ret, ()> {
+    ) -> Result<Self::Ret, Diagnostic> {
         Ok(None)
     }
 }
@@ -319,13 +349,12 @@ impl FunctionScan for values::GetElemPtr {
         &self,
         _value_slots: &mut HashMap<Value, usize>,
         _contain_pointer: &mut HashMap<Value, bool>,
-        n_local_var: &mut usize,
+        _n_local_var: &mut usize,
         _n_param_on_stack: &mut usize,
         _has_call: &mut bool,
         _value_data: Option<&ValueData>,
-    ) -> Result<Self::Ret, ()> {
-        *n_local_var += 1;
-        Ok(Some(*n_local_var - 1))
+    ) -> Result<Self::Ret, Diagnostic> {
+        Ok(None)
     }
 }
 
@@ -336,13 +365,12 @@ impl FunctionScan for values::GetPtr {
         &self,
         _value_slots: &mut HashMap<Value, usize>,
         _contain_pointer: &mut HashMap<Value, bool>,
-        n_local_var: &mut usize,
+        _n_local_var: &mut usize,
         _n_param_on_stack: &mut usize,
         _has_call: &mut bool,
         _value_data: Option<&ValueData>,
-    ) -> Result<Self::Ret, ()> {
-        *n_local_var += 1;
-        Ok(Some(*n_local_var - 1))
+    ) -> Result<Self::Ret, Diagnostic> {
+        Ok(None)
     }
 }
 
@@ -353,13 +381,12 @@ impl FunctionScan for values::Binary {
         &self,
         _value_slots: &mut HashMap<Value, usize>,
         _contain_pointer: &mut HashMap<Value, bool>,
-        n_local_var: &mut usize,
+        _n_local_var: &mut usize,
         _n_param_on_stack: &mut usize,
         _has_call: &mut bool,
         _value_data: Option<&ValueData>,
-    ) -> Result<Self::Ret, ()> {
-        *n_local_var += 1;
-        Ok(Some(*n_local_var - 1))
+    ) -> Result<Self::Ret, Diagnostic> {
+        Ok(None)
     }
 }
 
@@ -374,7 +401,7 @@ impl FunctionScan for values::Branch {
         _n_param_on_stack: &mut usize,
         _has_call: &mut bool,
         _value_data: Option<&ValueData>,
-    ) -> Result<Self::Ret, ()> {
+    ) -> Result<Self::Ret, Diagnostic> {
         Ok(None)
     }
 }
@@ -390,7 +417,7 @@ impl FunctionScan for values::Jump {
         _n_param_on_stack: &mut usize,
         _has_call: &mut bool,
         _value_data: Option<&ValueData>,
-    ) -> Result<Self::Ret, ()> {
+    ) -> Result<Self::Ret, Diagnostic> {
         Ok(None)
     }
 }
@@ -402,20 +429,18 @@ impl FunctionScan for values::Call {
         &self,
         _value_slots: &mut HashMap<Value, usize>,
         _contain_pointer: &mut HashMap<Value, bool>,
-        n_local_var: &mut usize,
+        _n_local_var: &mut usize,
         n_param_on_stack: &mut usize,
         has_call: &mut bool,
         _value_data: Option<&ValueData>,
-    ) -> Result<Self::Ret, ()> {
-        *has_call = *has_call || true;
+    ) -> Result<Self::Ret, Diagnostic> {
+        *has_call = true;
 
         let n_args = self.args().len();
-        let on_stack = n_args.checked_sub(8).unwrap_or(0);
+        let on_stack = n_args.saturating_sub(8);
         *n_param_on_stack = std::cmp::max(*n_param_on_stack, on_stack);
 
-        // Whether the function returns a value or not, we allocate a slot for the return value.
-        *n_local_var += 1;
-        Ok(Some(*n_local_var - 1))
+        Ok(None)
     }
 }
 
@@ -430,7 +455,7 @@ impl FunctionScan for values::Return {
         _n_param_on_stack: &mut usize,
         _has_call: &mut bool,
         _value_data: Option<&ValueData>,
-    ) -> Result<Self::Ret, ()> {
+    ) -> Result<Self::Ret, Diagnostic> {
         Ok(None)
     }
 }
@@ -0,0 +1,377 @@
+use std::fmt;
+
+/// A RISC-V register.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Reg {
+    Zero,
+    Ra,
+    Sp,
+    /// `t0`-`t6`.
+    T(u8),
+    /// `a0`-`a7`.
+    A(u8),
+    /// `s0`-`s11`.
+    S(u8),
+}
+
+impl fmt::Display for Reg {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Zero => write!(f, "zero"),
+            Self::Ra => write!(f, "ra"),
+            Self::Sp => write!(f, "sp"),
+            Self::T(i) => write!(f, "t{}", i),
+            Self::A(i) => write!(f, "a{}", i),
+            Self::S(i) => write!(f, "s{}", i),
+        }
+    }
+}
+
+/// A memory operand, i.e. `offset(base)`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Mem {
+    pub base: Reg,
+    pub offset: i32,
+}
+
+impl Mem {
+    /// A slot in the stack frame.
+    pub fn sp(offset: usize) -> Self {
+        Self {
+            base: Reg::Sp,
+            offset: offset as i32,
+        }
+    }
+}
+
+impl fmt::Display for Mem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}({})", self.offset, self.base)
+    }
+}
+
+/// The operation of an instruction with two source registers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    And,
+    Or,
+    Xor,
+    Sll,
+    Srl,
+    Sra,
+    Slt,
+    Sgt,
+}
+
+impl fmt::Display for BinaryOp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Self::Add => "add",
+            Self::Sub => "sub",
+            Self::Mul => "mul",
+            Self::Div => "div",
+            Self::Rem => "rem",
+            Self::And => "and",
+            Self::Or => "or",
+            Self::Xor => "xor",
+            Self::Sll => "sll",
+            Self::Srl => "srl",
+            Self::Sra => "sra",
+            Self::Slt => "slt",
+            Self::Sgt => "sgt",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// A RISC-V instruction (or pseudo instruction) of the generated code.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MachineInst {
+    Li {
+        rd: Reg,
+        imm: i32,
+    },
+    /// Load the address of a symbol.
+    La {
+        rd: Reg,
+        symbol: String,
+    },
+    Mv {
+        rd: Reg,
+        rs: Reg,
+    },
+    Lw {
+        rd: Reg,
+        mem: Mem,
+    },
+    Sw {
+        rs: Reg,
+        mem: Mem,
+    },
+    Addi {
+        rd: Reg,
+        rs: Reg,
+        imm: i32,
+    },
+    Binary {
+        op: BinaryOp,
+        rd: Reg,
+        rs1: Reg,
+        rs2: Reg,
+    },
+    Seqz {
+        rd: Reg,
+        rs: Reg,
+    },
+    Snez {
+        rd: Reg,
+        rs: Reg,
+    },
+    Beqz {
+        rs: Reg,
+        label: String,
+    },
+    Bnez {
+        rs: Reg,
+        label: String,
+    },
+    J {
+        label: String,
+    },
+    Call {
+        func: String,
+    },
+    Ret,
+}
+
+impl fmt::Display for MachineInst {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Li { rd, imm } => write!(f, "li {}, {}", rd, imm),
+            Self::La { rd, symbol } => write!(f, "la {}, {}", rd, symbol),
+            Self::Mv { rd, rs } => write!(f, "mv {}, {}", rd, rs),
+            Self::Lw { rd, mem } => write!(f, "lw {}, {}", rd, mem),
+            Self::Sw { rs, mem } => write!(f, "sw {}, {}", rs, mem),
+            Self::Addi { rd, rs, imm } => write!(f, "addi {}, {}, {}", rd, rs, imm),
+            Self::Binary { op, rd, rs1, rs2 } => write!(f, "{} {}, {}, {}", op, rd, rs1, rs2),
+            Self::Seqz { rd, rs } => write!(f, "seqz {}, {}", rd, rs),
+            Self::Snez { rd, rs } => write!(f, "snez {}, {}", rd, rs),
+            Self::Beqz { rs, label } => write!(f, "beqz {}, {}", rs, label),
+            Self::Bnez { rs, label } => write!(f, "bnez {}, {}", rs, label),
+            Self::J { label } => write!(f, "j {}", label),
+            Self::Call { func } => write!(f, "call {}", func),
+            Self::Ret => write!(f, "ret"),
+        }
+    }
+}
+
+/// An item of the initializer of a global value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DataItem {
+    /// `.word`
+    Word(i32),
+    /// `.zero`, whose size is in bytes.
+    Zero(usize),
+}
+
+/// A global value in the `.data` section.
+#[derive(Debug, Clone)]
+pub struct MachineGlobal {
+    pub name: String,
+    pub init: Vec<DataItem>,
+}
+
+/// A basic block of machine instructions, which starts with a label.
+#[derive(Debug, Clone)]
+pub struct MachineBlock {
+    pub label: String,
+    pub insts: Vec<MachineInst>,
+}
+
+/// A function, whose prologue runs before the first basic block.
+#[derive(Debug, Clone)]
+pub struct MachineFunction {
+    pub name: String,
+    pub prologue: Vec<MachineInst>,
+    pub blocks: Vec<MachineBlock>,
+}
+
+impl MachineFunction {
+    /// Remove the jumps to the next basic block, which is reached by falling through.
+    ///
+    /// A conditional branch to the next basic block followed by a jump is inverted,
+    /// so that it branches to the target of the jump instead.
+    pub fn remove_fallthrough_jumps(&mut self) {
+        for i in 1..self.blocks.len() {
+            let next = self.blocks[i].label.clone();
+            let insts = &mut self.blocks[i - 1].insts;
+            let branch = match &insts[..] {
+                [.., MachineInst::Bnez { rs, label }, MachineInst::J { label: target }]
+                    if *label == next =>
+                {
+                    Some(MachineInst::Beqz {
+                        rs: *rs,
+                        label: target.clone(),
+                    })
+                }
+                [.., MachineInst::Beqz { rs, label }, MachineInst::J { label: target }]
+                    if *label == next =>
+                {
+                    Some(MachineInst::Bnez {
+                        rs: *rs,
+                        label: target.clone(),
+                    })
+                }
+                [.., MachineInst::J { label }] if *label == next => None,
+                _ => continue,
+            };
+            insts.pop();
+            if let Some(branch) = branch {
+                insts.pop();
+                insts.push(branch);
+            }
+        }
+    }
+}
+
+/// A program of machine instructions, which is printed to RISC-V text at the end of the code generation.
+#[derive(Debug, Clone, Default)]
+pub struct MachineProgram {
+    pub globals: Vec<MachineGlobal>,
+    pub functions: Vec<MachineFunction>,
+}
+
+/// Print the instructions, one per line.
+#[cfg(test)]
+pub fn insts_to_text(insts: &[MachineInst]) -> String {
+    let lines: Vec<String> = insts.iter().map(|inst| format!("  {}", inst)).collect();
+    lines.join("\n")
+}
+
+impl fmt::Display for MachineProgram {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for global in self.globals.iter() {
+            writeln!(f, "  .data")?;
+            writeln!(f, "  .globl {}", global.name)?;
+            writeln!(f, "{}:", global.name)?;
+            for item in global.init.iter() {
+                match item {
+                    DataItem::Word(w) => writeln!(f, "  .word {}", w)?,
+                    DataItem::Zero(size) => writeln!(f, "  .zero {}", size)?,
+                }
+            }
+            writeln!(f)?;
+        }
+        for func in self.functions.iter() {
+            writeln!(f, "  .text")?;
+            writeln!(f, "  .globl {}", func.name)?;
+            writeln!(f, "{}:", func.name)?;
+            for inst in func.prologue.iter() {
+                writeln!(f, "  {}", inst)?;
+            }
+            for block in func.blocks.iter() {
+                writeln!(f, "{}:", block.label)?;
+                for inst in block.insts.iter() {
+                    writeln!(f, "  {}", inst)?;
+                }
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn machine_program_display_test() {
+        let program = MachineProgram {
+            globals: vec![MachineGlobal {
+                name: "g".into(),
+                init: vec![DataItem::Word(1), DataItem::Zero(8)],
+            }],
+            functions: vec![MachineFunction {
+                name: "main".into(),
+                prologue: vec![MachineInst::Addi {
+                    rd: Reg::Sp,
+                    rs: Reg::Sp,
+                    imm: -16,
+                }],
+                blocks: vec![MachineBlock {
+                    label: "entry".into(),
+                    insts: vec![
+                        MachineInst::Lw {
+                            rd: Reg::A(0),
+                            mem: Mem::sp(4),
+                        },
+                        MachineInst::Binary {
+                            op: BinaryOp::Sgt,
+                            rd: Reg::T(5),
+                            rs1: Reg::S(11),
+                            rs2: Reg::Zero,
+                        },
+                        MachineInst::Ret,
+                    ],
+                }],
+            }],
+        };
+        assert_eq!(
+            program.to_string(),
+            "  .data\n  .globl g\ng:\n  .word 1\n  .zero 8\n\n\
+             \x20 .text\n  .globl main\nmain:\n  addi sp, sp, -16\nentry:\n  lw a0, 4(sp)\n  sgt t5, s11, zero\n  ret\n\n"
+        );
+    }
+
+    #[test]
+    fn remove_fallthrough_jumps_test() {
+        let block = |label: &str, insts: Vec<MachineInst>| MachineBlock {
+            label: label.into(),
+            insts,
+        };
+        let j = |label: &str| MachineInst::J {
+            label: label.into(),
+        };
+        let mut func = MachineFunction {
+            name: "f".into(),
+            prologue: Vec::new(),
+            blocks: vec![
+                block(
+                    "a",
+                    vec![
+                        MachineInst::Bnez {
+                            rs: Reg::T(0),
+                            label: "b".into(),
+                        },
+                        j("c"),
+                    ],
+                ),
+                block("b", vec![j("c")]),
+                block(
+                    "c",
+                    vec![
+                        MachineInst::Beqz {
+                            rs: Reg::A(0),
+                            label: "d".into(),
+                        },
+                        j("a"),
+                    ],
+                ),
+                block("d", vec![MachineInst::Ret]),
+            ],
+        };
+        func.remove_fallthrough_jumps();
+        let texts: Vec<String> = func
+            .blocks
+            .iter()
+            .map(|b| insts_to_text(&b.insts))
+            .collect();
+        assert_eq!(texts, ["  beqz t0, c", "", "  bnez a0, a", "  ret"]);
+    }
+}
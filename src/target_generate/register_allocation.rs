@@ -0,0 +1,365 @@
+use super::machine::Reg;
+use koopa::ir::entities::ValueData;
+use koopa::ir::*;
+use std::collections::{HashMap, HashSet};
+
+/// The caller-saved registers values can live in.
+///
+/// `t0`-`t4` are the scratch registers of the generated instructions, so they are not allocated.
+/// `a0`-`a7` are allocated too, since the arguments are moved to them as a parallel copy right before a `call`,
+/// and the parameters are moved out of them in the prologue.
+/// A value in a caller-saved register is never live across a `call`.
+pub const CALLER_SAVED_REGS: [Reg; 10] = [
+    Reg::T(5),
+    Reg::T(6),
+    Reg::A(0),
+    Reg::A(1),
+    Reg::A(2),
+    Reg::A(3),
+    Reg::A(4),
+    Reg::A(5),
+    Reg::A(6),
+    Reg::A(7),
+];
+
+/// The callee-saved registers values can live in.
+///
+/// The ones in use are saved in the prologue and restored in the epilogue.
+pub const CALLEE_SAVED_REGS: [Reg; 12] = [
+    Reg::S(0),
+    Reg::S(1),
+    Reg::S(2),
+    Reg::S(3),
+    Reg::S(4),
+    Reg::S(5),
+    Reg::S(6),
+    Reg::S(7),
+    Reg::S(8),
+    Reg::S(9),
+    Reg::S(10),
+    Reg::S(11),
+];
+
+/// Where a value lives.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Home {
+    /// In a register during the whole lifetime.
+    Reg(Reg),
+    /// In the i-th spill slot of the stack frame.
+    Spill(usize),
+}
+
+/// The result of register allocation for a function.
+pub struct RegisterAllocation {
+    /// The home of each value that needs one (i.e. every value with a result, except `alloc`s).
+    pub homes: HashMap<Value, Home>,
+    /// The number of spill slots.
+    pub n_spill_slots: usize,
+    /// The callee-saved registers in use, in the order of `CALLEE_SAVED_REGS`.
+    pub used_callee_saved: Vec<Reg>,
+}
+
+/// The live interval of a value.
+///
+/// The positions are the indices of the instructions in the layout,
+/// where the start of each basic block (i.e. its parameters) has a position of its own.
+/// A parameter of a basic block is also live at the branches and jumps to the basic block.
+/// Lifetime holes are ignored, so the interval is the hull of all the positions the value is live at.
+#[derive(Debug)]
+struct Interval {
+    value: Value,
+    start: usize,
+    end: usize,
+    /// Whether there's a `call` strictly inside the interval, which clobbers caller-saved registers.
+    crosses_call: bool,
+}
+
+/// Return whether the value needs a home.
+///
+/// Constants have no home, and neither do `alloc`s, whose stack slots are decided by the function scan.
+pub fn needs_home(data: &ValueData) -> bool {
+    match data.kind() {
+        ValueKind::FuncArgRef(_) | ValueKind::BlockArgRef(_) => true,
+        ValueKind::Alloc(_) => false,
+        kind => kind.is_local_inst() && !data.ty().is_unit(),
+    }
+}
+
+/// Allocate registers for the values of a function definition by linear scan.
+pub fn allocate_registers(func_data: &FunctionData) -> RegisterAllocation {
+    linear_scan(live_intervals(func_data))
+}
+
+/// Compute the live interval of every value that needs a home.
+fn live_intervals(func_data: &FunctionData) -> Vec<Interval> {
+    let dfg = func_data.dfg();
+    let is_candidate = |v: Value| dfg.values().get(&v).is_some_and(needs_home);
+    let Some(entry) = func_data.layout().entry_bb() else {
+        return Vec::new();
+    };
+
+    // positions, and the definitions and upward-exposed uses of each basic block
+    let mut order = Vec::new();
+    let mut positions: HashMap<Value, Vec<usize>> = HashMap::new();
+    let mut call_positions = Vec::new();
+    let mut bb_ranges = HashMap::new();
+    let mut defs: HashMap<BasicBlock, HashSet<Value>> = HashMap::new();
+    let mut uses: HashMap<BasicBlock, HashSet<Value>> = HashMap::new();
+    let mut succs: HashMap<BasicBlock, Vec<BasicBlock>> = HashMap::new();
+    let mut pos = 0usize;
+    for (&bb, node) in func_data.layout().bbs() {
+        let bb_start = pos;
+        pos += 1;
+        let bb_defs = defs.entry(bb).or_default();
+        let bb_uses = uses.entry(bb).or_default();
+
+        let mut params = dfg.bb(bb).params().to_vec();
+        if bb == entry {
+            params.extend(func_data.params());
+        }
+        for param in params {
+            order.push(param);
+            positions.entry(param).or_default().push(bb_start);
+            bb_defs.insert(param);
+        }
+
+        for &inst in node.insts().keys() {
+            let inst_data = dfg.value(inst);
+            for v in inst_data.kind().value_uses() {
+                if is_candidate(v) {
+                    positions.entry(v).or_default().push(pos);
+                    if !bb_defs.contains(&v) {
+                        bb_uses.insert(v);
+                    }
+                }
+            }
+            if needs_home(inst_data) {
+                order.push(inst);
+                positions.entry(inst).or_default().push(pos);
+                bb_defs.insert(inst);
+            }
+            let targets = match inst_data.kind() {
+                ValueKind::Call(_) => {
+                    call_positions.push(pos);
+                    Vec::new()
+                }
+                ValueKind::Branch(br) => vec![br.true_bb(), br.false_bb()],
+                ValueKind::Jump(jump) => vec![jump.target()],
+                _ => Vec::new(),
+            };
+            // the parameters of the targets are written at the end of the basic block, when the arguments are passed
+            for &target in targets.iter() {
+                for &param in dfg.bb(target).params() {
+                    positions.entry(param).or_default().push(pos);
+                }
+            }
+            if !targets.is_empty() {
+                succs.insert(bb, targets);
+            }
+            pos += 1;
+        }
+        bb_ranges.insert(bb, (bb_start, pos - 1));
+    }
+
+    // backward data flow analysis of the live-out sets
+    let bbs: Vec<BasicBlock> = func_data.layout().bbs().keys().copied().collect();
+    let mut live_in: HashMap<BasicBlock, HashSet<Value>> = HashMap::new();
+    let mut live_out: HashMap<BasicBlock, HashSet<Value>> = HashMap::new();
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for &bb in bbs.iter().rev() {
+            let mut out = HashSet::new();
+            for succ in succs.get(&bb).into_iter().flatten() {
+                out.extend(live_in.get(succ).into_iter().flatten().copied());
+            }
+            let mut new_in = uses[&bb].clone();
+            new_in.extend(out.iter().copied().filter(|v| !defs[&bb].contains(v)));
+            if live_in.get(&bb) != Some(&new_in) {
+                live_in.insert(bb, new_in);
+                changed = true;
+            }
+            live_out.insert(bb, out);
+        }
+    }
+    for &bb in bbs.iter() {
+        let (bb_start, bb_end) = bb_ranges[&bb];
+        for &v in live_in[&bb].iter() {
+            positions.entry(v).or_default().push(bb_start);
+        }
+        for &v in live_out[&bb].iter() {
+            positions.entry(v).or_default().push(bb_end);
+        }
+    }
+
+    let mut intervals: Vec<Interval> = order
+        .into_iter()
+        .map(|value| {
+            let ps = &positions[&value];
+            let start = *ps.iter().min().unwrap();
+            let end = *ps.iter().max().unwrap();
+            Interval {
+                value,
+                start,
+                end,
+                crosses_call: call_positions.iter().any(|&c| start < c && c < end),
+            }
+        })
+        .collect();
+    // the sort is stable, so the values defined at the same position keep their order
+    intervals.sort_by_key(|i| i.start);
+    intervals
+}
+
+/// Assign registers to the intervals in the order of their starts, spilling the furthest-ending one under pressure.
+fn linear_scan(intervals: Vec<Interval>) -> RegisterAllocation {
+    let mut homes = HashMap::new();
+    let mut n_spill_slots = 0usize;
+    let mut used_callee_saved = HashSet::new();
+    // (end, register, value) of the intervals in registers
+    let mut active: Vec<(usize, Reg, Value)> = Vec::new();
+    let mut spill = |homes: &mut HashMap<Value, Home>, value: Value| {
+        homes.insert(value, Home::Spill(n_spill_slots));
+        n_spill_slots += 1;
+    };
+
+    for cur in intervals.iter() {
+        active.retain(|&(end, _, _)| end >= cur.start);
+        let is_free = |reg: &&Reg| !active.iter().any(|(_, r, _)| r == *reg);
+        let free = if cur.crosses_call {
+            CALLEE_SAVED_REGS.iter().find(is_free)
+        } else {
+            // prefer caller-saved registers, which need not be saved in the prologue
+            CALLER_SAVED_REGS
+                .iter()
+                .chain(CALLEE_SAVED_REGS.iter())
+                .find(is_free)
+        };
+
+        if let Some(&reg) = free {
+            homes.insert(cur.value, Home::Reg(reg));
+            active.push((cur.end, reg, cur.value));
+            continue;
+        }
+
+        // steal the register of the active interval ending last, if it ends after the current one
+        let victim = active
+            .iter()
+            .enumerate()
+            .filter(|(_, (_, reg, _))| !cur.crosses_call || CALLEE_SAVED_REGS.contains(reg))
+            .max_by_key(|(_, (end, _, _))| *end)
+            .map(|(i, _)| i);
+        match victim {
+            Some(i) if active[i].0 > cur.end => {
+                let (_, reg, value) = active.swap_remove(i);
+                spill(&mut homes, value);
+                homes.insert(cur.value, Home::Reg(reg));
+                active.push((cur.end, reg, cur.value));
+            }
+            _ => spill(&mut homes, cur.value),
+        }
+    }
+
+    for home in homes.values() {
+        if let Home::Reg(reg) = home {
+            if CALLEE_SAVED_REGS.contains(reg) {
+                used_callee_saved.insert(*reg);
+            }
+        }
+    }
+    let used_callee_saved = CALLEE_SAVED_REGS
+        .into_iter()
+        .filter(|reg| used_callee_saved.contains(reg))
+        .collect();
+
+    RegisterAllocation {
+        homes,
+        n_spill_slots,
+        used_callee_saved,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast_generate::parse_sysy_to_ast;
+    use crate::ir_generate::parse_ast_to_koopa_program;
+
+    fn allocate_main(input: &str) -> (Program, Function, RegisterAllocation) {
+        let ast = parse_sysy_to_ast(input).unwrap();
+        let program = parse_ast_to_koopa_program(&ast).unwrap();
+        let main = *program.func_layout().last().unwrap();
+        let allocation = allocate_registers(program.func(main));
+        (program, main, allocation)
+    }
+
+    #[test]
+    fn allocate_without_pressure_test() {
+        let (program, main, allocation) = allocate_main("int main() { int a = 1; return a + 2; }");
+        assert_eq!(allocation.n_spill_slots, 0);
+        assert!(allocation.used_callee_saved.is_empty());
+        let data = program.func(main);
+        for (_, node) in data.layout().bbs() {
+            for &inst in node.insts().keys() {
+                if needs_home(data.dfg().value(inst)) {
+                    assert!(matches!(allocation.homes[&inst], Home::Reg(_)));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn allocate_across_calls_test() {
+        let (program, main, allocation) =
+            allocate_main("int main() { return getint() + getint(); }");
+        let data = program.func(main);
+        // the result of the first `call` is live across the second one
+        let first_call = data
+            .layout()
+            .bbs()
+            .nodes()
+            .flat_map(|node| node.insts().keys())
+            .find(|&&inst| matches!(data.dfg().value(inst).kind(), ValueKind::Call(_)))
+            .unwrap();
+        let Home::Reg(reg) = allocation.homes[first_call] else {
+            panic!("the result of the call is spilled");
+        };
+        assert!(CALLEE_SAVED_REGS.contains(&reg));
+        assert_eq!(allocation.used_callee_saved, vec![reg]);
+    }
+
+    #[test]
+    fn allocate_leaf_test() {
+        // the 9 variables are loaded before adding them up, but there's no `call` to live across
+        let vars: Vec<String> = (0..9).map(|i| format!("v{}", i)).collect();
+        let input = format!(
+            "int main() {{ {} return {}{}; }}",
+            vars.iter()
+                .enumerate()
+                .map(|(i, v)| format!("int {} = {};", v, i))
+                .collect::<String>(),
+            vars.join(" + ("),
+            ")".repeat(vars.len() - 1)
+        );
+        let (_, _, allocation) = allocate_main(&input);
+        assert_eq!(allocation.n_spill_slots, 0);
+        assert!(allocation.used_callee_saved.is_empty());
+    }
+
+    #[test]
+    fn allocate_under_pressure_test() {
+        // `v0 + (v1 + (v2 + ...))` loads all the variables before adding them up
+        let vars: Vec<String> = (0..30).map(|i| format!("v{}", i)).collect();
+        let input = format!(
+            "int main() {{ {} return {}{}; }}",
+            vars.iter()
+                .map(|v| format!("int {} = getint();", v))
+                .collect::<String>(),
+            vars.join(" + ("),
+            ")".repeat(vars.len() - 1)
+        );
+        let (_, _, allocation) = allocate_main(&input);
+        assert!(allocation.n_spill_slots > 0);
+        assert_eq!(allocation.used_callee_saved.len(), CALLEE_SAVED_REGS.len());
+    }
+}
@@ -1,46 +1,71 @@
 use super::context::*;
+use super::function_call::function_arg_location;
 use super::function_scan::*;
+use super::machine::BinaryOp as MachineOp;
+use super::machine::{
+    DataItem, MachineBlock, MachineFunction, MachineGlobal, MachineInst, MachineProgram, Mem, Reg,
+};
 use super::value_location::*;
-use crate::tools::*;
+use crate::diagnostic::Diagnostic;
 use koopa::ir::entities::*;
 use koopa::ir::*;
 
-/// Generate RISC-V code from the given Koopa object.
+/// Generate RISC-V machine instructions from the given Koopa object.
 pub trait RiscvGenerate {
     /// The return type of the method `generate`.
     type Ret;
 
-    /// Generate RISC-V code.
+    /// Generate RISC-V machine instructions, which are appended to `insts`.
     ///
-    /// `lines` should always be empty when entering the method.
-    fn generate(&self, lines: &mut String, cxt: &mut ProgramContext) -> Result<Self::Ret, ()>;
+    /// An instruction with a result computes it to the register `cxt.target`, and returns `ValueLocation::Reg` of it.
+    fn generate(
+        &self,
+        insts: &mut Vec<MachineInst>,
+        cxt: &mut ProgramContext,
+    ) -> Result<Self::Ret, Diagnostic>;
 }
 
 impl RiscvGenerate for Program {
-    type Ret = ();
+    type Ret = MachineProgram;
 
-    fn generate(&self, lines: &mut String, cxt: &mut ProgramContext) -> Result<Self::Ret, ()> {
+    fn generate(
+        &self,
+        _insts: &mut Vec<MachineInst>,
+        cxt: &mut ProgramContext,
+    ) -> Result<Self::Ret, Diagnostic> {
         Type::set_ptr_size(4); // necessary according to the tutorial
+        let mut machine_program = MachineProgram::default();
+
+        // the labels of the basic blocks must differ from the names of the functions and the global values
+        for &func in self.func_layout() {
+            cxt.labels
+                .insert(String::from(&self.func(func).name()[1..]));
+        }
+        for &val in self.inst_layout() {
+            if let Some(name) = self.borrow_value(val).name() {
+                cxt.labels.insert(String::from(&name[1..]));
+            }
+        }
 
         // global variables
         for &val in self.inst_layout() {
             let val_data = self.borrow_value(val);
-            let Some(val_name) = val_data.name() else { // with "@" or "%" prefix
-                return Err(());
+            let Some(val_name) = val_data.name() else {
+                // with "@" or "%" prefix
+                return Err(Diagnostic::error("a global value has no name"));
             };
             // store the location of the global variable
             cxt.add_global_value(val, ValueLocation::Global(String::from(&val_name[1..])))?;
 
-            let mut initialization_line = String::new();
-            // call `generate` on `GlobalAlloc` to generate the initialization line
-            val_data.generate(&mut initialization_line, cxt)?;
-
-            // append the code lines to `lines`
-            append_line(lines, &format!("  .data"));
-            append_line(lines, &format!("  .globl {}", &val_name[1..]));
-            append_line(lines, &format!("{}:", &val_name[1..]));
-            append_line(lines, &initialization_line);
-            append_line(lines, " ");
+            let ValueKind::GlobalAlloc(alloc) = val_data.kind() else {
+                return Err(Diagnostic::error("a global value must be a `global alloc`"));
+            };
+            let mut init = Vec::new();
+            data_items(&self.borrow_value(alloc.init()), &mut init, cxt)?;
+            machine_program.globals.push(MachineGlobal {
+                name: String::from(&val_name[1..]),
+                init,
+            });
         }
 
         // function definitions
@@ -52,136 +77,199 @@ impl RiscvGenerate for Program {
             // Store the information in `cxt.func`.
             let func_data = self.func(func);
             cxt.func = Some(FunctionScanResult::try_from(func, func_data)?);
+            cxt.assign_basic_block_labels(func_data);
 
             // Then, generate the instructions in the function body.
-            let mut new_lines = String::new();
-            func_data.generate(&mut new_lines, cxt)?;
-
-            append_line(lines, &new_lines);
-            if !new_lines.is_empty() {
-                append_line(lines, " ");
+            if let Some(machine_func) = func_data.generate(&mut Vec::new(), cxt)? {
+                machine_program.functions.push(machine_func);
             }
         }
 
-        Ok(())
+        Ok(machine_program)
     }
 }
 
-impl RiscvGenerate for FunctionData {
-    type Ret = ();
-
-    fn generate(&self, lines: &mut String, cxt: &mut ProgramContext) -> Result<Self::Ret, ()> {
-        // skip the function if it's a declaration (rather than a definition)
-        if let None = self.layout().entry_bb() {
-            return Ok(());
+/// Flatten the initializer of a global value to the items of the `.data` section.
+fn data_items(
+    data: &ValueData,
+    items: &mut Vec<DataItem>,
+    cxt: &ProgramContext,
+) -> Result<(), Diagnostic> {
+    match data.kind() {
+        ValueKind::Integer(val) => items.push(DataItem::Word(val.value())),
+        // an undefined global value is initialized with zeros
+        ValueKind::ZeroInit(_) | ValueKind::Undef(_) => {
+            items.push(DataItem::Zero(data.ty().size()))
         }
-
-        let func_name = &self.name()[1..];
-        let mut name_lines = String::new();
-        append_line(&mut name_lines, "  .text");
-        append_line(&mut name_lines, &format!("  .globl {}", func_name));
-        append_line(&mut name_lines, &format!("{}:", func_name));
-
-        let mut body_lines = String::new();
-        for (bb, node) in self.layout().bbs() {
-            // get basic block name
-            let Some(bbd) = cxt.get_basic_block_data_in_current_function(*bb) else {
-                return Err(());
-            };
-            let &Some(ref bb_name) = bbd.name() else {
-                return Err(());
-            };
-            append_line(
-                &mut body_lines,
-                &format!("{}:", bb_name.replace("%", "").replace("@", "")),
+        ValueKind::Aggregate(val) => {
+            for &e in val.elems() {
+                let Some(elem_data) = cxt.get_value_data_locally_or_globally(e) else {
+                    return Err(Diagnostic::error(
+                        "an element of an aggregate is never defined",
+                    ));
+                };
+                data_items(&elem_data, items, cxt)?;
+            }
+        }
+        kind => {
+            return Err(
+                Diagnostic::error("the initializer of a global value must be a constant")
+                    .with_note(format!("found {:?}", kind)),
             );
+        }
+    }
+    Ok(())
+}
 
-            // generate basic block instructions
-            for &inst_val in node.insts().keys() {
-                let inst_val_data = self.dfg().value(inst_val); // an Koopa instruction
-                let mut new_lines = String::new();
-                let loc = inst_val_data.generate(&mut new_lines, cxt)?; // the location of the instruction's left-hand side
-
-                match loc {
-                    ValueLocation::PlaceHolder(p) => {
-                        // the last instruction must be `sw`
-                        let Some(real_loc) = cxt.get_value_location_local_or_global(inst_val) else{
-                            return Err(());
-                        };
-                        match real_loc {
-                            ValueLocation::Imm(s) | ValueLocation::Reg(s) => {
-                                new_lines = new_lines.replace(&p, &s);
-                            },
-                            ValueLocation::Stack(addr) => {
-                                let mut addr_lines = String::new();
-                                let valid_addr = get_valid_address(&addr, "t3", &mut addr_lines);
-                                new_lines = new_lines.replace(&p, &valid_addr);
-
-                                let new_line_vec = new_lines.split("\n").collect::<Vec<&str>>();
-                                let mut new_lines_but_last = new_line_vec[..(new_line_vec.len()-1)].join("\n");
-                                append_line(&mut new_lines_but_last, &addr_lines);
-                                append_line(&mut new_lines_but_last, new_line_vec[new_line_vec.len()-1]);
-                                new_lines = new_lines_but_last;
-                            },
-                            _ => return Err(()),
-                        }
-                    }
-                    _ => (),
-                }
+/// Generate the instructions that add `delta` to the stack pointer.
+fn adjust_sp(delta: i32) -> Vec<MachineInst> {
+    if (-2048..2048).contains(&delta) {
+        vec![MachineInst::Addi {
+            rd: Reg::Sp,
+            rs: Reg::Sp,
+            imm: delta,
+        }]
+    } else {
+        vec![
+            MachineInst::Li {
+                rd: Reg::T(0),
+                imm: delta,
+            },
+            MachineInst::Binary {
+                op: MachineOp::Add,
+                rd: Reg::Sp,
+                rs1: Reg::Sp,
+                rs2: Reg::T(0),
+            },
+        ]
+    }
+}
 
-                append_line(&mut body_lines, &new_lines);
-            }
+impl RiscvGenerate for FunctionData {
+    type Ret = Option<MachineFunction>;
+
+    fn generate(
+        &self,
+        _insts: &mut Vec<MachineInst>,
+        cxt: &mut ProgramContext,
+    ) -> Result<Self::Ret, Diagnostic> {
+        // skip the function if it's a declaration (rather than a definition)
+        if self.layout().entry_bb().is_none() {
+            return Ok(None);
         }
 
-        // add prologue and epilogue
-        append_line(lines, &name_lines);
+        // build the prologue and the epilogue, the latter of which is inserted before each `ret`
         let Some(ref func_info) = cxt.func else {
-            return Err(());
+            return Err(Diagnostic::error(format!(
+                "function `{}` has not been scanned",
+                self.name()
+            )));
         };
         let sp_shift = func_info.stack_frame_size;
 
-        let mut pro = String::new();
-        let mut epi = String::new();
+        let mut pro = Vec::new();
+        let mut epi = Vec::new();
         if sp_shift > 0 {
-            if sp_shift < 2048 {
-                append_line(&mut pro, &format!("  addi sp, sp, -{}", sp_shift));
-            } else {
-                append_line(&mut pro, &format!("  li t0, -{}", sp_shift));
-                append_line(&mut pro, &format!("  add sp, sp, t0"));
-            }
+            pro.extend(adjust_sp(-(sp_shift as i32)));
         }
         if let Some(ref ra_loc) = func_info.ra_slot_location {
             // there's a `call` in the function body, and we need to save the `ra` register
-            if let ValueLocation::Stack(ref ra_addr) = ra_loc {
-                let mut ra_lines = String::new();
-                let valid_ra_addr = get_valid_address(ra_addr, "t3", &mut ra_lines);
-                append_line(&mut pro, &ra_lines);
-                append_line(&mut pro, &format!("  sw ra, {}", valid_ra_addr));
-                append_line(&mut epi, &ra_lines);
-                append_line(&mut epi, &format!("  lw ra, {}", valid_ra_addr));
-            } else {
-                return Err(());
-            }
+            let ValueLocation::Stack(ra_mem) = ra_loc else {
+                return Err(Diagnostic::error(
+                    "the return address must be saved on the stack",
+                ));
+            };
+            let mut addr_insts = Vec::new();
+            let ra_mem = get_valid_address(*ra_mem, Reg::T(3), &mut addr_insts);
+            pro.extend(addr_insts.iter().cloned());
+            pro.push(MachineInst::Sw {
+                rs: Reg::Ra,
+                mem: ra_mem,
+            });
+            epi.extend(addr_insts);
+            epi.push(MachineInst::Lw {
+                rd: Reg::Ra,
+                mem: ra_mem,
+            });
         }
-        if sp_shift > 0 {
-            if sp_shift < 2048 {
-                append_line(&mut epi, &format!("  addi sp, sp, {}", sp_shift));
-            } else {
-                append_line(&mut epi, &format!("  li t0, {}", sp_shift));
-                append_line(&mut epi, &format!("  add sp, sp, t0"));
-            }
+        for &(reg, ref slot) in func_info.callee_saved_slots.iter() {
+            // save the callee-saved registers in use, and restore them in reverse order
+            let ValueLocation::Stack(mem) = slot else {
+                return Err(Diagnostic::error(
+                    "a callee-saved register must be saved on the stack",
+                ));
+            };
+            let mut addr_insts = Vec::new();
+            let mem = get_valid_address(*mem, Reg::T(3), &mut addr_insts);
+            pro.extend(addr_insts.iter().cloned());
+            pro.push(MachineInst::Sw { rs: reg, mem });
+            let mut restore = addr_insts;
+            restore.push(MachineInst::Lw { rd: reg, mem });
+            restore.append(&mut epi);
+            epi = restore;
         }
-        if pro.is_empty() {
-            pro = "  # no prologue".to_string();
+        // move the parameters from the registers or the stack they are passed in to their homes,
+        // as a parallel copy, since a parameter may live in the register another one is passed in
+        let copies: Vec<(ValueLocation, ValueLocation)> = self
+            .params()
+            .iter()
+            .enumerate()
+            .filter_map(|(i, param)| {
+                let home = func_info.value_locations.get(param)?;
+                Some((function_arg_location(i, sp_shift, false), home.clone()))
+            })
+            .collect();
+        pro.extend(parallel_copy(&copies));
+        if sp_shift > 0 {
+            epi.extend(adjust_sp(sp_shift as i32));
         }
-        if epi.is_empty() {
-            epi = "  # no epilogue".to_string();
+        cxt.epilogue = epi;
+
+        let mut blocks = Vec::new();
+        for (&bb, node) in self.layout().bbs() {
+            let label = cxt.get_basic_block_name_in_current_function(bb)?;
+
+            // generate basic block instructions
+            let mut insts = Vec::new();
+            for &inst_val in node.insts().keys() {
+                let inst_val_data = self.dfg().value(inst_val); // an Koopa instruction
+                                                                // the result is computed to its home register, or to `t0` if it's spilled
+                let home = cxt.get_value_location_local_or_global(inst_val);
+                cxt.target = match home {
+                    Some(ValueLocation::Reg(r)) => r,
+                    _ => Reg::T(0),
+                };
+                let loc = inst_val_data.generate(&mut insts, cxt)?;
+
+                if let ValueLocation::Reg(r) = loc {
+                    match home {
+                        Some(ValueLocation::Reg(_)) => {}
+                        Some(ValueLocation::Stack(mem)) => {
+                            // the value is spilled, so store it to the spill slot
+                            let mem = get_valid_address(mem, Reg::T(3), &mut insts);
+                            insts.push(MachineInst::Sw { rs: r, mem });
+                        }
+                        loc => {
+                            return Err(Diagnostic::error(
+                                "an instruction result cannot be stored",
+                            )
+                            .with_note(format!("its location is {:?}", loc)));
+                        }
+                    }
+                }
+            }
+            blocks.push(MachineBlock { label, insts });
+            // the edges split by the branch at the end of the basic block follow it
+            blocks.append(&mut cxt.edge_blocks);
         }
-        append_line(lines, &pro);
-        body_lines = body_lines.replace("<epilogue>", &epi);
-        append_line(lines, &body_lines);
 
-        Ok(())
+        let mut func = MachineFunction {
+            name: String::from(&self.name()[1..]),
+            prologue: pro,
+            blocks,
+        };
+        func.remove_fallthrough_jumps();
+        Ok(Some(func))
     }
 }
 
@@ -191,175 +279,185 @@ impl RiscvGenerate for Value {
     /// Find the location of the `Value`.
     ///
     /// Search in the HashMap `cxt.value_locations` by calling `cxt.get_value_location(..)`.
-    fn generate(&self, _lines: &mut String, cxt: &mut ProgramContext) -> Result<Self::Ret, ()> {
+    fn generate(
+        &self,
+        _insts: &mut Vec<MachineInst>,
+        cxt: &mut ProgramContext,
+    ) -> Result<Self::Ret, Diagnostic> {
         let Some(value_data) = cxt.get_value_data_locally_or_globally(*self) else {
-            return Err(());
+            return Err(Diagnostic::error(
+                "a value is used, but it is never defined",
+            ));
         };
 
         match value_data.kind() {
-            ValueKind::Integer(val) => Ok(ValueLocation::Imm(format!("{}", val.value()))),
-            _ => {
-                if let Some(loc) = cxt.get_value_location_local_or_global(*self) {
-                    Ok(loc.clone())
-                } else {
-                    Err(())
-                }
-            }
+            ValueKind::Integer(val) => Ok(ValueLocation::Imm(val.value())),
+            // an `undef` may be anything, and a scalar `zeroinit` is zero
+            ValueKind::Undef(_) | ValueKind::ZeroInit(_) => Ok(ValueLocation::Imm(0)),
+            kind => cxt
+                .get_value_location_local_or_global(*self)
+                .ok_or_else(|| {
+                    Diagnostic::error("no location is assigned to a value")
+                        .with_note(format!("the value is {:?}", kind))
+                }),
         }
     }
 }
 
+/// Get the location of an operand, whose content is the value of the operand.
+///
+/// The location of an `alloc` or a global value holds the data pointed to rather than the pointer itself,
+/// so the address is computed to `reg`, and the instructions are appended to `insts`.
+fn operand_location(
+    val: Value,
+    reg: Reg,
+    insts: &mut Vec<MachineInst>,
+    cxt: &mut ProgramContext,
+) -> Result<ValueLocation, Diagnostic> {
+    let loc = val.generate(insts, cxt)?;
+    let is_address = cxt
+        .get_value_data_locally_or_globally(val)
+        .is_some_and(|data| matches!(data.kind(), ValueKind::Alloc(_) | ValueKind::GlobalAlloc(_)));
+    if is_address {
+        insts.extend(loc.move_address_to_reg(reg));
+        return Ok(ValueLocation::Reg(reg));
+    }
+    Ok(loc)
+}
+
 impl RiscvGenerate for ValueData {
     type Ret = ValueLocation;
 
-    fn generate(&self, lines: &mut String, cxt: &mut ProgramContext) -> Result<Self::Ret, ()> {
+    fn generate(
+        &self,
+        insts: &mut Vec<MachineInst>,
+        cxt: &mut ProgramContext,
+    ) -> Result<Self::Ret, Diagnostic> {
         match self.kind() {
-            //////////////////////////////////////////////////////////////////////////////////////////////
-            // initialization of a global value                                                         //
-            //////////////////////////////////////////////////////////////////////////////////////////////
-
-            // integer constant
-            ValueKind::Integer(val) => val.generate(lines, cxt),
-            // zero initialization used in global value allocation
-            ValueKind::ZeroInit(val) => {
-                let mut initialization_line = String::new();
-                let ret = val.generate(&mut initialization_line, cxt);
-                // we have to figure out the size of the zero-initialized `ValueData`,
-                // rather than the size of a `ZeroInit`!!!
-                if initialization_line.contains("<type_size>") {
-                    let type_size = self.ty().size(); // If it's an array, `size()` will implement DFS and return the whole size.
-                    initialization_line =
-                        initialization_line.replace("<type_size>", &type_size.to_string());
-                };
-                append_line(lines, &initialization_line);
-                ret
-            }
-            // initialization of a global value using an aggregate (i.e. `{ ..., {...}, ... }`)
-            ValueKind::Aggregate(val) => val.generate(lines, cxt),
-
-            //////////////////////////////////////////////////////////////////////////////////////////////
-            // other instructions                                                                       //
-            //////////////////////////////////////////////////////////////////////////////////////////////
-
             // allocation operation
-            ValueKind::Alloc(val) => val.generate(lines, cxt),
-            // global value allocation
-            ValueKind::GlobalAlloc(val) => val.generate(lines, cxt),
+            ValueKind::Alloc(val) => val.generate(insts, cxt),
             // load operation
-            ValueKind::Load(val) => val.generate(lines, cxt),
+            ValueKind::Load(val) => val.generate(insts, cxt),
             // store operation
-            ValueKind::Store(val) => val.generate(lines, cxt),
+            ValueKind::Store(val) => val.generate(insts, cxt),
             // get element pointer
-            ValueKind::GetElemPtr(val) => val.generate(lines, cxt),
+            ValueKind::GetElemPtr(val) => val.generate(insts, cxt),
             // get pointer
-            ValueKind::GetPtr(val) => val.generate(lines, cxt),
+            ValueKind::GetPtr(val) => val.generate(insts, cxt),
             // binary operation
-            ValueKind::Binary(val) => val.generate(lines, cxt),
+            ValueKind::Binary(val) => val.generate(insts, cxt),
             // branch operation
-            ValueKind::Branch(val) => val.generate(lines, cxt),
+            ValueKind::Branch(val) => val.generate(insts, cxt),
             // jump operation
-            ValueKind::Jump(val) => val.generate(lines, cxt),
+            ValueKind::Jump(val) => val.generate(insts, cxt),
             // function call
-            ValueKind::Call(val) => val.generate(lines, cxt),
+            ValueKind::Call(val) => val.generate(insts, cxt),
             // function return
-            ValueKind::Return(val) => val.generate(lines, cxt),
-            // others
-            _ => Ok(ValueLocation::None),
-        }
-    }
-}
-
-impl RiscvGenerate for values::Integer {
-    type Ret = ValueLocation;
-
-    /// Initialize a global variable using an integer.
-    fn generate(&self, lines: &mut String, _cxt: &mut ProgramContext) -> Result<Self::Ret, ()> {
-        append_line(lines, &format!("  .word {}", self.value()));
-        Ok(ValueLocation::None)
-    }
-}
-
-impl RiscvGenerate for values::ZeroInit {
-    type Ret = ValueLocation;
-
-    /// Initialize a global variable using zeros.
-    fn generate(&self, lines: &mut String, _cxt: &mut ProgramContext) -> Result<Self::Ret, ()> {
-        append_line(lines, "  .zero <type_size>");
-        Ok(ValueLocation::None)
-    }
-}
-
-impl RiscvGenerate for values::Aggregate {
-    type Ret = ValueLocation;
-
-    /// Initialize a global variable using an aggregate.
-    fn generate(&self, lines: &mut String, cxt: &mut ProgramContext) -> Result<Self::Ret, ()> {
-        for e in self.elems() {
-            let Some(sub_value_data) = cxt.get_value_data_locally_or_globally(*e) else {
-                return Err(());
-            };
-            sub_value_data.generate(lines, cxt)?;
+            ValueKind::Return(val) => val.generate(insts, cxt),
+            // the parameters are not instructions, and get their contents from the caller or the predecessors
+            ValueKind::FuncArgRef(_) | ValueKind::BlockArgRef(_) => Ok(ValueLocation::None),
+            // the constants and the global values are not in any basic block
+            kind => Err(
+                Diagnostic::error("a constant or a global value is not an instruction")
+                    .with_note(format!("found {:?}", kind)),
+            ),
         }
-
-        Ok(ValueLocation::None)
     }
 }
 
 impl RiscvGenerate for values::Alloc {
     type Ret = ValueLocation;
 
-    fn generate(&self, _lines: &mut String, _cxt: &mut ProgramContext) -> Result<Self::Ret, ()> {
+    fn generate(
+        &self,
+        _insts: &mut Vec<MachineInst>,
+        _cxt: &mut ProgramContext,
+    ) -> Result<Self::Ret, Diagnostic> {
         Ok(ValueLocation::None)
     }
 }
 
-impl RiscvGenerate for values::GlobalAlloc {
-    type Ret = ValueLocation;
-
-    /// Initialize the global value by calling `generate` on the `ValueData` corresponding to the `init` field.
-    fn generate(&self, lines: &mut String, cxt: &mut ProgramContext) -> Result<Self::Ret, ()> {
-        let initializer = self.init();
-        let Some(initializer_data) = cxt.get_value_data_locally_or_globally(initializer) else {
-            return Err(());
-        };
-
-        initializer_data.generate(lines, cxt)
-    }
-}
-
 impl RiscvGenerate for values::Load {
     type Ret = ValueLocation;
 
-    fn generate(&self, lines: &mut String, cxt: &mut ProgramContext) -> Result<Self::Ret, ()> {
-        let src = self.src().generate(&mut String::new(), cxt)?;
-        append_line(lines, &src.move_content_to_reg("t0"));
+    fn generate(
+        &self,
+        insts: &mut Vec<MachineInst>,
+        cxt: &mut ProgramContext,
+    ) -> Result<Self::Ret, Diagnostic> {
+        let src = self.src().generate(insts, cxt)?;
+        let target = cxt.target;
         if cxt.location_of_value_contain_pointer(self.src())? {
-            append_line(lines, "  lw t0, 0(t0)");
+            let ptr = src.content_in_reg(Reg::T(0), insts);
+            insts.push(MachineInst::Lw {
+                rd: target,
+                mem: Mem {
+                    base: ptr,
+                    offset: 0,
+                },
+            });
+        } else {
+            insts.extend(src.move_content_to_reg(target));
         }
 
-        append_line(lines, "  sw t0, <tar>");
-        Ok(ValueLocation::PlaceHolder("<tar>".to_string()))
+        Ok(ValueLocation::Reg(target))
     }
 }
 
 impl RiscvGenerate for values::Store {
     type Ret = ValueLocation;
 
-    fn generate(&self, lines: &mut String, cxt: &mut ProgramContext) -> Result<Self::Ret, ()> {
-        let val = self.value().generate(&mut String::new(), cxt)?;
-        let dest = self.dest().generate(&mut String::new(), cxt)?;
+    fn generate(
+        &self,
+        insts: &mut Vec<MachineInst>,
+        cxt: &mut ProgramContext,
+    ) -> Result<Self::Ret, Diagnostic> {
+        let dest = self.dest().generate(insts, cxt)?;
+        let Some(val_data) = cxt.get_value_data_locally_or_globally(self.value()) else {
+            return Err(Diagnostic::error("the value of `store` is never defined"));
+        };
+        if !matches!(val_data.ty().kind(), TypeKind::Int32 | TypeKind::Pointer(_)) {
+            // an array is stored word by word
+            let mut words = Vec::new();
+            initializer_words(&val_data, &mut words, cxt)?;
+            let base = if cxt.location_of_value_contain_pointer(self.dest())? {
+                dest.content_in_reg(Reg::T(1), insts)
+            } else {
+                insts.extend(dest.move_address_to_reg(Reg::T(1)));
+                Reg::T(1)
+            };
+            for (i, word) in words.into_iter().enumerate() {
+                // the words of `undef` are left as they are
+                let Some(word) = word else {
+                    continue;
+                };
+                let rs = ValueLocation::Imm(word).content_in_reg(Reg::T(0), insts);
+                let mem = Mem {
+                    base,
+                    offset: 4 * i as i32,
+                };
+                let mem = get_valid_address(mem, Reg::T(3), insts);
+                insts.push(MachineInst::Sw { rs, mem });
+            }
+            return Ok(ValueLocation::None);
+        }
+        let val = operand_location(self.value(), Reg::T(2), insts, cxt)?;
 
         if cxt.location_of_value_contain_pointer(self.dest())? {
-            append_line(lines, &val.move_content_to_reg("t0"));
-            append_line(lines, &dest.move_content_to_reg("t1"));
-            append_line(lines, "  sw t0, 0(t1)");
+            let rs = val.content_in_reg(Reg::T(0), insts);
+            let base = dest.content_in_reg(Reg::T(1), insts);
+            insts.push(MachineInst::Sw {
+                rs,
+                mem: Mem { base, offset: 0 },
+            });
         } else {
             match dest {
                 ValueLocation::Stack(_) | ValueLocation::Global(_) => {
-                    append_line(lines, &val.move_content_to(dest.clone()));
+                    insts.extend(val.move_content_to(dest.clone()));
                 }
-                _ => {
-                    return Err(());
+                dest => {
+                    return Err(Diagnostic::error("cannot store a value to the destination")
+                        .with_note(format!("the destination is {:?}", dest)));
                 }
             }
         }
@@ -368,177 +466,343 @@ impl RiscvGenerate for values::Store {
     }
 }
 
+/// Flatten a constant array (an aggregate, `zeroinit` or `undef`) to its words, which are `None` for `undef`.
+fn initializer_words(
+    data: &ValueData,
+    words: &mut Vec<Option<i32>>,
+    cxt: &ProgramContext,
+) -> Result<(), Diagnostic> {
+    match data.kind() {
+        ValueKind::Integer(val) => words.push(Some(val.value())),
+        ValueKind::ZeroInit(_) => words.extend(std::iter::repeat_n(Some(0), data.ty().size() / 4)),
+        ValueKind::Undef(_) => words.extend(std::iter::repeat_n(None, data.ty().size() / 4)),
+        ValueKind::Aggregate(val) => {
+            for &e in val.elems() {
+                let Some(elem_data) = cxt.get_value_data_locally_or_globally(e) else {
+                    return Err(Diagnostic::error(
+                        "an element of an aggregate is never defined",
+                    ));
+                };
+                initializer_words(&elem_data, words, cxt)?;
+            }
+        }
+        kind => {
+            return Err(
+                Diagnostic::error("an array stored by `store` must be a constant")
+                    .with_note(format!("found {:?}", kind)),
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Generate the instructions that compute the byte offset of the `idx`-th element to register t1.
+fn element_offset(idx: &ValueLocation, elem_size: usize) -> Vec<MachineInst> {
+    let mut insts = Vec::new();
+    match idx {
+        ValueLocation::Imm(i) => {
+            let offset = *i as i64 * elem_size as i64;
+            insts.push(MachineInst::Li {
+                rd: Reg::T(1),
+                imm: offset as i32,
+            });
+        }
+        idx => {
+            let idx = idx.content_in_reg(Reg::T(1), &mut insts);
+            insts.push(MachineInst::Li {
+                rd: Reg::T(2),
+                imm: elem_size as i32,
+            });
+            insts.push(MachineInst::Binary {
+                op: MachineOp::Mul,
+                rd: Reg::T(1),
+                rs1: idx,
+                rs2: Reg::T(2),
+            });
+        }
+    }
+    insts
+}
+
 impl RiscvGenerate for values::GetElemPtr {
     type Ret = ValueLocation;
 
     /// Generate a pointer to the element.
-    fn generate(&self, lines: &mut String, cxt: &mut ProgramContext) -> Result<Self::Ret, ()> {
+    fn generate(
+        &self,
+        insts: &mut Vec<MachineInst>,
+        cxt: &mut ProgramContext,
+    ) -> Result<Self::Ret, Diagnostic> {
         // get two locations
-        let src = self.src().generate(&mut String::new(), cxt)?;
-        let idx = self.index().generate(&mut String::new(), cxt)?;
+        let src = self.src().generate(insts, cxt)?;
+        let idx = self.index().generate(insts, cxt)?;
 
         let Some(vd) = cxt.get_value_data_locally_or_globally(self.src()) else {
-            return Err(());
+            return Err(Diagnostic::error(
+                "the source of `getelemptr` is never defined",
+            ));
         };
-        let TypeKind::Pointer(ptr_base)  = vd.ty().kind() else {
-            return Err(());
+        let TypeKind::Pointer(ptr_base) = vd.ty().kind() else {
+            return Err(Diagnostic::error(format!(
+                "the source of `getelemptr` must be a pointer, found `{}`",
+                vd.ty()
+            )));
         };
         let TypeKind::Array(base_type, _) = ptr_base.kind() else {
-            return Err(());
+            return Err(Diagnostic::error(format!(
+                "the source of `getelemptr` must point to an array, found `{}`",
+                vd.ty()
+            )));
         };
         let base_type_size = base_type.size();
 
-        // compute the base address to register t0
-        let line1 = if cxt.location_of_value_contain_pointer(self.src())? {
-            src.move_content_to_reg("t0")
+        // compute the base address to a register
+        let base = if cxt.location_of_value_contain_pointer(self.src())? {
+            src.content_in_reg(Reg::T(0), insts)
         } else {
-            src.move_address_to_reg("t0")
+            insts.extend(src.move_address_to_reg(Reg::T(0)));
+            Reg::T(0)
         };
-        append_line(lines, &line1);
-        append_line(lines, &idx.move_content_to_reg("t1"));
-        append_line(lines, &format!("  li t2, {}", base_type_size));
-        append_line(lines, "  mul t1, t1, t2");
-        append_line(lines, "  add t0, t0, t1");
-        append_line(lines, "  sw t0, <tar>");
-
-        Ok(ValueLocation::PlaceHolder("<tar>".to_string()))
+        insts.extend(element_offset(&idx, base_type_size));
+        insts.push(MachineInst::Binary {
+            op: MachineOp::Add,
+            rd: cxt.target,
+            rs1: base,
+            rs2: Reg::T(1),
+        });
+
+        Ok(ValueLocation::Reg(cxt.target))
     }
 }
 
 impl RiscvGenerate for values::GetPtr {
     type Ret = ValueLocation;
-    
-    fn generate(&self, lines: &mut String, cxt: &mut ProgramContext) -> Result<Self::Ret, ()> {
+
+    fn generate(
+        &self,
+        insts: &mut Vec<MachineInst>,
+        cxt: &mut ProgramContext,
+    ) -> Result<Self::Ret, Diagnostic> {
         // get two locations
-        let src = self.src().generate(&mut String::new(), cxt)?;
-        let idx = self.index().generate(&mut String::new(), cxt)?;
+        let src = operand_location(self.src(), Reg::T(0), insts, cxt)?;
+        let idx = self.index().generate(insts, cxt)?;
 
         let Some(vd) = cxt.get_value_data_locally_or_globally(self.src()) else {
-            return Err(());
+            return Err(Diagnostic::error("the source of `getptr` is never defined"));
         };
-        dbg!(vd.ty());
-        let TypeKind::Pointer(ptr_base)  = vd.ty().kind() else {
-            return Err(());
+        let TypeKind::Pointer(ptr_base) = vd.ty().kind() else {
+            return Err(Diagnostic::error(format!(
+                "the source of `getptr` must be a pointer, found `{}`",
+                vd.ty()
+            )));
         };
         let ptr_base_size = ptr_base.size();
 
-        // compute the base address to register t0
-        append_line(lines, &src.move_content_to_reg("t0"));
-        append_line(lines, &idx.move_content_to_reg("t1"));
-        append_line(lines, &format!("  li t2, {}", ptr_base_size));
-        append_line(lines, "  mul t1, t1, t2");
-        append_line(lines, "  add t0, t0, t1");
-        append_line(lines, "  sw t0, <tar>");
-
-        Ok(ValueLocation::PlaceHolder("<tar>".to_string()))
+        // the base address is the pointer itself
+        let base = src.content_in_reg(Reg::T(0), insts);
+        insts.extend(element_offset(&idx, ptr_base_size));
+        insts.push(MachineInst::Binary {
+            op: MachineOp::Add,
+            rd: cxt.target,
+            rs1: base,
+            rs2: Reg::T(1),
+        });
+
+        Ok(ValueLocation::Reg(cxt.target))
     }
 }
 
 impl RiscvGenerate for values::Binary {
     type Ret = ValueLocation;
 
-    fn generate(&self, lines: &mut String, cxt: &mut ProgramContext) -> Result<Self::Ret, ()> {
-        let loc_l = self.lhs().generate(&mut String::new(), cxt)?;
-        let loc_r = self.rhs().generate(&mut String::new(), cxt)?;
-
-        append_line(lines, &loc_l.move_content_to_reg("t0"));
-        append_line(lines, &loc_r.move_content_to_reg("t1"));
-        match self.op() {
-            BinaryOp::NotEq => {
-                append_line(lines, "  xor t0, t0, t1");
-                append_line(lines, "  snez t0, t0");
-            }
-            BinaryOp::Eq => {
-                append_line(lines, "  xor t0, t0, t1");
-                append_line(lines, "  seqz t0, t0");
-            }
-            BinaryOp::Ge => {
-                append_line(lines, "  slt t0, t0, t1");
-                append_line(lines, "  seqz t0, t0");
-            }
-            BinaryOp::Le => {
-                append_line(lines, "  sgt t0, t0, t1");
-                append_line(lines, "  seqz t0, t0");
-            }
-            op @ _ => {
-                let verb = match op {
-                    BinaryOp::Gt => "sgt",
-                    BinaryOp::Lt => "slt",
-                    BinaryOp::Add => "add",
-                    BinaryOp::Sub => "sub",
-                    BinaryOp::Mul => "mul",
-                    BinaryOp::Div => "div",
-                    BinaryOp::Mod => "rem",
-                    BinaryOp::And => "and",
-                    BinaryOp::Or => "or",
-                    BinaryOp::Xor => "xor",
-                    BinaryOp::Shl => "sll",
-                    BinaryOp::Shr => "srl",
-                    BinaryOp::Sar => "sra",
-                    _ => return Err(()),
-                };
-                append_line(lines, &format!("  {} t0, t0, t1", verb));
-            }
+    fn generate(
+        &self,
+        insts: &mut Vec<MachineInst>,
+        cxt: &mut ProgramContext,
+    ) -> Result<Self::Ret, Diagnostic> {
+        let loc_l = self.lhs().generate(insts, cxt)?;
+        let loc_r = self.rhs().generate(insts, cxt)?;
+
+        // the operands are read before the destination register is written
+        let rs1 = loc_l.content_in_reg(Reg::T(0), insts);
+        let rs2 = loc_r.content_in_reg(Reg::T(1), insts);
+        let rd = cxt.target;
+        let (op, negate) = match self.op() {
+            BinaryOp::NotEq => (MachineOp::Xor, Some(false)),
+            BinaryOp::Eq => (MachineOp::Xor, Some(true)),
+            BinaryOp::Ge => (MachineOp::Slt, Some(true)),
+            BinaryOp::Le => (MachineOp::Sgt, Some(true)),
+            BinaryOp::Gt => (MachineOp::Sgt, None),
+            BinaryOp::Lt => (MachineOp::Slt, None),
+            BinaryOp::Add => (MachineOp::Add, None),
+            BinaryOp::Sub => (MachineOp::Sub, None),
+            BinaryOp::Mul => (MachineOp::Mul, None),
+            BinaryOp::Div => (MachineOp::Div, None),
+            BinaryOp::Mod => (MachineOp::Rem, None),
+            BinaryOp::And => (MachineOp::And, None),
+            BinaryOp::Or => (MachineOp::Or, None),
+            BinaryOp::Xor => (MachineOp::Xor, None),
+            BinaryOp::Shl => (MachineOp::Sll, None),
+            BinaryOp::Shr => (MachineOp::Srl, None),
+            BinaryOp::Sar => (MachineOp::Sra, None),
         };
-        append_line(lines, "  sw t0, <tar>");
+        insts.push(MachineInst::Binary { op, rd, rs1, rs2 });
+        // the comparisons that are not a single instruction test the result against zero
+        match negate {
+            Some(true) => insts.push(MachineInst::Seqz { rd, rs: rd }),
+            Some(false) => insts.push(MachineInst::Snez { rd, rs: rd }),
+            None => {}
+        }
 
-        Ok(ValueLocation::PlaceHolder("<tar>".to_string()))
+        Ok(ValueLocation::Reg(rd))
     }
 }
 
 impl RiscvGenerate for values::Branch {
     type Ret = ValueLocation;
 
-    fn generate(&self, lines: &mut String, cxt: &mut ProgramContext) -> Result<Self::Ret, ()> {
+    fn generate(
+        &self,
+        insts: &mut Vec<MachineInst>,
+        cxt: &mut ProgramContext,
+    ) -> Result<Self::Ret, Diagnostic> {
         let cond_value = self.cond();
-        let cond_loc = cond_value.generate(&mut String::new(), cxt)?;
-        append_line(lines, &cond_loc.move_content_to_reg("t0"));
+        let cond_loc = cond_value.generate(insts, cxt)?;
+        let cond = cond_loc.content_in_reg(Reg::T(0), insts);
 
         // look up basic block names
-        let Some(true_bb_data) = cxt.get_basic_block_data_in_current_function(self.true_bb()) else {
-            return Err(());
-        };
-        let Some(false_bb_data) = cxt.get_basic_block_data_in_current_function(self.false_bb()) else {
-            return Err(());
-        };
-        let Some(true_bb_name) = true_bb_data.name() else {
-            return Err(());
+        let true_bb_name = cxt.get_basic_block_name_in_current_function(self.true_bb())?;
+        let false_bb_name = cxt.get_basic_block_name_in_current_function(self.false_bb())?;
+
+        // The arguments are passed on the edges after the condition is tested.
+        // If both edges pass arguments, the edge to the true target is split by a basic block of its own.
+        let mut true_args = pass_bb_args(self.true_bb(), self.true_args(), cxt)?;
+        let false_args = pass_bb_args(self.false_bb(), self.false_args(), cxt)?;
+        if true_args.is_empty() {
+            insts.push(MachineInst::Bnez {
+                rs: cond,
+                label: true_bb_name,
+            });
+            insts.extend(false_args);
+            insts.push(MachineInst::J {
+                label: false_bb_name,
+            });
+        } else if false_args.is_empty() {
+            insts.push(MachineInst::Beqz {
+                rs: cond,
+                label: false_bb_name,
+            });
+            insts.extend(true_args);
+            insts.push(MachineInst::J {
+                label: true_bb_name,
+            });
+        } else {
+            let edge = cxt.fresh_label(&format!("{}_edge", true_bb_name));
+            insts.push(MachineInst::Bnez {
+                rs: cond,
+                label: edge.clone(),
+            });
+            insts.extend(false_args);
+            insts.push(MachineInst::J {
+                label: false_bb_name,
+            });
+            true_args.push(MachineInst::J {
+                label: true_bb_name,
+            });
+            cxt.edge_blocks.push(MachineBlock {
+                label: edge,
+                insts: true_args,
+            });
+        }
+
+        Ok(ValueLocation::None)
+    }
+}
+
+/// Generate the instructions that pass the arguments to the parameters of the basic block.
+///
+/// The arguments are moved as a parallel copy, since a parameter may be the argument of another one (e.g. in a loop).
+/// The register allocation makes the homes of the parameters live at the end of every predecessor,
+/// so the moves never overwrite a value that is still needed.
+fn pass_bb_args(
+    bb: BasicBlock,
+    args: &[Value],
+    cxt: &mut ProgramContext,
+) -> Result<Vec<MachineInst>, Diagnostic> {
+    let Some(bb_data) = cxt.get_basic_block_data_in_current_function(bb) else {
+        return Err(Diagnostic::error(
+            "a basic block is missing from the current function",
+        ));
+    };
+    let params = bb_data.params().to_vec();
+    if params.len() != args.len() {
+        return Err(Diagnostic::error(format!(
+            "a basic block with {} parameters is given {} arguments",
+            params.len(),
+            args.len()
+        )));
+    }
+
+    let mut moves = Vec::new();
+    for (&param, &arg) in params.iter().zip(args) {
+        let Some(dest) = cxt.get_value_location_local_or_global(param) else {
+            return Err(Diagnostic::error(
+                "no location is assigned to a basic block parameter",
+            ));
         };
-        let Some(false_bb_name) = false_bb_data.name() else {
-            return Err(());
+        let Some(arg_data) = cxt.get_value_data_locally_or_globally(arg) else {
+            return Err(Diagnostic::error(
+                "an argument of a basic block is never defined",
+            ));
         };
+        // the parameter may be anything if the argument is `undef`, so it's left as it is
+        if !matches!(arg_data.kind(), ValueKind::Undef(_)) {
+            moves.push((arg, dest));
+        }
+    }
+    move_operands(&moves, cxt)
+}
 
-        append_line(
-            lines,
-            &format!(
-                "  bnez t0, {}",
-                true_bb_name.replace("%", "").replace("@", "")
-            ),
-        );
-        append_line(
-            lines,
-            &format!("  j {}", false_bb_name.replace("%", "").replace("@", "")),
-        );
+/// Generate the instructions that move the operands to the destinations as a parallel copy.
+fn move_operands(
+    moves: &[(Value, ValueLocation)],
+    cxt: &mut ProgramContext,
+) -> Result<Vec<MachineInst>, Diagnostic> {
+    let mut copies = Vec::new();
+    let mut addresses = Vec::new();
+    for (val, dest) in moves {
+        let is_address = cxt
+            .get_value_data_locally_or_globally(*val)
+            .is_some_and(|data| {
+                matches!(data.kind(), ValueKind::Alloc(_) | ValueKind::GlobalAlloc(_))
+            });
+        if is_address {
+            addresses.push((*val, dest.clone()));
+        } else {
+            copies.push((val.generate(&mut Vec::new(), cxt)?, dest.clone()));
+        }
+    }
 
-        Ok(ValueLocation::None)
+    let mut insts = parallel_copy(&copies);
+    // the addresses read no other location, so they are computed after the parallel copy
+    for (val, dest) in addresses {
+        let loc = operand_location(val, Reg::T(0), &mut insts, cxt)?;
+        insts.extend(loc.move_content_to(dest));
     }
+    Ok(insts)
 }
 
 impl RiscvGenerate for values::Jump {
     type Ret = ValueLocation;
 
-    fn generate(&self, lines: &mut String, cxt: &mut ProgramContext) -> Result<Self::Ret, ()> {
-        let to_bb = self.target();
-        let Some(bb_data) = cxt.get_basic_block_data_in_current_function(to_bb) else {
-            return Err(());
-        };
-        let Some(bb_name) = bb_data.name() else {
-            return Err(());
-        };
-        append_line(
-            lines,
-            &format!("  j {}", bb_name.replace("%", "").replace("@", "")),
-        );
+    fn generate(
+        &self,
+        insts: &mut Vec<MachineInst>,
+        cxt: &mut ProgramContext,
+    ) -> Result<Self::Ret, Diagnostic> {
+        let bb_name = cxt.get_basic_block_name_in_current_function(self.target())?;
+        insts.extend(pass_bb_args(self.target(), self.args(), cxt)?);
+        insts.push(MachineInst::J { label: bb_name });
 
         Ok(ValueLocation::None)
     }
@@ -547,29 +811,48 @@ impl RiscvGenerate for values::Jump {
 impl RiscvGenerate for values::Call {
     type Ret = ValueLocation;
 
-    fn generate(&self, lines: &mut String, cxt: &mut ProgramContext) -> Result<Self::Ret, ()> {
-        // Since all the temporary variables are stored on the stack frame,
-        // we don't need to save the caller-saved registers!!!
+    fn generate(
+        &self,
+        insts: &mut Vec<MachineInst>,
+        cxt: &mut ProgramContext,
+    ) -> Result<Self::Ret, Diagnostic> {
+        // The values live across the call are in callee-saved registers or spill slots,
+        // so we don't need to save the caller-saved registers!!!
         // Nice!
 
         // Prepare the arguments.
-        let args = self.args();
-        for (i, arg) in args.iter().enumerate() {
-            let loc = arg.generate(&mut String::new(), cxt)?;
-            append_line(lines, &loc.act_as_function_arg(i));
+        // The ones on the stack are stored first, which writes no register a value lives in.
+        // The others are moved as a parallel copy, since an argument may live in the register of another one.
+        let mut moves = Vec::new();
+        for (i, &arg) in self.args().iter().enumerate() {
+            match function_arg_location(i, 0, true) {
+                ValueLocation::Reg(reg) => moves.push((arg, ValueLocation::Reg(reg))),
+                _ => {
+                    let loc = operand_location(arg, Reg::T(0), insts, cxt)?;
+                    insts.extend(loc.act_as_function_arg(i));
+                }
+            }
         }
+        insts.extend(move_operands(&moves, cxt)?);
 
         // Call the function.
         let callee = self.callee();
         let callee_data = cxt.get_function_data(callee);
-        append_line(lines, &format!("  call {}", &callee_data.name()[1..]));
+        insts.push(MachineInst::Call {
+            func: String::from(&callee_data.name()[1..]),
+        });
 
         // Get the return value.
-        if format!("{:?}", callee_data.ty()) == "()".to_string() {
+        let returns_unit =
+            matches!(callee_data.ty().kind(), TypeKind::Function(_, ret) if ret.is_unit());
+        if returns_unit {
             Ok(ValueLocation::None)
         } else {
-            append_line(lines, "  sw a0, <tar>");
-            Ok(ValueLocation::PlaceHolder("<tar>".to_string()))
+            insts.push(MachineInst::Mv {
+                rd: cxt.target,
+                rs: Reg::A(0),
+            });
+            Ok(ValueLocation::Reg(cxt.target))
         }
     }
 }
@@ -577,13 +860,17 @@ impl RiscvGenerate for values::Call {
 impl RiscvGenerate for values::Return {
     type Ret = ValueLocation;
 
-    fn generate(&self, lines: &mut String, cxt: &mut ProgramContext) -> Result<Self::Ret, ()> {
+    fn generate(
+        &self,
+        insts: &mut Vec<MachineInst>,
+        cxt: &mut ProgramContext,
+    ) -> Result<Self::Ret, Diagnostic> {
         if let Some(ret_val) = self.value() {
-            let loc = ret_val.generate(&mut String::new(), cxt)?;
-            append_line(lines, &loc.move_content_to_reg("a0"));
+            let loc = operand_location(ret_val, Reg::T(0), insts, cxt)?;
+            insts.extend(loc.move_content_to_reg(Reg::A(0)));
         }
-        append_line(lines, "<epilogue>"); // a place holder, which will be replaced by the epilogue in `FunctionData.generate`.
-        append_line(lines, "  ret");
+        insts.extend(cxt.epilogue.iter().cloned());
+        insts.push(MachineInst::Ret);
 
         Ok(ValueLocation::None)
     }
@@ -1,402 +1,546 @@
-use crate::tools::append_line;
-
 use super::function_call::function_arg_location;
+use super::machine::{BinaryOp, MachineInst, Mem, Reg};
 
 /// The location of a value.
-///
-/// The `String` contained in the variants can be used directly in RISC-V instructions!
 #[derive(Clone, PartialEq, Debug)]
-#[allow(dead_code)]
 pub enum ValueLocation {
     /// An Immediate value.
-    Imm(String),
+    Imm(i32),
     /// Located in a register.
-    Reg(String),
+    Reg(Reg),
     /// Located on the stack frame.
-    Stack(String),
+    Stack(Mem),
     /// Global value
     Global(String),
-    /// A placeholder.
-    ///
-    /// Used in the implementation of `FunctionData.generate(...)`,
-    /// in order to put the value location (returned by `cxt.get_value_location(Value)`) at a correct place.
-    PlaceHolder(String),
     None,
 }
 
-fn extract_base_and_offset(addr: &str) -> Option<(String, i32)> {
-    let splitted_result: Vec<&str> = addr.split(|c| c == '(' || c == ')').collect();
-    if splitted_result.len() != 3 {
-        return None;
-    }
-    let offset = splitted_result[0].parse::<i32>().ok()?;
-    let base = splitted_result[1].to_string();
-    Some((base, offset))
-}
-
-pub fn get_valid_address(addr: &str, temp_reg: &str, lines: &mut String) -> String {
-    let Some((base, offset)) = extract_base_and_offset(addr) else {
-        return addr.to_string();
-    };
-
-    if offset < 2048 && offset >= -2048 {
-        addr.to_string()
+/// Get a memory operand equivalent to `mem`, whose offset fits in the 12-bit immediate of `lw` and `sw`.
+///
+/// If the offset is too large, the address is computed to `temp_reg` by the instructions appended to `insts`.
+pub fn get_valid_address(mem: Mem, temp_reg: Reg, insts: &mut Vec<MachineInst>) -> Mem {
+    if (-2048..2048).contains(&mem.offset) {
+        mem
     } else {
-        append_line(lines, &format!("  li {}, {}", temp_reg, offset));
-        append_line(lines, &format!("  add {}, {}, {}", temp_reg, base, temp_reg));
-        format!("0({})", temp_reg)
+        insts.push(MachineInst::Li {
+            rd: temp_reg,
+            imm: mem.offset,
+        });
+        insts.push(MachineInst::Binary {
+            op: BinaryOp::Add,
+            rd: temp_reg,
+            rs1: mem.base,
+            rs2: temp_reg,
+        });
+        Mem {
+            base: temp_reg,
+            offset: 0,
+        }
     }
 }
 
 impl ValueLocation {
-    /// Generate the instruction (a `String`) that moves the value (with the location) to the given register.
-    pub fn move_content_to_reg(&self, reg: &str) -> String {
+    /// Generate the instructions that move the value (with the location) to the given register.
+    pub fn move_content_to_reg(&self, reg: Reg) -> Vec<MachineInst> {
+        let mut insts = Vec::new();
         match self {
-            Self::Imm(val) => {
-                format!("  li {}, {}", reg, val)
-            }
-            Self::Reg(r) => {
-                format!("  mv {}, {}", reg, r)
-            }
-            Self::Stack(addr) => {
-                let mut lines = String::new();
-                let addr = get_valid_address(addr, "t3", &mut lines);
-                append_line(&mut lines, &format!("  lw {}, {}", reg, addr));
-                lines
+            Self::Imm(val) => insts.push(MachineInst::Li { rd: reg, imm: *val }),
+            Self::Reg(r) => insts.push(MachineInst::Mv { rd: reg, rs: *r }),
+            Self::Stack(mem) => {
+                let mem = get_valid_address(*mem, Reg::T(3), &mut insts);
+                insts.push(MachineInst::Lw { rd: reg, mem });
             }
             Self::Global(s) => {
-                format!("  la t0, {}\n  lw {}, 0(t0)", s, reg)
+                insts.push(MachineInst::La {
+                    rd: Reg::T(0),
+                    symbol: s.clone(),
+                });
+                insts.push(MachineInst::Lw {
+                    rd: reg,
+                    mem: Mem {
+                        base: Reg::T(0),
+                        offset: 0,
+                    },
+                });
             }
-            _ => String::new(),
+            Self::None => {}
         }
+        insts
     }
 
-    /// Generate the instruction (a `String`) that moves the value (with the location) to the given stack address.
-    pub fn move_content_to_stack(&self, addr: &str) -> String {
-        let mut lines = String::new();
-        let addr = get_valid_address(addr, "t3", &mut lines);
-
+    /// Get a register holding the content of the value.
+    ///
+    /// If the value is not in a register yet, the instructions moving it to `reg` are appended to `insts`.
+    pub fn content_in_reg(&self, reg: Reg, insts: &mut Vec<MachineInst>) -> Reg {
         match self {
-            Self::Imm(val) => {
-                append_line(&mut lines, &format!("  li t0, {}\n  sw t0, {}", val, addr));
+            Self::Reg(r) => *r,
+            Self::Imm(0) => Reg::Zero,
+            _ => {
+                insts.extend(self.move_content_to_reg(reg));
+                reg
             }
-            Self::Reg(r) => {
-                append_line(&mut lines, &format!("  sw {}, {}", r, addr));
-            }
-            Self::Stack(addr2) => {
-                let addr2 = get_valid_address(addr2, "t4", &mut lines);
-                append_line(&mut lines, &format!("  lw t0, {}\n  sw t0, {}", addr2, addr));
-            }
-            Self::Global(s) => {
-                append_line(&mut lines, &format!("  la t0, {}\n  lw t0, 0(t0)\n  sw t0, {}", s, addr));
-            }
-            _ => {},
         }
+    }
 
-        lines
+    /// Generate the instructions that move the value (with the location) to the given stack address.
+    pub fn move_content_to_stack(&self, mem: Mem) -> Vec<MachineInst> {
+        let mut insts = Vec::new();
+        let mem = get_valid_address(mem, Reg::T(3), &mut insts);
+        let rs = match self {
+            Self::Reg(r) => *r,
+            Self::Stack(mem2) => {
+                let mem2 = get_valid_address(*mem2, Reg::T(4), &mut insts);
+                insts.push(MachineInst::Lw {
+                    rd: Reg::T(0),
+                    mem: mem2,
+                });
+                Reg::T(0)
+            }
+            Self::None => return Vec::new(),
+            _ => {
+                insts.extend(self.move_content_to_reg(Reg::T(0)));
+                Reg::T(0)
+            }
+        };
+        insts.push(MachineInst::Sw { rs, mem });
+        insts
     }
 
-    pub fn move_content_to_global(&self, name: &str) -> String {
+    /// Generate the instructions that move the value (with the location) to the given global value.
+    pub fn move_content_to_global(&self, name: &str) -> Vec<MachineInst> {
+        let la = |rd| MachineInst::La {
+            rd,
+            symbol: String::from(name),
+        };
+        let at = |base| Mem { base, offset: 0 };
+        let mut insts = Vec::new();
         match self {
-            Self::Imm(val) => {
-                format!("  li t0, {}\n  la t1, {}\n  sw t0, 0(t1)", val, name)
-            }
             Self::Reg(r) => {
-                format!("  la t0, {}\n  sw {}, 0(t0)", name, r)
+                insts.push(la(Reg::T(0)));
+                insts.push(MachineInst::Sw {
+                    rs: *r,
+                    mem: at(Reg::T(0)),
+                });
             }
-            Self::Stack(addr) => {
-                let mut lines = String::new();
-                let addr = get_valid_address(addr, "t3", &mut lines);
-                append_line(&mut lines, &format!("  la t0, {}\n  lw t1, {}\n  sw t1, 0(t0)", name, addr));
-                lines
+            Self::Stack(_) => {
+                insts.push(la(Reg::T(0)));
+                insts.extend(self.move_content_to_reg(Reg::T(1)));
+                insts.push(MachineInst::Sw {
+                    rs: Reg::T(1),
+                    mem: at(Reg::T(0)),
+                });
             }
-            Self::Global(s) => {
-                format!(
-                    "  la t0, {}\n  lw t0, 0(t0)\n  la t1, {}\n  sw t0, 0(t1)",
-                    s, name
-                )
+            Self::Imm(_) | Self::Global(_) => {
+                insts.extend(self.move_content_to_reg(Reg::T(0)));
+                insts.push(la(Reg::T(1)));
+                insts.push(MachineInst::Sw {
+                    rs: Reg::T(0),
+                    mem: at(Reg::T(1)),
+                });
             }
-            _ => String::new(),
+            Self::None => {}
         }
+        insts
     }
 
-    /// Generate the instruction (a `String`) that moves the value (with the location) to the given destination.
-    pub fn move_content_to(&self, dest: ValueLocation) -> String {
+    /// Generate the instructions that move the value (with the location) to the given destination.
+    pub fn move_content_to(&self, dest: ValueLocation) -> Vec<MachineInst> {
         match dest {
-            Self::Reg(r) => self.move_content_to_reg(&r),
-            Self::Stack(addr) => self.move_content_to_stack(&addr),
+            Self::Reg(r) => self.move_content_to_reg(r),
+            Self::Stack(mem) => self.move_content_to_stack(mem),
             Self::Global(name) => self.move_content_to_global(&name),
-            _ => String::new(),
+            _ => Vec::new(),
         }
     }
 
-    pub fn move_address_to_reg(&self, reg: &str) -> String {
+    /// Generate the instructions that move the address of the value (on the stack or global) to the register.
+    pub fn move_address_to_reg(&self, reg: Reg) -> Vec<MachineInst> {
         match self {
-            Self::Stack(addr) => {
-                let Some((base, offset)) = extract_base_and_offset(addr) else {
-                    return String::new();
-                };
-                if offset < 2048 && offset >= -2048 {
-                    format!("  addi {}, {}, {}", reg, base, offset)
-                } else {
-                    format!("  li {}, {}\n  add {}, {}, {}", reg, offset, reg, base, reg)
-                }
-            }
-            Self::Global(s) => {
-                format!("  la {}, {}", reg, s)
-            }
-            _ => String::new(),
+            Self::Stack(mem) if (-2048..2048).contains(&mem.offset) => vec![MachineInst::Addi {
+                rd: reg,
+                rs: mem.base,
+                imm: mem.offset,
+            }],
+            Self::Stack(mem) => vec![
+                MachineInst::Li {
+                    rd: reg,
+                    imm: mem.offset,
+                },
+                MachineInst::Binary {
+                    op: BinaryOp::Add,
+                    rd: reg,
+                    rs1: mem.base,
+                    rs2: reg,
+                },
+            ],
+            Self::Global(s) => vec![MachineInst::La {
+                rd: reg,
+                symbol: s.clone(),
+            }],
+            _ => Vec::new(),
         }
     }
 
-    /// Generate the instruction (a `String`) that treat the value as the i-th argument for a function being called now.
-    pub fn act_as_function_arg(&self, i: usize) -> String {
+    /// Generate the instructions that treat the value as the i-th argument for a function being called now.
+    pub fn act_as_function_arg(&self, i: usize) -> Vec<MachineInst> {
         let dest = function_arg_location(i, 0, true);
         self.move_content_to(dest)
     }
 }
 
+/// Generate the instructions that move the values from the sources to the destinations at the same time,
+/// i.e. every source is read before any destination is written.
+///
+/// The destinations must be different registers or stack slots.
+/// A cycle of moves (e.g. swapping two registers) is broken by the scratch register `t2`.
+pub fn parallel_copy(copies: &[(ValueLocation, ValueLocation)]) -> Vec<MachineInst> {
+    let mut insts = Vec::new();
+    // the immediates read no location, so they are moved after all the others
+    let (immediates, mut pending): (Vec<_>, Vec<_>) = copies
+        .iter()
+        .filter(|(src, dest)| src != dest)
+        .cloned()
+        .partition(|(src, _)| matches!(src, ValueLocation::Imm(_)));
+
+    while !pending.is_empty() {
+        // a destination can be written once no pending move reads it
+        let ready = pending
+            .iter()
+            .position(|(_, dest)| !pending.iter().any(|(src, _)| src == dest));
+        match ready {
+            Some(i) => {
+                let (src, dest) = pending.remove(i);
+                insts.extend(src.move_content_to(dest));
+            }
+            None => {
+                // every destination is read by another move, so they form cycles
+                let saved = pending[0].1.clone();
+                insts.extend(saved.move_content_to_reg(Reg::T(2)));
+                for (src, _) in pending.iter_mut() {
+                    if *src == saved {
+                        *src = ValueLocation::Reg(Reg::T(2));
+                    }
+                }
+            }
+        }
+    }
+    for (src, dest) in immediates {
+        insts.extend(src.move_content_to(dest));
+    }
+    insts
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::target_generate::machine::insts_to_text;
 
     #[test]
     fn move_content_to_test() {
         assert_eq!(
-            ValueLocation::Imm("1".into()).move_content_to(ValueLocation::Reg("a0".into())),
+            insts_to_text(&ValueLocation::Imm(1).move_content_to(ValueLocation::Reg(Reg::A(0)))),
             "  li a0, 1"
         );
         assert_eq!(
-            ValueLocation::Imm("1".into()).move_content_to(ValueLocation::Stack("0(sp)".into())),
+            insts_to_text(&ValueLocation::Imm(1).move_content_to(ValueLocation::Stack(Mem::sp(0)))),
             "  li t0, 1\n  sw t0, 0(sp)"
         );
         assert_eq!(
-            ValueLocation::Imm("1".into()).move_content_to(ValueLocation::Global("a".into())),
+            insts_to_text(
+                &ValueLocation::Imm(1).move_content_to(ValueLocation::Global("a".into()))
+            ),
             "  li t0, 1\n  la t1, a\n  sw t0, 0(t1)"
         );
 
         assert_eq!(
-            ValueLocation::Reg("a0".into()).move_content_to(ValueLocation::Reg("a1".into())),
+            insts_to_text(
+                &ValueLocation::Reg(Reg::A(0)).move_content_to(ValueLocation::Reg(Reg::A(1)))
+            ),
             "  mv a1, a0"
         );
         assert_eq!(
-            ValueLocation::Reg("a0".into()).move_content_to(ValueLocation::Stack("0(sp)".into(),)),
+            insts_to_text(
+                &ValueLocation::Reg(Reg::A(0)).move_content_to(ValueLocation::Stack(Mem::sp(0)))
+            ),
             "  sw a0, 0(sp)"
         );
         assert_eq!(
-            ValueLocation::Reg("a0".into()).move_content_to(ValueLocation::Global("a".into())),
+            insts_to_text(
+                &ValueLocation::Reg(Reg::A(0)).move_content_to(ValueLocation::Global("a".into()))
+            ),
             "  la t0, a\n  sw a0, 0(t0)"
         );
 
         assert_eq!(
-            ValueLocation::Stack("0(sp)".into(),).move_content_to(ValueLocation::Reg("a0".into())),
+            insts_to_text(
+                &ValueLocation::Stack(Mem::sp(0)).move_content_to(ValueLocation::Reg(Reg::A(0)))
+            ),
             "  lw a0, 0(sp)"
         );
         assert_eq!(
-            ValueLocation::Stack("0(sp)".into(),)
-                .move_content_to(ValueLocation::Stack("4(sp)".into(),)),
+            insts_to_text(
+                &ValueLocation::Stack(Mem::sp(0)).move_content_to(ValueLocation::Stack(Mem::sp(4)))
+            ),
             "  lw t0, 0(sp)\n  sw t0, 4(sp)"
         );
         assert_eq!(
-            ValueLocation::Stack("0(sp)".into(),)
-                .move_content_to(ValueLocation::Global("a".into())),
+            insts_to_text(
+                &ValueLocation::Stack(Mem::sp(0))
+                    .move_content_to(ValueLocation::Global("a".into()))
+            ),
             "  la t0, a\n  lw t1, 0(sp)\n  sw t1, 0(t0)"
         );
 
         assert_eq!(
-            ValueLocation::Global("a".into()).move_content_to(ValueLocation::Reg("a0".into())),
+            insts_to_text(
+                &ValueLocation::Global("a".into()).move_content_to(ValueLocation::Reg(Reg::A(0)))
+            ),
             "  la t0, a\n  lw a0, 0(t0)"
         );
         assert_eq!(
-            ValueLocation::Global("a".into())
-                .move_content_to(ValueLocation::Stack("0(sp)".into(),)),
+            insts_to_text(
+                &ValueLocation::Global("a".into())
+                    .move_content_to(ValueLocation::Stack(Mem::sp(0)))
+            ),
             "  la t0, a\n  lw t0, 0(t0)\n  sw t0, 0(sp)"
         );
         assert_eq!(
-            ValueLocation::Global("a".into()).move_content_to(ValueLocation::Global("b".into())),
+            insts_to_text(
+                &ValueLocation::Global("a".into())
+                    .move_content_to(ValueLocation::Global("b".into()))
+            ),
             "  la t0, a\n  lw t0, 0(t0)\n  la t1, b\n  sw t0, 0(t1)"
         )
     }
 
+    #[test]
+    fn parallel_copy_test() {
+        let reg = ValueLocation::Reg;
+        // a chain is moved from its end
+        assert_eq!(
+            insts_to_text(&parallel_copy(&[
+                (reg(Reg::S(0)), reg(Reg::S(1))),
+                (reg(Reg::S(1)), reg(Reg::S(2)))
+            ])),
+            "  mv s2, s1\n  mv s1, s0"
+        );
+        // a swap needs the scratch register
+        assert_eq!(
+            insts_to_text(&parallel_copy(&[
+                (reg(Reg::S(0)), reg(Reg::S(1))),
+                (reg(Reg::S(1)), reg(Reg::S(0)))
+            ])),
+            "  mv t2, s1\n  mv s1, s0\n  mv s0, t2"
+        );
+        // the immediates come last, and a move to itself is dropped
+        assert_eq!(
+            insts_to_text(&parallel_copy(&[
+                (ValueLocation::Imm(1), reg(Reg::S(0))),
+                (reg(Reg::S(0)), ValueLocation::Stack(Mem::sp(0))),
+                (reg(Reg::S(2)), reg(Reg::S(2))),
+            ])),
+            "  sw s0, 0(sp)\n  li s0, 1"
+        );
+    }
+
+    #[test]
+    fn content_in_reg_test() {
+        let mut insts = Vec::new();
+        assert_eq!(
+            ValueLocation::Reg(Reg::S(1)).content_in_reg(Reg::T(0), &mut insts),
+            Reg::S(1)
+        );
+        assert_eq!(
+            ValueLocation::Imm(0).content_in_reg(Reg::T(0), &mut insts),
+            Reg::Zero
+        );
+        assert!(insts.is_empty());
+        assert_eq!(
+            ValueLocation::Stack(Mem::sp(4)).content_in_reg(Reg::T(1), &mut insts),
+            Reg::T(1)
+        );
+        assert_eq!(insts_to_text(&insts), "  lw t1, 4(sp)");
+    }
+
     #[test]
     fn act_as_function_arg_test() {
         assert_eq!(
-            ValueLocation::Imm("1".into()).act_as_function_arg(0),
+            insts_to_text(&ValueLocation::Imm(1).act_as_function_arg(0)),
             "  li a0, 1"
         );
         assert_eq!(
-            ValueLocation::Imm("1".into()).act_as_function_arg(1),
+            insts_to_text(&ValueLocation::Imm(1).act_as_function_arg(1)),
             "  li a1, 1"
         );
         assert_eq!(
-            ValueLocation::Imm("1".into()).act_as_function_arg(2),
+            insts_to_text(&ValueLocation::Imm(1).act_as_function_arg(2)),
             "  li a2, 1"
         );
         assert_eq!(
-            ValueLocation::Imm("1".into()).act_as_function_arg(3),
+            insts_to_text(&ValueLocation::Imm(1).act_as_function_arg(3)),
             "  li a3, 1"
         );
         assert_eq!(
-            ValueLocation::Imm("1".into()).act_as_function_arg(4),
+            insts_to_text(&ValueLocation::Imm(1).act_as_function_arg(4)),
             "  li a4, 1"
         );
         assert_eq!(
-            ValueLocation::Imm("1".into()).act_as_function_arg(5),
+            insts_to_text(&ValueLocation::Imm(1).act_as_function_arg(5)),
             "  li a5, 1"
         );
         assert_eq!(
-            ValueLocation::Imm("1".into()).act_as_function_arg(6),
+            insts_to_text(&ValueLocation::Imm(1).act_as_function_arg(6)),
             "  li a6, 1"
         );
         assert_eq!(
-            ValueLocation::Imm("1".into()).act_as_function_arg(7),
+            insts_to_text(&ValueLocation::Imm(1).act_as_function_arg(7)),
             "  li a7, 1"
         );
         assert_eq!(
-            ValueLocation::Imm("1".into()).act_as_function_arg(8),
+            insts_to_text(&ValueLocation::Imm(1).act_as_function_arg(8)),
             "  li t0, 1\n  sw t0, 0(sp)"
         );
         assert_eq!(
-            ValueLocation::Imm("1".into()).act_as_function_arg(9),
+            insts_to_text(&ValueLocation::Imm(1).act_as_function_arg(9)),
             "  li t0, 1\n  sw t0, 4(sp)"
         );
         assert_eq!(
-            ValueLocation::Imm("1".into()).act_as_function_arg(10),
+            insts_to_text(&ValueLocation::Imm(1).act_as_function_arg(10)),
             "  li t0, 1\n  sw t0, 8(sp)"
         );
 
         assert_eq!(
-            ValueLocation::Reg("a0".into()).act_as_function_arg(0),
+            insts_to_text(&ValueLocation::Reg(Reg::A(0)).act_as_function_arg(0)),
             "  mv a0, a0"
         );
         assert_eq!(
-            ValueLocation::Reg("a0".into()).act_as_function_arg(1),
+            insts_to_text(&ValueLocation::Reg(Reg::A(0)).act_as_function_arg(1)),
             "  mv a1, a0"
         );
         assert_eq!(
-            ValueLocation::Reg("a0".into()).act_as_function_arg(2),
+            insts_to_text(&ValueLocation::Reg(Reg::A(0)).act_as_function_arg(2)),
             "  mv a2, a0"
         );
         assert_eq!(
-            ValueLocation::Reg("a0".into()).act_as_function_arg(3),
+            insts_to_text(&ValueLocation::Reg(Reg::A(0)).act_as_function_arg(3)),
             "  mv a3, a0"
         );
         assert_eq!(
-            ValueLocation::Reg("a0".into()).act_as_function_arg(4),
+            insts_to_text(&ValueLocation::Reg(Reg::A(0)).act_as_function_arg(4)),
             "  mv a4, a0"
         );
         assert_eq!(
-            ValueLocation::Reg("a0".into()).act_as_function_arg(5),
+            insts_to_text(&ValueLocation::Reg(Reg::A(0)).act_as_function_arg(5)),
             "  mv a5, a0"
         );
         assert_eq!(
-            ValueLocation::Reg("a0".into()).act_as_function_arg(6),
+            insts_to_text(&ValueLocation::Reg(Reg::A(0)).act_as_function_arg(6)),
             "  mv a6, a0"
         );
         assert_eq!(
-            ValueLocation::Reg("a0".into()).act_as_function_arg(7),
+            insts_to_text(&ValueLocation::Reg(Reg::A(0)).act_as_function_arg(7)),
             "  mv a7, a0"
         );
         assert_eq!(
-            ValueLocation::Reg("a0".into()).act_as_function_arg(8),
+            insts_to_text(&ValueLocation::Reg(Reg::A(0)).act_as_function_arg(8)),
             "  sw a0, 0(sp)"
         );
         assert_eq!(
-            ValueLocation::Reg("a0".into()).act_as_function_arg(9),
+            insts_to_text(&ValueLocation::Reg(Reg::A(0)).act_as_function_arg(9)),
             "  sw a0, 4(sp)"
         );
         assert_eq!(
-            ValueLocation::Reg("a0".into()).act_as_function_arg(10),
+            insts_to_text(&ValueLocation::Reg(Reg::A(0)).act_as_function_arg(10)),
             "  sw a0, 8(sp)"
         );
 
         assert_eq!(
-            ValueLocation::Stack("0(sp)".into(),).act_as_function_arg(0),
+            insts_to_text(&ValueLocation::Stack(Mem::sp(0)).act_as_function_arg(0)),
             "  lw a0, 0(sp)"
         );
         assert_eq!(
-            ValueLocation::Stack("0(sp)".into(),).act_as_function_arg(1),
+            insts_to_text(&ValueLocation::Stack(Mem::sp(0)).act_as_function_arg(1)),
             "  lw a1, 0(sp)"
         );
         assert_eq!(
-            ValueLocation::Stack("0(sp)".into(),).act_as_function_arg(2),
+            insts_to_text(&ValueLocation::Stack(Mem::sp(0)).act_as_function_arg(2)),
             "  lw a2, 0(sp)"
         );
         assert_eq!(
-            ValueLocation::Stack("0(sp)".into(),).act_as_function_arg(3),
+            insts_to_text(&ValueLocation::Stack(Mem::sp(0)).act_as_function_arg(3)),
             "  lw a3, 0(sp)"
         );
         assert_eq!(
-            ValueLocation::Stack("0(sp)".into(),).act_as_function_arg(4),
+            insts_to_text(&ValueLocation::Stack(Mem::sp(0)).act_as_function_arg(4)),
             "  lw a4, 0(sp)"
         );
         assert_eq!(
-            ValueLocation::Stack("0(sp)".into(),).act_as_function_arg(5),
+            insts_to_text(&ValueLocation::Stack(Mem::sp(0)).act_as_function_arg(5)),
             "  lw a5, 0(sp)"
         );
         assert_eq!(
-            ValueLocation::Stack("0(sp)".into(),).act_as_function_arg(6),
+            insts_to_text(&ValueLocation::Stack(Mem::sp(0)).act_as_function_arg(6)),
             "  lw a6, 0(sp)"
         );
         assert_eq!(
-            ValueLocation::Stack("0(sp)".into(),).act_as_function_arg(7),
+            insts_to_text(&ValueLocation::Stack(Mem::sp(0)).act_as_function_arg(7)),
             "  lw a7, 0(sp)"
         );
         assert_eq!(
-            ValueLocation::Stack("0(sp)".into(),).act_as_function_arg(8),
+            insts_to_text(&ValueLocation::Stack(Mem::sp(0)).act_as_function_arg(8)),
             "  lw t0, 0(sp)\n  sw t0, 0(sp)"
         );
         assert_eq!(
-            ValueLocation::Stack("0(sp)".into(),).act_as_function_arg(9),
+            insts_to_text(&ValueLocation::Stack(Mem::sp(0)).act_as_function_arg(9)),
             "  lw t0, 0(sp)\n  sw t0, 4(sp)"
         );
         assert_eq!(
-            ValueLocation::Stack("0(sp)".into(),).act_as_function_arg(10),
+            insts_to_text(&ValueLocation::Stack(Mem::sp(0)).act_as_function_arg(10)),
             "  lw t0, 0(sp)\n  sw t0, 8(sp)"
         );
 
         assert_eq!(
-            ValueLocation::Global("a".into()).act_as_function_arg(0),
+            insts_to_text(&ValueLocation::Global("a".into()).act_as_function_arg(0)),
             "  la t0, a\n  lw a0, 0(t0)"
         );
         assert_eq!(
-            ValueLocation::Global("a".into()).act_as_function_arg(1),
+            insts_to_text(&ValueLocation::Global("a".into()).act_as_function_arg(1)),
             "  la t0, a\n  lw a1, 0(t0)"
         );
         assert_eq!(
-            ValueLocation::Global("a".into()).act_as_function_arg(2),
+            insts_to_text(&ValueLocation::Global("a".into()).act_as_function_arg(2)),
             "  la t0, a\n  lw a2, 0(t0)"
         );
         assert_eq!(
-            ValueLocation::Global("a".into()).act_as_function_arg(3),
+            insts_to_text(&ValueLocation::Global("a".into()).act_as_function_arg(3)),
             "  la t0, a\n  lw a3, 0(t0)"
         );
         assert_eq!(
-            ValueLocation::Global("a".into()).act_as_function_arg(4),
+            insts_to_text(&ValueLocation::Global("a".into()).act_as_function_arg(4)),
             "  la t0, a\n  lw a4, 0(t0)"
         );
         assert_eq!(
-            ValueLocation::Global("a".into()).act_as_function_arg(5),
+            insts_to_text(&ValueLocation::Global("a".into()).act_as_function_arg(5)),
             "  la t0, a\n  lw a5, 0(t0)"
         );
         assert_eq!(
-            ValueLocation::Global("a".into()).act_as_function_arg(6),
+            insts_to_text(&ValueLocation::Global("a".into()).act_as_function_arg(6)),
             "  la t0, a\n  lw a6, 0(t0)"
         );
         assert_eq!(
-            ValueLocation::Global("a".into()).act_as_function_arg(7),
+            insts_to_text(&ValueLocation::Global("a".into()).act_as_function_arg(7)),
             "  la t0, a\n  lw a7, 0(t0)"
         );
         assert_eq!(
-            ValueLocation::Global("a".into()).act_as_function_arg(8),
+            insts_to_text(&ValueLocation::Global("a".into()).act_as_function_arg(8)),
             "  la t0, a\n  lw t0, 0(t0)\n  sw t0, 0(sp)"
         );
         assert_eq!(
-            ValueLocation::Global("a".into()).act_as_function_arg(9),
+            insts_to_text(&ValueLocation::Global("a".into()).act_as_function_arg(9)),
             "  la t0, a\n  lw t0, 0(t0)\n  sw t0, 4(sp)"
         );
         assert_eq!(
-            ValueLocation::Global("a".into()).act_as_function_arg(10),
+            insts_to_text(&ValueLocation::Global("a".into()).act_as_function_arg(10)),
             "  la t0, a\n  lw t0, 0(t0)\n  sw t0, 8(sp)"
         );
     }
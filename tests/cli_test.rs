@@ -0,0 +1,118 @@
+use std::fs;
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+fn compiler(args: &[&str], stdin: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_sysy_compiler"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    // the driver does not read stdin when it's given an input file, and may exit before the write
+    let written = child.stdin.take().unwrap().write_all(stdin.as_bytes());
+    if let Err(e) = written {
+        assert_eq!(e.kind(), std::io::ErrorKind::BrokenPipe, "{}", e);
+    }
+    child.wait_with_output().unwrap()
+}
+
+#[test]
+fn cli_judge_test() {
+    let dir = std::env::temp_dir().join(format!("sysy_compiler_cli_test_{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let input = dir.join("main.sy");
+    fs::write(&input, "int main() { return 3; }").unwrap();
+    let input = input.to_str().unwrap();
+
+    // the invocation of the course judge is quiet, and writes the output file
+    for (mode, text) in [
+        ("-koopa", "ret 3"),
+        ("-riscv", "li a0, 3"),
+        ("-perf", "li a0, 3"),
+    ] {
+        let output = dir.join("main.out");
+        let result = compiler(&[mode, input, "-o", output.to_str().unwrap()], "");
+        assert!(result.status.success());
+        assert!(result.stdout.is_empty());
+        assert!(fs::read_to_string(&output).unwrap().contains(text));
+    }
+
+    // several stages are written next to the output file
+    let stem = dir.join("out");
+    let result = compiler(
+        &["--emit=tokens,koopa", "-o", stem.to_str().unwrap(), input],
+        "",
+    );
+    assert!(result.status.success());
+    assert!(fs::read_to_string(stem.with_extension("tokens"))
+        .unwrap()
+        .starts_with("1:1 keyword int\n"));
+    assert!(fs::read_to_string(stem.with_extension("koopa"))
+        .unwrap()
+        .contains("ret 3"));
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn cli_stdio_test() {
+    let result = compiler(&["--emit=ast", "-"], "int main() { return 0; }");
+    assert!(result.status.success());
+    assert!(String::from_utf8(result.stdout)
+        .unwrap()
+        .starts_with("CompUnit {"));
+
+    let result = compiler(
+        &["-v", "--emit=koopa", "-O2", "-o", "-", "-"],
+        "int main() { return 0; }",
+    );
+    assert!(result.status.success());
+    assert!(String::from_utf8(result.stdout)
+        .unwrap()
+        .contains("fun @main(): i32"));
+    assert!(String::from_utf8(result.stderr).unwrap().contains("Ast:"));
+}
+
+#[test]
+fn cli_exit_code_test() {
+    let code = |args: &[&str], stdin: &str| compiler(args, stdin).status.code();
+    assert_eq!(code(&["-"], "int main() { return 0; }"), Some(0));
+    assert_eq!(code(&["--emit=bytes", "-"], ""), Some(1));
+    assert_eq!(code(&["-riscv", "no_such_file.sy", "-o", "-"], ""), Some(1));
+    assert_eq!(code(&["-"], "int main() { return 0 }"), Some(2));
+    assert_eq!(code(&["--emit=tokens", "-"], "int a = 1 & 2;"), Some(2));
+    assert_eq!(code(&["-"], "int main() { return x; }"), Some(3));
+    assert_eq!(
+        code(&["--enable-pass=nothing", "-"], "int main() { return 0; }"),
+        Some(1)
+    );
+}
+
+#[test]
+fn cli_koopa_input_test() {
+    let koopa = "fun @main(): i32 {\n%entry:\n  %0 = add 1, 2\n  ret %0\n}\n";
+    let result = compiler(&["-x", "koopa", "--emit=koopa,riscv", "-"], koopa);
+    assert!(result.status.success());
+    let stdout = String::from_utf8(result.stdout).unwrap();
+    assert!(stdout.contains("%0 = add 1, 2") && stdout.contains("main:"));
+
+    // the extension `.koopa` selects the language
+    let dir = std::env::temp_dir().join(format!("sysy_compiler_koopa_test_{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let input = dir.join("main.koopa");
+    fs::write(&input, koopa).unwrap();
+    let input = input.to_str().unwrap();
+    let result = compiler(&["-riscv", input, "-o", "-"], "");
+    assert!(result.status.success());
+    // the output file would overwrite the input file
+    assert_eq!(
+        compiler(&["--emit=koopa", input], "").status.code(),
+        Some(1)
+    );
+    fs::remove_dir_all(&dir).unwrap();
+
+    let code = |args: &[&str], stdin: &str| compiler(args, stdin).status.code();
+    assert_eq!(code(&["-x", "koopa", "--emit=ast", "-"], koopa), Some(1));
+    assert_eq!(code(&["-x", "koopa", "-"], "fun @main(): i32 {"), Some(2));
+}
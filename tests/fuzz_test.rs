@@ -0,0 +1,13 @@
+use sysy_compiler::fuzz;
+
+#[test]
+fn fuzz_test() {
+    let findings = fuzz::fuzz(20240601, 40);
+    for finding in &findings {
+        eprintln!(
+            "seed {}: {}\n{}",
+            finding.seed, finding.message, finding.program.source
+        );
+    }
+    assert!(findings.is_empty());
+}
@@ -0,0 +1,10 @@
+use std::path::Path;
+use sysy_compiler::*;
+
+#[test]
+fn harness_test_testcases() {
+    let report = harness::run_tests(Path::new("tests/testcases")).unwrap();
+    println!("{}", report);
+    assert!(!report.results.is_empty());
+    assert!(report.all_passed(), "{}", report);
+}
@@ -148,4 +148,147 @@ fn koopa_mod_test_lv9_3() {
         "tests/sysy_scripts/lv9-3.c",
         "tests/koopa_scripts/lv9-3.koopa",
     );
-}
\ No newline at end of file
+}
+
+#[test]
+fn koopa_diagnostic_test_assign_to_const() {
+    let src = "int main() {\n  const int a = 1;\n  a = 2;\n  return a;\n}\n";
+    let ast = ast_generate::parse_sysy_to_ast(src).unwrap();
+    let diags = semantic::check_ast(&ast).unwrap_err();
+    assert_eq!(diags.len(), 1);
+    let diag = &diags[0];
+    assert!(diag.is_error());
+    let primary = diag.primary.as_ref().unwrap();
+    assert_eq!((primary.span.line, primary.span.col), (3, 3));
+    assert_eq!(diag.secondary[0].span.line, 2);
+}
+
+#[test]
+fn koopa_int_literal_test() {
+    let src = "const int M = -2147483648;\nint main() {\n  int a = -2147483648;\n  return a + M + 0xFFFFFFFF;\n}\n";
+    let ast = ast_generate::parse_sysy_to_ast(src).unwrap();
+    let text = ir_generate::parse_ast_to_koopa_text(&ast).unwrap();
+    assert!(text.contains("-2147483648"));
+    ir_generate::get_koopa_program(&text).unwrap();
+
+    let src = "int main() {\n  return 2147483648;\n}\n";
+    let ast = ast_generate::parse_sysy_to_ast(src).unwrap();
+    let diag = ir_generate::parse_ast_to_koopa_text(&ast).unwrap_err();
+    let primary = diag.primary.as_ref().unwrap();
+    assert_eq!((primary.span.line, primary.span.col), (2, 10));
+}
+
+#[test]
+fn koopa_prototype_test() {
+    let src = r#"
+int sum(int a[][2], int n);
+void _sysy_starttime(int lineno);
+int is_odd(int n);
+int main() {
+    int a[2][2] = {1, 2, 3, 4};
+    _sysy_starttime(1);
+    return is_odd(3) + sum(a, 2);
+}
+int is_odd(int n) {
+    if (n == 0) return 0;
+    if (n == 1) return 1;
+    return is_odd(n - 2);
+}
+"#;
+    let ast = ast_generate::parse_sysy_to_ast(src).unwrap();
+    semantic::check_ast(&ast).unwrap();
+    let text = ir_generate::parse_ast_to_koopa_text(&ast).unwrap();
+    assert!(text.contains("decl @sum(*[i32, 2], i32): i32"));
+    assert!(text.contains("decl @_sysy_starttime(i32)\n"));
+    assert!(!text.contains("decl @is_odd"));
+    // the functions are kept in the order of the source code
+    assert!(text.find("fun @main").unwrap() < text.find("fun @is_odd").unwrap());
+}
+
+#[test]
+fn koopa_mutual_recursion_test() {
+    let src = r#"
+int is_even(int n);
+int is_odd(int n) {
+    if (n == 0) return 0;
+    return is_even(n - 1);
+}
+int is_even(int n) {
+    if (n == 0) return 1;
+    return is_odd(n - 1);
+}
+int main() {
+    return is_odd(7);
+}
+"#;
+    let ast = ast_generate::parse_sysy_to_ast(src).unwrap();
+    semantic::check_ast(&ast).unwrap();
+    let program = ir_generate::parse_ast_to_koopa_program(&ast).unwrap();
+    let text = ir_generate::koopa_program_to_text(&program).unwrap();
+    assert!(text.contains("call @is_even("));
+    assert!(text.contains("call @is_odd("));
+    target_generate::parse_koopa_program_to_riscv(&program).unwrap();
+}
+
+/// Compile the SysY source code to Koopa text, run it on the interpreter, and check the output and the exit code.
+fn koopa_interpret_test(source: &str, stdin: &str, stdout: &str, exit_code: i32) {
+    let ast = ast_generate::parse_sysy_to_ast(source).unwrap();
+    semantic::check_ast(&ast).unwrap();
+    let text = ir_generate::parse_ast_to_koopa_text(&ast).unwrap();
+    let program = ir_generate::get_koopa_program(&text).unwrap();
+    let result = koopa_interpret::interpret_koopa(&program, stdin).unwrap();
+    assert_eq!(result.stdout, stdout, "{}", text);
+    assert_eq!(result.exit_code, exit_code, "{}", text);
+}
+
+#[test]
+fn koopa_interpret_test_scripts() {
+    let cases = [
+        ("lv1", "", "", 112),
+        ("lv2", "", "", 514),
+        ("lv3-1", "", "", 0),
+        ("lv3-2", "", "", 7),
+        ("lv3-3", "", "", 1),
+        ("lv4-1", "", "", 3),
+        ("lv4-2", "", "", 2),
+        ("lv5", "", "", 0),
+        ("lv6-1", "", "", 3),
+        ("lv6-2", "", "", 1),
+        ("lv7-1", "", "", 26),
+        ("lv7-2", "", "", 2),
+        ("lv8-0", "", "", 5),
+        ("lv8-1", "", "", 6),
+        ("lv8-2", "42", "", 42),
+        ("lv8-3", "", "", 15),
+        ("lv9-1", "", "", 1),
+        ("lv9-2", "", "", 0),
+        ("lv9-3", "", "0\n1\n2\n3\n4\n5\n6\n7\n8\n9\n", 0),
+    ];
+    for (name, stdin, stdout, exit_code) in cases {
+        let source = fs::read_to_string(format!("tests/sysy_scripts/{}.c", name)).unwrap();
+        koopa_interpret_test(&source, stdin, stdout, exit_code);
+    }
+}
+
+#[test]
+fn koopa_interpret_test_io() {
+    let source = r"
+int a[2][3] = {{1, 2}, {3}};
+int main() {
+  int n = getarray(a[1]);
+  putarray(6, a[0]);
+  int i = 0, sum = 0;
+  while (i < 6) {
+    if (a[i / 3][i % 3] % 2 == 0) {
+      i = i + 1;
+      continue;
+    }
+    sum = sum + a[i / 3][i % 3];
+    i = i + 1;
+  }
+  putint(sum);
+  putch(getch());
+  return n * 100 + sum;
+}";
+    koopa_interpret_test(source, "2 5 7!", "6: 1 2 0 5 7 0\n13!", 213);
+}
@@ -1,9 +1,7 @@
   .text
   .globl main
 main:
-  # no prologue
 entry_1:
   li a0, 514
-  # no epilogue
   ret
- 
\ No newline at end of file
+
@@ -1,22 +1,12 @@
   .text
   .globl main
 main:
-  addi sp, sp, -16
 entry_1:
-  li t0, 0
   li t1, 6
-  xor t0, t0, t1
-  seqz t0, t0
-  sw t0, 0(sp)
-  li t0, 0
-  lw t1, 0(sp)
-  sub t0, t0, t1
-  sw t0, 4(sp)
-  li t0, 0
-  lw t1, 4(sp)
-  sub t0, t0, t1
-  sw t0, 8(sp)
-  lw a0, 8(sp)
-  addi sp, sp, 16
+  xor t5, zero, t1
+  seqz t5, t5
+  sub t6, zero, t5
+  sub t5, zero, t6
+  mv a0, t5
   ret
- 
\ No newline at end of file
+
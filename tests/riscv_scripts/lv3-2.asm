@@ -1,17 +1,12 @@
   .text
   .globl main
 main:
-  addi sp, sp, -16
 entry_1:
   li t0, 2
   li t1, 3
-  mul t0, t0, t1
-  sw t0, 0(sp)
+  mul t5, t0, t1
   li t0, 1
-  lw t1, 0(sp)
-  add t0, t0, t1
-  sw t0, 4(sp)
-  lw a0, 4(sp)
-  addi sp, sp, 16
+  add t6, t0, t5
+  mv a0, t6
   ret
- 
\ No newline at end of file
+
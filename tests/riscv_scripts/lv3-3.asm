@@ -1,40 +1,25 @@
   .text
   .globl main
 main:
-  addi sp, sp, -32
+  addi sp, sp, -16
 entry_1:
-  li t0, 0
   li t1, 3
-  xor t0, t0, t1
-  seqz t0, t0
-  sw t0, 4(sp)
-  lw t0, 4(sp)
-  li t1, 0
-  xor t0, t0, t1
-  snez t0, t0
-  sw t0, 8(sp)
-  lw t0, 8(sp)
-  sw t0, 0(sp)
-  lw t0, 8(sp)
-  bnez t0, and_rhs_1
-  j and_end_1
+  xor t5, zero, t1
+  seqz t5, t5
+  xor t6, t5, zero
+  snez t6, t6
+  sw t6, 0(sp)
+  beqz t6, and_end_1
 and_rhs_1:
   li t0, 1
-  li t1, 0
-  xor t0, t0, t1
-  snez t0, t0
-  sw t0, 12(sp)
-  lw t0, 12(sp)
-  sw t0, 0(sp)
-  j and_end_1
+  xor t5, t0, zero
+  snez t5, t5
+  sw t5, 0(sp)
 and_end_1:
-  lw t0, 0(sp)
-  sw t0, 16(sp)
+  lw t5, 0(sp)
   li t0, 1
-  lw t1, 16(sp)
-  sub t0, t0, t1
-  sw t0, 20(sp)
-  lw a0, 20(sp)
-  addi sp, sp, 32
+  sub t6, t0, t5
+  mv a0, t6
+  addi sp, sp, 16
   ret
- 
\ No newline at end of file
+
@@ -1,17 +1,11 @@
   .text
   .globl main
 main:
-  addi sp, sp, -16
 entry_1:
   li t0, 2
-  li t1, 0
-  sub t0, t0, t1
-  sw t0, 0(sp)
-  lw t0, 0(sp)
+  sub t5, t0, zero
   li t1, 1
-  add t0, t0, t1
-  sw t0, 4(sp)
-  lw a0, 4(sp)
-  addi sp, sp, 16
+  add t6, t5, t1
+  mv a0, t6
   ret
- 
\ No newline at end of file
+
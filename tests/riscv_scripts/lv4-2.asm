@@ -1,41 +1,26 @@
   .text
   .globl main
 main:
-  addi sp, sp, -48
+  addi sp, sp, -16
 entry_1:
   li t0, 10
   li t1, 4
-  mul t0, t0, t1
-  sw t0, 4(sp)
-  lw t0, 4(sp)
-  sw t0, 0(sp)
+  mul t5, t0, t1
+  sw t5, 0(sp)
   li t0, 4
   li t1, 1
-  sub t0, t0, t1
-  sw t0, 12(sp)
-  lw t0, 12(sp)
-  sw t0, 8(sp)
-  lw t0, 0(sp)
-  sw t0, 16(sp)
-  lw t0, 16(sp)
+  sub t5, t0, t1
+  sw t5, 4(sp)
+  lw t5, 0(sp)
   li t1, 1
-  add t0, t0, t1
-  sw t0, 20(sp)
-  lw t0, 8(sp)
-  sw t0, 24(sp)
-  lw t0, 20(sp)
-  lw t1, 24(sp)
-  rem t0, t0, t1
-  sw t0, 28(sp)
-  lw t0, 28(sp)
-  sw t0, 0(sp)
-  lw t0, 0(sp)
-  sw t0, 32(sp)
-  lw t0, 32(sp)
+  add t6, t5, t1
+  lw t5, 4(sp)
+  rem a0, t6, t5
+  sw a0, 0(sp)
+  lw t5, 0(sp)
   li t1, 4
-  rem t0, t0, t1
-  sw t0, 36(sp)
-  lw a0, 36(sp)
-  addi sp, sp, 48
+  rem t6, t5, t1
+  mv a0, t6
+  addi sp, sp, 16
   ret
- 
\ No newline at end of file
+
@@ -1,7 +1,7 @@
   .text
   .globl main
 main:
-  addi sp, sp, -32
+  addi sp, sp, -16
 entry_1:
   li t0, 1
   sw t0, 0(sp)
@@ -9,16 +9,11 @@ entry_1:
   sw t0, 0(sp)
   li t0, 3
   sw t0, 4(sp)
-  lw t0, 0(sp)
-  sw t0, 8(sp)
-  lw t0, 8(sp)
+  lw t5, 0(sp)
   li t1, 6
-  add t0, t0, t1
-  sw t0, 12(sp)
-  lw t0, 12(sp)
+  add t6, t5, t1
   li t1, 1
-  sub t0, t0, t1
-  sw t0, 16(sp)
-  addi sp, sp, 32
+  sub t5, t6, t1
+  addi sp, sp, 16
   ret
- 
\ No newline at end of file
+
@@ -1,101 +1,56 @@
   .text
   .globl main
 main:
-  addi sp, sp, -96
+  addi sp, sp, -16
 entry_1:
   li t0, 2
   sw t0, 0(sp)
-  lw t0, 0(sp)
-  sw t0, 4(sp)
-  lw t0, 4(sp)
-  bnez t0, if_then_1
-  j if_else_1
+  lw t5, 0(sp)
+  beqz t5, if_else_1
 if_then_1:
-  lw t0, 0(sp)
-  sw t0, 8(sp)
-  lw t0, 8(sp)
+  lw t5, 0(sp)
   li t1, 1
-  add t0, t0, t1
-  sw t0, 12(sp)
-  lw t0, 12(sp)
-  sw t0, 0(sp)
-  lw t0, 0(sp)
-  sw t0, 20(sp)
+  add t6, t5, t1
+  sw t6, 0(sp)
+  lw t5, 0(sp)
   li t0, 2
-  lw t1, 20(sp)
-  mul t0, t0, t1
-  sw t0, 24(sp)
-  lw t0, 24(sp)
-  sw t0, 16(sp)
+  mul t6, t0, t5
+  sw t6, 4(sp)
   li t0, 0
-  sw t0, 28(sp)
-  lw t0, 28(sp)
-  sw t0, 32(sp)
-  lw t0, 32(sp)
-  bnez t0, if_then_2
-  j if_else_2
-if_else_1:
-  li t0, 4
-  sw t0, 0(sp)
-  j if_end_1
+  sw t0, 8(sp)
+  lw t5, 8(sp)
+  beqz t5, if_else_2
 if_then_2:
-  lw t0, 16(sp)
-  sw t0, 36(sp)
-  lw t0, 36(sp)
+  lw t5, 4(sp)
   li t1, 1
-  add t0, t0, t1
-  sw t0, 40(sp)
-  lw t0, 40(sp)
-  sw t0, 16(sp)
-  lw t0, 16(sp)
-  sw t0, 44(sp)
-  lw t0, 44(sp)
-  li t1, 0
-  slt t0, t0, t1
-  sw t0, 48(sp)
-  lw t0, 48(sp)
-  bnez t0, if_then_3
-  j if_else_3
+  add t6, t5, t1
+  sw t6, 4(sp)
+  lw t5, 4(sp)
+  slt t6, t5, zero
+  beqz t6, if_end_3
+if_then_3:
+  lw t5, 4(sp)
+  sub t6, zero, t5
+  sw t6, 4(sp)
+if_end_3:
+  j if_end_2
 if_else_2:
-  lw t0, 16(sp)
-  sw t0, 52(sp)
-  lw t0, 52(sp)
+  lw t5, 4(sp)
   li t1, 4
-  rem t0, t0, t1
-  sw t0, 56(sp)
-  lw t0, 56(sp)
-  sw t0, 16(sp)
-  j if_end_2
-if_end_1:
-  lw t0, 0(sp)
-  sw t0, 60(sp)
-  lw a0, 60(sp)
-  addi sp, sp, 96
-  ret
-if_then_3:
-  lw t0, 16(sp)
-  sw t0, 64(sp)
-  li t0, 0
-  lw t1, 64(sp)
-  sub t0, t0, t1
-  sw t0, 68(sp)
-  lw t0, 68(sp)
-  sw t0, 16(sp)
-  j if_end_3
-if_else_3:
-  j if_end_3
+  rem t6, t5, t1
+  sw t6, 4(sp)
 if_end_2:
-  lw t0, 28(sp)
-  sw t0, 72(sp)
-  lw t0, 16(sp)
-  sw t0, 76(sp)
-  lw t0, 72(sp)
-  lw t1, 76(sp)
-  add t0, t0, t1
-  sw t0, 80(sp)
-  lw t0, 80(sp)
-  sw t0, 28(sp)
+  lw t5, 8(sp)
+  lw t6, 4(sp)
+  add a0, t5, t6
+  sw a0, 8(sp)
   j if_end_1
-if_end_3:
-  j if_end_2
- 
\ No newline at end of file
+if_else_1:
+  li t0, 4
+  sw t0, 0(sp)
+if_end_1:
+  lw t5, 0(sp)
+  mv a0, t5
+  addi sp, sp, 16
+  ret
+
@@ -1,79 +1,46 @@
   .text
   .globl main
 main:
-  addi sp, sp, -64
+  addi sp, sp, -16
 entry_1:
   li t0, 0
   sw t0, 0(sp)
   li t0, 1
   sw t0, 4(sp)
-  lw t0, 4(sp)
-  sw t0, 8(sp)
-  lw t0, 8(sp)
+  lw t5, 4(sp)
   li t1, 10
-  slt t0, t0, t1
-  sw t0, 12(sp)
-  lw t0, 12(sp)
-  bnez t0, if_then_1
-  j if_else_1
+  slt t6, t5, t1
+  beqz t6, if_end_1
 if_then_1:
-  j while_entry_1
-if_else_1:
-  j if_end_1
 while_entry_1:
-  lw t0, 0(sp)
-  sw t0, 16(sp)
-  lw t0, 16(sp)
+  lw t5, 0(sp)
   li t1, 3
-  slt t0, t0, t1
-  sw t0, 20(sp)
-  lw t0, 20(sp)
-  bnez t0, while_body_1
-  j while_end_1
-if_end_1:
-  lw t0, 4(sp)
-  sw t0, 24(sp)
-  lw a0, 24(sp)
-  addi sp, sp, 64
-  ret
+  slt t6, t5, t1
+  beqz t6, while_end_1
 while_body_1:
-  lw t0, 4(sp)
-  sw t0, 28(sp)
-  lw t0, 28(sp)
+  lw t5, 4(sp)
   li t1, 10
-  slt t0, t0, t1
-  sw t0, 32(sp)
-  lw t0, 32(sp)
-  bnez t0, if_then_2
-  j if_else_2
-while_end_1:
-  j if_end_1
+  slt t6, t5, t1
+  beqz t6, if_else_1
 if_then_2:
-  lw t0, 4(sp)
-  sw t0, 36(sp)
-  lw t0, 4(sp)
-  sw t0, 40(sp)
-  lw t0, 36(sp)
-  lw t1, 40(sp)
-  mul t0, t0, t1
-  sw t0, 44(sp)
-  lw t0, 44(sp)
+  lw t5, 4(sp)
+  lw t6, 4(sp)
+  mul a0, t5, t6
   li t1, 1
-  add t0, t0, t1
-  sw t0, 48(sp)
-  lw t0, 48(sp)
-  sw t0, 4(sp)
+  add t5, a0, t1
+  sw t5, 4(sp)
   j if_end_2
-if_else_2:
-  lw t0, 0(sp)
-  sw t0, 52(sp)
-  lw t0, 52(sp)
+if_else_1:
+  lw t5, 0(sp)
   li t1, 1
-  add t0, t0, t1
-  sw t0, 56(sp)
-  lw t0, 56(sp)
-  sw t0, 0(sp)
-  j if_end_2
+  add t6, t5, t1
+  sw t6, 0(sp)
 if_end_2:
   j while_entry_1
- 
\ No newline at end of file
+while_end_1:
+if_end_1:
+  lw t5, 4(sp)
+  mv a0, t5
+  addi sp, sp, 16
+  ret
+
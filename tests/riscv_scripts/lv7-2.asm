@@ -1,74 +1,46 @@
   .text
   .globl main
 main:
-  addi sp, sp, -64
+  addi sp, sp, -16
 entry_1:
   li t0, 0
   sw t0, 0(sp)
   li t0, 1
   sw t0, 4(sp)
-  j while_entry_1
 while_entry_1:
-  lw t0, 0(sp)
-  sw t0, 8(sp)
-  li t0, 0
-  lw t1, 8(sp)
-  xor t0, t0, t1
-  seqz t0, t0
-  sw t0, 12(sp)
-  lw t0, 12(sp)
-  bnez t0, while_body_1
-  j while_end_1
+  lw t5, 0(sp)
+  xor t6, zero, t5
+  seqz t6, t6
+  beqz t6, while_end_1
 while_body_1:
-  j while_entry_2
-while_end_1:
-  lw t0, 4(sp)
-  sw t0, 16(sp)
-  lw a0, 16(sp)
-  addi sp, sp, 64
-  ret
 while_entry_2:
-  lw t0, 4(sp)
-  sw t0, 20(sp)
-  lw t0, 20(sp)
+  lw t5, 4(sp)
   li t1, 3
-  slt t0, t0, t1
-  sw t0, 24(sp)
-  lw t0, 24(sp)
-  bnez t0, while_body_2
-  j while_end_2
+  slt t6, t5, t1
+  beqz t6, while_end_2
 while_body_2:
-  lw t0, 4(sp)
-  sw t0, 28(sp)
-  lw t0, 28(sp)
+  lw t5, 4(sp)
   li t1, 2
-  rem t0, t0, t1
-  sw t0, 32(sp)
-  lw t0, 32(sp)
-  bnez t0, if_then_1
-  j if_else_1
-while_end_2:
-  j while_entry_1
+  rem t6, t5, t1
+  beqz t6, if_else_1
 if_then_1:
-  lw t0, 4(sp)
-  sw t0, 36(sp)
-  lw t0, 36(sp)
+  lw t5, 4(sp)
   li t1, 1
-  add t0, t0, t1
-  sw t0, 40(sp)
-  lw t0, 40(sp)
-  sw t0, 4(sp)
+  add t6, t5, t1
+  sw t6, 4(sp)
   j if_end_1
 if_else_1:
   j while_entry_2
 if_end_1:
-  lw t0, 0(sp)
-  sw t0, 44(sp)
-  lw t0, 44(sp)
+  lw t5, 0(sp)
   li t1, 1
-  add t0, t0, t1
-  sw t0, 48(sp)
-  lw t0, 48(sp)
-  sw t0, 0(sp)
-  j while_end_2
- 
\ No newline at end of file
+  add t6, t5, t1
+  sw t6, 0(sp)
+while_end_2:
+  j while_entry_1
+while_end_1:
+  lw t5, 4(sp)
+  mv a0, t5
+  addi sp, sp, 16
+  ret
+
@@ -2,26 +2,22 @@
   .globl half
 half:
   addi sp, sp, -16
+  mv t5, a0
 entry_1:
-  sw a0, 0(sp)
-  lw t0, 0(sp)
-  sw t0, 4(sp)
-  lw t0, 4(sp)
+  sw t5, 0(sp)
+  lw t5, 0(sp)
   li t1, 2
-  div t0, t0, t1
-  sw t0, 8(sp)
-  lw a0, 8(sp)
+  div t6, t5, t1
+  mv a0, t6
   addi sp, sp, 16
   ret
- 
+
   .text
   .globl f
 f:
-  # no prologue
 entry_2:
-  # no epilogue
   ret
- 
+
   .text
   .globl main
 main:
@@ -31,9 +27,9 @@ entry_3:
   call f
   li a0, 10
   call half
-  sw a0, 4(sp)
-  lw a0, 4(sp)
+  mv t5, a0
+  mv a0, t5
   lw ra, 12(sp)
   addi sp, sp, 16
   ret
- 
\ No newline at end of file
+
@@ -5,9 +5,9 @@ main:
   sw ra, 12(sp)
 entry_1:
   call getint
-  sw a0, 0(sp)
-  lw a0, 0(sp)
+  mv t5, a0
+  mv a0, t5
   lw ra, 12(sp)
   addi sp, sp, 16
   ret
- 
\ No newline at end of file
+
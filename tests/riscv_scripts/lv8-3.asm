@@ -2,16 +2,16 @@
   .globl z_1
 z_1:
   .word 5
- 
+
   .data
   .globl var_1
 var_1:
   .zero 4
- 
+
   .text
   .globl main
 main:
-  addi sp, sp, -32
+  addi sp, sp, -16
 entry_1:
   li t0, 3
   sw t0, 0(sp)
@@ -19,19 +19,12 @@ entry_1:
   la t1, var_1
   sw t0, 0(t1)
   la t0, var_1
-  lw t0, 0(t0)
-  sw t0, 4(sp)
-  lw t0, 4(sp)
+  lw t5, 0(t0)
   li t1, 1
-  add t0, t0, t1
-  sw t0, 8(sp)
-  lw t0, 0(sp)
-  sw t0, 12(sp)
-  lw t0, 8(sp)
-  lw t1, 12(sp)
-  mul t0, t0, t1
-  sw t0, 16(sp)
-  lw a0, 16(sp)
-  addi sp, sp, 32
+  add t6, t5, t1
+  lw t5, 0(sp)
+  mul a0, t6, t5
+  mv a0, a0
+  addi sp, sp, 16
   ret
- 
\ No newline at end of file
+
@@ -145,4 +145,144 @@ fn riscv_mod_test_lv9_3() {
         "tests/sysy_scripts/lv9-3.c",
         "tests/riscv_scripts/lv9-3.asm",
     );
-}
\ No newline at end of file
+}
+
+#[test]
+fn riscv_perf_verify_each_test() {
+    let output = std::env::temp_dir().join("sysy_compiler_perf_lv9-3.asm");
+    let options = opt::OptOptions {
+        verify_each: true,
+        ..opt::OptOptions::default()
+    };
+    run_with_options(
+        Mode::Perf,
+        "tests/sysy_scripts/lv9-3.c",
+        output.to_str().unwrap(),
+        &options,
+    )
+    .unwrap();
+}
+
+/// Compile the SysY file to RISC-V, run it on the simulator, and check the output and the exit code.
+fn riscv_simulate_test(input: &str, stdin: &str, stdout: &str, exit_code: i32) {
+    for mode in [Mode::Riscv, Mode::Perf] {
+        let name = format!(
+            "sysy_compiler_simulate_{:?}_{}.asm",
+            mode,
+            std::path::Path::new(input)
+                .file_stem()
+                .unwrap()
+                .to_str()
+                .unwrap()
+        );
+        let output = std::env::temp_dir().join(name);
+        run(mode, input, output.to_str().unwrap()).unwrap();
+        let text = std::fs::read_to_string(&output).unwrap();
+        let result = riscv_simulate::simulate_riscv(&text, stdin).unwrap();
+        assert_eq!(result.stdout, stdout, "{}", input);
+        assert_eq!(result.exit_code, exit_code, "{}", input);
+    }
+}
+
+/// Like `riscv_simulate_test`, with the SysY source code written to a temporary file first.
+fn riscv_simulate_source_test(name: &str, source: &str, stdin: &str, stdout: &str, exit_code: i32) {
+    let input = std::env::temp_dir().join(format!("sysy_compiler_simulate_{}.c", name));
+    std::fs::write(&input, source).unwrap();
+    riscv_simulate_test(input.to_str().unwrap(), stdin, stdout, exit_code);
+}
+
+#[test]
+fn riscv_simulate_test_scripts() {
+    let cases = [
+        ("lv1", "", "", 112),
+        ("lv2", "", "", 514),
+        ("lv3-1", "", "", 0),
+        ("lv3-2", "", "", 7),
+        ("lv3-3", "", "", 1),
+        ("lv4-1", "", "", 3),
+        ("lv4-2", "", "", 2),
+        ("lv6-1", "", "", 3),
+        ("lv6-2", "", "", 1),
+        ("lv7-1", "", "", 26),
+        ("lv7-2", "", "", 2),
+        ("lv8-0", "", "", 5),
+        ("lv8-1", "", "", 6),
+        ("lv8-2", "42", "", 42),
+        ("lv8-3", "", "", 15),
+        ("lv9-1", "", "", 1),
+        ("lv9-2", "", "", 0),
+        ("lv9-3", "", "0\n1\n2\n3\n4\n5\n6\n7\n8\n9\n", 0),
+    ];
+    for (name, stdin, stdout, exit_code) in cases {
+        let input = format!("tests/sysy_scripts/{}.c", name);
+        riscv_simulate_test(&input, stdin, stdout, exit_code);
+    }
+}
+
+#[test]
+fn riscv_simulate_test_io() {
+    let source = r"
+int a[10];
+int main() {
+  int n = getarray(a);
+  int i = 0, sum = 0;
+  while (i < n) {
+    sum = sum + a[i];
+    a[i] = a[i] * 2;
+    i = i + 1;
+  }
+  putarray(n, a);
+  putint(sum);
+  putch(getch());
+  putch(getch());
+  return getint() - sum;
+}";
+    riscv_simulate_source_test(
+        "io",
+        source,
+        "4 1 -2 3 10\nx -7",
+        "4: 2 -4 6 20\n12\nx",
+        -19,
+    );
+}
+
+#[test]
+fn riscv_simulate_test_register_pressure() {
+    // 20 values are alive at once, and some of them live across calls
+    let mut source = String::from("int id(int x) { return x; }\nint main() {\n");
+    for i in 0..20 {
+        source += &format!("  int v{} = getint();\n", i);
+    }
+    let sum = (0..20)
+        .map(|i| format!("(v{} * id({}))", i, i % 3))
+        .collect::<Vec<_>>()
+        .join(" + ");
+    source += &format!("  return {};\n}}\n", sum);
+    let stdin = (1..=20)
+        .map(|i| i.to_string())
+        .collect::<Vec<_>>()
+        .join(" ");
+    let expected: i32 = (0..20).map(|i| (i + 1) * (i % 3)).sum();
+    riscv_simulate_source_test("pressure", &source, &stdin, "", expected);
+}
+
+#[test]
+fn riscv_simulate_test_recursion() {
+    let source = r"
+int fib(int n) {
+  if (n < 2) return n;
+  return fib(n - 1) + fib(n - 2);
+}
+int sum10(int a, int b, int c, int d, int e, int f, int g, int h, int i, int j) {
+  return a - b + c - d + e - f + g - h + i * j;
+}
+int main() {
+  int big[1000];
+  big[999] = fib(15);
+  putint(big[999]);
+  putch(10);
+  return sum10(1, 2, 3, 4, 5, 6, 7, 8, 9, 10) + big[999] / 61;
+}";
+    // fib(15) = 610, and sum10 = -4 + 90
+    riscv_simulate_source_test("recursion", source, "", "610\n", 96);
+}